@@ -5,11 +5,17 @@ use std::io::{self, Write};
 use std::time::Duration;
 use tracing::{debug, error, info};
 
+use crate::approval::prompt_for_approval;
 use crate::happe_client::HappeClient;
+use gemini_ipc::happe_request::PromptReference;
 use crate::output::print_happe_response;
 
 /// Runs a single query mode, sending one prompt to the HAPPE daemon and displaying the response
-pub async fn run_single_query(prompt: String, happe_client: &HappeClient) -> Result<()> {
+pub async fn run_single_query(
+    prompt: String,
+    mcp_prompt: Option<PromptReference>,
+    happe_client: &HappeClient,
+) -> Result<()> {
     info!("Running single query: {}", prompt);
     info!("Using session ID: {}", happe_client.session_id());
 
@@ -25,7 +31,8 @@ pub async fn run_single_query(prompt: String, happe_client: &HappeClient) -> Res
     spinner.enable_steady_tick(Duration::from_millis(120));
 
     // Send request to HAPPE daemon
-    match happe_client.send_query(prompt).await {
+    let approve = |request: &_| spinner.suspend(|| prompt_for_approval(request));
+    match happe_client.send_query_with_prompt(prompt, mcp_prompt, approve).await {
         Ok(response) => {
             spinner.finish_and_clear();
 
@@ -93,7 +100,8 @@ pub async fn run_interactive_chat(happe_client: &HappeClient) -> Result<()> {
 
         // Send request to HAPPE daemon
         debug!("Sending query to HAPPE daemon: {}", input);
-        match happe_client.send_query(input.to_string()).await {
+        let approve = |request: &_| spinner.suspend(|| prompt_for_approval(request));
+        match happe_client.send_query(input.to_string(), approve).await {
             Ok(response) => {
                 spinner.finish_and_clear();
 
@@ -0,0 +1,81 @@
+use colored::*;
+use dialoguer::console::Term;
+use dialoguer::{Input, Select};
+use gemini_ipc::happe_request::{ApprovalDecision, ApprovalRequest, RiskLevel};
+use tracing::warn;
+
+/// Ask the user whether a tool call the model requested may run. Calls are
+/// denied when there is no terminal to ask on.
+pub fn prompt_for_approval(request: &ApprovalRequest) -> ApprovalDecision {
+    let term = Term::stderr();
+    if !term.is_term() {
+        return ApprovalDecision::Deny {
+            reason: Some("no terminal to ask the user".to_string()),
+        };
+    }
+
+    let risk = match request.risk {
+        RiskLevel::Low => "low risk".green(),
+        RiskLevel::Medium => "medium risk".yellow(),
+        RiskLevel::High => "high risk".red().bold(),
+    };
+    eprintln!(
+        "\n{}: {}/{} ({})",
+        "Tool Call".blue().bold(),
+        request.server,
+        request.tool,
+        risk
+    );
+    if let Some(reason) = &request.reason {
+        eprintln!("  {}", reason);
+    }
+    let arguments =
+        serde_json::to_string_pretty(&request.arguments).unwrap_or_else(|_| request.arguments.to_string());
+    for line in arguments.lines() {
+        eprintln!("  {}", line);
+    }
+
+    let choice = Select::new()
+        .with_prompt("Run this tool call?")
+        .items(&["Allow once", "Allow always", "Deny", "Edit arguments"])
+        .default(0)
+        .interact_on_opt(&term);
+    match choice {
+        Ok(Some(0)) => ApprovalDecision::AllowOnce,
+        Ok(Some(1)) => ApprovalDecision::AllowAlways,
+        Ok(Some(3)) => edit_arguments(&term, request),
+        Ok(_) => ApprovalDecision::Deny { reason: None },
+        Err(e) => {
+            warn!("Failed to ask for tool call approval: {}", e);
+            ApprovalDecision::Deny {
+                reason: Some("the user could not be asked".to_string()),
+            }
+        }
+    }
+}
+
+/// Let the user replace the arguments of a call with a JSON object
+fn edit_arguments(term: &Term, request: &ApprovalRequest) -> ApprovalDecision {
+    let edited = Input::<String>::new()
+        .with_prompt("Arguments (JSON)")
+        .with_initial_text(request.arguments.to_string())
+        .validate_with(|input: &String| -> Result<(), String> {
+            match serde_json::from_str::<serde_json::Value>(input) {
+                Ok(value) if value.is_object() => Ok(()),
+                Ok(_) => Err("Arguments must be a JSON object".to_string()),
+                Err(e) => Err(format!("Invalid JSON: {}", e)),
+            }
+        })
+        .interact_text_on(term);
+    match edited.map(|input| serde_json::from_str(&input)) {
+        Ok(Ok(arguments)) => ApprovalDecision::EditArgs { arguments },
+        Ok(Err(e)) => {
+            warn!("Failed to parse edited arguments: {}", e);
+            ApprovalDecision::Deny { reason: None }
+        }
+        Err(e) => {
+            warn!("Failed to read edited arguments: {}", e);
+            ApprovalDecision::Deny { reason: None }
+        }
+    }
+}
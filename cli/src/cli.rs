@@ -13,6 +13,14 @@ pub struct Args {
     #[arg(short, long, default_value_t = false)]
     pub interactive: bool,
 
+    /// Expand an MCP prompt template (`server/prompt`) before the prompt text
+    #[arg(long, value_name = "SERVER/PROMPT")]
+    pub mcp_prompt: Option<String>,
+
+    /// Argument for the MCP prompt template, as KEY=VALUE (repeatable)
+    #[arg(long = "prompt-arg", value_name = "KEY=VALUE", value_parser = parse_prompt_arg, requires = "mcp_prompt")]
+    pub prompt_args: Vec<(String, String)>,
+
     /// Start a new session, ignoring any existing session ID
     #[arg(long, default_value_t = false)]
     pub new_session: bool,
@@ -25,11 +33,11 @@ pub struct Args {
     #[arg(long, env = "HAPPE_IPC_PATH")]
     pub happe_ipc_path: Option<PathBuf>,
 
-    /// Run in filesystem MCP server mode (Kept for standalone server functionality)
+    /// Run the filesystem MCP server on stdio (configured with FILESYSTEM_MCP_* variables)
     #[arg(long, default_value_t = false)]
     pub filesystem_mcp: bool,
 
-    /// Run in command MCP server mode (Kept for standalone server functionality)
+    /// Run the command MCP server on stdio (configured with COMMAND_MCP_* variables)
     #[arg(long, default_value_t = false)]
     pub command_mcp: bool,
 
@@ -37,3 +45,10 @@ pub struct Args {
     #[arg(long, default_value_t = false)]
     pub memory_store_mcp: bool,
 }
+
+/// Parse a `KEY=VALUE` prompt template argument
+fn parse_prompt_arg(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", arg))
+}
@@ -1,9 +1,11 @@
 use anyhow::{anyhow, Context, Result};
 use gemini_core::config::UnifiedConfig;
-use gemini_ipc::happe_request::{HappeQueryRequest, HappeQueryResponse};
+use gemini_ipc::framing::{Hello, IpcClient, Reply};
+use gemini_ipc::happe_request::{
+    ApprovalDecision, ApprovalReply, ApprovalRequest, HappeEvent, HappeQueryRequest,
+    HappeQueryResponse, PromptReference, APPROVALS_CAPABILITY,
+};
 use std::path::PathBuf;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
 use tracing::{debug, error, info, instrument};
 use uuid::Uuid;
 
@@ -53,12 +55,6 @@ fn get_default_happe_socket_path() -> Result<PathBuf> {
         }
     }
 
-    // Then try /tmp
-    let tmp_path = PathBuf::from("/tmp").join(socket_file);
-    if tmp_path.exists() {
-        return Ok(tmp_path);
-    }
-
     // Finally fall back to default path in cache dir
     let cache_dir =
         dirs::cache_dir().ok_or_else(|| anyhow!("Could not determine cache directory"))?;
@@ -108,8 +104,8 @@ impl HappeClient {
 
     /// Establishes a connection to the HAPPE daemon's IPC socket.
     #[instrument(skip(self))]
-    async fn connect(&self) -> Result<UnixStream> {
-        UnixStream::connect(&self.socket_path)
+    async fn connect(&self) -> Result<IpcClient> {
+        IpcClient::connect(&self.socket_path, Hello::new("gemini-cli"))
             .await
             .with_context(|| {
                 format!(
@@ -119,63 +115,64 @@ impl HappeClient {
             })
     }
 
-    /// Sends a query to the HAPPE daemon and receives the response.
-    #[instrument(skip(self, query))]
-    pub async fn send_query(&self, query: String) -> Result<HappeQueryResponse> {
+    /// Sends a query to the HAPPE daemon and receives the response, asking
+    /// `approve` about the tool calls that need the user's approval.
+    pub async fn send_query(
+        &self,
+        query: String,
+        approve: impl FnMut(&ApprovalRequest) -> ApprovalDecision,
+    ) -> Result<HappeQueryResponse> {
+        self.send_query_with_prompt(query, None, approve).await
+    }
+
+    /// Sends a query, optionally preceded by an MCP prompt template, to the HAPPE daemon.
+    #[instrument(skip(self, query, approve))]
+    pub async fn send_query_with_prompt(
+        &self,
+        query: String,
+        prompt: Option<PromptReference>,
+        mut approve: impl FnMut(&ApprovalRequest) -> ApprovalDecision,
+    ) -> Result<HappeQueryResponse> {
         debug!("Connecting to HAPPE daemon...");
-        let mut stream = self.connect().await?;
+        let client = self.connect().await?;
         debug!("Connected. Sending query: {}", query);
 
         let request = HappeQueryRequest { 
             query,
             session_id: Some(self.session_id.clone()),
+            cwd: std::env::current_dir()
+                .ok()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+            prompt,
+            approvals: client.peer().has_capability(APPROVALS_CAPABILITY),
+        };
+        let mut exchange = client
+            .start(&request)
+            .context("Failed to send query to HAPPE")?;
+        debug!("Query sent successfully");
+
+        // Answer approval requests until the response arrives
+        let response: HappeQueryResponse = loop {
+            match exchange.next().await.context("Failed to read reply from HAPPE")? {
+                Reply::Message(message) => match serde_json::from_value(message) {
+                    Ok(HappeEvent::ApprovalRequired(approval)) => {
+                        debug!("Approval requested for {}/{}", approval.server, approval.tool);
+                        let reply = ApprovalReply {
+                            decision: approve(&approval),
+                            id: approval.id,
+                        };
+                        exchange
+                            .send_message(&reply)
+                            .context("Failed to send approval reply to HAPPE")?;
+                    }
+                    Err(e) => debug!("Ignoring unknown message from HAPPE: {}", e),
+                },
+                Reply::Response(payload) => {
+                    break serde_json::from_value(payload)
+                        .context("Failed to deserialize HappeQueryResponse")?
+                }
+            }
         };
-        
-        let serialized_request =
-            serde_json::to_vec(&request).context("Failed to serialize HappeQueryRequest")?;
-        let len = serialized_request.len() as u32;
-
-        // Send length prefix in little-endian format
-        stream
-            .write_all(&len.to_le_bytes())
-            .await
-            .context("Failed to write request length to HAPPE socket")?;
-        // Send request body
-        stream
-            .write_all(&serialized_request)
-            .await
-            .context("Failed to write request body to HAPPE socket")?;
-        stream
-            .flush()
-            .await
-            .context("Failed to flush HAPPE socket after writing request")?;
-        debug!("Query sent successfully ({} bytes)", len);
-
-        // Read response length in little-endian format
-        debug!("Waiting for response length...");
-        let mut size_buf = [0u8; 4];
-        stream
-            .read_exact(&mut size_buf)
-            .await
-            .context("Failed to read response length from HAPPE socket")?;
-        let response_len = u32::from_le_bytes(size_buf);
-        debug!("Received response length: {}", response_len);
-
-        if response_len == 0 {
-            return Err(anyhow!("Received zero-length response from HAPPE"));
-        }
-
-        // Read response body
-        let mut response_buffer = vec![0; response_len as usize];
-        stream
-            .read_exact(&mut response_buffer)
-            .await
-            .context("Failed to read response body from HAPPE socket")?;
-        debug!("Received response body ({} bytes)", response_len);
-
-        // Deserialize response
-        let response: HappeQueryResponse = serde_json::from_slice(&response_buffer)
-            .context("Failed to deserialize HappeQueryResponse")?;
 
         // Update session_id if it was changed by the server
         if let Some(new_session_id) = &response.session_id {
@@ -188,10 +185,26 @@ impl HappeClient {
         Ok(response)
     }
 
+    /// Sends a request answered with a bare response, without approval requests
+    async fn send_request(&self, request: HappeQueryRequest) -> Result<HappeQueryResponse> {
+        self.connect()
+            .await?
+            .request(&request)
+            .await
+            .context("HAPPE request failed")
+    }
+
     /// Sends a simple ping request to test the connection.
     pub async fn test_connection(&self) -> Result<bool> {
         info!("Testing connection to HAPPE daemon...");
-        match self.send_query("__PING__".to_string()).await {
+        let request = HappeQueryRequest {
+            query: "__PING__".to_string(),
+            session_id: Some(self.session_id.clone()),
+            cwd: None,
+            prompt: None,
+            approvals: false,
+        };
+        match self.send_request(request).await {
             Ok(resp) => {
                 // HAPPE should ideally respond with a specific PONG or similar
                 // For now, just check if the response is successful
@@ -215,60 +228,15 @@ impl HappeClient {
 
     /// Lists all active sessions from the HAPPE daemon.
     pub async fn list_sessions(&self) -> Result<Vec<String>> {
-        debug!("Connecting to HAPPE daemon to list sessions...");
-        let mut stream = self.connect().await?;
-        debug!("Connected. Sending list_sessions command...");
-
+        debug!("Sending list_sessions command...");
         let request = HappeQueryRequest { 
             query: "__LIST_SESSIONS__".to_string(),
             session_id: Some(self.session_id.clone()),
+            cwd: None,
+            prompt: None,
+            approvals: false,
         };
-        
-        let serialized_request =
-            serde_json::to_vec(&request).context("Failed to serialize HappeQueryRequest")?;
-        let len = serialized_request.len() as u32;
-
-        // Send length prefix in little-endian format
-        stream
-            .write_all(&len.to_le_bytes())
-            .await
-            .context("Failed to write request length to HAPPE socket")?;
-        // Send request body
-        stream
-            .write_all(&serialized_request)
-            .await
-            .context("Failed to write request body to HAPPE socket")?;
-        stream
-            .flush()
-            .await
-            .context("Failed to flush HAPPE socket after writing request")?;
-        debug!("List sessions command sent successfully ({} bytes)", len);
-
-        // Read response length in little-endian format
-        debug!("Waiting for response length...");
-        let mut size_buf = [0u8; 4];
-        stream
-            .read_exact(&mut size_buf)
-            .await
-            .context("Failed to read response length from HAPPE socket")?;
-        let response_len = u32::from_le_bytes(size_buf);
-        debug!("Received response length: {}", response_len);
-
-        if response_len == 0 {
-            return Err(anyhow!("Received zero-length response from HAPPE"));
-        }
-
-        // Read response body
-        let mut response_buffer = vec![0; response_len as usize];
-        stream
-            .read_exact(&mut response_buffer)
-            .await
-            .context("Failed to read response body from HAPPE socket")?;
-        debug!("Received response body ({} bytes)", response_len);
-
-        // Deserialize response
-        let response: HappeQueryResponse = serde_json::from_slice(&response_buffer)
-            .context("Failed to deserialize HappeQueryResponse")?;
+        let response = self.send_request(request).await?;
 
         if let Some(error) = response.error {
             return Err(anyhow!("Error listing sessions: {}", error));
@@ -2,11 +2,16 @@ use clap::Parser;
 use colored::*;
 use dotenv::dotenv;
 use gemini_core::config::UnifiedConfig;
+use gemini_ipc::happe_request::PromptReference;
 use log::LevelFilter;
 use std::error::Error;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
 
 // Modules used by the refactored CLI
 mod app;
+mod approval;
 mod cli;
 mod happe_client; // Renamed from ipc_client
 mod logging;
@@ -55,6 +60,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Parse command-line arguments
     let args = Args::parse();
 
+    // The built-in MCP servers replace this process and never reach HAPPE
+    if args.filesystem_mcp {
+        return Err(exec_mcp_server("filesystem-mcp").into());
+    }
+    if args.command_mcp {
+        return Err(exec_mcp_server("command-mcp").into());
+    }
+
     // Get HAPPE socket path from args or config
     let happe_ipc_path = args
         .happe_ipc_path
@@ -118,8 +131,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
             log_error(&format!("Error in interactive chat: {}", e));
             eprintln!("{}", format!("Interactive chat failed: {}", e).red());
         }
-    } else if let Some(prompt) = args.prompt.clone() {
-        if let Err(e) = crate::app::run_single_query(prompt, &happe_client).await {
+    } else if args.prompt.is_some() || args.mcp_prompt.is_some() {
+        let prompt = args.prompt.clone().unwrap_or_default();
+        let mcp_prompt = args.mcp_prompt.clone().map(|name| PromptReference {
+            name,
+            arguments: args.prompt_args.iter().cloned().collect(),
+        });
+        if let Err(e) = crate::app::run_single_query(prompt, mcp_prompt, &happe_client).await {
             log_error(&format!("Error processing prompt: {}", e));
             // Error is already printed in run_single_query
         }
@@ -130,3 +148,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Replace this process with a built-in MCP server binary, looked up next to this
+/// executable and then on `PATH`. The server reads its settings from the
+/// environment, which it inherits. Only returns if the exec fails.
+fn exec_mcp_server(binary: &str) -> std::io::Error {
+    let sibling = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(binary)))
+        .filter(|path| path.is_file());
+    let program = sibling.unwrap_or_else(|| PathBuf::from(binary));
+    log_info(&format!("Starting MCP server {}", program.display()));
+    let error = Command::new(&program).exec();
+    std::io::Error::new(error.kind(), format!("Failed to run {}: {}", program.display(), error))
+}
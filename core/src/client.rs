@@ -32,6 +32,11 @@ impl GeminiClient {
         })
     }
 
+    /// Name of the model used for requests
+    pub fn model_name(&self) -> &str {
+        &self.model.model_name
+    }
+
     /// Get the base API URL
     fn get_base_url(&self) -> String {
         format!(
@@ -100,6 +105,7 @@ impl GeminiClient {
                 candidate_count: None,
                 max_output_tokens: None,
                 response_mime_type: None,
+                stop_sequences: None,
             }),
         }
     }
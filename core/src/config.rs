@@ -51,10 +51,11 @@ impl Default for GeminiApiConfig {
 }
 
 /// Transport mechanism for MCP servers
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum McpTransport {
     /// Standard input/output communication
+    #[default]
     Stdio,
     /// Server-Sent Events over HTTP
     SSE {
@@ -70,10 +71,267 @@ pub enum McpTransport {
         /// Optional headers to include with requests
         headers: Option<HashMap<String, String>>,
     },
+    /// Replay of a recorded session in place of the server (see `record`)
+    Replay {
+        /// Recording written by a server with `record` set
+        recording: String,
+        /// Wait as long as the recorded server did before each answer
+        #[serde(default)]
+        realtime: bool,
+    },
+}
+
+// Header values usually carry credentials, so they are left out of debug output
+impl std::fmt::Debug for McpTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stdio => f.write_str("Stdio"),
+            Self::SSE { url, headers } => f
+                .debug_struct("SSE")
+                .field("url", url)
+                .field("headers", &headers.as_ref().map(RedactedValues))
+                .finish(),
+            Self::WebSocket { url, headers } => f
+                .debug_struct("WebSocket")
+                .field("url", url)
+                .field("headers", &headers.as_ref().map(RedactedValues))
+                .finish(),
+            Self::Replay { recording, realtime } => f
+                .debug_struct("Replay")
+                .field("recording", recording)
+                .field("realtime", realtime)
+                .finish(),
+        }
+    }
+}
+
+/// Debug view of a map that shows its keys but not its values
+struct RedactedValues<'a>(&'a HashMap<String, String>);
+
+impl std::fmt::Debug for RedactedValues<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.keys().map(|key| (key, "<redacted>")))
+            .finish()
+    }
+}
+
+/// When the MCP host launches a server process
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum McpStartup {
+    /// Launch the server when the host starts
+    #[default]
+    Eager,
+    /// Launch the server on its first tool call or capability request
+    Lazy,
+}
+
+impl McpStartup {
+    pub fn is_eager(&self) -> bool {
+        *self == Self::Eager
+    }
+}
+
+/// Timeout, concurrency and retry settings for requests sent to an MCP server
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpRequestPolicy {
+    /// Default timeout in seconds for tool calls (30s when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// Per-tool timeout overrides in seconds, keyed by tool name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tool_timeouts: HashMap<String, u64>,
+
+    /// Maximum number of requests in flight at once; further requests wait in a queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<usize>,
+
+    /// Tools that are safe to call again with the same arguments
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub idempotent_tools: Vec<String>,
+
+    /// Retry policy for idempotent tools (no retries when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<McpRetryPolicy>,
+}
+
+impl McpRequestPolicy {
+    /// Configured timeout for a tool, falling back to the server default
+    pub fn timeout_for(&self, tool_name: &str) -> Option<u64> {
+        self.tool_timeouts.get(tool_name).copied().or(self.timeout)
+    }
+
+    /// Whether a tool is marked idempotent (`*` marks every tool)
+    pub fn is_idempotent(&self, tool_name: &str) -> bool {
+        self.idempotent_tools
+            .iter()
+            .any(|t| t == "*" || t == tool_name)
+    }
+}
+
+/// Retry policy for idempotent MCP tool calls that time out or lose their connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpRetryPolicy {
+    /// Total number of attempts, including the first one
+    #[serde(default = "default_retry_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry in milliseconds, doubled for each further retry
+    #[serde(default = "default_retry_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+impl Default for McpRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_attempts(),
+            backoff_ms: default_retry_backoff_ms(),
+        }
+    }
+}
+
+/// Opt-in cache of tool results, for tools whose output only depends on their arguments
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpCacheConfig {
+    /// Cached tools and how long their results stay valid, in seconds (`*` matches every tool)
+    pub tools: HashMap<String, u64>,
+
+    /// Maximum number of results kept for the server; the oldest are evicted first
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+
+    /// Results larger than this many bytes (serialized) are not cached
+    #[serde(default = "default_cache_max_entry_bytes")]
+    pub max_entry_bytes: usize,
+}
+
+fn default_cache_max_entries() -> usize {
+    256
+}
+
+fn default_cache_max_entry_bytes() -> usize {
+    1024 * 1024
+}
+
+impl McpCacheConfig {
+    /// How long results of a tool are cached, if at all
+    pub fn ttl_for(&self, tool_name: &str) -> Option<u64> {
+        self.tools
+            .get(tool_name)
+            .or_else(|| self.tools.get("*"))
+            .copied()
+            .filter(|ttl| *ttl > 0)
+    }
+}
+
+/// Allows a server to request LLM completions from the host via `sampling/createMessage`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpSamplingPolicy {
+    /// Upper bound on `maxTokens` for a single request; larger requests are clamped
+    #[serde(default = "default_sampling_max_tokens")]
+    pub max_tokens: u32,
+
+    /// Total tokens the server may consume while the host is running (unlimited when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_budget: Option<u64>,
+}
+
+fn default_sampling_max_tokens() -> u32 {
+    1024
+}
+
+impl Default for McpSamplingPolicy {
+    fn default() -> Self {
+        Self {
+            max_tokens: default_sampling_max_tokens(),
+            token_budget: None,
+        }
+    }
+}
+
+/// Restrictions applied to a stdio server process before it starts (Linux only)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct McpSandboxConfig {
+    /// Host environment variables passed to the server; when set, nothing else is
+    /// inherited. Variables from the server's `env` are always set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_allowlist: Option<Vec<String>>,
+
+    /// Working directory of the server process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+
+    /// CPU time limit in seconds (RLIMIT_CPU)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_seconds: Option<u64>,
+
+    /// Address space limit in MiB (RLIMIT_AS)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u64>,
+
+    /// Maximum number of open files (RLIMIT_NOFILE)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<u64>,
+
+    /// Run the server in an empty network namespace
+    #[serde(default)]
+    pub no_network: bool,
+
+    /// Restrict the filesystem to the listed paths using Landlock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filesystem: Option<McpSandboxFilesystem>,
+
+    /// Block system calls using a seccomp preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seccomp: Option<McpSeccompPreset>,
+
+    /// Refuse to start the server if a requested feature is unavailable on this
+    /// kernel, instead of starting it without that feature
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// Filesystem view of a sandboxed server. System directories (`/usr`, `/lib`,
+/// `/etc`, ...) are always readable so the server can start.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct McpSandboxFilesystem {
+    /// Paths the server may read and execute
+    #[serde(default)]
+    pub read_only: Vec<PathBuf>,
+
+    /// Paths the server may also create, modify and delete files in
+    #[serde(default)]
+    pub read_write: Vec<PathBuf>,
+}
+
+/// System calls blocked for a sandboxed server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum McpSeccompPreset {
+    /// Block administrative and debugging calls (mount, ptrace, module loading, bpf, ...)
+    Default,
+    /// Also block sockets other than Unix domain sockets
+    Strict,
 }
 
 /// Configuration for an MCP server
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct McpServerConfig {
     /// Name of the server (used as an identifier)
@@ -86,6 +344,7 @@ pub struct McpServerConfig {
     pub transport: McpTransport,
 
     /// Command and arguments to run the server
+    #[serde(default)]
     pub command: Vec<String>,
 
     /// Additional arguments to pass to the command
@@ -99,6 +358,76 @@ pub struct McpServerConfig {
     /// Tools that should run without confirmation
     #[serde(default)]
     pub auto_execute: Vec<String>,
+
+    /// Whether the server is launched at host startup or on first use
+    #[serde(default, skip_serializing_if = "McpStartup::is_eager")]
+    pub startup: McpStartup,
+
+    /// Stop the server after this many seconds without requests (relaunched on demand)
+    #[serde(default, alias = "idle_timeout", skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+
+    /// Timeouts, concurrency limit and retry policy for requests to this server
+    #[serde(flatten)]
+    pub requests: McpRequestPolicy,
+
+    /// Sampling permission; servers without it cannot request completions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<McpSamplingPolicy>,
+
+    /// Directories always reported to the server through `roots/list`, in addition to
+    /// the client's current workspace
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<String>,
+
+    /// Sandbox for stdio servers; servers without one run with the host's privileges
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<McpSandboxConfig>,
+
+    /// Result cache for read-only tools (no caching when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<McpCacheConfig>,
+
+    /// File to append the JSON-RPC traffic with the server to, for replay with the
+    /// `replay` transport
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<String>,
+
+    /// Original `${...}` references of `env` and header values resolved by
+    /// `load_mcp_servers`, written back in place of the secrets when saving
+    #[serde(skip)]
+    pub secret_refs: McpSecretRefs,
+}
+
+// `env` values are left out of debug output since they usually carry credentials
+impl std::fmt::Debug for McpServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpServerConfig")
+            .field("name", &self.name)
+            .field("enabled", &self.enabled)
+            .field("transport", &self.transport)
+            .field("command", &self.command)
+            .field("args", &self.args)
+            .field("env", &RedactedValues(&self.env))
+            .field("auto_execute", &self.auto_execute)
+            .field("startup", &self.startup)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("requests", &self.requests)
+            .field("sampling", &self.sampling)
+            .field("roots", &self.roots)
+            .field("sandbox", &self.sandbox)
+            .field("cache", &self.cache)
+            .field("record", &self.record)
+            .field("secret_refs", &self.secret_refs)
+            .finish()
+    }
+}
+
+/// Unresolved `${...}` references of an MCP server, keyed by variable or header name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct McpSecretRefs {
+    pub env: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
 }
 
 /// Represents the unified configuration for the entire Gemini Suite.
@@ -129,6 +458,10 @@ pub struct UnifiedConfig {
     #[serde(default)]
     pub daemon_manager: DaemonManagerConfig,
 
+    /// Access control for the Unix sockets of mcp-hostd, HAPPE and IDA
+    #[serde(default)]
+    pub ipc: IpcConfig,
+
     /// Gemini API configuration (shared by multiple components)
     #[serde(default)]
     pub gemini_api: GeminiApiConfig,
@@ -166,6 +499,19 @@ pub struct HappeConfig {
 
     /// System prompt for HAPPE interactions
     pub system_prompt: Option<String>,
+
+    /// Maximum number of function calls from one model response executed at once
+    pub tool_parallelism: Option<usize>,
+
+    /// Maximum number of tool calls a session may have running at once, across
+    /// its concurrent queries
+    pub session_tool_parallelism: Option<usize>,
+
+    /// Seconds after which a tool call is reported to the model as timed out
+    pub tool_timeout_secs: Option<u64>,
+
+    /// Seconds to wait for the user to approve a tool call before denying it
+    pub approval_timeout_secs: Option<u64>,
 }
 
 /// Memory broker LLM configuration
@@ -229,6 +575,133 @@ pub struct McpConfig {
 
     /// Path to the MCP host daemon socket
     pub mcp_host_socket_path: Option<PathBuf>,
+
+    /// Allow/deny/ask rules enforced by mcp-hostd for every tool call
+    #[serde(default)]
+    pub tool_policy: ToolPolicyConfig,
+
+    /// Hash-chained log of every tool call handled by mcp-hostd
+    #[serde(default)]
+    pub audit: McpAuditConfig,
+
+    /// mcp-hostd acting as an MCP server for external clients
+    #[serde(default)]
+    pub aggregator: McpAggregatorConfig,
+}
+
+/// Settings for serving the combined `server/tool` namespace to external MCP clients.
+/// The `mcp-bridge` stdio binary is always available; the HTTP endpoint is opt-in.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct McpAggregatorConfig {
+    /// Port of the Streamable HTTP endpoint on 127.0.0.1 (disabled when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_port: Option<u16>,
+}
+
+/// Settings for the mcp-hostd audit log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct McpAuditConfig {
+    /// Whether tool calls are recorded
+    #[serde(default = "default_audit_enabled")]
+    pub enabled: bool,
+
+    /// Location of the JSONL log (defaults to `audit.jsonl` in the local data directory)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    /// Globs over argument names (at any depth, case-insensitive) whose values are
+    /// replaced with `<redacted>` before they are written
+    #[serde(default = "default_audit_redactions")]
+    pub redact_arguments: Vec<String>,
+}
+
+fn default_audit_enabled() -> bool {
+    true
+}
+
+fn default_audit_redactions() -> Vec<String> {
+    ["*password*", "*secret*", "*token*", "*api_key*", "*apikey*", "authorization"]
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect()
+}
+
+impl Default for McpAuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_audit_enabled(),
+            path: None,
+            redact_arguments: default_audit_redactions(),
+        }
+    }
+}
+
+/// Tool execution policy: rules are checked in order and the first rule whose
+/// `tool` glob matches `server/tool` decides the outcome
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ToolPolicyConfig {
+    /// Outcome for calls that match no rule
+    #[serde(default)]
+    pub default: ToolPolicyAction,
+
+    #[serde(default)]
+    pub rules: Vec<ToolPolicyRule>,
+}
+
+/// Outcome of a tool policy rule
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolPolicyAction {
+    #[default]
+    Allow,
+    Deny,
+    /// Run only once the user has approved the call
+    Ask,
+}
+
+/// A tool policy rule
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ToolPolicyRule {
+    /// Glob over `server/tool` names, e.g. `filesystem/*` or `*/delete_*`
+    pub tool: String,
+
+    pub action: ToolPolicyAction,
+
+    /// Explanation returned to the model when a call is denied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<String>,
+
+    /// Constraints on the arguments of allowed or asked calls; a call that violates
+    /// one is denied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<ToolArgumentConstraint>,
+}
+
+/// Constraint on one argument of a tool call. String arguments and arrays of strings
+/// are checked; calls without the argument are denied unless it is `optional`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ToolArgumentConstraint {
+    /// Argument name; nested fields are separated by dots
+    pub argument: String,
+
+    /// The value must be an absolute path under one of these directories
+    /// (`~` and `$VAR` are expanded, symlinks are resolved)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path_under: Vec<String>,
+
+    /// The value must fully match one of these regular expressions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<String>,
+
+    /// Allow calls without the argument (or with `null`), which servers fill in
+    /// with their own default
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
 }
 
 /// Configuration specific to the Daemon Manager.
@@ -242,6 +715,40 @@ pub struct DaemonManagerConfig {
     pub show_config_editor: Option<String>,
 }
 
+/// Who may connect to the daemons' Unix sockets. Connections from the daemon's
+/// own uid are always accepted.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct IpcConfig {
+    /// Other uids allowed to connect
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_uids: Vec<u32>,
+
+    /// Secret clients must prove they know before their first request, for setups
+    /// that proxy the sockets. Supports `${file:PATH}`, `${cmd:COMMAND}` and
+    /// `${VAR}` references.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_secret: Option<String>,
+}
+
+impl IpcConfig {
+    /// The shared secret with its references resolved, if one is configured
+    pub fn resolve_shared_secret(&self) -> GeminiResult<Option<String>> {
+        let Some(secret) = &self.shared_secret else {
+            return Ok(None);
+        };
+        let resolved = interpolate_secrets(secret)
+            .map_err(|e| GeminiError::ConfigError(format!("IPC shared secret: {}", e)))?
+            .unwrap_or_else(|| secret.clone());
+        if resolved.is_empty() {
+            return Err(GeminiError::ConfigError(
+                "IPC shared secret is empty".to_string(),
+            ));
+        }
+        Ok(Some(resolved))
+    }
+}
+
 /// Claude-compatible server configuration for serializing to the JSON format.
 #[derive(Serialize)]
 struct ClaudeServer {
@@ -252,6 +759,22 @@ struct ClaudeServer {
     env: HashMap<String, String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     enabled: Option<bool>,
+    #[serde(skip_serializing_if = "McpStartup::is_eager")]
+    startup: McpStartup,
+    #[serde(rename = "idleTimeout", skip_serializing_if = "Option::is_none")]
+    idle_timeout: Option<u64>,
+    #[serde(flatten)]
+    requests: McpRequestPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sampling: Option<McpSamplingPolicy>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roots: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sandbox: Option<McpSandboxConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<McpCacheConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record: Option<String>,
 }
 
 impl UnifiedConfig {
@@ -362,6 +885,7 @@ impl UnifiedConfig {
             mcp: mcp.unwrap_or_default(),
             daemon_manager: daemon_manager.unwrap_or_default(),
             gemini_api: gemini_api.unwrap_or_default(),
+            ipc: IpcConfig::default(),
         }
     }
 }
@@ -414,6 +938,9 @@ pub fn get_mcp_servers_config_path() -> GeminiResult<PathBuf> {
 }
 
 /// Loads MCP server configurations from the default location or a specific path.
+///
+/// `${ENV_VAR}`, `${file:/path}` and `${cmd:command}` references in `env` values and
+/// SSE/WebSocket headers are resolved here; the originals are kept in `secret_refs`.
 pub fn load_mcp_servers(config_path: Option<&Path>) -> GeminiResult<Vec<McpServerConfig>> {
     let path = if let Some(p) = config_path {
         p.to_path_buf()
@@ -441,7 +968,7 @@ pub fn load_mcp_servers(config_path: Option<&Path>) -> GeminiResult<Vec<McpServe
     let servers_vec_result: Result<Vec<McpServerConfig>, _> = serde_json::from_str(&content);
 
     if let Ok(servers) = servers_vec_result {
-        return Ok(servers);
+        return resolve_secret_refs(servers);
     }
 
     // If that fails, try parsing as an object with a "servers" field (newer format)
@@ -453,7 +980,7 @@ pub fn load_mcp_servers(config_path: Option<&Path>) -> GeminiResult<Vec<McpServe
     let servers_container_result: Result<ServersContainer, _> = serde_json::from_str(&content);
 
     if let Ok(container) = servers_container_result {
-        return Ok(container.servers);
+        return resolve_secret_refs(container.servers);
     }
 
     // If that fails too, try parsing as the Claude-compatible format
@@ -466,6 +993,22 @@ pub fn load_mcp_servers(config_path: Option<&Path>) -> GeminiResult<Vec<McpServe
         env: HashMap<String, String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         enabled: Option<bool>,
+        #[serde(default)]
+        startup: McpStartup,
+        #[serde(default, rename = "idleTimeout", alias = "idle_timeout")]
+        idle_timeout: Option<u64>,
+        #[serde(flatten)]
+        requests: McpRequestPolicy,
+        #[serde(default)]
+        sampling: Option<McpSamplingPolicy>,
+        #[serde(default)]
+        roots: Vec<String>,
+        #[serde(default)]
+        sandbox: Option<McpSandboxConfig>,
+        #[serde(default)]
+        cache: Option<McpCacheConfig>,
+        #[serde(default)]
+        record: Option<String>,
     }
 
     #[derive(serde::Deserialize)]
@@ -488,10 +1031,19 @@ pub fn load_mcp_servers(config_path: Option<&Path>) -> GeminiResult<Vec<McpServe
                 args: server.args,
                 env: server.env,
                 auto_execute: Vec::new(),
+                startup: server.startup,
+                idle_timeout: server.idle_timeout,
+                requests: server.requests,
+                sampling: server.sampling,
+                roots: server.roots,
+                sandbox: server.sandbox,
+                cache: server.cache,
+                record: server.record,
+                secret_refs: McpSecretRefs::default(),
             });
         }
 
-        return Ok(servers);
+        return resolve_secret_refs(servers);
     }
 
     // All parsing attempts failed
@@ -523,8 +1075,23 @@ pub fn save_mcp_servers(
             ClaudeServer {
                 command,
                 args: server.args.clone(),
-                env: server.env.clone(),
+                env: server
+                    .env
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = server.secret_refs.env.get(key).unwrap_or(value);
+                        (key.clone(), value.clone())
+                    })
+                    .collect(),
                 enabled: Some(server.enabled),
+                startup: server.startup,
+                idle_timeout: server.idle_timeout,
+                requests: server.requests.clone(),
+                sampling: server.sampling.clone(),
+                roots: server.roots.clone(),
+                sandbox: server.sandbox.clone(),
+                cache: server.cache.clone(),
+                record: server.record.clone(),
             },
         );
     }
@@ -565,3 +1132,113 @@ pub fn save_mcp_servers(
 
     Ok(())
 }
+
+/// Resolve the `${...}` references of every server, remembering the original values
+fn resolve_secret_refs(mut servers: Vec<McpServerConfig>) -> GeminiResult<Vec<McpServerConfig>> {
+    for server in &mut servers {
+        let name = server.name.clone();
+        let mut refs = McpSecretRefs::default();
+
+        for (key, value) in server.env.iter_mut() {
+            if let Some(resolved) = interpolate_secrets(value).map_err(|e| {
+                GeminiError::ConfigError(format!(
+                    "MCP server '{}': cannot resolve env variable '{}': {}",
+                    name, key, e
+                ))
+            })? {
+                refs.env.insert(key.clone(), std::mem::replace(value, resolved));
+            }
+        }
+
+        if let McpTransport::SSE {
+            headers: Some(headers),
+            ..
+        }
+        | McpTransport::WebSocket {
+            headers: Some(headers),
+            ..
+        } = &mut server.transport
+        {
+            for (key, value) in headers.iter_mut() {
+                if let Some(resolved) = interpolate_secrets(value).map_err(|e| {
+                    GeminiError::ConfigError(format!(
+                        "MCP server '{}': cannot resolve header '{}': {}",
+                        name, key, e
+                    ))
+                })? {
+                    refs.headers
+                        .insert(key.clone(), std::mem::replace(value, resolved));
+                }
+            }
+        }
+
+        server.secret_refs = refs;
+    }
+    Ok(servers)
+}
+
+/// Expand the `${...}` references in a value. Returns `None` if it contains none;
+/// `$${` stands for a literal `${`.
+fn interpolate_secrets(value: &str) -> Result<Option<String>, String> {
+    if !value.contains("${") {
+        return Ok(None);
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated reference in '{}'", value))?;
+        let reference = &rest[start + 2..start + end];
+        result.push_str(&resolve_secret(reference)?);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(Some(result))
+}
+
+/// Resolve a single reference: `file:PATH`, `cmd:COMMAND` or an environment variable
+fn resolve_secret(reference: &str) -> Result<String, String> {
+    if let Some(path) = reference.strip_prefix("file:") {
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => dirs::home_dir()
+                .ok_or_else(|| "could not determine home directory".to_string())?
+                .join(relative),
+            None => PathBuf::from(path),
+        };
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("${{file:{}}}: {}", path.display(), e))?;
+        Ok(content.trim_end_matches(['\r', '\n']).to_string())
+    } else if let Some(command) = reference.strip_prefix("cmd:") {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .map_err(|e| format!("${{cmd:{}}}: {}", command, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "${{cmd:{}}} exited with {}: {}",
+                command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let stdout = String::from_utf8(output.stdout)
+            .map_err(|_| format!("${{cmd:{}}} printed invalid UTF-8", command))?;
+        Ok(stdout.trim_end_matches(['\r', '\n']).to_string())
+    } else if reference.is_empty() {
+        Err("empty reference '${}'".to_string())
+    } else {
+        std::env::var(reference)
+            .map_err(|_| format!("environment variable {} is not set", reference))
+    }
+}
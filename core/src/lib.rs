@@ -29,5 +29,8 @@ pub use errors::*;
 pub mod rpc_types;
 // pub use rpc_types::*; // Replace glob export
 pub use rpc_types::{
-    JsonRpcError, Request, Resource, Response, ServerCapabilities, Tool as RpcTool,
+    EmbeddedResource, GetPromptResult, JsonRpcError, ListPromptsResult,
+    ListResourceTemplatesResult, Prompt, PromptArgument, PromptMessage, ReadResourceResult,
+    Request, Resource, ResourceTemplate, Response, ServerCapabilities, Tool as RpcTool,
+    ToolCallResult, ToolContent,
 };
@@ -80,7 +80,8 @@ pub struct ServerCapabilities {
     pub tools: Vec<Tool>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub resources: Vec<Resource>,
-    // Add other capabilities like prompts, sampling support here if needed
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub prompts: Vec<Prompt>,
 }
 
 /// Definition of a tool provided by an MCP server.
@@ -90,13 +91,253 @@ pub struct Tool {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Value>, // JSON Schema for parameters
-                                   // Add result schema if needed
+    // Add result schema if needed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+impl Tool {
+    /// Returns true if the server declares that the tool does not modify its
+    /// environment.
+    pub fn is_read_only(&self) -> bool {
+        self.annotations
+            .as_ref()
+            .and_then(|annotations| annotations.read_only_hint)
+            .unwrap_or(false)
+    }
+}
+
+/// Hints an MCP server gives about the behavior of a tool. They are not
+/// guarantees, so clients only rely on them for scheduling, never for security.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
 }
 
 /// Definition of a resource managed by an MCP server.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct Resource {
     pub name: String,
     pub description: Option<String>,
-    // Add schema/type information if needed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A parameterized resource, addressed by an RFC 6570 URI template.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+impl ResourceTemplate {
+    /// Returns true if `uri` can be produced by expanding the template.
+    ///
+    /// Supports simple `{var}` expressions, which match one path segment, and
+    /// reserved or exploded expressions (`{+var}`, `{/var*}`, ...), which match
+    /// any remaining characters up to the next literal.
+    pub fn matches(&self, uri: &str) -> bool {
+        template_matches(&self.uri_template, uri)
+    }
+}
+
+fn template_matches(template: &str, uri: &str) -> bool {
+    let Some(start) = template.find('{') else {
+        return template == uri;
+    };
+    let Some(len) = template[start..].find('}') else {
+        return template == uri;
+    };
+    let (literal, expression) = (&template[..start], &template[start + 1..start + len]);
+    let rest = &template[start + len + 1..];
+    let Some(uri) = uri.strip_prefix(literal) else {
+        return false;
+    };
+
+    let greedy = expression.starts_with(['+', '#', '/']) || expression.ends_with('*');
+    // Try every split point for the variable's value, shortest first
+    for (end, _) in uri.char_indices().skip(1).chain([(uri.len(), ' ')]) {
+        let value = &uri[..end];
+        if !greedy && value.contains('/') {
+            break;
+        }
+        if template_matches(rest, &uri[end..]) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Result of an MCP `resources/templates/list` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResourceTemplatesResult {
+    #[serde(default)]
+    pub resource_templates: Vec<ResourceTemplate>,
+}
+
+/// Result of an MCP `resources/read` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ReadResourceResult {
+    #[serde(default)]
+    pub contents: Vec<EmbeddedResource>,
+}
+
+/// Definition of a prompt template provided by an MCP server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Prompt {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<PromptArgument>,
+}
+
+/// An argument accepted by a prompt template.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Result of an MCP `prompts/list` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ListPromptsResult {
+    #[serde(default)]
+    pub prompts: Vec<Prompt>,
+}
+
+/// A message produced by expanding a prompt template.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptMessage {
+    pub role: String, // "user" or "assistant"
+    pub content: ToolContent,
+}
+
+/// Result of an MCP `prompts/get` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct GetPromptResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<PromptMessage>,
+}
+
+/// A single content block in the result of an MCP `tools/call` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolContent {
+    Text {
+        text: String,
+    },
+    Image {
+        data: String, // base64-encoded
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        data: String, // base64-encoded
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Resource {
+        resource: EmbeddedResource,
+    },
+    ResourceLink {
+        uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(rename = "mimeType", default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+    /// Content types added by newer protocol revisions
+    #[serde(other)]
+    Unknown,
+}
+
+/// Resource contents embedded in a tool result or returned by `resources/read`;
+/// exactly one of `text` or `blob` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddedResource {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>, // base64-encoded
+}
+
+/// Result of an MCP `tools/call` request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallResult {
+    #[serde(default)]
+    pub content: Vec<ToolContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+    /// Set when the tool itself failed; the content describes the error
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+impl ToolCallResult {
+    /// Parse a standard `tools/call` result. Returns `None` for the free-form
+    /// results produced by older servers, which have no `content` array.
+    pub fn from_standard(value: &Value) -> Option<Self> {
+        if !value.get("content").is_some_and(Value::is_array) {
+            return None;
+        }
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Parse any tool result, wrapping free-form results as structured content.
+    pub fn from_value(value: Value) -> Self {
+        if let Some(result) = Self::from_standard(&value) {
+            return result;
+        }
+        let text = match &value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        Self {
+            content: vec![ToolContent::Text { text }],
+            structured_content: Some(value),
+            is_error: false,
+        }
+    }
+
+    /// All text blocks joined with newlines.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|c| match c {
+                ToolContent::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
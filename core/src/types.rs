@@ -98,6 +98,17 @@ pub struct Part {
     pub function_call: Option<FunctionCall>,
     #[serde(rename = "functionResponse", skip_serializing_if = "Option::is_none")]
     pub function_response: Option<FunctionResponse>,
+    #[serde(rename = "inlineData", default, skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<Blob>,
+}
+
+/// Inline binary data (images, audio, files) sent as part of a content
+#[derive(Serialize, Clone, Debug, Deserialize, PartialEq)]
+pub struct Blob {
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    /// Base64-encoded bytes
+    pub data: String,
 }
 
 impl Part {
@@ -106,6 +117,7 @@ impl Part {
             text: Some(text),
             function_call: None,
             function_response: None,
+            inline_data: None,
         }
     }
 
@@ -114,6 +126,16 @@ impl Part {
             text: None,
             function_call: None,
             function_response: Some(FunctionResponse { name, response }),
+            inline_data: None,
+        }
+    }
+
+    pub fn inline_data(mime_type: String, data: String) -> Self {
+        Self {
+            text: None,
+            function_call: None,
+            function_response: None,
+            inline_data: Some(Blob { mime_type, data }),
         }
     }
 }
@@ -133,18 +155,36 @@ pub struct GenerationConfig {
     pub max_output_tokens: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
 }
 
 /// Response from Gemini API
 #[derive(Deserialize, Debug, Serialize)]
 pub struct GenerateContentResponse {
     pub candidates: Vec<Candidate>,
+    #[serde(rename = "usageMetadata", default, skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<UsageMetadata>,
+}
+
+/// Token usage reported by the API
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: Option<u64>,
+    #[serde(default)]
+    pub candidates_token_count: Option<u64>,
+    #[serde(default)]
+    pub total_token_count: Option<u64>,
 }
 
 /// Candidate in the response
 #[derive(Deserialize, Debug, Serialize)]
 pub struct Candidate {
     pub content: Option<ContentResponsePart>,
+    #[serde(rename = "finishReason", default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
 }
 
 /// Content part in the response
@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use gemini_core::config::UnifiedConfig;
+use gemini_mcp::audit::{self, AuditEntry, AuditOutcome};
+use std::path::PathBuf;
+
+// Filters for `audit show`
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub server: Option<String>,
+    pub tool: Option<String>,
+    pub session: Option<String>,
+    pub outcome: Option<String>,
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// Resolve the audit log location from the argument, the unified config or the default
+fn get_audit_log_path(path: Option<PathBuf>) -> Result<PathBuf> {
+    path.or_else(|| UnifiedConfig::load().mcp.audit.path)
+        .or_else(gemini_mcp::config::get_audit_log_path)
+        .ok_or_else(|| anyhow!("Could not determine the audit log location"))
+}
+
+// Verify the hash chain of the audit log; fails if any entry was altered
+pub fn verify_audit_log(path: Option<PathBuf>) -> Result<()> {
+    let path = get_audit_log_path(path)?;
+    let verification = audit::verify(&path).map_err(|e| anyhow!(e))?;
+
+    match verification.error {
+        None => {
+            println!(
+                "{} {} entries in {}",
+                "Audit log intact:".green().bold(),
+                verification.valid_entries,
+                path.display()
+            );
+            Ok(())
+        }
+        Some((line, reason)) => Err(anyhow!(
+            "Audit log {} is broken at line {} ({}); {} entries before it are intact",
+            path.display(),
+            line,
+            reason,
+            verification.valid_entries
+        )),
+    }
+}
+
+// Print the entries matching the filter, oldest first
+pub fn show_audit_log(path: Option<PathBuf>, filter: AuditFilter, json: bool) -> Result<()> {
+    let path = get_audit_log_path(path)?;
+    let outcome = filter
+        .outcome
+        .as_deref()
+        .map(|outcome| {
+            serde_json::from_value::<AuditOutcome>(serde_json::Value::String(outcome.to_string()))
+                .map_err(|_| {
+                    anyhow!(
+                        "Unknown outcome '{}' (expected success, error, denied or approval_required)",
+                        outcome
+                    )
+                })
+        })
+        .transpose()?;
+    let since = filter.since.as_deref().map(parse_since).transpose()?;
+
+    let entries = audit::read_entries(&path).map_err(|e| anyhow!(e))?;
+    let mut matching: Vec<&AuditEntry> = entries
+        .iter()
+        .filter(|entry| filter.server.as_ref().is_none_or(|s| &entry.server == s))
+        .filter(|entry| filter.tool.as_ref().is_none_or(|t| &entry.tool == t))
+        .filter(|entry| {
+            filter
+                .session
+                .as_ref()
+                .is_none_or(|s| entry.session.as_ref() == Some(s))
+        })
+        .filter(|entry| outcome.is_none_or(|o| entry.outcome == o))
+        .filter(|entry| since.is_none_or(|since| entry.timestamp >= since))
+        .collect();
+    if let Some(limit) = filter.limit {
+        let skip = matching.len().saturating_sub(limit);
+        matching.drain(..skip);
+    }
+
+    for entry in matching {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(entry).context("Failed to serialize audit entry")?
+            );
+            continue;
+        }
+
+        let outcome = match entry.outcome {
+            AuditOutcome::Success => "success".green(),
+            AuditOutcome::Error => "error".red(),
+            AuditOutcome::Denied => "denied".red().bold(),
+            AuditOutcome::ApprovalRequired => "approval required".yellow(),
+        };
+        println!(
+            "#{} {} {}/{} {} ({} ms) client={}{}",
+            entry.seq,
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.server,
+            entry.tool,
+            outcome,
+            entry.duration_ms,
+            entry.client,
+            entry
+                .session
+                .as_ref()
+                .map(|s| format!(" session={}", s))
+                .unwrap_or_default()
+        );
+        println!("    args: {}", entry.arguments);
+        if let Some(error) = &entry.error {
+            println!("    error: {}", error);
+        }
+    }
+    Ok(())
+}
+
+// Accept RFC 3339 timestamps or plain dates (midnight UTC)
+fn parse_since(since: &str) -> Result<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(since) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(since, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+        .ok_or_else(|| anyhow!("Invalid --since '{}': expected YYYY-MM-DD or RFC 3339", since))
+}
@@ -2,8 +2,10 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use colored::Colorize;
 use std::env;
+use std::path::PathBuf;
 use tracing::{debug, info};
 
+mod audit;
 mod config;
 mod daemon;
 mod mcp;
@@ -48,6 +50,10 @@ enum Commands {
     #[command(subcommand)]
     Config(ConfigCommands),
 
+    /// Tool execution audit log commands
+    #[command(subcommand)]
+    Audit(AuditCommands),
+
     /// Show status of all daemons and MCP servers
     Status,
 
@@ -102,7 +108,7 @@ enum DaemonCommands {
 
 #[derive(Subcommand, Debug)]
 enum McpCommands {
-    /// List all MCP servers
+    /// List all MCP servers, with their live state if mcp-hostd is running
     List,
     /// Enable an MCP server
     Enable {
@@ -122,6 +128,26 @@ enum McpCommands {
         #[arg(required = true)]
         name: String,
     },
+    /// Start an MCP server in the running mcp-hostd
+    Start {
+        /// Name of the MCP server to start
+        #[arg(required = true)]
+        name: String,
+    },
+    /// Stop an MCP server in the running mcp-hostd (it starts again on its next request)
+    Stop {
+        /// Name of the MCP server to stop
+        #[arg(required = true)]
+        name: String,
+    },
+    /// Restart an MCP server in the running mcp-hostd
+    Restart {
+        /// Name of the MCP server to restart
+        #[arg(required = true)]
+        name: String,
+    },
+    /// Make the running mcp-hostd apply changes to the MCP server configuration
+    Reload,
     /// Install a new MCP server
     Install {
         /// Path to the server executable or configuration
@@ -142,6 +168,50 @@ enum McpCommands {
     Migrate,
 }
 
+#[derive(Subcommand, Debug)]
+enum AuditCommands {
+    /// Verify that no audit log entry was altered, reordered or removed
+    Verify {
+        /// Audit log to check (defaults to the configured log)
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+    /// Show audit log entries, optionally filtered
+    Show {
+        /// Audit log to read (defaults to the configured log)
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Only calls to this server
+        #[arg(long)]
+        server: Option<String>,
+
+        /// Only calls of this tool
+        #[arg(long)]
+        tool: Option<String>,
+
+        /// Only calls from this client session
+        #[arg(long)]
+        session: Option<String>,
+
+        /// Only calls with this outcome (success, error, denied, approval_required)
+        #[arg(long)]
+        outcome: Option<String>,
+
+        /// Only calls at or after this time (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Show at most this many of the most recent matching entries
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+
+        /// Print entries as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 enum ConfigCommands {
     /// Edit configuration for a component
@@ -164,6 +234,37 @@ enum ConfigCommands {
     },
 }
 
+// Apply the MCP server configuration to mcp-hostd if it is running.
+// Returns whether the daemon was reached.
+async fn reload_running_host() -> Result<bool> {
+    let Some(reload) = mcp::reload_live_config()
+        .await
+        .context("Failed to reload mcp-hostd configuration")?
+    else {
+        return Ok(false);
+    };
+    if reload.added.is_empty()
+        && reload.removed.is_empty()
+        && reload.restarted.is_empty()
+        && reload.updated.is_empty()
+    {
+        info!("mcp-hostd configuration is up to date");
+    }
+    for name in &reload.added {
+        info!("mcp-hostd added MCP server {}", name.green());
+    }
+    for name in &reload.removed {
+        info!("mcp-hostd removed MCP server {}", name.yellow());
+    }
+    for name in &reload.restarted {
+        info!("mcp-hostd restarted MCP server {}", name.blue());
+    }
+    for name in &reload.updated {
+        info!("mcp-hostd updated MCP server {}", name.blue());
+    }
+    Ok(true)
+}
+
 fn setup_logging(verbose: bool) {
     let level = if verbose {
         tracing::Level::DEBUG
@@ -235,8 +336,27 @@ async fn main() -> Result<()> {
                     let servers = mcp::list_servers()
                         .await
                         .context("Failed to list MCP servers")?;
-                    for (name, status) in servers {
-                        println!("{}: {}", name, status);
+                    let live = mcp::live_servers()
+                        .await
+                        .context("Failed to query mcp-hostd")?;
+                    match live {
+                        Some(live) => {
+                            for status in &live {
+                                println!("{}: {}", status.name, mcp::describe_live_server(status));
+                            }
+                            // Disabled servers are not loaded by the daemon
+                            for (name, status) in servers {
+                                if !live.iter().any(|s| s.name == name) {
+                                    println!("{}: {}", name, status);
+                                }
+                            }
+                        }
+                        None => {
+                            for (name, status) in servers {
+                                println!("{}: {}", name, status);
+                            }
+                            println!("(mcp-hostd is not running; showing configuration only)");
+                        }
                     }
                 }
                 McpCommands::Enable { name } => {
@@ -244,30 +364,68 @@ async fn main() -> Result<()> {
                         .await
                         .with_context(|| format!("Failed to enable MCP server {}", name))?;
                     info!("MCP server {} enabled successfully", name.green());
+                    reload_running_host().await?;
                 }
                 McpCommands::Disable { name } => {
                     mcp::disable_server(&name)
                         .await
                         .with_context(|| format!("Failed to disable MCP server {}", name))?;
                     info!("MCP server {} disabled successfully", name.green());
+                    reload_running_host().await?;
                 }
                 McpCommands::Status { name } => {
-                    let status = mcp::check_server_status(&name).await.with_context(|| {
-                        format!("Failed to check status of MCP server {}", name)
-                    })?;
-                    println!("{}: {}", name, status);
+                    let live = mcp::live_servers()
+                        .await
+                        .context("Failed to query mcp-hostd")?
+                        .and_then(|servers| servers.into_iter().find(|s| s.name == name));
+                    match live {
+                        Some(status) => {
+                            println!("{}: {}", name, mcp::describe_live_server(&status))
+                        }
+                        None => {
+                            let status = mcp::check_server_status(&name).await.with_context(|| {
+                                format!("Failed to check status of MCP server {}", name)
+                            })?;
+                            println!("{}: {}", name, status);
+                        }
+                    }
+                }
+                McpCommands::Start { name } => {
+                    let status = mcp::start_live_server(&name)
+                        .await
+                        .with_context(|| format!("Failed to start MCP server {}", name))?;
+                    println!("{}: {}", name, mcp::describe_live_server(&status));
+                }
+                McpCommands::Stop { name } => {
+                    let status = mcp::stop_live_server(&name)
+                        .await
+                        .with_context(|| format!("Failed to stop MCP server {}", name))?;
+                    println!("{}: {}", name, mcp::describe_live_server(&status));
+                }
+                McpCommands::Restart { name } => {
+                    let status = mcp::restart_live_server(&name)
+                        .await
+                        .with_context(|| format!("Failed to restart MCP server {}", name))?;
+                    println!("{}: {}", name, mcp::describe_live_server(&status));
+                }
+                McpCommands::Reload => {
+                    if !reload_running_host().await? {
+                        println!("mcp-hostd is not running; changes apply when it starts");
+                    }
                 }
                 McpCommands::Install { path, name } => {
                     let server_name = mcp::install_server(&path, name)
                         .await
                         .with_context(|| format!("Failed to install MCP server from {}", path))?;
                     info!("MCP server {} installed successfully", server_name.green());
+                    reload_running_host().await?;
                 }
                 McpCommands::Uninstall { name } => {
                     mcp::uninstall_server(&name)
                         .await
                         .with_context(|| format!("Failed to uninstall MCP server {}", name))?;
                     info!("MCP server {} uninstalled successfully", name.green());
+                    reload_running_host().await?;
                 }
                 McpCommands::Migrate => {
                     info!("Migrating MCP server configuration to Claude-compatible format...");
@@ -302,6 +460,31 @@ async fn main() -> Result<()> {
                 info!("Configuration for {} reset to defaults", component.green());
             }
         },
+        Commands::Audit(cmd) => match cmd {
+            AuditCommands::Verify { path } => {
+                audit::verify_audit_log(path).context("Audit log verification failed")?;
+            }
+            AuditCommands::Show {
+                path,
+                server,
+                tool,
+                session,
+                outcome,
+                since,
+                limit,
+                json,
+            } => {
+                let filter = audit::AuditFilter {
+                    server,
+                    tool,
+                    session,
+                    outcome,
+                    since,
+                    limit,
+                };
+                audit::show_audit_log(path, filter, json).context("Failed to show audit log")?;
+            }
+        },
         Commands::Status => {
             println!("{}", "=== Gemini Suite Status ===".bold());
 
@@ -330,6 +513,15 @@ async fn main() -> Result<()> {
             let mcp_statuses = mcp::list_servers()
                 .await
                 .context("Failed to list MCP servers")?;
+            // Live state from mcp-hostd replaces the configured status when it is running
+            let live_statuses = mcp::live_servers().await.ok().flatten().unwrap_or_default();
+            let describe = |name: &str, status: &mcp::ServerStatus| {
+                live_statuses
+                    .iter()
+                    .find(|live| live.name == name)
+                    .map(mcp::describe_live_server)
+                    .unwrap_or_else(|| status.to_string())
+            };
 
             let max_mcp_name_length = mcp_statuses
                 .keys()
@@ -344,7 +536,7 @@ async fn main() -> Result<()> {
                     println!(
                         "  {:<width$} : {} (built-in)",
                         name,
-                        status,
+                        describe(name, status),
                         width = max_mcp_name_length
                     );
                 }
@@ -362,7 +554,7 @@ async fn main() -> Result<()> {
                         println!(
                             "  {:<width$} : {}",
                             name,
-                            status,
+                            describe(name, status),
                             width = max_mcp_name_length
                         );
                     }
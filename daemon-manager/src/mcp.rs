@@ -1,7 +1,14 @@
 use anyhow::{anyhow, Context, Result};
-use colored::Colorize;
+use colored::{ColoredString, Colorize};
 use dirs::home_dir;
+use gemini_core::config::UnifiedConfig;
+use gemini_ipc::daemon_messages::{
+    DaemonRequest, DaemonResponse, ServerList, ServerState, ServerStatus as LiveServerStatus,
+};
+use gemini_ipc::framing::{Hello, IpcClient, IpcError};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
 use std::io::ErrorKind;
@@ -32,6 +39,9 @@ pub struct McpServer {
     pub connection: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub command_string: Option<String>,
+    /// Program and leading arguments, as written by gemini-cli's own config format
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub command: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub args: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -121,6 +131,7 @@ fn read_mcp_config() -> Result<McpServers> {
                         transport: "stdio".to_string(), // Claude format assumes stdio
                         connection: None,
                         command_string: Some(server.command),
+                        command: None,
                         args: Some(server.args),
                         env: Some(server.env),
                         auto_execute: Some(Vec::new()), // Claude format doesn't specify auto_execute
@@ -172,56 +183,50 @@ fn read_mcp_config() -> Result<McpServers> {
     }
 }
 
-// Write MCP servers configuration in the Claude-compatible format
+// Write MCP servers configuration in the Claude-compatible format.
+// Settings this tool does not manage (startup, timeouts, sandbox, ...) are kept
+// from the existing entry of each server.
 fn write_mcp_config(servers: &McpServers) -> Result<()> {
     let config_path = get_mcp_config_path()?;
+    let mut existing = read_existing_entries(&config_path);
 
     // Convert to Claude-compatible format
-    let mut claude_servers = HashMap::new();
+    let mut claude_servers = serde_json::Map::new();
 
     for server in &servers.servers {
+        let command_parts = server.command.clone().unwrap_or_default();
         let command = server
             .command_string
             .clone()
-            .or_else(|| {
-                server
-                    .args
-                    .as_ref()
-                    .and_then(|cmd| cmd.first().cloned())
-            })
+            .or_else(|| command_parts.first().cloned())
             .unwrap_or_default();
 
-        let args = server.args.clone().unwrap_or_else(|| {
-            // If we have a command array with more than one element, use all but the first as args
-            server
-                .args
-                .as_ref()
-                .map(|cmd| {
-                    if cmd.len() > 1 {
-                        cmd[1..].to_vec()
-                    } else {
-                        Vec::new()
-                    }
-                })
-                .unwrap_or_default()
-        });
+        // Arguments after the program in a command array come before the listed args
+        let mut args: Vec<String> = if server.command_string.is_some() {
+            Vec::new()
+        } else {
+            command_parts.iter().skip(1).cloned().collect()
+        };
+        args.extend(server.args.clone().unwrap_or_default());
 
         let env = server.env.clone().unwrap_or_default();
 
-        claude_servers.insert(
-            server.name.clone(),
-            ClaudeServer {
-                command,
-                args,
-                env,
-                enabled: server.enabled,
-            },
-        );
+        let managed = serde_json::to_value(ClaudeServer {
+            command,
+            args,
+            env,
+            enabled: server.enabled,
+        })
+        .context("Failed to serialize MCP server entry")?;
+
+        let mut entry = existing.remove(&server.name).unwrap_or_default();
+        if let Value::Object(managed) = managed {
+            entry.extend(managed);
+        }
+        claude_servers.insert(server.name.clone(), Value::Object(entry));
     }
 
-    let claude_config = ClaudeServersConfig {
-        mcp_servers: claude_servers,
-    };
+    let claude_config = serde_json::json!({ "mcpServers": claude_servers });
 
     // Serialize in the Claude-compatible format
     let content = serde_json::to_string_pretty(&claude_config)
@@ -242,6 +247,53 @@ fn write_mcp_config(servers: &McpServers) -> Result<()> {
     Ok(())
 }
 
+// Entries of the existing config file by server name, in any of the supported formats
+fn read_existing_entries(config_path: &PathBuf) -> HashMap<String, serde_json::Map<String, Value>> {
+    let Ok(content) = fs::read_to_string(config_path) else {
+        return HashMap::new();
+    };
+    let Ok(value) = serde_json::from_str::<Value>(&content) else {
+        return HashMap::new();
+    };
+
+    let mut entries = HashMap::new();
+    match value {
+        Value::Object(mut root) => {
+            if let Some(Value::Object(servers)) = root.remove("mcpServers") {
+                for (name, entry) in servers {
+                    if let Value::Object(entry) = entry {
+                        entries.insert(name, entry);
+                    }
+                }
+            } else if let Some(Value::Array(servers)) = root.remove("servers") {
+                collect_named_entries(servers, &mut entries);
+            }
+        }
+        Value::Array(servers) => collect_named_entries(servers, &mut entries),
+        _ => {}
+    }
+    entries
+}
+
+// Legacy array entries carry their name and command in fields the Claude format replaces
+fn collect_named_entries(
+    servers: Vec<Value>,
+    entries: &mut HashMap<String, serde_json::Map<String, Value>>,
+) {
+    for server in servers {
+        let Value::Object(mut entry) = server else {
+            continue;
+        };
+        let Some(Value::String(name)) = entry.remove("name") else {
+            continue;
+        };
+        for legacy in ["transport", "connection", "command_string", "command"] {
+            entry.remove(legacy);
+        }
+        entries.insert(name, entry);
+    }
+}
+
 // List all MCP servers and their status
 pub async fn list_servers() -> Result<HashMap<String, ServerStatus>> {
     let config = read_mcp_config()?;
@@ -304,6 +356,7 @@ pub async fn enable_server(name: &str) -> Result<()> {
                     name: name.to_string(),
                     transport: "stdio".to_string(),
                     command_string: Some(command_str.clone()),
+                    command: None,
                     args: Some(Vec::new()),
                     connection: None,
                     enabled: Some(true),
@@ -346,6 +399,7 @@ pub async fn disable_server(name: &str) -> Result<()> {
                     name: name.to_string(),
                     transport: "stdio".to_string(),
                     command_string: Some(command_str.clone()),
+                    command: None,
                     args: Some(Vec::new()),
                     connection: None,
                     enabled: Some(false),
@@ -487,6 +541,7 @@ pub async fn install_server(path: &str, custom_name: Option<String>) -> Result<S
         name: name.clone(),
         transport: "stdio".to_string(),
         command_string: Some(command),
+        command: None,
         connection: None,
         enabled: Some(true),
         args: Some(args),
@@ -586,3 +641,155 @@ pub async fn migrate_mcp_config() -> Result<()> {
 
     Ok(())
 }
+
+// --- Live daemon management ---
+
+// Socket of the running mcp-hostd, as configured or at its standard location
+fn host_socket_path() -> PathBuf {
+    UnifiedConfig::load()
+        .mcp
+        .mcp_host_socket_path
+        .unwrap_or_else(|| {
+            dirs::runtime_dir()
+                .or_else(dirs::data_local_dir)
+                .unwrap_or_else(|| PathBuf::from("/tmp"))
+                .join("gemini-cli")
+                .join("mcp-hostd.sock")
+        })
+}
+
+// Connect to mcp-hostd, or None if it is not running
+async fn connect_host() -> Option<IpcClient> {
+    let socket_path = host_socket_path();
+    match IpcClient::connect(&socket_path, Hello::new("gemini-manager")).await {
+        Ok(client) => Some(client),
+        Err(IpcError::Io(e)) if e.kind() == ErrorKind::PermissionDenied => {
+            tracing::warn!("mcp-hostd refused the connection: {}", e);
+            None
+        }
+        Err(e) => {
+            tracing::debug!(
+                "mcp-hostd is not reachable at {}: {}",
+                socket_path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+// Send one request over the connection and decode the expected result
+async fn host_request<T: DeserializeOwned>(
+    client: &IpcClient,
+    request: &DaemonRequest,
+) -> Result<T> {
+    let payload: Value = client
+        .request(request)
+        .await
+        .context("Request to mcp-hostd failed")?;
+
+    DaemonResponse::decode_value::<T>(payload)
+        .context("Invalid response from mcp-hostd")?
+        .map_err(|e| anyhow!(e.message))
+}
+
+// Send a request to the running daemon, failing if it is not running
+async fn require_host<T: DeserializeOwned>(request: DaemonRequest) -> Result<T> {
+    let client = connect_host()
+        .await
+        .ok_or_else(|| anyhow!("mcp-hostd is not running"))?;
+    host_request(&client, &request).await
+}
+
+/// Servers affected by a configuration reload of the running daemon
+#[derive(Debug, Deserialize)]
+pub struct ConfigReload {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Servers restarted because their command, environment or transport changed
+    pub restarted: Vec<String>,
+    /// Servers whose other settings were applied without a restart
+    pub updated: Vec<String>,
+}
+
+// Status of the servers in the running daemon, or None if it is not running
+pub async fn live_servers() -> Result<Option<Vec<LiveServerStatus>>> {
+    let Some(client) = connect_host().await else {
+        return Ok(None);
+    };
+    let list: ServerList = host_request(&client, &DaemonRequest::ListServers).await?;
+    Ok(Some(list.servers))
+}
+
+// Start a server in the running daemon
+pub async fn start_live_server(name: &str) -> Result<LiveServerStatus> {
+    require_host(DaemonRequest::StartServer {
+        name: name.to_string(),
+    })
+    .await
+}
+
+// Stop a server in the running daemon
+pub async fn stop_live_server(name: &str) -> Result<LiveServerStatus> {
+    require_host(DaemonRequest::StopServer {
+        name: name.to_string(),
+    })
+    .await
+}
+
+// Restart a server in the running daemon
+pub async fn restart_live_server(name: &str) -> Result<LiveServerStatus> {
+    require_host(DaemonRequest::RestartServer {
+        name: name.to_string(),
+    })
+    .await
+}
+
+// Make the running daemon re-read mcp_servers.json; None if it is not running
+pub async fn reload_live_config() -> Result<Option<ConfigReload>> {
+    let Some(client) = connect_host().await else {
+        return Ok(None);
+    };
+    host_request(&client, &DaemonRequest::ReloadConfig)
+        .await
+        .map(Some)
+}
+
+pub fn colored_state(state: ServerState) -> ColoredString {
+    let label = state.to_string();
+    match state {
+        ServerState::Running => label.green(),
+        ServerState::Initializing => label.yellow(),
+        ServerState::Stopped => label.normal(),
+        ServerState::Failed => label.red(),
+    }
+}
+
+pub fn format_uptime(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+// One-line summary of a live server for listings
+pub fn describe_live_server(status: &LiveServerStatus) -> String {
+    let mut line = format!(
+        "{} ({}, {}), {} tools",
+        colored_state(status.state),
+        status.transport,
+        if status.eager { "eager" } else { "lazy" },
+        status.tools
+    );
+    if let Some(pid) = status.pid {
+        line.push_str(&format!(", pid {}", pid));
+    }
+    if let Some(uptime) = status.uptime_secs {
+        line.push_str(&format!(", up {}", format_uptime(uptime)));
+    }
+    if let Some(error) = &status.last_error {
+        line.push_str(&format!("\n    last error: {}", error.red()));
+    }
+    line
+}
@@ -0,0 +1,338 @@
+//! Approval of tool calls by the user of the client that sent the query
+//!
+//! Before the function calls of a model response run, each is checked with mcp-hostd.
+//! Calls its policy denies are refused, and calls of tools in their server's
+//! `auto_execute` list run. The others are sent to the client as approval requests,
+//! suspending the turn until the user allows, denies or edits the call, or denying it
+//! if there is no answer in time. For clients that do not answer approval requests,
+//! calls the policy allows run as before and calls it asks about are denied.
+//!
+//! mcp-hostd runs a call its policy asks about only with the token it issued when
+//! the call was checked, so approved calls are sent with the token of their check.
+
+use crate::mcp_client::McpHostClient;
+use crate::session::Session;
+use crate::tool_calls::{ToolCall, ToolTarget};
+use gemini_core::config::HappeConfig;
+use gemini_ipc::daemon_messages::{ToolCallCheck, ToolCallDecision};
+use gemini_ipc::happe_request::{ApprovalDecision, ApprovalRequest, RiskLevel};
+use gemini_mcp::gemini::FunctionCall;
+use gemini_mcp::ToolNameRegistry;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Session data key of the tools the user allowed always, one `server/tool` per line
+const ALLOWED_TOOLS_KEY: &str = "allowed_tools";
+
+/// Words in tool names that suggest a call changes or runs something
+const HIGH_RISK_WORDS: &[&str] = &[
+    "write", "edit", "delete", "remove", "move", "rename", "create", "update", "insert",
+    "drop", "execute", "exec", "run", "command", "shell", "kill", "install", "send",
+];
+
+/// Words in tool names that suggest a call only reads
+const LOW_RISK_WORDS: &[&str] = &[
+    "read", "get", "list", "search", "find", "show", "status", "info", "describe",
+];
+
+/// An approval request waiting for the client's decision
+pub struct PendingApproval {
+    pub request: ApprovalRequest,
+    reply: oneshot::Sender<ApprovalDecision>,
+}
+
+impl PendingApproval {
+    /// Resume the turn with the user's decision
+    pub fn answer(self, decision: ApprovalDecision) {
+        // The turn may have ended in the meantime, leaving nobody to resume
+        let _ = self.reply.send(decision);
+    }
+}
+
+/// Gets the user's decisions on the tool calls of one turn
+pub struct Approver {
+    requests: Option<mpsc::Sender<PendingApproval>>,
+    timeout: Duration,
+}
+
+impl Approver {
+    /// An approver for clients that do not answer approval requests, denying every
+    /// call it is asked about
+    pub fn non_interactive() -> Self {
+        Self {
+            requests: None,
+            timeout: DEFAULT_APPROVAL_TIMEOUT,
+        }
+    }
+
+    /// An approver that passes its requests to the returned receiver, for the
+    /// connection of the client to answer
+    pub fn channel(config: &HappeConfig) -> (Self, mpsc::Receiver<PendingApproval>) {
+        let (requests, receiver) = mpsc::channel(1);
+        let approver = Self {
+            requests: Some(requests),
+            timeout: approval_timeout(config),
+        };
+        (approver, receiver)
+    }
+
+    /// Whether a client answers the requests
+    pub fn is_interactive(&self) -> bool {
+        self.requests.is_some()
+    }
+
+    /// Ask the user about a call, denying it if there is no answer
+    pub async fn decide(&self, request: ApprovalRequest) -> ApprovalDecision {
+        let Some(requests) = &self.requests else {
+            return denied("the client does not answer approval requests");
+        };
+
+        let (reply, decision) = oneshot::channel();
+        if requests.send(PendingApproval { request, reply }).await.is_err() {
+            return denied("the client disconnected");
+        }
+        match tokio::time::timeout(self.timeout, decision).await {
+            Ok(Ok(decision)) => decision,
+            Ok(Err(_)) => denied("the client disconnected"),
+            Err(_) => denied(&format!(
+                "no decision within {} seconds",
+                self.timeout.as_secs()
+            )),
+        }
+    }
+}
+
+/// How long to wait for the user's decision on a call
+pub fn approval_timeout(config: &HappeConfig) -> Duration {
+    config
+        .approval_timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_APPROVAL_TIMEOUT)
+}
+
+fn denied(reason: &str) -> ApprovalDecision {
+    ApprovalDecision::Deny {
+        reason: Some(reason.to_string()),
+    }
+}
+
+/// Risk of a call: high if the policy asks about it or the tool name suggests it
+/// changes something, low if the name suggests it only reads, medium otherwise
+pub fn risk_level(tool: &str, decision: ToolCallDecision) -> RiskLevel {
+    if decision == ToolCallDecision::Ask {
+        return RiskLevel::High;
+    }
+    let lower = tool.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .collect();
+    if words.iter().any(|word| HIGH_RISK_WORDS.contains(word)) {
+        RiskLevel::High
+    } else if words.iter().any(|word| LOW_RISK_WORDS.contains(word)) {
+        RiskLevel::Low
+    } else {
+        RiskLevel::Medium
+    }
+}
+
+/// Check the function calls of a model response, asking the user about those that
+/// need approval one at a time in the order of the calls
+pub async fn review_calls(
+    mcp_client: &McpHostClient,
+    approver: &Approver,
+    session: &mut Session,
+    tool_names: &ToolNameRegistry,
+    calls: Vec<FunctionCall>,
+) -> Vec<ToolCall> {
+    let mut reviewed = Vec::with_capacity(calls.len());
+    for call in calls {
+        // Resolve the function name to the server and tool it was declared for
+        let target = match tool_names.resolve(&call.name) {
+            Some((server, tool)) => {
+                review_call(mcp_client, approver, session, server, tool, call.arguments).await
+            }
+            None => {
+                warn!(name = call.name, "Model called an unknown function");
+                Err(format!("Unknown function: {}", call.name))
+            }
+        };
+        reviewed.push(ToolCall {
+            function_name: call.name,
+            target,
+        });
+    }
+    reviewed
+}
+
+async fn review_call(
+    mcp_client: &McpHostClient,
+    approver: &Approver,
+    session: &mut Session,
+    server: &str,
+    tool: &str,
+    arguments: serde_json::Value,
+) -> Result<ToolTarget, String> {
+    let target = |arguments, approval_token| ToolTarget {
+        server: server.to_string(),
+        tool: tool.to_string(),
+        arguments,
+        approval_token,
+    };
+
+    // Every call is checked, since calls the policy asks about need the token
+    // mcp-hostd issues with the check even when the user has allowed them
+    let check = check_call(mcp_client, server, tool, &arguments).await;
+    match check.decision {
+        ToolCallDecision::Allow => return Ok(target(arguments, None)),
+        ToolCallDecision::Deny => return Err(policy_denied(check)),
+        // Only confirm calls the policy allows with users who can answer
+        ToolCallDecision::Confirm if !approver.is_interactive() => {
+            return Ok(target(arguments, None))
+        }
+        ToolCallDecision::Confirm | ToolCallDecision::Ask => {}
+    }
+
+    let qualified_name = format!("{}/{}", server, tool);
+    if allowed_tools(session).any(|name| name == qualified_name) {
+        return Ok(target(arguments, check.approval_token));
+    }
+
+    let request = ApprovalRequest {
+        id: Uuid::new_v4().to_string(),
+        server: server.to_string(),
+        tool: tool.to_string(),
+        arguments: arguments.clone(),
+        risk: risk_level(tool, check.decision),
+        reason: check.rationale.clone(),
+    };
+    info!(server, tool, id = request.id, "Asking the user to approve a tool call");
+    match approver.decide(request).await {
+        ApprovalDecision::AllowOnce => Ok(target(arguments, check.approval_token)),
+        ApprovalDecision::AllowAlways => {
+            allow_always(session, &qualified_name);
+            Ok(target(arguments, check.approval_token))
+        }
+        ApprovalDecision::EditArgs { arguments } => {
+            // The token only covers the checked arguments, and the policy may treat
+            // the edited ones differently
+            let check = check_call(mcp_client, server, tool, &arguments).await;
+            match check.decision {
+                ToolCallDecision::Deny => Err(policy_denied(check)),
+                _ => Ok(target(arguments, check.approval_token)),
+            }
+        }
+        ApprovalDecision::Deny { reason } => {
+            info!(server, tool, ?reason, "User denied a tool call");
+            Err(match reason {
+                Some(reason) => format!("The user denied the tool call: {}", reason),
+                None => "The user denied the tool call".to_string(),
+            })
+        }
+    }
+}
+
+/// Check a call with mcp-hostd, asking the user about it if the check fails
+async fn check_call(
+    mcp_client: &McpHostClient,
+    server: &str,
+    tool: &str,
+    arguments: &serde_json::Value,
+) -> ToolCallCheck {
+    mcp_client
+        .check_tool_call(server, tool, arguments)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(server, tool, error = %e, "Failed to check tool call, asking the user");
+            ToolCallCheck {
+                decision: ToolCallDecision::Confirm,
+                rationale: None,
+                approval_token: None,
+            }
+        })
+}
+
+fn policy_denied(check: ToolCallCheck) -> String {
+    format!(
+        "Tool call denied by policy: {}",
+        check.rationale.unwrap_or_default()
+    )
+}
+
+fn allowed_tools(session: &Session) -> impl Iterator<Item = &str> {
+    session
+        .get(ALLOWED_TOOLS_KEY)
+        .map(String::as_str)
+        .unwrap_or_default()
+        .lines()
+}
+
+fn allow_always(session: &mut Session, qualified_name: &str) {
+    let mut names: Vec<&str> = allowed_tools(session).collect();
+    names.push(qualified_name);
+    let names = names.join("\n");
+    session.set(ALLOWED_TOOLS_KEY.to_string(), names);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risk_level() {
+        assert_eq!(risk_level("read_file", ToolCallDecision::Confirm), RiskLevel::Low);
+        assert_eq!(risk_level("write_file", ToolCallDecision::Confirm), RiskLevel::High);
+        assert_eq!(risk_level("fetch_url", ToolCallDecision::Confirm), RiskLevel::Medium);
+        assert_eq!(risk_level("list_directory", ToolCallDecision::Ask), RiskLevel::High);
+    }
+
+    #[tokio::test]
+    async fn test_approver_answers_and_times_out() {
+        let (approver, mut requests) = Approver::channel(&HappeConfig {
+            approval_timeout_secs: Some(1),
+            ..HappeConfig::default()
+        });
+        let request = |id: &str| ApprovalRequest {
+            id: id.to_string(),
+            server: "filesystem".to_string(),
+            tool: "write_file".to_string(),
+            arguments: serde_json::json!({}),
+            risk: RiskLevel::High,
+            reason: None,
+        };
+
+        let client = tokio::spawn(async move {
+            let pending = requests.recv().await.unwrap();
+            assert_eq!(pending.request.id, "a1");
+            pending.answer(ApprovalDecision::AllowOnce);
+            // Leave the second request unanswered
+            let _pending = requests.recv().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        });
+        assert_eq!(approver.decide(request("a1")).await, ApprovalDecision::AllowOnce);
+        assert_eq!(
+            approver.decide(request("a2")).await,
+            denied("no decision within 1 seconds")
+        );
+        client.await.unwrap();
+
+        assert_eq!(
+            Approver::non_interactive().decide(request("a3")).await,
+            denied("the client does not answer approval requests")
+        );
+    }
+
+    #[test]
+    fn test_allow_always_is_remembered() {
+        let mut session = Session::new("session".to_string());
+        allow_always(&mut session, "filesystem/write_file");
+        allow_always(&mut session, "shell/run");
+        assert_eq!(
+            allowed_tools(&session).collect::<Vec<_>>(),
+            ["filesystem/write_file", "shell/run"]
+        );
+    }
+}
@@ -4,11 +4,11 @@ use gemini_core::config::{UnifiedConfig, HappeConfig, get_unified_config_path};
 use gemini_happe::http_server;
 use gemini_happe::ipc_server;
 use gemini_happe::mcp_client::McpHostClient;
+use gemini_ipc::security::{self, SocketAccess};
 use std::net::SocketAddr;
 use tracing::{error, info, Level};
 use tracing_subscriber::FmtSubscriber;
 use std::path::PathBuf;
-use std::fs; // Added for create_dir_all
 
 #[derive(Parser, Debug)]
 #[command(name = "happe-daemon", about = "HAPPE daemon for Gemini Suite")]
@@ -56,11 +56,7 @@ struct Args {
 
 // Helper function to determine the MCP socket path (mirrors mcp-hostd logic)
 fn get_dynamic_mcp_socket_path() -> anyhow::Result<PathBuf> {
-    let base_dir = dirs::runtime_dir()
-        .or_else(dirs::data_local_dir)
-        .ok_or_else(|| anyhow::anyhow!("Could not determine runtime or data local directory"))?;
-    let socket_dir = base_dir.join("gemini-cli"); // Consistent directory name
-    fs::create_dir_all(&socket_dir) // Ensure the directory exists
+    let socket_dir = security::socket_dir()
         .map_err(|e| anyhow::anyhow!("Failed to create socket directory: {}", e))?;
     Ok(socket_dir.join("mcp-hostd.sock")) // Consistent socket filename
 }
@@ -213,10 +209,12 @@ async fn main() -> anyhow::Result<()> {
             .happe_socket_path
             .clone()
             .ok_or_else(|| anyhow::anyhow!("HAPPE IPC socket path not configured"))?;
+        let ipc_access = SocketAccess::from_config(&unified_config.ipc)
+            .map_err(|e| anyhow::anyhow!("Invalid IPC access settings: {}", e))?;
 
         tasks.push(tokio::spawn(async move {
             if let Err(e) =
-                ipc_server::run_server(ipc_socket_path, ipc_config, ipc_client, ipc_mcp_client).await
+                ipc_server::run_server(ipc_socket_path, ipc_access, ipc_config, ipc_client, ipc_mcp_client).await
             {
                 error!(error = %e, "IPC server failed");
             }
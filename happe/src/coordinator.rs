@@ -2,11 +2,17 @@ use crate::ida_client::IdaClient;
 use crate::llm_client;
 use crate::mcp_client::{self, McpHostClient};
 use crate::session::Session;
+use crate::tool_calls::ToolCalls;
 use anyhow::{anyhow, Result};
 use gemini_core::client::GeminiClient;
 use gemini_core::types::{Content, Part};
-use gemini_ipc::internal_messages::{ConversationTurn, MemoryItem};
-use gemini_mcp::gemini::build_mcp_system_prompt;
+use gemini_ipc::internal_messages::{default_ida_socket_path, ConversationTurn, MemoryItem};
+use gemini_ipc::happe_request::PromptReference;
+use gemini_mcp::gemini::{
+    build_mcp_system_prompt, prompt_to_contents, resource_contents_to_parts, tool_error_response,
+    tool_result_to_parts,
+};
+use gemini_mcp::ToolNameRegistry;
 use tracing::{debug, error, info, warn};
 use gemini_core::config::HappeConfig;
 
@@ -14,23 +20,31 @@ use gemini_core::config::HappeConfig;
 pub async fn process_query(
     config: &HappeConfig,
     mcp_client: &McpHostClient,
+    tool_calls: &ToolCalls<'_>,
     gemini_client: &GeminiClient,
     session: &mut Session,
     query: String,
+    prompt: Option<&PromptReference>,
 ) -> Result<String> {
     // Get conversation history from the session data
     let history_contents = get_conversation_history(session); // Now Vec<Content>
+
+    // Expand a referenced MCP prompt template into messages preceding the query
+    let prompt_contents = match prompt {
+        Some(reference) => expand_prompt(mcp_client, reference).await?,
+        None => Vec::new(),
+    };
     
     // Get recent conversation context for memory retrieval (last 3 turns)
     // let conversation_context = extract_conversation_context(&history, 3); // Commented out - history type changed
     let conversation_context = None; // Or retrieve differently if needed by IDA
     
     // Get relevant memories from IDA
-    let ida_socket_path_str = config
-        .ida_socket_path
-        .as_ref()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|| "/tmp/gemini_suite_ida.sock".to_string());
+    let ida_socket_path = match &config.ida_socket_path {
+        Some(path) => path.clone(),
+        None => default_ida_socket_path()?,
+    };
+    let ida_socket_path_str = ida_socket_path.to_string_lossy().to_string();
         
     let memories = match IdaClient::get_memories(&ida_socket_path_str, &query, conversation_context).await {
         Ok(mem) => {
@@ -55,9 +69,11 @@ pub async fn process_query(
     let mcp_capabilities_prompt =
         build_mcp_system_prompt(&capabilities.tools, &capabilities.resources);
 
+    let tool_names = ToolNameRegistry::new(&capabilities.tools);
     let tools = if !capabilities.tools.is_empty() {
         Some(vec![mcp_client::generate_tool_declarations(
             &capabilities.tools,
+            &tool_names,
         )])
     } else {
         None
@@ -68,15 +84,24 @@ pub async fn process_query(
     let system_prompt = format!("{}\n{}", base_system_prompt, mcp_capabilities_prompt);
 
     // Construct the parts for the current query + memories
-    let current_query_parts = construct_prompt_parts(&query, &memories);
+    let mut current_query_parts = construct_prompt_parts(&query, &memories);
+
+    // Attach the contents of resources mentioned as `@uri` or `@server:uri`
+    for mention in parse_resource_mentions(&query) {
+        current_query_parts.extend(read_mentioned_resource(mcp_client, &mention).await);
+    }
     let current_query_content = Content {
         parts: current_query_parts,
         role: Some("user".to_string()),
     };
     
-    // Combine history with the current query content for the first LLM call
+    // Combine history, the expanded prompt and the current query content for the first LLM call
     let mut initial_llm_contents = history_contents.clone();
-    initial_llm_contents.push(current_query_content.clone()); // Clone query content for history tracking
+    initial_llm_contents.extend(prompt_contents);
+    // A prompt reference may be sent without any query text of its own
+    if !query.trim().is_empty() {
+        initial_llm_contents.push(current_query_content.clone()); // Clone query content for history tracking
+    }
 
     // Log the user query part specifically
     debug!(query = query, "Constructed prompt content including history");
@@ -119,6 +144,7 @@ pub async fn process_query(
                     arguments: fc.arguments.clone(),
                 }),
                 function_response: None,
+                inline_data: None,
             });
         }
         if !model_parts.is_empty() {
@@ -144,61 +170,31 @@ pub async fn process_query(
         );
 
         let mut tool_results_parts: Vec<Part> = vec![];
-
-        for function_call in current_function_calls {
-            // Extract server and tool names from the function call name
-            // Allow both formats: 'server.tool_name' and 'tool_name'
-            // Also handle cases like 'command__mcp_execute_command' which should map to 'command-mcp'/'execute_command'
-            let (server_name, tool_name) = if function_call.name.contains('.') {
-                // Format is server.tool_name
-                let parts: Vec<&str> = function_call.name.splitn(2, '.').collect();
-                (parts[0].to_string(), parts[1].to_string())
-            } else if function_call.name.contains("__mcp_") {
-                // Format is server__mcp_tool_name (where server is command, filesystem, etc.)
-                let parts: Vec<&str> = function_call.name.splitn(2, "__mcp_").collect();
-                (format!("{}-mcp", parts[0]), parts[1].to_string())
-            } else {
-                // Format is just tool_name, try to infer server from first part
-                // Default to "command-mcp" for simple commands
-                let server = if function_call.name.starts_with("execute_") {
-                    "command-mcp".to_string()
-                } else if function_call.name.contains("file") || function_call.name.contains("directory") {
-                    "filesystem-mcp".to_string()
-                } else if function_call.name.contains("memory") {
-                    "memory-store-mcp".to_string()
-                } else {
-                    // Log the error but still try with command-mcp as fallback
-                    warn!(name = function_call.name, "Unrecognized function call format, using command-mcp as fallback");
-                    "command-mcp".to_string()
-                };
-                (server, function_call.name.clone())
-            };
-
-            info!(server = server_name, tool = tool_name, "Executing tool");
-
-            match mcp_client
-                .execute_tool(&server_name, &tool_name, function_call.arguments) // Use original arguments
-                .await
-            {
+        // Inline data returned by tools, keyed by the function call that produced it
+        let mut tool_attachments: Vec<(String, Vec<Part>)> = vec![];
+
+        // Review the calls, then run them concurrently; results come back in the
+        // order of the calls
+        let results = tool_calls
+            .run(session, &capabilities.tools, &tool_names, current_function_calls)
+            .await;
+        for (function_name, result) in results {
+            match result {
                 Ok(result) => {
-                    debug!(result = ?result, "Tool execution succeeded");
-                    // Add the successful tool result part for the LLM using the constructor
-                     tool_results_parts.push(
-                         Part::function_response(
-                             function_call.name.clone(), // Use original function name
-                             result // Result is already serde_json::Value
-                         )
-                     );
+                    debug!(function = function_name, result = ?result, "Tool execution succeeded");
+                    // Map MCP content to a function response plus inline-data attachments
+                    let parts = tool_result_to_parts(&function_name, &result);
+                    tool_results_parts.push(parts.function_response);
+                    if !parts.attachments.is_empty() {
+                        tool_attachments.push((function_name, parts.attachments));
+                    }
                 }
-                Err(e) => {
-                    error!(error = %e, "Tool execution failed");
+                Err(message) => {
                     // Add an error result part for the LLM using the constructor
-                    tool_results_parts.push(
-                        Part::function_response(
-                             function_call.name.clone(),
-                             serde_json::json!({ "error": format!("Tool execution failed: {}", e) })
-                        )
-                     );
+                    tool_results_parts.push(Part::function_response(
+                        function_name,
+                        tool_error_response(&message),
+                    ));
                 }
             }
         }
@@ -209,6 +205,22 @@ pub async fn process_query(
                 parts: tool_results_parts,
                 role: Some("function".to_string()), // Use "function" role for tool results
             });
+
+            // Images, audio and binary resources follow as a user content so the model can see them
+            if !tool_attachments.is_empty() {
+                let mut attachment_parts = vec![];
+                for (function_name, attachments) in tool_attachments {
+                    attachment_parts.push(Part::text(format!(
+                        "Attachments returned by {}:",
+                        function_name
+                    )));
+                    attachment_parts.extend(attachments);
+                }
+                current_contents.push(Content {
+                    parts: attachment_parts,
+                    role: Some("user".to_string()),
+                });
+            }
         } else {
              // No tool results were generated, break the loop
              break;
@@ -256,6 +268,7 @@ pub async fn process_query(
                         arguments: fc.arguments.clone(),
                     }),
                      function_response: None,
+                     inline_data: None,
                 });
             }
              if !model_parts.is_empty() {
@@ -356,6 +369,86 @@ pub fn update_session_history(session: &mut Session, turn: ConversationTurn) {
     }
 }
 
+/// Expand a `server/prompt` reference into conversation contents via the MCP host
+async fn expand_prompt(
+    mcp_client: &McpHostClient,
+    reference: &PromptReference,
+) -> Result<Vec<Content>> {
+    let (server_name, prompt_name) = reference
+        .name
+        .split_once('/')
+        .ok_or_else(|| anyhow!("Prompt '{}' must be named as server/prompt", reference.name))?;
+
+    let result = mcp_client
+        .get_prompt(server_name, prompt_name, reference.arguments.clone())
+        .await
+        .map_err(|e| anyhow!("Failed to expand prompt '{}': {}", reference.name, e))?;
+    let contents = prompt_to_contents(&result);
+    info!(prompt = %reference.name, messages = contents.len(), "Expanded MCP prompt");
+    Ok(contents)
+}
+
+/// A resource mentioned in a query as `@scheme://...` or `@server:uri`
+#[derive(Debug, Clone, PartialEq)]
+struct ResourceMention {
+    server: Option<String>,
+    uri: String,
+}
+
+/// Find resource mentions in a query. `@file:///path` names a URI and lets the MCP
+/// host find the server; `@server:uri` reads `uri` from a specific server.
+fn parse_resource_mentions(query: &str) -> Vec<ResourceMention> {
+    let mut mentions = Vec::new();
+    for word in query.split_whitespace() {
+        let Some(reference) = word.strip_prefix('@') else {
+            continue;
+        };
+        // Drop sentence punctuation following the mention
+        let reference = reference.trim_end_matches([',', ';', ':', '!', '?', ')', '.']);
+        let Some((head, tail)) = reference.split_once(':') else {
+            continue;
+        };
+        if head.is_empty() || tail.is_empty() {
+            continue;
+        }
+        let mention = if tail.starts_with("//") {
+            ResourceMention {
+                server: None,
+                uri: reference.to_string(),
+            }
+        } else {
+            ResourceMention {
+                server: Some(head.to_string()),
+                uri: tail.to_string(),
+            }
+        };
+        if !mentions.contains(&mention) {
+            mentions.push(mention);
+        }
+    }
+    mentions
+}
+
+/// Read a mentioned resource into prompt parts; failures are reported to the model as text
+async fn read_mentioned_resource(mcp_client: &McpHostClient, mention: &ResourceMention) -> Vec<Part> {
+    match mcp_client
+        .read_resource(mention.server.as_deref(), &mention.uri)
+        .await
+    {
+        Ok(resource) => {
+            info!(server = %resource.server, uri = %mention.uri, "Attached mentioned resource");
+            resource_contents_to_parts(&resource.contents)
+        }
+        Err(e) => {
+            warn!(uri = %mention.uri, error = %e, "Failed to read mentioned resource");
+            vec![Part::text(format!(
+                "(The resource {} could not be read: {})",
+                mention.uri, e
+            ))]
+        }
+    }
+}
+
 // Construct Content parts for the current query + memories
 // History will be prepended separately
 fn construct_prompt_parts(query: &str, memories: &[MemoryItem]) -> Vec<Part> {
@@ -417,3 +510,28 @@ fn extract_conversation_context(history: &[ConversationTurn], max_turns: usize)
         Some(context)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resource_mentions() {
+        let mentions = parse_resource_mentions(
+            "Compare @file:///tmp/a.txt with @docs:guide://intro, not user@example.com or @alice.",
+        );
+        assert_eq!(
+            mentions,
+            vec![
+                ResourceMention {
+                    server: None,
+                    uri: "file:///tmp/a.txt".to_string(),
+                },
+                ResourceMention {
+                    server: Some("docs".to_string()),
+                    uri: "guide://intro".to_string(),
+                },
+            ]
+        );
+    }
+}
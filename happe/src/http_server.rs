@@ -1,22 +1,31 @@
+use crate::approval::{self, Approver, PendingApproval};
 use crate::coordinator;
 use crate::mcp_client::McpHostClient;
+use crate::tool_calls::{ToolCallLimiter, ToolCalls};
 use gemini_core::config::HappeConfig;
 use crate::session::{InMemorySessionStore, Session, SessionStoreRef};
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use chrono::{Duration, Utc};
 use gemini_core::client::GeminiClient;
+use gemini_ipc::happe_request::{ApprovalDecision, ApprovalReply, ApprovalRequest, PromptReference};
 use gemini_ipc::internal_messages::ConversationTurn;
+use gemini_ipc::security::is_local_origin;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::{debug, error, info};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 /// Application state shared with all routes
@@ -25,15 +34,46 @@ pub struct AppState {
     config: Arc<HappeConfig>,
     gemini_client: Arc<GeminiClient>,
     mcp_client: Arc<McpHostClient>,
+    tool_limiter: Arc<ToolCallLimiter>,
     session_store: SessionStoreRef,
+    /// Turns suspended until the client answers an approval request, by request id
+    pending_turns: Arc<Mutex<HashMap<String, PendingTurn>>>,
+}
+
+/// A query running in the background for a client that answers approval requests
+struct Turn {
+    approvals: mpsc::Receiver<PendingApproval>,
+    response: JoinHandle<QueryResponse>,
+}
+
+/// A turn waiting for the answer to one of its approval requests
+struct PendingTurn {
+    approval: PendingApproval,
+    turn: Turn,
 }
 
 /// Request model for queries
 #[derive(Deserialize)]
 pub struct QueryRequest {
+    #[serde(default)]
     query: String,
     #[serde(default)]
     session_id: Option<String>,
+    /// MCP prompt template to expand before the query
+    #[serde(default)]
+    prompt: Option<PromptReference>,
+    /// Answer approval requests for tool calls; the response is then a `QueryEvent`
+    #[serde(default)]
+    approvals: bool,
+}
+
+/// What a query from a client that answers approval requests produced next:
+/// a tool call to answer at `/approvals/{id}`, or the final response
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueryEvent {
+    ApprovalRequired(ApprovalRequest),
+    Response(QueryResponse),
 }
 
 /// Response model for queries
@@ -50,6 +90,17 @@ pub struct QueryResponse {
 pub enum ApiError {
     InternalError(anyhow::Error),
     SessionError(String),
+    NotFound(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InternalError(e) => write!(f, "Internal server error: {}", e),
+            Self::SessionError(e) => write!(f, "Session error: {}", e),
+            Self::NotFound(e) => f.write_str(e),
+        }
+    }
 }
 
 impl IntoResponse for ApiError {
@@ -72,6 +123,15 @@ impl IntoResponse for ApiError {
                     error: Some(format!("Session error: {}", e)),
                 });
                 (StatusCode::BAD_REQUEST, body).into_response()
+            },
+            Self::NotFound(e) => {
+                debug!(error = %e, "Not found");
+                let body = Json(QueryResponse {
+                    response: String::new(),
+                    session_id: String::new(),
+                    error: Some(e),
+                });
+                (StatusCode::NOT_FOUND, body).into_response()
             }
         }
     }
@@ -91,15 +151,19 @@ pub async fn run_server(
 
     // Create shared state
     let state = AppState {
+        tool_limiter: Arc::new(ToolCallLimiter::new(&config)),
         config: Arc::new(config),
         gemini_client: Arc::new(gemini_client),
         mcp_client: Arc::new(mcp_client),
         session_store,
+        pending_turns: Arc::default(),
     };
 
-    // Set up CORS
+    // Set up CORS; only pages served from this machine may call the API
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(AllowOrigin::predicate(|origin: &HeaderValue, _| {
+            origin.to_str().is_ok_and(is_local_origin)
+        }))
         .allow_methods(Any)
         .allow_headers(Any);
 
@@ -107,6 +171,9 @@ pub async fn run_server(
     let app = Router::new()
         .route("/", get(health))
         .route("/query", post(handle_query))
+        .route("/approvals/:id", post(handle_approval))
+        .route("/ws", get(handle_websocket))
+        .layer(middleware::from_fn(check_origin))
         .layer(cors)
         .with_state(state);
 
@@ -117,6 +184,21 @@ pub async fn run_server(
         .map_err(|e| anyhow::anyhow!("Failed to start HTTP server: {}", e))
 }
 
+/// Reject requests from pages of other sites, which could otherwise submit queries
+/// and approve their tool calls. Requests without an Origin header come from
+/// non-browser clients and are accepted.
+async fn check_origin<B>(request: Request<B>, next: Next<B>) -> Response {
+    let allowed = match request.headers().get("origin") {
+        Some(origin) => origin.to_str().is_ok_and(is_local_origin),
+        None => true,
+    };
+    if !allowed {
+        warn!(origin = ?request.headers().get("origin"), "Rejected request from a non-local origin");
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    next.run(request).await
+}
+
 /// Health check handler
 async fn health() -> impl IntoResponse {
     "HAPPE is running"
@@ -127,21 +209,162 @@ async fn handle_query(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(payload): Json<QueryRequest>,
-) -> Result<Json<QueryResponse>, ApiError> {
-    // Get or create session
-    let session_id = get_or_create_session_id(headers, payload.session_id).await?;
+) -> Result<Response, ApiError> {
+    let session_id = get_or_create_session_id(headers, payload.session_id.clone()).await?;
+    if !payload.approvals {
+        let response = run_query(state, Approver::non_interactive(), session_id, payload).await?;
+        return Ok(Json(response).into_response());
+    }
+
+    // Run the turn in the background so it can wait for approvals across requests
+    let (approver, approvals) = Approver::channel(&state.config);
+    let response = tokio::spawn(run_query(state.clone(), approver, session_id.clone(), payload));
+    let response = async move {
+        match response.await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => error_response(session_id, e.to_string()),
+            Err(e) => error_response(session_id, format!("Query task failed: {}", e)),
+        }
+    };
+    let turn = Turn {
+        approvals,
+        response: tokio::spawn(response),
+    };
+    Ok(Json(next_event(&state, turn).await).into_response())
+}
+
+/// Handler for the answer to an approval request, returning what the turn produced next
+async fn handle_approval(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(decision): Json<ApprovalDecision>,
+) -> Result<Json<QueryEvent>, ApiError> {
+    let pending = lock_pending_turns(&state)
+        .remove(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("No pending approval request {}", id)))?;
+    pending.approval.answer(decision);
+    Ok(Json(next_event(&state, pending.turn).await))
+}
+
+/// Wait for the next approval request of a turn, parking the turn until it is
+/// answered, or for its response
+async fn next_event(state: &AppState, mut turn: Turn) -> QueryEvent {
+    tokio::select! {
+        Some(approval) = turn.approvals.recv() => {
+            let request = approval.request.clone();
+            lock_pending_turns(state).insert(request.id.clone(), PendingTurn { approval, turn });
+
+            // A turn whose request times out has denied it and goes on without us,
+            // so it is forgotten even if no other turn comes along
+            let timeout = approval::approval_timeout(&state.config);
+            let pending_turns = Arc::clone(&state.pending_turns);
+            let id = request.id.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                let expired = pending_turns.lock().unwrap_or_else(PoisonError::into_inner).remove(&id);
+                if expired.is_some() {
+                    debug!(request_id = %id, "Forgot turn whose approval request timed out");
+                }
+            });
+            QueryEvent::ApprovalRequired(request)
+        }
+        // The task never panics, since failures are turned into responses
+        response = &mut turn.response => QueryEvent::Response(response.unwrap_or_else(|e| {
+            error_response(String::new(), format!("Query task failed: {}", e))
+        })),
+    }
+}
+
+fn lock_pending_turns(state: &AppState) -> std::sync::MutexGuard<'_, HashMap<String, PendingTurn>> {
+    state.pending_turns.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Handler for WebSocket connections. Each query sent as a `QueryRequest` is
+/// answered with `QueryEvent`s; approval requests among them are answered with
+/// `ApprovalReply`s before the response arrives.
+async fn handle_websocket(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| async move {
+        if let Err(e) = run_websocket(socket, state).await {
+            warn!(error = %e, "WebSocket connection failed");
+        }
+    })
+}
+
+async fn run_websocket(mut socket: WebSocket, state: AppState) -> anyhow::Result<()> {
+    while let Some(message) = socket.recv().await {
+        let request: QueryRequest = match message? {
+            Message::Text(text) => serde_json::from_str(&text)?,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let session_id = match &request.session_id {
+            Some(id) if !id.is_empty() => id.clone(),
+            _ => Uuid::new_v4().to_string(),
+        };
+
+        let (approver, mut approvals) = Approver::channel(&state.config);
+        let turn = run_query(state.clone(), approver, session_id.clone(), request);
+        tokio::pin!(turn);
+        let mut pending: HashMap<String, PendingApproval> = HashMap::new();
+        let response = loop {
+            tokio::select! {
+                response = &mut turn => {
+                    break response.unwrap_or_else(|e| error_response(session_id.clone(), e.to_string()))
+                }
+                Some(approval) = approvals.recv() => {
+                    let event = QueryEvent::ApprovalRequired(approval.request.clone());
+                    socket.send(Message::Text(serde_json::to_string(&event)?)).await?;
+                    pending.insert(approval.request.id.clone(), approval);
+                }
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        let reply: ApprovalReply = match serde_json::from_str(&text) {
+                            Ok(reply) => reply,
+                            Err(e) => {
+                                warn!(error = %e, "Ignoring invalid approval reply");
+                                continue;
+                            }
+                        };
+                        match pending.remove(&reply.id) {
+                            Some(approval) => approval.answer(reply.decision),
+                            None => warn!(id = %reply.id, "Ignoring reply to an unknown approval request"),
+                        }
+                    }
+                    // The turn goes on without the client, denying what needs approval
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                },
+            }
+        };
+        let event = QueryEvent::Response(response);
+        socket.send(Message::Text(serde_json::to_string(&event)?)).await?;
+    }
+    Ok(())
+}
+
+/// Process a query in its session, reporting failures in the response
+async fn run_query(
+    state: AppState,
+    approver: Approver,
+    session_id: String,
+    payload: QueryRequest,
+) -> Result<QueryResponse, ApiError> {
     let mut session = get_or_create_session(&state.session_store, &session_id).await?;
     
     // Set session expiry (1 hour from now)
     session.set_expiry(Utc::now() + Duration::hours(1));
     
     // Process the query
+    let tool_calls = ToolCalls::new(&state.mcp_client, &state.tool_limiter, &approver);
     match coordinator::process_query(
         &state.config,
         &state.mcp_client,
+        &tool_calls,
         &state.gemini_client,
         &mut session,
-        payload.query.clone(),
+        payload.query,
+        payload.prompt.as_ref(),
     )
     .await
     {
@@ -152,11 +375,11 @@ async fn handle_query(
                 // Continue despite error
             }
             
-            Ok(Json(QueryResponse {
+            Ok(QueryResponse {
                 response,
                 session_id: session.id,
                 error: None,
-            }))
+            })
         },
         Err(e) => {
             error!(error = %e, "Failed to process query");
@@ -166,15 +389,19 @@ async fn handle_query(
                 error!(error = %save_err, "Failed to save session after query error");
             }
             
-            Ok(Json(QueryResponse {
-                response: String::new(),
-                session_id: session.id,
-                error: Some(format!("Failed to process query: {}", e)),
-            }))
+            Ok(error_response(session.id, format!("Failed to process query: {}", e)))
         }
     }
 }
 
+fn error_response(session_id: String, error: String) -> QueryResponse {
+    QueryResponse {
+        response: String::new(),
+        session_id,
+        error: Some(error),
+    }
+}
+
 /// Get or create a session ID from headers or request payload
 async fn get_or_create_session_id(
     headers: HeaderMap,
@@ -221,3 +448,33 @@ async fn get_or_create_session(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_check_origin() {
+        let app = Router::new()
+            .route("/", get(health))
+            .layer(middleware::from_fn(check_origin));
+        let status = |origin: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = Request::builder().uri("/");
+                if let Some(origin) = origin {
+                    request = request.header("origin", origin);
+                }
+                let request = request.body(Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(status(None).await, StatusCode::OK);
+        assert_eq!(status(Some("http://localhost:5173")).await, StatusCode::OK);
+        assert_eq!(status(Some("https://example.com")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Some("null")).await, StatusCode::FORBIDDEN);
+    }
+}
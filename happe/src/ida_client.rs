@@ -1,8 +1,7 @@
+use gemini_ipc::framing::{Hello, IpcClient, IpcError};
 use gemini_ipc::internal_messages::{ConversationTurn, InternalMessage, MemoryItem};
 use std::time::Duration;
 use thiserror::Error;
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
-use tokio::net::UnixStream;
 use tracing::{debug, error, warn};
 
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(2); // Timeout for establishing connection
@@ -13,13 +12,11 @@ const RETRY_DELAY: Duration = Duration::from_millis(100);
 #[derive(Error, Debug)]
 pub enum IdaClientError {
     #[error("Failed to connect to IDA socket {0}: {1}")]
-    ConnectionFailed(String, #[source] io::Error),
+    ConnectionFailed(String, #[source] IpcError),
     #[error("Connection attempt timed out after {0:?}")]
     ConnectionTimeout(Duration),
-    #[error("IO error during communication with IDA: {0}")]
-    Io(#[from] io::Error),
-    #[error("Serialization error: {0}")]
-    Serialization(#[from] serde_json::Error),
+    #[error("Communication with IDA failed: {0}")]
+    Ipc(#[from] IpcError),
     #[error("Received unexpected response type from IDA")]
     UnexpectedResponse,
 }
@@ -34,10 +31,11 @@ pub struct IdaClient {}
 
 impl IdaClient {
     // Helper function to connect with retries and timeout
-    async fn connect_with_retry(socket_path: &str) -> Result<UnixStream> {
+    async fn connect_with_retry(socket_path: &str) -> Result<IpcClient> {
         let mut last_error = None;
         for attempt in 0..=MAX_RETRIES {
-            match tokio::time::timeout(CONNECT_TIMEOUT, UnixStream::connect(socket_path)).await {
+            let connect = IpcClient::connect(socket_path, Hello::new("happe"));
+            match tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
                 Ok(Ok(stream)) => {
                     debug!("Connected to IDA socket {}", socket_path);
                     return Ok(stream);
@@ -66,10 +64,10 @@ impl IdaClient {
         Err(IdaClientError::ConnectionFailed(
             socket_path.to_string(),
             last_error.unwrap_or_else(|| {
-                io::Error::new(
+                IpcError::Io(io::Error::new(
                     io::ErrorKind::Other,
                     "Connection attempt failed without specific error",
-                )
+                ))
             }),
         ))
     }
@@ -80,10 +78,7 @@ impl IdaClient {
         query: &str,
         conversation_context: Option<String>
     ) -> Result<Vec<MemoryItem>> {
-        let stream = Self::connect_with_retry(socket_path).await?;
-        let (reader, writer) = stream.into_split();
-        let mut buf_reader = BufReader::new(reader);
-        let mut buf_writer = BufWriter::new(writer);
+        let client = Self::connect_with_retry(socket_path).await?;
 
         // Log before moving the value
         let has_context = conversation_context.is_some();
@@ -93,43 +88,29 @@ impl IdaClient {
             query: query.to_string(),
             conversation_context, // Include the conversation context
         };
-        let serialized = serde_json::to_vec(&request)?;
-        let len_bytes = (serialized.len() as u32).to_be_bytes();
-        buf_writer.write_all(&len_bytes).await?;
-        buf_writer.write_all(&serialized).await?;
-        buf_writer.flush().await?;
-        debug!(query, has_context, "Sent GetMemoriesRequest");
-
-        // Receive response
-        let mut len_bytes_resp = [0u8; 4];
-        buf_reader.read_exact(&mut len_bytes_resp).await?;
-        let len_resp = u32::from_be_bytes(len_bytes_resp) as usize;
-        let mut buffer_resp = vec![0u8; len_resp];
-        buf_reader.read_exact(&mut buffer_resp).await?;
-        let response: InternalMessage = serde_json::from_slice(&buffer_resp)?;
+        debug!(query, has_context, "Sending GetMemoriesRequest");
+        let response: InternalMessage = client.request(&request).await?;
         debug!("Received response from IDA");
 
         match response {
             InternalMessage::GetMemoriesResponse { memories } => Ok(memories),
             _ => Err(IdaClientError::UnexpectedResponse),
         }
-        // Connection automatically closed when stream/reader/writer go out of scope
+        // Connection automatically closed when the client goes out of scope
     }
 
-    // Static method: Connects, sends, disconnects.
+    // Static method: Connects, sends, waits for the acknowledgement, disconnects.
     pub async fn store_turn_async(socket_path: &str, turn_data: ConversationTurn) -> Result<()> {
-        let stream = Self::connect_with_retry(socket_path).await?;
-        let (_reader, writer) = stream.into_split(); // Don't need reader
-        let mut buf_writer = BufWriter::new(writer);
+        let client = Self::connect_with_retry(socket_path).await?;
 
+        // IDA acknowledges the turn and stores it in the background
         let request = InternalMessage::StoreTurnRequest { turn_data };
-        let serialized = serde_json::to_vec(&request)?;
-        let len_bytes = (serialized.len() as u32).to_be_bytes();
-        buf_writer.write_all(&len_bytes).await?;
-        buf_writer.write_all(&serialized).await?;
-        buf_writer.flush().await?;
+        match client.request(&request).await? {
+            InternalMessage::StoreTurnAccepted => {}
+            _ => return Err(IdaClientError::UnexpectedResponse),
+        }
         debug!("Sent StoreTurnRequest asynchronously");
         Ok(())
-        // Connection automatically closed when stream/writer go out of scope
+        // Connection automatically closed when the client goes out of scope
     }
 }
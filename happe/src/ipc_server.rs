@@ -1,43 +1,50 @@
+use crate::approval::{Approver, PendingApproval};
 use crate::coordinator;
 use crate::mcp_client::McpHostClient;
+use crate::tool_calls::{ToolCallLimiter, ToolCalls};
 use crate::session::{InMemorySessionStore, Session, SessionStoreRef};
 use anyhow::Result;
 use gemini_core::config::HappeConfig;
 use gemini_core::client::GeminiClient;
-use gemini_ipc::happe_request::{HappeQueryRequest, HappeQueryResponse};
+use gemini_ipc::framing::{self, ErrorCode, Hello, IncomingRequest};
+use gemini_ipc::happe_request::{
+    ApprovalReply, HappeEvent, HappeQueryRequest, HappeQueryResponse, APPROVALS_CAPABILITY,
+};
 use gemini_ipc::internal_messages::ConversationTurn;
-use std::path::Path;
+use gemini_ipc::security::{self, SocketAccess};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{UnixListener, UnixStream};
+use tokio::net::UnixStream;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 use chrono::{Duration, Utc};
 
+/// Session data key holding the client's working directory
+const SESSION_CWD_KEY: &str = "cwd";
+
 /// Shared state for the IPC server
 pub struct IpcServerState {
     config: Arc<HappeConfig>,
     gemini_client: Arc<GeminiClient>,
     mcp_client: Arc<McpHostClient>,
+    tool_limiter: Arc<ToolCallLimiter>,
     session_store: SessionStoreRef,
+    directories: Arc<ActiveDirectories>,
 }
 
-/// Run the IPC server
+/// Run the IPC server, accepting only the clients `access` allows
 pub async fn run_server(
     socket_path: impl AsRef<Path>,
+    access: SocketAccess,
     config: HappeConfig,
     gemini_client: GeminiClient,
     mcp_client: McpHostClient,
 ) -> Result<()> {
     let socket_path = socket_path.as_ref();
 
-    // Clean up existing socket if it exists
-    if socket_path.exists() {
-        tokio::fs::remove_file(socket_path).await?;
-    }
-
-    // Create the Unix socket listener
-    let listener = UnixListener::bind(socket_path)?;
+    // Create the Unix socket listener, replacing a stale socket
+    let listener = security::bind(socket_path)?;
     info!("Started IPC server on {}", socket_path.display());
 
     // Create session store
@@ -45,10 +52,12 @@ pub async fn run_server(
 
     // Create shared state
     let state = Arc::new(IpcServerState {
+        tool_limiter: Arc::new(ToolCallLimiter::new(&config)),
         config: Arc::new(config),
         gemini_client: Arc::new(gemini_client),
         mcp_client: Arc::new(mcp_client),
         session_store,
+        directories: Arc::default(),
     });
 
     // Start a periodic task to clean up expired sessions
@@ -73,10 +82,17 @@ pub async fn run_server(
     // Accept and handle connections
     loop {
         match listener.accept().await {
-            Ok((stream, _addr)) => {
-                debug!("Accepted new IPC connection");
+            Ok((mut stream, _addr)) => {
                 let state_clone = Arc::clone(&state);
+                let access = access.clone();
                 tokio::spawn(async move {
+                    match access.authorize(&mut stream).await {
+                        Ok(peer) => debug!(uid = peer.uid, pid = ?peer.pid, "Accepted new IPC connection"),
+                        Err(e) => {
+                            warn!(error = %e, "Rejected IPC connection");
+                            return;
+                        }
+                    }
                     if let Err(e) = handle_connection(stream, state_clone).await {
                         error!(error = %e, "Error handling IPC connection");
                     }
@@ -89,93 +105,176 @@ pub async fn run_server(
     }
 }
 
-/// Handle a single connection
-async fn handle_connection(mut stream: UnixStream, state: Arc<IpcServerState>) -> Result<()> {
-    // Read request size
-    let mut size_buf = [0u8; 4];
-    stream.read_exact(&mut size_buf).await?;
-    let msg_size = u32::from_le_bytes(size_buf) as usize;
-
-    // Read request data
-    let mut msg_buf = vec![0u8; msg_size];
-    stream.read_exact(&mut msg_buf).await?;
+/// Handle a connection, answering its requests concurrently
+async fn handle_connection(stream: UnixStream, state: Arc<IpcServerState>) -> Result<()> {
+    let hello = Hello::new("happe").with_capability(APPROVALS_CAPABILITY);
+    framing::serve(stream, hello, move |request| handle_request(request, Arc::clone(&state))).await?;
+    debug!("IPC client disconnected");
+    Ok(())
+}
 
+/// Answer a single request
+async fn handle_request(mut request: IncomingRequest, state: Arc<IpcServerState>) {
     // Parse request
-    let request: HappeQueryRequest = serde_json::from_slice(&msg_buf)?;
-    debug!(query = %request.query, "Received IPC query request");
+    let query: HappeQueryRequest = match request.parse() {
+        Ok(query) => query,
+        Err(e) => {
+            warn!(error = %e, "Received an invalid IPC request");
+            if let Err(e) = request.fail(ErrorCode::InvalidRequest, format!("Invalid request: {}", e)) {
+                warn!(error = %e, "Failed to send IPC error");
+            }
+            return;
+        }
+    };
+    debug!(query = %query.query, "Received IPC query request");
 
     // Check for special commands
-    let response = if request.query == "__LIST_SESSIONS__" {
+    let response = if query.query == "__LIST_SESSIONS__" {
         // Return list of active sessions
-        handle_list_sessions(&state).await?
-    } else if request.query == "__PING__" {
+        handle_list_sessions(&state).await
+    } else if query.query == "__PING__" {
         // It's a ping request - just acknowledge it
-        HappeQueryResponse {
+        Ok(HappeQueryResponse {
             response: "PONG".to_string(),
-            session_id: request.session_id.clone(),
+            session_id: query.session_id.clone(),
             error: None,
-        }
+        })
+    } else if query.approvals {
+        handle_interactive_query(&mut request, &state, query).await
     } else {
-        // Regular query processing
-        // Get or create a session
-        let session_id = request.session_id.unwrap_or_else(|| Uuid::new_v4().to_string());
-        let mut session = get_or_create_session(&state.session_store, &session_id).await?;
-        
-        // Set session expiry to 24 hours from now
-        session.set_expiry(Utc::now() + Duration::hours(24));
-
-        // Process the query
-        match coordinator::process_query(
-            &state.config,
-            &state.mcp_client,
-            &state.gemini_client,
-            &mut session,
-            request.query.clone(),
-        )
-        .await
-        {
-            Ok(response_text) => {
-                // Save the session (state was potentially modified in process_query)
-                if let Err(e) = state.session_store.save_session(session.clone()).await {
-                    error!(error = %e, "Failed to save session");
-                    // Continue despite error
-                }
-                
-                HappeQueryResponse {
-                    response: response_text,
-                    session_id: Some(session_id),
-                    error: None,
+        handle_query(&state, &Approver::non_interactive(), query).await
+    };
+
+    let sent = match response {
+        Ok(response) => request.respond(&response),
+        Err(e) => {
+            error!(error = %e, "Failed to handle IPC request");
+            request.fail(ErrorCode::Internal, e.to_string())
+        }
+    };
+    match sent {
+        Ok(()) => debug!("Sent IPC response"),
+        Err(e) => warn!(error = %e, "Failed to send IPC response"),
+    }
+}
+
+/// Handle a query from a client that answers approval requests, which are
+/// exchanged as messages of the request
+async fn handle_interactive_query(
+    request: &mut IncomingRequest,
+    state: &IpcServerState,
+    query: HappeQueryRequest,
+) -> Result<HappeQueryResponse> {
+    let (approver, mut approvals) = Approver::channel(&state.config);
+    let turn = handle_query(state, &approver, query);
+    tokio::pin!(turn);
+    let mut pending: HashMap<String, PendingApproval> = HashMap::new();
+    let mut connected = true;
+    loop {
+        tokio::select! {
+            response = &mut turn => return response,
+            Some(approval) = approvals.recv() => {
+                // Approvals dropped unanswered are denied
+                if !connected {
+                    continue;
                 }
-            },
-            Err(e) => {
-                error!(error = %e, "Failed to process query");
-                
-                // Save the session anyway to preserve any state changes
-                if let Err(save_err) = state.session_store.save_session(session.clone()).await {
-                    error!(error = %save_err, "Failed to save session after query error");
+                let event = HappeEvent::ApprovalRequired(approval.request.clone());
+                match request.send_message(&event) {
+                    Ok(()) => {
+                        pending.insert(approval.request.id.clone(), approval);
+                    }
+                    Err(e) => warn!(error = %e, "Failed to send approval request"),
                 }
-                
-                HappeQueryResponse {
-                    response: String::new(),
-                    session_id: Some(session_id),
-                    error: Some(format!("Failed to process query: {}", e)),
+            }
+            message = request.next_message(), if connected => match message {
+                Some(message) => match serde_json::from_value::<ApprovalReply>(message) {
+                    Ok(reply) => match pending.remove(&reply.id) {
+                        Some(approval) => approval.answer(reply.decision),
+                        // Answers to requests that already timed out are too late
+                        None => warn!(id = %reply.id, "Ignoring reply to an unknown approval request"),
+                    },
+                    Err(e) => warn!(error = %e, "Ignoring invalid approval reply"),
+                },
+                // The turn goes on without the client, denying what needs approval
+                None => {
+                    connected = false;
+                    pending.clear();
                 }
+            },
+        }
+    }
+}
+
+/// Process a query in its session, reporting failures in the response
+async fn handle_query(
+    state: &IpcServerState,
+    approver: &Approver,
+    request: HappeQueryRequest,
+) -> Result<HappeQueryResponse> {
+    // Get or create a session
+    let session_id = request.session_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut session = get_or_create_session(&state.session_store, &session_id).await?;
+
+    // Set session expiry to 24 hours from now
+    session.set_expiry(Utc::now() + Duration::hours(24));
+
+    // Keep MCP roots pointed at the client's project
+    let _directory = match &request.cwd {
+        Some(cwd) => {
+            session.set(SESSION_CWD_KEY.to_string(), cwd.to_string());
+            let (directory, alone) = ActiveDirectories::enter(&state.directories, Path::new(cwd));
+            if alone {
+                sync_workspace_root(&state.mcp_client, &session, cwd).await;
+            } else {
+                debug!(session_id = %session_id, "Queries in other directories are running; MCP workspace root unchanged");
             }
+            Some(directory)
         }
+        None => None,
     };
 
-    // Serialize and send response
-    let response_data = serde_json::to_vec(&response)?;
-    let response_size = response_data.len() as u32;
+    // Process the query
+    let tool_calls = ToolCalls::new(&state.mcp_client, &state.tool_limiter, approver);
+    let response = match coordinator::process_query(
+        &state.config,
+        &state.mcp_client,
+        &tool_calls,
+        &state.gemini_client,
+        &mut session,
+        request.query,
+        request.prompt.as_ref(),
+    )
+    .await
+    {
+        Ok(response_text) => {
+            // Save the session (state was potentially modified in process_query)
+            if let Err(e) = state.session_store.save_session(session.clone()).await {
+                error!(error = %e, "Failed to save session");
+                // Continue despite error
+            }
 
-    // Write response size
-    stream.write_all(&response_size.to_le_bytes()).await?;
+            HappeQueryResponse {
+                response: response_text,
+                session_id: Some(session_id),
+                error: None,
+            }
+        },
+        Err(e) => {
+            error!(error = %e, "Failed to process query");
 
-    // Write response data
-    stream.write_all(&response_data).await?;
+            // Save the session anyway to preserve any state changes
+            if let Err(save_err) = state.session_store.save_session(session.clone()).await {
+                error!(error = %save_err, "Failed to save session after query error");
+            }
 
-    debug!("Sent IPC response");
-    Ok(())
+            HappeQueryResponse {
+                response: String::new(),
+                session_id: Some(session_id),
+                error: Some(format!("Failed to process query: {}", e)),
+            }
+        }
+    };
+    Ok(response)
 }
 
 /// Handle listing active sessions
@@ -196,6 +295,57 @@ async fn handle_list_sessions(state: &IpcServerState) -> Result<HappeQueryRespon
     })
 }
 
+/// Make the session's working directory the workspace root reported to MCP
+/// servers; mcp-hostd only notifies the servers when the roots actually change.
+async fn sync_workspace_root(mcp_client: &McpHostClient, session: &Session, cwd: &str) {
+    match mcp_client.set_workspace_roots(vec![PathBuf::from(cwd)]).await {
+        Ok(true) => info!(session_id = %session.id, cwd = %cwd, "Updated MCP workspace root"),
+        Ok(false) => debug!(session_id = %session.id, "MCP workspace root unchanged"),
+        Err(e) => warn!(error = %e, "Failed to update MCP workspace root"),
+    }
+}
+
+/// Working directories of the queries in progress. The MCP workspace root is
+/// shared by every session, so it only follows a query's directory while no query
+/// in another directory is running.
+#[derive(Default)]
+struct ActiveDirectories {
+    queries: std::sync::Mutex<HashMap<PathBuf, usize>>,
+}
+
+impl ActiveDirectories {
+    /// Count a query running in `cwd` until the returned guard is dropped, and
+    /// whether `cwd` is the only directory in use
+    fn enter(directories: &Arc<Self>, cwd: &Path) -> (ActiveDirectory, bool) {
+        let mut queries = directories.queries.lock().unwrap();
+        *queries.entry(cwd.to_path_buf()).or_default() += 1;
+        let alone = queries.len() == 1;
+        let directory = ActiveDirectory {
+            directories: Arc::clone(directories),
+            cwd: cwd.to_path_buf(),
+        };
+        (directory, alone)
+    }
+}
+
+/// A query counted in `ActiveDirectories`
+struct ActiveDirectory {
+    directories: Arc<ActiveDirectories>,
+    cwd: PathBuf,
+}
+
+impl Drop for ActiveDirectory {
+    fn drop(&mut self) {
+        let mut queries = self.directories.queries.lock().unwrap();
+        if let Some(count) = queries.get_mut(&self.cwd) {
+            *count -= 1;
+            if *count == 0 {
+                queries.remove(&self.cwd);
+            }
+        }
+    }
+}
+
 /// Get an existing session or create a new one
 async fn get_or_create_session(
     session_store: &SessionStoreRef,
@@ -217,3 +367,68 @@ async fn get_or_create_session(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gemini_ipc::daemon_messages::{DaemonRequest, DaemonResponse, DaemonResult};
+    use std::sync::Mutex;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_workspace_root_follows_each_query() {
+        let socket_path = std::env::temp_dir().join(format!("happe-test-{}.sock", Uuid::new_v4()));
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let roots: Arc<Mutex<Vec<PathBuf>>> = Arc::default();
+
+        // Stand-in for mcp-hostd, keeping every root it is sent
+        let received = Arc::clone(&roots);
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = framing::serve(stream, Hello::new("mcp-hostd"), move |request| {
+                let received = Arc::clone(&received);
+                async move {
+                    let Ok(DaemonRequest::SetWorkspaceRoots { roots }) = request.parse() else {
+                        let _ = request.fail(ErrorCode::InvalidRequest, "unexpected request");
+                        return;
+                    };
+                    let mut received = received.lock().unwrap();
+                    let changed = received.last() != roots.first();
+                    received.extend(roots);
+                    let output = serde_json::json!({ "changed": changed });
+                    let _ = request.respond(&DaemonResponse::success(DaemonResult::ExecutionOutput(output)));
+                }
+            })
+            .await;
+        });
+
+        let mcp_client = McpHostClient::new(socket_path.clone());
+        let (a, b) = (Session::new("a".to_string()), Session::new("b".to_string()));
+        sync_workspace_root(&mcp_client, &a, "/work/a").await;
+        sync_workspace_root(&mcp_client, &b, "/work/b").await;
+        sync_workspace_root(&mcp_client, &a, "/work/a").await;
+        let _ = std::fs::remove_file(&socket_path);
+
+        assert_eq!(
+            *roots.lock().unwrap(),
+            ["/work/a", "/work/b", "/work/a"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn test_workspace_root_only_follows_a_lone_directory() {
+        let directories: Arc<ActiveDirectories> = Arc::default();
+        let (first, alone) = ActiveDirectories::enter(&directories, Path::new("/work/a"));
+        assert!(alone);
+        let (second, alone) = ActiveDirectories::enter(&directories, Path::new("/work/a"));
+        assert!(alone, "queries in the same directory agree on the root");
+        let (other, alone) = ActiveDirectories::enter(&directories, Path::new("/work/b"));
+        assert!(!alone, "a query in another directory is running");
+
+        drop((first, second));
+        let (_again, alone) = ActiveDirectories::enter(&directories, Path::new("/work/b"));
+        assert!(alone);
+        drop(other);
+        assert_eq!(directories.queries.lock().unwrap().len(), 1);
+    }
+}
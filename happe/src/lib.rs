@@ -1,3 +1,4 @@
+pub mod approval;
 pub mod coordinator;
 pub mod http_server;
 pub mod ida_client;
@@ -5,3 +6,4 @@ pub mod ipc_server;
 pub mod llm_client;
 pub mod mcp_client;
 pub mod session;
+pub mod tool_calls;
@@ -1,23 +1,33 @@
 use anyhow::{anyhow, Result};
-use gemini_core::rpc_types::ServerCapabilities;
+use gemini_core::rpc_types::{GetPromptResult, ListPromptsResult, Prompt, ServerCapabilities};
 use gemini_core::types::{FunctionDeclaration, Tool};
 use gemini_ipc::daemon_messages::{
-    DaemonRequest, DaemonResponse, DaemonResult, ResponsePayload, ResponseStatus,
+    DaemonRequest, DaemonResponse, DaemonResult, ResourceContents, ResponsePayload,
+    ResponseStatus, ToolCallCheck,
 };
+use gemini_ipc::framing::{Hello, IpcClient};
+use gemini_mcp::ToolNameRegistry;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
-/// Client to communicate with the MCP host daemon
+/// Client to communicate with the MCP host daemon. Clones share one connection,
+/// which carries their requests concurrently and is reopened when lost.
 #[derive(Clone)]
 pub struct McpHostClient {
     socket_path: PathBuf,
+    connection: Arc<Mutex<Option<IpcClient>>>,
 }
 
 impl McpHostClient {
     pub fn new(socket_path: PathBuf) -> Self {
-        Self { socket_path }
+        Self {
+            socket_path,
+            connection: Arc::default(),
+        }
     }
 
     /// Helper function to determine the default socket path if none is provided
@@ -29,48 +39,51 @@ impl McpHostClient {
         socket_dir.join("mcp-hostd.sock")
     }
 
-    /// Connect to the MCP host daemon and send a request
-    async fn send_request(&self, request: DaemonRequest) -> Result<DaemonResponse> {
-        // Connect to the Unix socket
-        let mut stream = UnixStream::connect(&self.socket_path)
+    /// The open connection to the daemon, connecting if there is none
+    async fn connection(&self) -> Result<IpcClient> {
+        let mut connection = self.connection.lock().await;
+        if let Some(client) = connection.as_ref().filter(|client| !client.is_closed()) {
+            return Ok(client.clone());
+        }
+        let client = IpcClient::connect(&self.socket_path, Hello::new("happe"))
             .await
             .map_err(|e| anyhow!("Failed to connect to MCP host daemon: {}", e))?;
+        *connection = Some(client.clone());
+        Ok(client)
+    }
 
-        // Serialize the request
-        let request_bytes = serde_json::to_vec(&request)
-            .map_err(|e| anyhow!("Failed to serialize request: {}", e))?;
-
-        // Send the length prefix followed by the data
-        stream
-            .write_u32(request_bytes.len() as u32)
-            .await
-            .map_err(|e| anyhow!("Failed to write request length: {}", e))?;
-
-        stream
-            .write_all(&request_bytes)
-            .await
-            .map_err(|e| anyhow!("Failed to write request data: {}", e))?;
-
-        // Read the response length
-        let response_len = stream
-            .read_u32()
-            .await
-            .map_err(|e| anyhow!("Failed to read response length: {}", e))?;
-
-        // Read the response payload
-        let mut buffer = vec![0u8; response_len as usize];
-        stream
-            .read_exact(&mut buffer)
-            .await
-            .map_err(|e| anyhow!("Failed to read response data: {}", e))?;
+    /// Connect to the MCP host daemon and send a request
+    async fn send_request(&self, request: DaemonRequest) -> Result<DaemonResponse> {
+        let payload = self.exchange(request).await?;
 
         // Deserialize the response
-        let response: DaemonResponse = serde_json::from_slice(&buffer)
+        let response: DaemonResponse = serde_json::from_value(payload)
             .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
 
         Ok(response)
     }
 
+    /// Send a request whose success payload is known to be a `T`
+    async fn send_typed_request<T: DeserializeOwned>(&self, request: DaemonRequest) -> Result<T> {
+        let payload = self.exchange(request).await?;
+
+        match DaemonResponse::decode_value::<T>(payload)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?
+        {
+            Ok(result) => Ok(result),
+            Err(error) => Err(anyhow!("MCP host daemon error: {}", error.message)),
+        }
+    }
+
+    /// Send a request and read back the raw response payload
+    async fn exchange(&self, request: DaemonRequest) -> Result<Value> {
+        self.connection()
+            .await?
+            .request(&request)
+            .await
+            .map_err(|e| anyhow!("MCP host daemon request failed: {}", e))
+    }
+
     /// Get capabilities from the MCP host daemon
     pub async fn get_capabilities(&self) -> Result<ServerCapabilities> {
         let response = self.send_request(DaemonRequest::GetCapabilities).await?;
@@ -88,51 +101,110 @@ impl McpHostClient {
         }
     }
 
-    /// Execute a tool via the MCP host daemon
+    /// Execute a tool via the MCP host daemon; the session id is recorded in its audit log.
+    /// `approval_token`, from checking the call, runs a call the daemon's policy asks about.
     pub async fn execute_tool(
         &self,
         server_name: &str,
         tool_name: &str,
         args: Value,
+        approval_token: Option<&str>,
+        session_id: Option<&str>,
     ) -> Result<Value> {
         let request = DaemonRequest::ExecuteTool {
             server: server_name.to_owned(),
             tool: tool_name.to_owned(),
             args,
+            approval_token: approval_token.map(str::to_owned),
+            session_id: session_id.map(str::to_owned),
         };
 
-        let response = self.send_request(request).await?;
-
-        match response {
-            DaemonResponse {
-                status: ResponseStatus::Success,
-                payload: ResponsePayload::Result(DaemonResult::ExecutionOutput(output)),
-            } => Ok(output),
-            DaemonResponse {
-                status: ResponseStatus::Error,
-                payload: ResponsePayload::Error(error),
-            } => Err(anyhow!("Tool execution failed: {}", error.message)),
-            _ => Err(anyhow!("Unexpected response from MCP host daemon")),
+        // Tool results are objects, which `DaemonResult` would take for capabilities
+        let payload = self.exchange(request).await?;
+        match DaemonResponse::decode_value::<Value>(payload)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?
+        {
+            Ok(output) => Ok(output),
+            Err(error) => Err(anyhow!("{}", error.message)),
         }
     }
+
+    /// Ask the MCP host daemon whether a tool call may run without the user's approval
+    pub async fn check_tool_call(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        args: &Value,
+    ) -> Result<ToolCallCheck> {
+        self.send_typed_request(DaemonRequest::CheckToolCall {
+            server: server_name.to_owned(),
+            tool: tool_name.to_owned(),
+            args: args.clone(),
+        })
+        .await
+    }
+
+    /// Replace the workspace directories the MCP host reports to servers as roots.
+    /// Returns true if the roots changed.
+    pub async fn set_workspace_roots(&self, roots: Vec<PathBuf>) -> Result<bool> {
+        let output: Value = self
+            .send_typed_request(DaemonRequest::SetWorkspaceRoots { roots })
+            .await?;
+
+        Ok(output
+            .get("changed")
+            .and_then(Value::as_bool)
+            .unwrap_or(false))
+    }
+
+    /// List prompt templates from all servers, named `server/prompt`
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>> {
+        let result: ListPromptsResult = self.send_typed_request(DaemonRequest::ListPrompts).await?;
+        Ok(result.prompts)
+    }
+
+    /// Expand a prompt template on a server
+    pub async fn get_prompt(
+        &self,
+        server_name: &str,
+        prompt_name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        self.send_typed_request(DaemonRequest::GetPrompt {
+            server: server_name.to_owned(),
+            prompt: prompt_name.to_owned(),
+            arguments,
+        })
+        .await
+    }
+
+    /// Read a resource by URI, on the given server or whichever server provides it
+    pub async fn read_resource(
+        &self,
+        server_name: Option<&str>,
+        uri: &str,
+    ) -> Result<ResourceContents> {
+        self.send_typed_request(DaemonRequest::ReadResource {
+            server: server_name.map(str::to_owned),
+            uri: uri.to_owned(),
+        })
+        .await
+    }
 }
 
-/// Generate a Tool declaration based on server capabilities
-pub fn generate_tool_declarations(tools: &[gemini_core::rpc_types::Tool]) -> Tool {
+/// Generate a Tool declaration based on server capabilities, naming each
+/// function as `names` does so calls can be resolved back to their tool
+pub fn generate_tool_declarations(
+    tools: &[gemini_core::rpc_types::Tool],
+    names: &ToolNameRegistry,
+) -> Tool {
     let function_declarations: Vec<FunctionDeclaration> = tools
         .iter()
         .filter_map(|tool| {
-            // Get the original name with server prefix
-            let original_name = tool.name.clone();
-            
-            // Process the name to make it compatible with Gemini API requirements
-            let normalized_name = original_name.replace("/", "."); // Replace slashes with dots
-            
-            // Validate the normalized name
-            if !is_valid_function_name(&normalized_name) {
-                tracing::warn!("Skipping tool with invalid name after normalization: {}", original_name);
+            let Some(function_name) = names.function_name(&tool.name) else {
+                tracing::warn!("Skipping tool without a server prefix: {}", tool.name);
                 return None;
-            }
+            };
 
             // Clone parameters and ensure the type field is set
             let mut parameters = tool
@@ -155,7 +227,7 @@ pub fn generate_tool_declarations(tools: &[gemini_core::rpc_types::Tool]) -> Too
             }
 
             Some(FunctionDeclaration {
-                name: normalized_name,
+                name: function_name.to_string(),
                 description: tool.description.clone(),
                 parameters,
             })
@@ -166,20 +238,3 @@ pub fn generate_tool_declarations(tools: &[gemini_core::rpc_types::Tool]) -> Too
         function_declarations,
     }
 }
-
-/// Check if a function name is valid according to Gemini API requirements
-fn is_valid_function_name(name: &str) -> bool {
-    if name.is_empty() || name.len() > 64 {
-        return false;
-    }
-
-    // First character must be a letter or underscore
-    let first_char = name.chars().next().unwrap();
-    if !first_char.is_alphabetic() && first_char != '_' {
-        return false;
-    }
-
-    // Rest of the characters must be alphanumeric, underscore, dash, or dot
-    name.chars()
-        .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
-}
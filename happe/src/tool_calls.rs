@@ -0,0 +1,364 @@
+//! Concurrent execution of the function calls in a model response
+//!
+//! The calls are first reviewed one at a time, asking the user about those that need
+//! approval (see `approval`). A later call may depend on the effect of an earlier one,
+//! so the approved calls to a server run in the order the model made them, except that
+//! consecutive calls of tools the server annotates as read-only run together. Calls to
+//! different servers run at the same time. At most `tool-parallelism` calls run per
+//! response and `session-tool-parallelism` per session across its concurrent queries.
+//! Results are returned in the order of the calls, and a call that fails or times out
+//! is reported as an error without holding back the others.
+
+use crate::approval::{self, Approver};
+use crate::mcp_client::McpHostClient;
+use crate::session::Session;
+use futures::future::join_all;
+use gemini_core::config::HappeConfig;
+use gemini_core::rpc_types::Tool;
+use gemini_mcp::gemini::FunctionCall;
+use gemini_mcp::ToolNameRegistry;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{error, info};
+
+const DEFAULT_TOOL_PARALLELISM: usize = 4;
+const DEFAULT_SESSION_TOOL_PARALLELISM: usize = 8;
+const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Limits on running tool calls, shared by the queries of all sessions
+pub struct ToolCallLimiter {
+    parallelism: usize,
+    session_parallelism: usize,
+    timeout: Duration,
+    sessions: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ToolCallLimiter {
+    pub fn new(config: &HappeConfig) -> Self {
+        Self {
+            parallelism: config
+                .tool_parallelism
+                .unwrap_or(DEFAULT_TOOL_PARALLELISM)
+                .max(1),
+            session_parallelism: config
+                .session_tool_parallelism
+                .unwrap_or(DEFAULT_SESSION_TOOL_PARALLELISM)
+                .max(1),
+            timeout: config
+                .tool_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TOOL_TIMEOUT),
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Permits of a session; those of other sessions without running calls are dropped
+    fn session_permits(&self, session_id: &str) -> Arc<Semaphore> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.retain(|id, permits| id == session_id || Arc::strong_count(permits) > 1);
+        sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.session_parallelism)))
+            .clone()
+    }
+
+    /// Run `call` on every item within the limits, returning the results in item order.
+    /// Items are ordered as `order` places them with respect to each other.
+    pub async fn run_all<T, R, F, Fut>(
+        &self,
+        session_id: &str,
+        items: Vec<T>,
+        order: impl Fn(&T) -> CallOrder,
+        call: F,
+    ) -> Vec<Result<R, String>>
+    where
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = Result<R, String>>,
+    {
+        let count = items.len();
+        let queues = queue_items(items, order);
+
+        let batch = Semaphore::new(self.parallelism);
+        let session = self.session_permits(session_id);
+        let (batch, session, call) = (&batch, &session, &call);
+        let run = |item| async move {
+            // Neither semaphore is ever closed, so acquiring cannot fail
+            let _batch_permit = batch.acquire().await;
+            let _session_permit = session.acquire().await;
+            match tokio::time::timeout(self.timeout, call(item)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!(
+                    "Tool call timed out after {} seconds",
+                    self.timeout.as_secs()
+                )),
+            }
+        };
+
+        // Queues run at the same time, the groups of a queue one after another
+        let finished = join_all(queues.into_iter().map(|groups| async move {
+            let mut results = Vec::new();
+            for group in groups {
+                let (indices, items): (Vec<usize>, Vec<T>) = group.items.into_iter().unzip();
+                let group_results = join_all(items.into_iter().map(run)).await;
+                results.extend(indices.into_iter().zip(group_results));
+            }
+            results
+        }))
+        .await;
+
+        let mut results: Vec<Option<Result<R, String>>> = (0..count).map(|_| None).collect();
+        for (index, result) in finished.into_iter().flatten() {
+            results[index] = Some(result);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("every item belongs to a queue"))
+            .collect()
+    }
+}
+
+/// How a call is ordered with respect to the other calls of its response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallOrder {
+    /// Runs at the same time as every other call
+    Independent,
+    /// Runs after the earlier calls of the same queue, or together with them when
+    /// they and this call are all shared
+    Queued { queue: String, shared: bool },
+}
+
+/// Items that run together, with their position among all items
+struct Group<T> {
+    shared: bool,
+    items: Vec<(usize, T)>,
+}
+
+/// Split items into queues of groups: a queue's groups run one after another, the
+/// items of a group at the same time
+fn queue_items<T>(items: Vec<T>, order: impl Fn(&T) -> CallOrder) -> Vec<Vec<Group<T>>> {
+    let mut queues: Vec<Vec<Group<T>>> = Vec::new();
+    let mut queue_indices: HashMap<String, usize> = HashMap::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let (queue, shared) = match order(&item) {
+            CallOrder::Independent => {
+                queues.push(vec![Group {
+                    shared: false,
+                    items: vec![(index, item)],
+                }]);
+                continue;
+            }
+            CallOrder::Queued { queue, shared } => (queue, shared),
+        };
+        let queue = *queue_indices.entry(queue).or_insert_with(|| {
+            queues.push(Vec::new());
+            queues.len() - 1
+        });
+        let groups = &mut queues[queue];
+        match groups.last_mut() {
+            Some(group) if group.shared && shared => group.items.push((index, item)),
+            _ => groups.push(Group {
+                shared,
+                items: vec![(index, item)],
+            }),
+        }
+    }
+    queues
+}
+
+/// A function call of the model, resolved to its tool and reviewed
+pub struct ToolCall {
+    pub function_name: String,
+    /// What to run, or the error to report to the model instead
+    pub target: Result<ToolTarget, String>,
+}
+
+/// The tool a function call runs, with the arguments to run it with
+pub struct ToolTarget {
+    pub server: String,
+    pub tool: String,
+    pub arguments: Value,
+    /// Token mcp-hostd issued for this call when its policy asks about it, sent
+    /// once the user has approved the call
+    pub approval_token: Option<String>,
+}
+
+/// Runs the function calls of one query: reviews them with the approver of the
+/// client that sent it, then executes them within the shared limits
+pub struct ToolCalls<'a> {
+    mcp_client: &'a McpHostClient,
+    limiter: &'a ToolCallLimiter,
+    approver: &'a Approver,
+}
+
+impl<'a> ToolCalls<'a> {
+    pub fn new(
+        mcp_client: &'a McpHostClient,
+        limiter: &'a ToolCallLimiter,
+        approver: &'a Approver,
+    ) -> Self {
+        Self {
+            mcp_client,
+            limiter,
+            approver,
+        }
+    }
+
+    /// Run the function calls of a model response to `tools`. Each call's function
+    /// name is returned with its result, or with the error to report to the model, in
+    /// the order of `calls`.
+    pub async fn run(
+        &self,
+        session: &mut Session,
+        tools: &[Tool],
+        tool_names: &ToolNameRegistry,
+        calls: Vec<FunctionCall>,
+    ) -> Vec<(String, Result<Value, String>)> {
+        let calls =
+            approval::review_calls(self.mcp_client, self.approver, session, tool_names, calls)
+                .await;
+        let read_only: HashSet<&str> = tools
+            .iter()
+            .filter(|tool| tool.is_read_only())
+            .map(|tool| tool.name.as_str())
+            .collect();
+        execute_tool_calls(self.mcp_client, self.limiter, &session.id, &read_only, calls).await
+    }
+}
+
+/// Execute reviewed tool calls through mcp-hostd, those to a server in order unless
+/// their tools are in `read_only` (qualified `server/tool` names)
+async fn execute_tool_calls(
+    mcp_client: &McpHostClient,
+    limiter: &ToolCallLimiter,
+    session_id: &str,
+    read_only: &HashSet<&str>,
+    calls: Vec<ToolCall>,
+) -> Vec<(String, Result<Value, String>)> {
+    let names: Vec<String> = calls.iter().map(|call| call.function_name.clone()).collect();
+    let order = |call: &ToolCall| match &call.target {
+        Ok(target) => CallOrder::Queued {
+            queue: target.server.clone(),
+            shared: read_only.contains(format!("{}/{}", target.server, target.tool).as_str()),
+        },
+        Err(_) => CallOrder::Independent,
+    };
+    let results = limiter
+        .run_all(session_id, calls, order, |call| async move {
+            let target = call.target?;
+            info!(server = target.server, tool = target.tool, "Executing tool");
+            mcp_client
+                .execute_tool(
+                    &target.server,
+                    &target.tool,
+                    target.arguments,
+                    target.approval_token.as_deref(),
+                    Some(session_id),
+                )
+                .await
+                .map_err(|e| {
+                    error!(server = target.server, tool = target.tool, error = %e, "Tool execution failed");
+                    format!("Tool execution failed: {}", e)
+                })
+        })
+        .await;
+    names.into_iter().zip(results).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_run_all_limits_and_order() {
+        let limiter = ToolCallLimiter::new(&HappeConfig {
+            tool_parallelism: Some(3),
+            session_tool_parallelism: Some(2),
+            tool_timeout_secs: Some(1),
+            ..HappeConfig::default()
+        });
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        // Later calls finish first, one fails and one outlives the timeout
+        let results = limiter
+            .run_all("session", vec![40u64, 30, 20, 10, 5000], |_| CallOrder::Independent, |millis| {
+                let (running, peak) = (&running, &peak);
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(millis)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    if millis == 20 {
+                        Err("failed".to_string())
+                    } else {
+                        Ok(millis)
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(results[..4], [Ok(40), Ok(30), Err("failed".to_string()), Ok(10)]);
+        assert_eq!(results[4], Err("Tool call timed out after 1 seconds".to_string()));
+        assert_eq!(limiter.sessions.lock().unwrap().len(), 1);
+        limiter.session_permits("other");
+        assert_eq!(limiter.sessions.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_all_orders_calls_to_a_server() {
+        let limiter = ToolCallLimiter::new(&HappeConfig::default());
+        let events = Mutex::new(Vec::new());
+
+        // (queue, shared, name): the reads of "a" may overlap each other but not the
+        // writes around them, while "b" and the independent call run alongside "a"
+        let items = vec![
+            ("a", false, "write1"),
+            ("a", true, "read1"),
+            ("b", false, "other"),
+            ("a", true, "read2"),
+            ("a", false, "write2"),
+            ("", false, "independent"),
+        ];
+        let results = limiter
+            .run_all(
+                "session",
+                items,
+                |&(queue, shared, _)| match queue {
+                    "" => CallOrder::Independent,
+                    _ => CallOrder::Queued {
+                        queue: queue.to_string(),
+                        shared,
+                    },
+                },
+                |(_, _, name)| {
+                    let events = &events;
+                    async move {
+                        events.lock().unwrap().push(format!("start {}", name));
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        events.lock().unwrap().push(format!("end {}", name));
+                        Ok::<_, String>(name)
+                    }
+                },
+            )
+            .await;
+
+        assert_eq!(
+            results,
+            ["write1", "read1", "other", "read2", "write2", "independent"].map(Ok)
+        );
+        let events = events.into_inner().unwrap();
+        let position = |event: &str| events.iter().position(|e| e == event).unwrap();
+        assert!(position("end write1") < position("start read1"));
+        assert!(position("start read2") < position("end read1"));
+        assert!(position("end read1") < position("start write2"));
+        assert!(position("end read2") < position("start write2"));
+        assert!(position("start other") < position("end write1"));
+        assert!(position("start independent") < position("end write1"));
+    }
+}
@@ -11,6 +11,7 @@ use std::sync::Arc;
 
 use gemini_memory::MemoryStore;
 use gemini_core::config::UnifiedConfig;
+use gemini_ipc::security::SocketAccess;
 
 // Define command-line arguments using clap
 #[derive(Parser, Debug)]
@@ -106,10 +107,14 @@ async fn main() -> Result<()> {
         info!("No Broker LLM provider configured.");
     }
 
+    let access = SocketAccess::from_config(&unified_config.ipc)
+        .map_err(|e| anyhow!("Invalid IPC access settings: {}", e))?;
+
     // Start the IPC server, passing all components
     info!("Starting IPC server...");
     if let Err(e) = ipc_server::run_server(
         config.clone(), // Pass the IDA config (still gemini_core::IdaConfig type)
+        access,
         memory_store,
         None, // Pass None for McpHostInterface here too, if needed by run_server signature
         llm_client,
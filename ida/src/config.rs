@@ -2,6 +2,7 @@ use anyhow::{anyhow, Result};
 use gemini_core::config::{
     IdaConfig as CoreIdaConfig, UnifiedConfig,
 };
+use gemini_ipc::internal_messages::default_ida_socket_path;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -41,7 +42,8 @@ pub struct MemoryBrokerConfig {
 impl Default for IdaConfig {
     fn default() -> Self {
         Self {
-            ida_socket_path: PathBuf::from("/tmp/gemini_suite_ida.sock"),
+            ida_socket_path: default_ida_socket_path()
+                .unwrap_or_else(|_| PathBuf::from("ida-daemon.sock")),
             memory_db_path: PathBuf::from("memory/lance_db"),
             max_memory_results: 10,
             semantic_similarity_threshold: 0.7,
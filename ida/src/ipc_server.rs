@@ -1,15 +1,15 @@
 use crate::llm_clients::LLMClient;
 use crate::{memory_mcp_client, storage};
 use gemini_core::config::IdaConfig;
-use gemini_ipc::internal_messages::InternalMessage;
+use gemini_ipc::framing::{self, ErrorCode, Hello, IncomingRequest, IpcError};
+use gemini_ipc::internal_messages::{default_ida_socket_path, InternalMessage};
+use gemini_ipc::security::{self, SocketAccess};
 use gemini_memory::broker::McpHostInterface;
 use gemini_memory::MemoryStore;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{UnixListener, UnixStream};
+use tokio::net::UnixStream;
 use tracing::{debug, error, info, instrument, warn};
-use anyhow::anyhow;
 
 /// Configuration for the IDA daemon
 #[derive(Debug, Clone)]
@@ -32,6 +32,8 @@ pub enum ServerError {
     Memory(#[from] crate::memory_mcp_client::MemoryError),
     #[error("Anyhow error: {0}")]
     Anyhow(#[from] anyhow::Error),
+    #[error("IPC error: {0}")]
+    Ipc(#[from] IpcError),
 }
 
 /// Holds shared state for the IDA daemon and connection handlers
@@ -55,26 +57,21 @@ impl std::fmt::Debug for ServerState {
     }
 }
 
-#[instrument(skip(memory_store, mcp_host, llm_client))]
+#[instrument(skip(access, memory_store, mcp_host, llm_client))]
 pub async fn run_server(
     config: IdaConfig,
+    access: SocketAccess,
     memory_store: Arc<MemoryStore>,
     mcp_host: Option<Arc<dyn McpHostInterface + Send + Sync>>,
     llm_client: Option<Arc<dyn LLMClient + Send + Sync>>,
 ) -> Result<(), ServerError> {
-    let ipc_path_str = config
-        .ida_socket_path
-        .clone()
-        .ok_or_else(|| ServerError::Anyhow(anyhow!("IDA socket path not configured")))?;
-    let ipc_path = Path::new(&ipc_path_str);
-
-    // Clean up existing socket file if it exists
-    if ipc_path.exists() {
-        warn!("IPC socket file already exists, removing: {:?}", ipc_path);
-        tokio::fs::remove_file(ipc_path).await?;
-    }
+    let ipc_path = match &config.ida_socket_path {
+        Some(path) => path.clone(),
+        None => default_ida_socket_path()?,
+    };
 
-    let listener = UnixListener::bind(ipc_path)?;
+    // Replaces a socket file left behind by an earlier run
+    let listener = security::bind(&ipc_path)?;
     info!("IDA Daemon listening on IPC path: {:?}", ipc_path);
 
     // Create a cloneable state containing all necessary components
@@ -87,12 +84,19 @@ pub async fn run_server(
 
     loop {
         match listener.accept().await {
-            Ok((stream, _addr)) => {
-                info!("Accepted new IPC connection");
+            Ok((mut stream, _addr)) => {
                 // Clone server state for the new connection handler
                 let connection_state = server_state.clone();
-                // Spawn a task to handle this connection independently
+                let access = access.clone();
+                // Spawn a task to check and then handle this connection independently
                 tokio::spawn(async move {
+                    match access.authorize(&mut stream).await {
+                        Ok(peer) => info!("Accepted new IPC connection from uid {}", peer.uid),
+                        Err(e) => {
+                            warn!("Rejected IPC connection: {}", e);
+                            return;
+                        }
+                    }
                     if let Err(e) = handle_connection(stream, connection_state).await {
                         error!("Error handling connection: {}", e);
                     }
@@ -111,119 +115,95 @@ pub async fn run_server(
 }
 
 #[instrument(skip(stream, state), name = "ipc_connection_handler")]
-async fn handle_connection(
-    mut stream: UnixStream,
-    state: Arc<ServerState>,
-) -> Result<(), ServerError> {
-    let mut buffer = Vec::with_capacity(4096); // Start with 4KB, might need adjustment
-
-    loop {
-        // Read data from the stream
-        buffer.clear(); // Clear buffer for new message
-                        // Simple length-prefixing: Read u32 length first
-        let length = match stream.read_u32().await {
-            Ok(len) => len,
-            Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                info!("Connection closed by peer.");
-                break; // Clean exit
-            }
-            Err(e) => {
-                error!("Failed to read message length: {}", e);
-                return Err(ServerError::Io(e));
+async fn handle_connection(stream: UnixStream, state: Arc<ServerState>) -> Result<(), ServerError> {
+    framing::serve(stream, Hello::new("ida"), move |request| {
+        let state = state.clone();
+        async move {
+            if let Err(e) = handle_request(request, state).await {
+                error!("Failed to handle IPC request: {}", e);
             }
-        };
-
-        if length == 0 {
-            warn!("Received zero-length message, ignoring.");
-            continue;
         }
+    })
+    .await?;
+    info!("Connection closed by peer.");
+    Ok(())
+}
 
-        // Read the actual message bytes
-        buffer.resize(length as usize, 0);
-        if let Err(e) = stream.read_exact(&mut buffer).await {
-            error!("Failed to read message body: {}", e);
-            return Err(ServerError::Io(e));
+/// Answer one request; failures are reported to the client in an error frame
+async fn handle_request(
+    request: IncomingRequest,
+    state: Arc<ServerState>,
+) -> Result<(), ServerError> {
+    // Deserialize the message
+    let message: InternalMessage = match request.parse() {
+        Ok(msg) => msg,
+        Err(e) => {
+            error!("Failed to deserialize IPC message: {}", e);
+            request.fail(ErrorCode::InvalidRequest, format!("Invalid message: {}", e))?;
+            return Err(ServerError::Serialization(e));
         }
-
-        debug!("Received {} bytes from IPC stream.", buffer.len());
-
-        // Deserialize the message
-        let message: InternalMessage = match serde_json::from_slice(&buffer) {
-            Ok(msg) => msg,
-            Err(e) => {
-                error!("Failed to deserialize IPC message: {}", e);
-                // Optionally send an error back to the client?
-                return Err(ServerError::Serialization(e));
-            }
-        };
-
-        debug!("Deserialized message: {:?}", message);
-
-        // Process the message based on its type
-        match message {
-            InternalMessage::GetMemoriesRequest {
-                query,
+    };
+
+    debug!("Deserialized message: {:?}", message);
+
+    // Process the message based on its type
+    match message {
+        InternalMessage::GetMemoriesRequest {
+            query,
+            conversation_context,
+        } => {
+            info!("Processing GetMemoriesRequest for query: {}", query);
+
+            // Use max_memory_results from config, providing a default
+            let max_results = state.config.max_memory_results.unwrap_or(5);
+
+            // Use the real memory retrieval function
+            let memories = match memory_mcp_client::retrieve_memories(
+                &query,
+                state.memory_store.clone(),
+                max_results, // Use the resolved value
+                &state.llm_client,
                 conversation_context,
-            } => {
-                info!("Processing GetMemoriesRequest for query: {}", query);
-
-                // Use max_memory_results from config, providing a default
-                let max_results = state.config.max_memory_results.unwrap_or(5);
-
-                // Use the real memory retrieval function
-                let memories = memory_mcp_client::retrieve_memories(
-                    &query,
-                    state.memory_store.clone(),
-                    max_results, // Use the resolved value
-                    &state.llm_client,
-                    conversation_context,
-                )
-                .await?;
-
-                let response = InternalMessage::GetMemoriesResponse { memories };
-                send_message(&mut stream, &response).await?;
-            }
-            InternalMessage::StoreTurnRequest { turn_data } => {
-                info!("Processing StoreTurnRequest, spawning background task.");
+            )
+            .await
+            {
+                Ok(memories) => memories,
+                Err(e) => {
+                    request.fail(
+                        ErrorCode::Internal,
+                        format!("Failed to retrieve memories: {}", e),
+                    )?;
+                    return Err(e.into());
+                }
+            };
+
+            let response = InternalMessage::GetMemoriesResponse { memories };
+            request.respond(&response)?;
+            info!("Message sent successfully.");
+        }
+        InternalMessage::StoreTurnRequest { turn_data } => {
+            info!("Processing StoreTurnRequest, spawning background task.");
 
-                // Clone the memory store for the background task
-                let memory_store_clone = state.memory_store.clone();
+            // Clone the memory store for the background task
+            let memory_store_clone = state.memory_store.clone();
 
-                // Spawn a background task for storage and continue handling connection
-                tokio::spawn(async move {
-                    if let Err(e) = storage::handle_storage(turn_data, memory_store_clone).await {
-                        error!("Error in background storage task: {}", e);
-                    }
-                });
+            // Spawn a background task for storage and acknowledge the request right away
+            tokio::spawn(async move {
+                if let Err(e) = storage::handle_storage(turn_data, memory_store_clone).await {
+                    error!("Error in background storage task: {}", e);
+                }
+            });
 
-                // No response is sent for this message type
-            }
-            // Handle other message types if added later
-            _ => {
-                warn!("Received unhandled message type");
-                // Optionally send an error or ignore
-            }
+            request.respond(&InternalMessage::StoreTurnAccepted)?;
+        }
+        // Handle other message types if added later
+        _ => {
+            warn!("Received unhandled message type");
+            request.fail(ErrorCode::InvalidRequest, "Unhandled message type")?;
         }
     }
 
     Ok(())
 }
 
-// Helper function to send a message with length prefix
-async fn send_message(
-    stream: &mut UnixStream,
-    message: &InternalMessage,
-) -> Result<(), ServerError> {
-    let serialized = serde_json::to_vec(message)?;
-    let len = serialized.len() as u32;
-
-    debug!("Sending message length: {}", len);
-    stream.write_u32(len).await?;
-    debug!("Sending message body ({} bytes)", serialized.len());
-    stream.write_all(&serialized).await?;
-    stream.flush().await?; // Ensure data is sent
-    info!("Message sent successfully.");
-    Ok(())
-}
-
 // TODO: Add thiserror to Cargo.toml if not already present
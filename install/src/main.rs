@@ -11,6 +11,7 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::thread::sleep;
 use std::time::Duration;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
 use toml::Table;
 
 #[derive(Parser)]
@@ -140,7 +141,9 @@ fn get_runtime_dir() -> Result<PathBuf> {
         })
         .ok_or_else(|| anyhow!("Could not determine runtime or local data directory"))?;
     let runtime_dir = base_dir.join("gemini-suite");
-    fs::create_dir_all(&runtime_dir)?;
+    // Holds the daemon sockets, so only the owner may enter it
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(&runtime_dir)?;
+    fs::set_permissions(&runtime_dir, fs::Permissions::from_mode(0o700))?;
     Ok(runtime_dir)
 }
 
@@ -511,8 +514,9 @@ fn install_all(
     // 2. Build necessary binaries
     build_binaries(install_manager_tool)?;
 
-    // 3. Install CLI binary
+    // 3. Install CLI binary and the stdio bridge for external MCP clients
     install_binary("gemini-cli", install_dir)?;
+    install_binary("mcp-bridge", install_dir)?;
 
     // 4. Install Daemon binaries
     for daemon in daemons {
@@ -566,6 +570,7 @@ fn build_binaries(build_manager: bool) -> Result<()> {
     let mut binaries = vec![
         "gemini-cli",
         "mcp-hostd",
+        "mcp-bridge",
         "happe-daemon",
         "ida-daemon",
     ];
@@ -659,7 +664,6 @@ fn install_binary(bin_name: &str, install_dir: &Path) -> Result<()> {
     // Make executable
     #[cfg(unix)]
     {
-        use std::os::unix::fs::PermissionsExt;
         let mut perms = fs::metadata(&dst_path)?.permissions();
         perms.set_mode(0o755); // rwxr-xr-x
         fs::set_permissions(&dst_path, perms)?;
@@ -845,6 +849,12 @@ storage_path = "{}/memory/models"
 # Path to MCP host daemon socket
 # mcp_host_socket_path = "{}"
 
+[ipc]
+# Other users (uids) allowed to connect to the daemon sockets besides your own
+# allowed-uids = []
+# Secret clients must prove they know before each connection, for proxied sockets
+# shared-secret = "${{file:~/.config/gemini-suite/ipc-secret}}"
+
 [daemon-manager]
 # Where to install daemon executables
 # daemon_install_path = "{}"
@@ -953,8 +963,9 @@ fn uninstall_all(
         stop_daemon_if_running(daemon, &runtime_dir)?;
     }
 
-    // 2. Remove CLI binary
+    // 2. Remove CLI binary and the stdio bridge
     uninstall_binary("gemini-cli", install_dir)?;
+    uninstall_binary("mcp-bridge", install_dir)?;
 
     // 3. Remove Daemon binaries
     for daemon in daemons {
@@ -1046,8 +1057,9 @@ fn update_all(
     // 2. Build necessary binaries
     build_binaries(update_manager_tool)?;
 
-    // 3. Install/Update CLI binary
+    // 3. Install/Update CLI binary and the stdio bridge
     install_binary("gemini-cli", install_dir)?;
+    install_binary("mcp-bridge", install_dir)?;
 
     // 4. Install/Update Daemon binaries (if requested)
     if update_daemons {
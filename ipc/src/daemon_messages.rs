@@ -1,8 +1,14 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
 // Use the existing ServerCapabilities from core, assuming it's been moved to core::rpc_types
 // If not, adjust the path accordingly.
-use gemini_core::rpc_types::ServerCapabilities;
+use gemini_core::rpc_types::{
+    EmbeddedResource, GetPromptResult, ListPromptsResult, ListResourceTemplatesResult,
+    ServerCapabilities,
+};
 
 /// Represents a request sent from the CLI client to the MCP host daemon.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -15,12 +21,77 @@ pub enum DaemonRequest {
         server: String,
         tool: String,
         args: Value,
+        /// Token from the `CheckToolCall` of this call, sent once the user has
+        /// approved a call whose policy outcome is `ask`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        approval_token: Option<String>,
+        /// Client session the call belongs to, recorded in the audit log
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+    },
+    /// Request how a tool call would be treated, without running it.
+    /// Answered with `ExecutionOutput(ToolCallCheck)`.
+    CheckToolCall {
+        server: String,
+        tool: String,
+        args: Value,
     },
     /// Request to generate an embedding for text using the embedding server.
     GenerateEmbedding { text: String, model_variant: String },
     /// Request to get broker capabilities for MemoryStore
     GetBrokerCapabilities,
-    // Future requests can be added here, e.g., ShutdownDaemon, ListServers, etc.
+    /// Request to replace the workspace directories reported to servers as roots.
+    /// Answered with `ExecutionOutput({"changed": bool})`.
+    SetWorkspaceRoots { roots: Vec<PathBuf> },
+    /// Request to list prompt templates from all servers.
+    ListPrompts,
+    /// Request to expand a prompt template on a specific server.
+    GetPrompt {
+        server: String,
+        prompt: String,
+        #[serde(default)]
+        arguments: HashMap<String, String>,
+    },
+    /// Request to list resource templates from all running servers.
+    ListResourceTemplates,
+    /// Request to read a resource by URI, on the given server or whichever server provides it.
+    ReadResource { server: Option<String>, uri: String },
+    /// Request to subscribe to updates of a resource.
+    /// Answered with `ExecutionOutput({"subscribed": true})`.
+    SubscribeResource { server: String, uri: String },
+    /// Request to cancel a resource subscription.
+    /// Answered with `ExecutionOutput({"subscribed": false})`.
+    UnsubscribeResource { server: String, uri: String },
+    /// Request to fetch and clear the resource updates received since the last call
+    /// on this connection; every connection sees every update.
+    TakeResourceUpdates,
+    /// JSON-RPC message (or batch) from an external MCP client, answered by the daemon
+    /// acting as an MCP server. Answered with `ExecutionOutput({"message": response})`,
+    /// where `response` is `null` for notifications.
+    McpMessage { message: Value },
+    /// Request the status of every configured server.
+    ListServers,
+    /// Request to start a configured server; answered with its status.
+    StartServer { name: String },
+    /// Request to stop a running server; answered with its status.
+    StopServer { name: String },
+    /// Request to stop a server and start it again; answered with its status.
+    RestartServer { name: String },
+    /// Request to re-read `mcp_servers.json` and apply the differences. Answered with
+    /// `ExecutionOutput({"added": [..], "removed": [..], "restarted": [..], "updated": [..]})`.
+    ReloadConfig,
+    /// Request to read a resource from a specific server by name or URI.
+    /// Answered with `ExecutionOutput({"resource": resource})`, where `resource` is the
+    /// `resources/read` result. `params` are not part of `resources/read` and are ignored.
+    GetResource {
+        server: String,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<Value>,
+    },
+    /// Request to stop all servers and exit the daemon.
+    /// Answered with `ExecutionOutput({"shutdown": true})` before the servers are stopped.
+    Shutdown,
 }
 
 /// Represents a response sent from the MCP host daemon back to the CLI client.
@@ -60,14 +131,135 @@ pub enum DaemonResult {
     Embedding(Vec<f32>),
     /// Contains broker capabilities for MemoryStore.
     BrokerCapabilities(BrokerCapabilities),
-    // Future success results can be added here
+    /// Contains the prompt templates of all servers, named `server/prompt`.
+    Prompts(ListPromptsResult),
+    /// Contains the messages of an expanded prompt template.
+    Prompt(GetPromptResult),
+    /// Contains the resource templates of all running servers, named `server/template`.
+    ResourceTemplates(ListResourceTemplatesResult),
+    /// Contains the contents of a resource and the server that provided it.
+    ResourceContents(ResourceContents),
+    /// Contains the subscribed resources that changed.
+    ResourceUpdates(ResourceUpdates),
+    /// Contains the status of every configured server.
+    Servers(ServerList),
+    /// Contains the status of a single server.
+    Server(ServerStatus),
 }
 
 /// Contains details about an error that occurred during daemon request processing.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DaemonError {
     pub message: String,
-    // Consider adding an optional error code or more details later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<DaemonErrorCode>,
+}
+
+/// Machine-readable reason for a daemon error
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DaemonErrorCode {
+    /// The tool policy denied the call; the message holds the rationale
+    PolicyDenied,
+    /// The tool policy requires the user's approval before the call can run
+    ApprovalRequired,
+}
+
+/// How the daemon treats a tool call, combining the tool policy with the
+/// server's `auto_execute` list
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ToolCallCheck {
+    pub decision: ToolCallDecision,
+    /// Rationale of the policy rule that decided, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<String>,
+    /// For `ask`, the token that runs this call with these arguments once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_token: Option<String>,
+}
+
+/// Outcome of checking a tool call
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallDecision {
+    /// Allowed by the policy and set to run without confirmation
+    Allow,
+    /// Allowed by the policy, but clients should confirm it with the user
+    Confirm,
+    /// The policy requires the user's approval; the call must be sent with the
+    /// check's `approval_token`
+    Ask,
+    /// The policy denies the call
+    Deny,
+}
+
+/// Contents of a resource read through the daemon
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResourceContents {
+    /// Server that provided the resource
+    pub server: String,
+    pub contents: Vec<EmbeddedResource>,
+}
+
+/// Subscribed resources that changed since the connection last sent `TakeResourceUpdates`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ResourceUpdates {
+    pub updates: Vec<ResourceUpdate>,
+}
+
+/// A resource that changed on a server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUpdate {
+    pub server: String,
+    pub uri: String,
+}
+
+/// Status of every server configured in the daemon
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ServerList {
+    pub servers: Vec<ServerStatus>,
+}
+
+/// Status of a server configured in the daemon
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServerStatus {
+    pub name: String,
+    pub state: ServerState,
+    /// `stdio`, `sse` or `websocket`
+    pub transport: String,
+    /// Whether the server starts with the daemon rather than on first use
+    pub eager: bool,
+    /// Counts reported by the running server, or remembered from its last run
+    pub tools: usize,
+    pub resources: usize,
+    pub prompts: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Lifecycle state of a server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerState {
+    Running,
+    Initializing,
+    Stopped,
+    Failed,
+}
+
+impl std::fmt::Display for ServerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ServerState::Running => "running",
+            ServerState::Initializing => "initializing",
+            ServerState::Stopped => "stopped",
+            ServerState::Failed => "failed",
+        })
+    }
 }
 
 /// Simplified capabilities structure for the memory broker
@@ -98,7 +290,48 @@ impl DaemonResponse {
     pub fn error(message: String) -> Self {
         Self {
             status: ResponseStatus::Error,
-            payload: ResponsePayload::Error(DaemonError { message }),
+            payload: ResponsePayload::Error(DaemonError {
+                message,
+                code: None,
+            }),
+        }
+    }
+
+    /// Creates an error response with a machine-readable code.
+    pub fn error_with_code(message: String, code: DaemonErrorCode) -> Self {
+        Self {
+            status: ResponseStatus::Error,
+            payload: ResponsePayload::Error(DaemonError {
+                message,
+                code: Some(code),
+            }),
+        }
+    }
+
+    /// Decodes a serialized response whose success payload is expected to be a `T`.
+    ///
+    /// `DaemonResult` is untagged, so any JSON object payload deserializes as
+    /// `Capabilities`; callers that know which result they asked for should use this.
+    pub fn decode<T: DeserializeOwned>(
+        bytes: &[u8],
+    ) -> Result<Result<T, DaemonError>, serde_json::Error> {
+        Self::decode_value(serde_json::from_slice(bytes)?)
+    }
+
+    /// Like `decode`, for a response already parsed as JSON, such as the payload
+    /// of a response frame
+    pub fn decode_value<T: DeserializeOwned>(
+        mut value: Value,
+    ) -> Result<Result<T, DaemonError>, serde_json::Error> {
+        let status = value
+            .as_object_mut()
+            .and_then(|obj| obj.remove("status"))
+            .map(serde_json::from_value::<ResponseStatus>)
+            .transpose()?
+            .ok_or_else(|| serde::de::Error::missing_field("status"))?;
+        match status {
+            ResponseStatus::Success => serde_json::from_value(value).map(Ok),
+            ResponseStatus::Error => serde_json::from_value(value).map(Err),
         }
     }
 }
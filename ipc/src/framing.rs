@@ -0,0 +1,848 @@
+// Framing shared by the sockets of mcp-hostd, HAPPE and IDA.
+//
+// Every message is a frame: a big-endian u32 length followed by that many bytes
+// of JSON, at most the receiver's `max_frame_size`. After the access checks of
+// `security`, the client sends a `Hello` with its protocol version, capabilities
+// and frame size limit, and the daemon answers with its own `Hello`, or with an
+// error frame when it does not speak that version.
+//
+// Each request carries an id chosen by the client, so many requests can be in
+// flight on one connection. The daemon answers a request with one response or
+// error frame with the same id. Before that, either side may send messages with
+// the id, as HAPPE and its clients do for tool-call approvals. Error frames
+// without an id concern the whole connection, which the daemon closes after
+// sending one.
+
+use crate::security;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Version of the framing protocol; both sides must speak the same one
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Largest frame accepted unless a `Hello` says otherwise, in bytes
+pub const MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+
+// Time either side waits for the other's hello
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First frame in each direction of a connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub version: u32,
+    /// Program on this side of the connection, for logs
+    pub name: String,
+    /// Optional features this side supports, such as HAPPE's `approvals`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Largest frame this side accepts
+    pub max_frame_size: u32,
+}
+
+impl Hello {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            name: name.into(),
+            capabilities: Vec::new(),
+            max_frame_size: MAX_FRAME_SIZE,
+        }
+    }
+
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.push(capability.into());
+        self
+    }
+
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// A frame on a daemon socket
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Frame {
+    Hello(Hello),
+    /// Client -> daemon: a request, answered by a response or error frame with its id
+    Request {
+        id: u64,
+        payload: Value,
+    },
+    /// Either direction: part of the exchange of a request that is not its answer
+    Message {
+        id: u64,
+        payload: Value,
+    },
+    /// Daemon -> client: the answer to a request
+    Response {
+        id: u64,
+        payload: Value,
+    },
+    /// Daemon -> client: the failure of a request, or of the whole connection
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+        #[serde(flatten)]
+        error: ErrorFrame,
+    },
+}
+
+/// What went wrong, in an error frame
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorFrame {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ErrorFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Machine-readable kind of an error frame
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// A frame could not be parsed or was not expected
+    InvalidFrame,
+    /// A frame exceeded the receiver's `max_frame_size`
+    FrameTooLarge,
+    /// The client's protocol version is not the daemon's
+    UnsupportedVersion,
+    /// The payload of a request is not a request the daemon understands
+    InvalidRequest,
+    /// The daemon failed to answer the request
+    Internal,
+}
+
+/// Failure of a connection or of one of its requests
+#[derive(Debug)]
+pub enum IpcError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A frame was larger than the receiving side accepts
+    FrameTooLarge {
+        size: usize,
+        limit: u32,
+    },
+    /// The daemon answered with an error frame
+    Remote(ErrorFrame),
+    /// The peer did not follow the protocol
+    Protocol(String),
+    /// The connection closed before the answer arrived
+    Disconnected(String),
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Json(e) => write!(f, "Invalid JSON: {}", e),
+            Self::FrameTooLarge { size, limit } => {
+                write!(
+                    f,
+                    "Frame of {} bytes exceeds the limit of {} bytes",
+                    size, limit
+                )
+            }
+            Self::Remote(error) => write!(f, "{}", error),
+            Self::Protocol(message) => write!(f, "Protocol error: {}", message),
+            Self::Disconnected(reason) => write!(f, "Connection lost: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl From<io::Error> for IpcError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for IpcError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl IpcError {
+    /// Code of the error frame reporting this failure to the peer
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::FrameTooLarge { .. } => ErrorCode::FrameTooLarge,
+            Self::Remote(error) => error.code,
+            _ => ErrorCode::InvalidFrame,
+        }
+    }
+}
+
+/// Encode a frame with its length prefix, failing if it is larger than `limit`
+pub fn encode(frame: &Frame, limit: u32) -> Result<Vec<u8>, IpcError> {
+    let body = serde_json::to_vec(frame)?;
+    if body.len() > limit as usize {
+        return Err(IpcError::FrameTooLarge {
+            size: body.len(),
+            limit,
+        });
+    }
+    let mut bytes = Vec::with_capacity(4 + body.len());
+    bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&body);
+    Ok(bytes)
+}
+
+/// Read a frame of at most `limit` bytes, or None if the peer closed the connection
+pub async fn read_frame(
+    reader: &mut (impl AsyncRead + Unpin),
+    limit: u32,
+) -> Result<Option<Frame>, IpcError> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > limit {
+        return Err(IpcError::FrameTooLarge {
+            size: len as usize,
+            limit,
+        });
+    }
+    let mut body = vec![0u8; len as usize];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Write a frame, failing if it is larger than the peer's `limit`
+pub async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    frame: &Frame,
+    limit: u32,
+) -> Result<(), IpcError> {
+    writer.write_all(&encode(frame, limit)?).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn with_timeout<T>(
+    operation: impl Future<Output = Result<T, IpcError>>,
+) -> Result<T, IpcError> {
+    tokio::time::timeout(HELLO_TIMEOUT, operation)
+        .await
+        .map_err(|_| {
+            IpcError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "IPC hello timed out",
+            ))
+        })?
+}
+
+fn error_frame(id: Option<u64>, code: ErrorCode, message: impl Into<String>) -> Frame {
+    Frame::Error {
+        id,
+        error: ErrorFrame {
+            code,
+            message: message.into(),
+        },
+    }
+}
+
+/// Serve a daemon connection: exchange hellos, then pass every request to
+/// `handle`, each in a task of its own. Returns when the client disconnects;
+/// requests still running are answered as long as the connection lasts.
+pub async fn serve<F, Fut>(stream: UnixStream, hello: Hello, handle: F) -> Result<(), IpcError>
+where
+    F: Fn(IncomingRequest) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let limit = hello.max_frame_size;
+    let (mut reader, mut writer) = stream.into_split();
+
+    let peer = match with_timeout(read_frame(&mut reader, limit)).await? {
+        Some(Frame::Hello(peer)) if peer.version == PROTOCOL_VERSION => Arc::new(peer),
+        Some(Frame::Hello(peer)) => {
+            let message = format!(
+                "Protocol version {} is not supported, {} speaks version {}",
+                peer.version, hello.name, PROTOCOL_VERSION
+            );
+            let frame = error_frame(None, ErrorCode::UnsupportedVersion, message.clone());
+            write_frame(&mut writer, &frame, MAX_FRAME_SIZE).await?;
+            return Err(IpcError::Protocol(message));
+        }
+        Some(_) => {
+            let message = "Expected a hello frame";
+            let frame = error_frame(None, ErrorCode::InvalidFrame, message);
+            write_frame(&mut writer, &frame, MAX_FRAME_SIZE).await?;
+            return Err(IpcError::Protocol(message.to_string()));
+        }
+        None => return Ok(()),
+    };
+    write_frame(&mut writer, &Frame::Hello(hello), peer.max_frame_size).await?;
+
+    // Answers of concurrent requests are written in the order they are ready
+    let (frames, mut outgoing) = mpsc::unbounded_channel::<Vec<u8>>();
+    tokio::spawn(async move {
+        while let Some(bytes) = outgoing.recv().await {
+            if writer.write_all(&bytes).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut inboxes: HashMap<u64, mpsc::UnboundedSender<Value>> = HashMap::new();
+    loop {
+        let frame = match read_frame(&mut reader, limit).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            Err(IpcError::Io(e)) => return Err(IpcError::Io(e)),
+            Err(e) => {
+                // The stream may be out of step with the frames, so give up on it
+                if let Ok(bytes) =
+                    encode(&error_frame(None, e.code(), e.to_string()), MAX_FRAME_SIZE)
+                {
+                    let _ = frames.send(bytes);
+                }
+                return Err(e);
+            }
+        };
+        match frame {
+            Frame::Request { id, payload } => {
+                inboxes.retain(|_, inbox| !inbox.is_closed());
+                // An id may only be reused once its request was answered. An error
+                // frame with the id would end the request still using it, so the
+                // connection is failed instead; running requests are still answered.
+                if inboxes.contains_key(&id) {
+                    let message = format!("Request id {} is already in use", id);
+                    if let Ok(bytes) = encode(
+                        &error_frame(None, ErrorCode::InvalidFrame, message.clone()),
+                        MAX_FRAME_SIZE,
+                    ) {
+                        let _ = frames.send(bytes);
+                    }
+                    return Err(IpcError::Protocol(message));
+                }
+                let (inbox, messages) = mpsc::unbounded_channel();
+                inboxes.insert(id, inbox);
+                tokio::spawn(handle(IncomingRequest {
+                    id,
+                    payload,
+                    peer: Arc::clone(&peer),
+                    messages,
+                    frames: frames.clone(),
+                    answered: false,
+                }));
+            }
+            Frame::Message { id, payload } => {
+                // Messages for requests that were already answered are dropped
+                if let Some(inbox) = inboxes.get(&id) {
+                    let _ = inbox.send(payload);
+                }
+            }
+            frame => {
+                let message = format!("Unexpected frame from a client: {:?}", frame);
+                if let Ok(bytes) = encode(
+                    &error_frame(None, ErrorCode::InvalidFrame, message.clone()),
+                    MAX_FRAME_SIZE,
+                ) {
+                    let _ = frames.send(bytes);
+                }
+                return Err(IpcError::Protocol(message));
+            }
+        }
+    }
+}
+
+/// A request received by `serve`, answered with `respond` or `fail`. A request
+/// dropped without an answer is failed with an internal error.
+pub struct IncomingRequest {
+    pub id: u64,
+    pub payload: Value,
+    peer: Arc<Hello>,
+    messages: mpsc::UnboundedReceiver<Value>,
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    answered: bool,
+}
+
+impl IncomingRequest {
+    /// The client's hello
+    pub fn peer(&self) -> &Hello {
+        &self.peer
+    }
+
+    /// Deserialize the payload
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.payload)
+    }
+
+    /// Wait for the next message the client sends for this request, or None if
+    /// the client disconnected
+    pub async fn next_message(&mut self) -> Option<Value> {
+        self.messages.recv().await
+    }
+
+    /// Send the client a message for this request
+    pub fn send_message(&self, payload: &impl Serialize) -> Result<(), IpcError> {
+        let frame = Frame::Message {
+            id: self.id,
+            payload: serde_json::to_value(payload)?,
+        };
+        self.send(&frame)
+    }
+
+    /// Answer the request. An answer too large for the client is replaced by an
+    /// error frame saying so; one that cannot be queued at all is failed on drop.
+    pub fn respond(mut self, payload: &impl Serialize) -> Result<(), IpcError> {
+        let frame = Frame::Response {
+            id: self.id,
+            payload: serde_json::to_value(payload)?,
+        };
+        match self.send(&frame) {
+            Ok(()) => {
+                self.answered = true;
+                Ok(())
+            }
+            Err(e @ IpcError::FrameTooLarge { .. }) => {
+                self.send(&error_frame(Some(self.id), e.code(), e.to_string()))?;
+                self.answered = true;
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Answer the request with an error frame
+    pub fn fail(mut self, code: ErrorCode, message: impl Into<String>) -> Result<(), IpcError> {
+        self.send(&error_frame(Some(self.id), code, message))?;
+        self.answered = true;
+        Ok(())
+    }
+
+    fn send(&self, frame: &Frame) -> Result<(), IpcError> {
+        let bytes = encode(frame, self.peer.max_frame_size)?;
+        self.frames
+            .send(bytes)
+            .map_err(|_| IpcError::Disconnected("the client disconnected".to_string()))
+    }
+}
+
+impl Drop for IncomingRequest {
+    fn drop(&mut self) {
+        if !self.answered {
+            let frame = error_frame(
+                Some(self.id),
+                ErrorCode::Internal,
+                "The request was dropped without an answer",
+            );
+            let _ = self.send(&frame);
+        }
+    }
+}
+
+/// A frame the daemon sent for a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// Part of the exchange; more frames follow
+    Message(Value),
+    /// The answer, ending the exchange
+    Response(Value),
+}
+
+type Replies = mpsc::UnboundedSender<Result<Reply, IpcError>>;
+
+// Requests waiting for their answer, by id; None once the connection is lost
+type Pending = Arc<Mutex<Option<HashMap<u64, Replies>>>>;
+
+fn lock(pending: &Pending) -> MutexGuard<'_, Option<HashMap<u64, Replies>>> {
+    pending.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Client connection to a daemon socket, shared by any number of concurrent
+/// requests. Clones use the same connection.
+#[derive(Clone)]
+pub struct IpcClient {
+    inner: Arc<ClientInner>,
+}
+
+struct ClientInner {
+    peer: Hello,
+    next_id: AtomicU64,
+    pending: Pending,
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    tasks: [JoinHandle<()>; 2],
+}
+
+impl Drop for ClientInner {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl IpcClient {
+    /// Connect to a daemon socket with the configured access settings and
+    /// exchange hellos
+    pub async fn connect(path: impl AsRef<Path>, hello: Hello) -> Result<Self, IpcError> {
+        let stream = security::connect(path).await?;
+        Self::handshake(stream, hello).await
+    }
+
+    /// Exchange hellos on a connected stream
+    pub async fn handshake(stream: UnixStream, hello: Hello) -> Result<Self, IpcError> {
+        let limit = hello.max_frame_size;
+        let (mut reader, mut writer) = stream.into_split();
+        let peer = with_timeout(async {
+            write_frame(&mut writer, &Frame::Hello(hello), MAX_FRAME_SIZE).await?;
+            match read_frame(&mut reader, limit).await? {
+                Some(Frame::Hello(peer)) => Ok(peer),
+                Some(Frame::Error { error, .. }) => Err(IpcError::Remote(error)),
+                Some(frame) => Err(IpcError::Protocol(format!(
+                    "Expected a hello frame, got {:?}",
+                    frame
+                ))),
+                None => Err(IpcError::Disconnected(
+                    "the daemon closed the connection".to_string(),
+                )),
+            }
+        })
+        .await?;
+        if peer.version != PROTOCOL_VERSION {
+            return Err(IpcError::Protocol(format!(
+                "{} speaks protocol version {}, not {}",
+                peer.name, peer.version, PROTOCOL_VERSION
+            )));
+        }
+
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let (frames, mut outgoing) = mpsc::unbounded_channel::<Vec<u8>>();
+        let writer_task = tokio::spawn(async move {
+            while let Some(bytes) = outgoing.recv().await {
+                if writer.write_all(&bytes).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let reader_task = tokio::spawn(read_replies(reader, limit, Arc::clone(&pending)));
+
+        Ok(Self {
+            inner: Arc::new(ClientInner {
+                peer,
+                next_id: AtomicU64::new(1),
+                pending,
+                frames,
+                tasks: [writer_task, reader_task],
+            }),
+        })
+    }
+
+    /// The daemon's hello
+    pub fn peer(&self) -> &Hello {
+        &self.inner.peer
+    }
+
+    /// Whether the connection was lost, so new requests would fail
+    pub fn is_closed(&self) -> bool {
+        lock(&self.inner.pending).is_none()
+    }
+
+    /// Send a request and wait for its response
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        payload: &impl Serialize,
+    ) -> Result<T, IpcError> {
+        self.start(payload)?.response().await
+    }
+
+    /// Send a request whose exchange includes messages, such as a HAPPE query
+    /// with tool-call approvals
+    pub fn start(&self, payload: &impl Serialize) -> Result<Exchange, IpcError> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let frame = Frame::Request {
+            id,
+            payload: serde_json::to_value(payload)?,
+        };
+        let bytes = encode(&frame, self.inner.peer.max_frame_size)?;
+
+        let (sender, replies) = mpsc::unbounded_channel();
+        {
+            let mut pending = lock(&self.inner.pending);
+            let requests = pending.as_mut().ok_or_else(|| {
+                IpcError::Disconnected("the daemon closed the connection".to_string())
+            })?;
+            // The daemon fails the connection when an id in flight is reused
+            if requests.contains_key(&id) {
+                return Err(IpcError::Protocol(format!("Request id {} is already in use", id)));
+            }
+            requests.insert(id, sender);
+        }
+        let exchange = Exchange {
+            id,
+            client: self.clone(),
+            replies,
+        };
+        self.send(bytes)?;
+        Ok(exchange)
+    }
+
+    fn send(&self, bytes: Vec<u8>) -> Result<(), IpcError> {
+        self.inner
+            .frames
+            .send(bytes)
+            .map_err(|_| IpcError::Disconnected("the daemon closed the connection".to_string()))
+    }
+}
+
+// Pass the frames the daemon sends to the requests they belong to
+async fn read_replies(mut reader: OwnedReadHalf, limit: u32, pending: Pending) {
+    let reason = loop {
+        let (id, reply, last) = match read_frame(&mut reader, limit).await {
+            Ok(Some(Frame::Message { id, payload })) => (id, Ok(Reply::Message(payload)), false),
+            Ok(Some(Frame::Response { id, payload })) => (id, Ok(Reply::Response(payload)), true),
+            Ok(Some(Frame::Error {
+                id: Some(id),
+                error,
+            })) => (id, Err(IpcError::Remote(error)), true),
+            Ok(Some(Frame::Error { id: None, error })) => break error.to_string(),
+            Ok(Some(frame)) => break format!("unexpected frame from the daemon: {:?}", frame),
+            Ok(None) => break "the daemon closed the connection".to_string(),
+            Err(e) => break e.to_string(),
+        };
+        let mut pending = lock(&pending);
+        let Some(requests) = pending.as_mut() else {
+            return;
+        };
+        let replies = if last {
+            requests.remove(&id)
+        } else {
+            requests.get(&id).cloned()
+        };
+        // Requests whose exchange was dropped no longer want their frames
+        if let Some(replies) = replies {
+            let _ = replies.send(reply);
+        }
+    };
+
+    // Fail the requests still waiting, and any started from now on
+    if let Some(requests) = lock(&pending).take() {
+        for replies in requests.into_values() {
+            let _ = replies.send(Err(IpcError::Disconnected(reason.clone())));
+        }
+    }
+}
+
+/// The exchange of one request, ended by its response
+pub struct Exchange {
+    id: u64,
+    client: IpcClient,
+    replies: mpsc::UnboundedReceiver<Result<Reply, IpcError>>,
+}
+
+impl Exchange {
+    /// Wait for the next frame the daemon sends for the request
+    pub async fn next(&mut self) -> Result<Reply, IpcError> {
+        self.replies.recv().await.unwrap_or_else(|| {
+            Err(IpcError::Disconnected(
+                "the daemon closed the connection".to_string(),
+            ))
+        })
+    }
+
+    /// Send the daemon a message for the request
+    pub fn send_message(&self, payload: &impl Serialize) -> Result<(), IpcError> {
+        let frame = Frame::Message {
+            id: self.id,
+            payload: serde_json::to_value(payload)?,
+        };
+        self.client
+            .send(encode(&frame, self.client.inner.peer.max_frame_size)?)
+    }
+
+    /// Wait for the response, skipping any messages
+    pub async fn response<T: DeserializeOwned>(mut self) -> Result<T, IpcError> {
+        loop {
+            if let Reply::Response(payload) = self.next().await? {
+                return Ok(serde_json::from_value(payload)?);
+            }
+        }
+    }
+}
+
+impl Drop for Exchange {
+    fn drop(&mut self) {
+        if let Some(requests) = lock(&self.client.inner.pending).as_mut() {
+            requests.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn serve_pair<F, Fut>(
+        server_hello: Hello,
+        client_hello: Hello,
+        handle: F,
+    ) -> Result<IpcClient, IpcError>
+    where
+        F: Fn(IncomingRequest) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (server_end, client_end) = UnixStream::pair().unwrap();
+        tokio::spawn(serve(server_end, server_hello, handle));
+        IpcClient::handshake(client_end, client_hello).await
+    }
+
+    // Answers `{"sleep": ms, "value": v}` with v after ms, `{"repeat": n}` with a
+    // string of n bytes, and `{"ask": q}` by sending q as a message and answering
+    // with the client's reply
+    async fn test_handler(mut request: IncomingRequest) {
+        if let Some(n) = request.payload.get("repeat").and_then(Value::as_u64) {
+            let _ = request.respond(&"x".repeat(n as usize));
+        } else if let Some(question) = request.payload.get("ask").cloned() {
+            request.send_message(&question).unwrap();
+            let answer = request.next_message().await.unwrap();
+            request.respond(&answer).unwrap();
+        } else if let Some(ms) = request.payload.get("sleep").and_then(Value::as_u64) {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            let value = request.payload["value"].clone();
+            request.respond(&value).unwrap();
+        } else {
+            request
+                .fail(ErrorCode::InvalidRequest, "Unknown request")
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_requests() {
+        let client = serve_pair(
+            Hello::new("daemon").with_capability("approvals"),
+            Hello::new("client"),
+            test_handler,
+        )
+        .await
+        .unwrap();
+        assert!(client.peer().has_capability("approvals"));
+
+        // The slower request is answered last without holding back the other
+        let (slow, fast) = (
+            json!({ "sleep": 100, "value": "slow" }),
+            json!({ "sleep": 0, "value": "fast" }),
+        );
+        let (slow, fast) = tokio::join!(
+            client.request::<String>(&slow),
+            client.request::<String>(&fast)
+        );
+        assert_eq!(
+            (slow.unwrap(), fast.unwrap()),
+            ("slow".to_string(), "fast".to_string())
+        );
+
+        let mut exchange = client.start(&json!({ "ask": "approve?" })).unwrap();
+        assert_eq!(
+            exchange.next().await.unwrap(),
+            Reply::Message(json!("approve?"))
+        );
+        exchange.send_message(&json!("yes")).unwrap();
+        assert_eq!(
+            exchange.next().await.unwrap(),
+            Reply::Response(json!("yes"))
+        );
+
+        match client.request::<Value>(&json!({})).await {
+            Err(IpcError::Remote(error)) => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("expected an error frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_version_and_frame_limits() {
+        let old_client = Hello {
+            version: PROTOCOL_VERSION + 1,
+            ..Hello::new("client")
+        };
+        match serve_pair(Hello::new("daemon"), old_client, test_handler).await {
+            Err(IpcError::Remote(error)) => assert_eq!(error.code, ErrorCode::UnsupportedVersion),
+            other => panic!("expected an error frame, got {:?}", other.map(|_| ())),
+        }
+
+        let small = |name| Hello {
+            max_frame_size: 128,
+            ..Hello::new(name)
+        };
+        let client = serve_pair(small("daemon"), small("client"), test_handler)
+            .await
+            .unwrap();
+        let large = "x".repeat(200);
+        // Too large for the daemon, so it is never sent
+        assert!(matches!(
+            client.start(&json!({ "sleep": 0, "value": large })),
+            Err(IpcError::FrameTooLarge { .. })
+        ));
+        // A response too large for the client is replaced by an error frame
+        match client.request::<String>(&json!({ "repeat": 200 })).await {
+            Err(IpcError::Remote(error)) => assert_eq!(error.code, ErrorCode::FrameTooLarge),
+            other => panic!("expected an error frame, got {:?}", other),
+        }
+        assert!(!client.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_request_ids() {
+        let (server_end, mut client_end) = UnixStream::pair().unwrap();
+        tokio::spawn(serve(server_end, Hello::new("daemon"), test_handler));
+        write_frame(&mut client_end, &Frame::Hello(Hello::new("client")), MAX_FRAME_SIZE)
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_frame(&mut client_end, MAX_FRAME_SIZE).await,
+            Ok(Some(Frame::Hello(_)))
+        ));
+
+        let request = |value: &str| Frame::Request {
+            id: 1,
+            payload: json!({ "sleep": 100, "value": value }),
+        };
+        write_frame(&mut client_end, &request("first"), MAX_FRAME_SIZE)
+            .await
+            .unwrap();
+        write_frame(&mut client_end, &request("second"), MAX_FRAME_SIZE)
+            .await
+            .unwrap();
+
+        // The reuse fails the connection with an error frame that names no
+        // request, and the first request is still answered before it closes
+        match read_frame(&mut client_end, MAX_FRAME_SIZE).await {
+            Ok(Some(Frame::Error { id, error })) => {
+                assert_eq!((id, error.code), (None, ErrorCode::InvalidFrame))
+            }
+            other => panic!("expected an error frame, got {:?}", other),
+        }
+        assert_eq!(
+            read_frame(&mut client_end, MAX_FRAME_SIZE).await.unwrap(),
+            Some(Frame::Response {
+                id: 1,
+                payload: json!("first")
+            })
+        );
+        assert_eq!(read_frame(&mut client_end, MAX_FRAME_SIZE).await.unwrap(), None);
+    }
+}
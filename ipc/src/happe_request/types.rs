@@ -1,4 +1,10 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Capability in HAPPE's `Hello` saying it sends approval requests to clients
+/// that set `approvals`
+pub const APPROVALS_CAPABILITY: &str = "approvals";
 
 /// A request from a client to the HAPPE daemon
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -7,6 +13,27 @@ pub struct HappeQueryRequest {
     pub query: String,
     /// Optional session ID to maintain conversation context
     pub session_id: Option<String>,
+    /// Working directory of the client, used as the workspace root for MCP servers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// MCP prompt template to expand into the conversation before the query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<PromptReference>,
+    /// Whether the client answers approval requests for tool calls, which HAPPE
+    /// then sends as `HappeEvent` messages before the response; otherwise calls
+    /// that need approval are denied.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub approvals: bool,
+}
+
+/// Reference to an MCP prompt template and the arguments to expand it with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PromptReference {
+    /// Prompt name in the form `server/prompt`
+    pub name: String,
+    /// Template arguments
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub arguments: HashMap<String, String>,
 }
 
 /// A response from the HAPPE daemon to a client
@@ -19,3 +46,98 @@ pub struct HappeQueryResponse {
     /// Session ID used for this conversation
     pub session_id: Option<String>,
 }
+
+/// How much harm a tool call could do, shown when asking for approval
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+/// A tool call the model requested that needs the user's approval to run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApprovalRequest {
+    /// Identifies the request in the client's `ApprovalReply`
+    pub id: String,
+    pub server: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub risk: RiskLevel,
+    /// Why the call needs approval, e.g. the rationale of a policy rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// The user's answer to an approval request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Run this call
+    AllowOnce,
+    /// Run this call and later calls of the same tool without asking
+    AllowAlways,
+    /// Do not run the call; the model is told it was denied
+    Deny {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Run the call with these arguments instead
+    EditArgs { arguments: Value },
+}
+
+/// A client's answer to the approval request with the same id
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApprovalReply {
+    pub id: String,
+    #[serde(flatten)]
+    pub decision: ApprovalDecision,
+}
+
+/// A message HAPPE sends while a query runs, to clients that answer approval
+/// requests. Each approval request is answered with an `ApprovalReply` message;
+/// the query's response follows them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HappeEvent {
+    ApprovalRequired(ApprovalRequest),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_approval_messages() {
+        let reply: ApprovalReply = serde_json::from_value(json!({
+            "id": "a1",
+            "decision": "edit_args",
+            "arguments": { "path": "/tmp" },
+        }))
+        .unwrap();
+        assert_eq!(
+            reply.decision,
+            ApprovalDecision::EditArgs {
+                arguments: json!({ "path": "/tmp" })
+            }
+        );
+        let reply: ApprovalReply =
+            serde_json::from_value(json!({ "id": "a2", "decision": "deny" })).unwrap();
+        assert_eq!(reply.decision, ApprovalDecision::Deny { reason: None });
+
+        let event = HappeEvent::ApprovalRequired(ApprovalRequest {
+            id: "a1".to_string(),
+            server: "filesystem".to_string(),
+            tool: "write_file".to_string(),
+            arguments: json!({}),
+            risk: RiskLevel::High,
+            reason: None,
+        });
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], "approval_required");
+        assert_eq!(value["risk"], "high");
+        assert_eq!(serde_json::from_value::<HappeEvent>(value).unwrap(), event);
+    }
+}
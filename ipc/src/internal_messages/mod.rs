@@ -1,8 +1,14 @@
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 pub mod types;
 pub use types::*;
 
+/// Socket IDA listens on when `ida_socket_path` is not configured
+pub fn default_ida_socket_path() -> std::io::Result<PathBuf> {
+    Ok(crate::security::socket_dir()?.join("ida-daemon.sock"))
+}
+
 /// Enum defining messages passed between HAPPE and IDA.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum InternalMessage {
@@ -16,5 +22,7 @@ pub enum InternalMessage {
     GetMemoriesResponse { memories: Vec<MemoryItem> },
     /// HAPPE -> IDA: Asynchronous notification with data from a completed turn for storage.
     StoreTurnRequest { turn_data: ConversationTurn },
+    /// IDA -> HAPPE: The turn was accepted and is being stored in the background.
+    StoreTurnAccepted,
     // TODO: Add other messages as needed, e.g., health checks, configuration updates?
 }
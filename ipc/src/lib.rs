@@ -1,5 +1,7 @@
 // This crate centralizes Inter-Process Communication definitions and logic.
 
 pub mod daemon_messages; // For CLI <-> mcp-host communication
+pub mod framing; // Length-prefixed frames, handshake and request ids for all daemon sockets
 pub mod happe_request;
 pub mod internal_messages; // For HAPPE <-> IDA communication
+pub mod security; // Socket permissions and peer checks for all daemon sockets
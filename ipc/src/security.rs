@@ -0,0 +1,329 @@
+// Access control for the Unix sockets of mcp-hostd, HAPPE and IDA.
+//
+// Sockets are created with mode 0600, and the default socket directory is only
+// accessible to its owner (0700). Each accepted connection is checked against the
+// peer's uid (SO_PEERCRED): the daemon's own uid and the configured
+// `allowed-uids` are accepted.
+//
+// When a shared secret is configured, the client must also answer a challenge
+// before its first request, which covers setups that proxy the sockets (where
+// the peer uid is the proxy's). The server sends a random nonce, the client
+// replies with SHA-256(secret || nonce) and the server answers with a single
+// status byte. The secret itself never crosses the socket.
+
+use gemini_core::config::{IpcConfig, UnifiedConfig};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+
+const NONCE_LEN: usize = 32;
+const DIGEST_LEN: usize = 32;
+const ACCEPTED: u8 = 1;
+const REJECTED: u8 = 0;
+
+// Time either side waits for the other's part of the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Directory holding the daemons' default sockets: `gemini-cli` in the runtime
+/// directory (or the local data directory), created and kept at mode 0700
+pub fn socket_dir() -> io::Result<PathBuf> {
+    let base_dir = dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not determine runtime or data local directory",
+            )
+        })?;
+    let dir = base_dir.join("gemini-cli");
+    create_private_dir(&dir)?;
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    Ok(dir)
+}
+
+/// Create `dir` and any missing parents with mode 0700
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// Bind a listener at `path` with mode 0600, replacing a stale socket left by a
+/// previous run. A missing parent directory is created with mode 0700.
+///
+/// The socket is bound inside a fresh 0700 directory next to `path` and renamed
+/// into place once its mode is set, so it is never reachable with the mode the
+/// umask would give it. Changing the umask instead would affect every thread.
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    create_private_dir(parent)?;
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a socket path", path.display()),
+        )
+    })?;
+    // A random suffix keeps concurrent binds in the same directory apart
+    let suffix: String = random_nonce()?[..8].iter().map(|b| format!("{b:02x}")).collect();
+    let staging = parent.join(format!(".bind-{}-{suffix}", std::process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let result = bind_staged(&staging.join(file_name), path);
+    let _ = fs::remove_dir(&staging);
+    result
+}
+
+fn bind_staged(staged: &Path, path: &Path) -> io::Result<UnixListener> {
+    let listener = UnixListener::bind(staged)?;
+    if let Err(e) = fs::set_permissions(staged, fs::Permissions::from_mode(0o600))
+        .and_then(|()| fs::rename(staged, path))
+    {
+        let _ = fs::remove_file(staged);
+        return Err(e);
+    }
+    Ok(listener)
+}
+
+/// Who may connect to a daemon socket
+#[derive(Clone, Default)]
+pub struct SocketAccess {
+    allowed_uids: Vec<u32>,
+    shared_secret: Option<String>,
+}
+
+impl std::fmt::Debug for SocketAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SocketAccess")
+            .field("allowed_uids", &self.allowed_uids)
+            .field("shared_secret", &self.shared_secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl SocketAccess {
+    pub fn new(allowed_uids: Vec<u32>, shared_secret: Option<String>) -> Self {
+        Self {
+            allowed_uids,
+            shared_secret,
+        }
+    }
+
+    /// Access rules from the `[ipc]` section, resolving the shared secret
+    pub fn from_config(config: &IpcConfig) -> Result<Self, String> {
+        let shared_secret = config.resolve_shared_secret().map_err(|e| e.to_string())?;
+        Ok(Self::new(config.allowed_uids.clone(), shared_secret))
+    }
+
+    /// Whether clients must complete the shared-secret handshake
+    pub fn requires_handshake(&self) -> bool {
+        self.shared_secret.is_some()
+    }
+
+    /// The configured shared secret, if any
+    pub fn shared_secret(&self) -> Option<&str> {
+        self.shared_secret.as_deref()
+    }
+
+    /// Check a newly accepted connection: the peer's uid, then the shared secret
+    /// when one is configured. Returns the peer's uid and pid.
+    pub async fn authorize(&self, stream: &mut UnixStream) -> io::Result<PeerIdentity> {
+        let cred = stream.peer_cred()?;
+        let peer = PeerIdentity {
+            uid: cred.uid(),
+            pid: cred.pid().map(|pid| pid as u32),
+        };
+        if peer.uid != current_uid() && !self.allowed_uids.contains(&peer.uid) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("uid {} is not allowed to connect", peer.uid),
+            ));
+        }
+
+        if let Some(secret) = &self.shared_secret {
+            let nonce = random_nonce()?;
+            let mut answer = [0u8; DIGEST_LEN];
+            with_timeout(async {
+                stream.write_all(&nonce).await?;
+                stream.flush().await?;
+                stream.read_exact(&mut answer).await.map(|_| ())
+            })
+            .await?;
+            let accepted = constant_time_eq(&answer, &handshake_digest(secret, &nonce));
+            let status = if accepted { ACCEPTED } else { REJECTED };
+            with_timeout(async {
+                stream.write_all(&[status]).await?;
+                stream.flush().await
+            })
+            .await?;
+            if !accepted {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("uid {} failed the shared-secret handshake", peer.uid),
+                ));
+            }
+        }
+        Ok(peer)
+    }
+
+    /// Client side of the handshake, a no-op when no shared secret is configured
+    pub async fn handshake(&self, stream: &mut UnixStream) -> io::Result<()> {
+        let Some(secret) = &self.shared_secret else {
+            return Ok(());
+        };
+        let mut nonce = [0u8; NONCE_LEN];
+        with_timeout(stream.read_exact(&mut nonce)).await?;
+        let mut status = [0u8; 1];
+        with_timeout(async {
+            stream.write_all(&handshake_digest(secret, &nonce)).await?;
+            stream.flush().await?;
+            stream.read_exact(&mut status).await
+        })
+        .await?;
+        if status[0] != ACCEPTED {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the daemon rejected the IPC shared secret",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Connect to a daemon socket and complete the handshake
+    pub async fn connect(&self, path: impl AsRef<Path>) -> io::Result<UnixStream> {
+        let mut stream = UnixStream::connect(path).await?;
+        self.handshake(&mut stream).await?;
+        Ok(stream)
+    }
+}
+
+/// Identity of an accepted client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerIdentity {
+    pub uid: u32,
+    pub pid: Option<u32>,
+}
+
+/// Connect to a daemon socket using the `[ipc]` settings of the unified
+/// configuration, which are loaded once per process
+pub async fn connect(path: impl AsRef<Path>) -> io::Result<UnixStream> {
+    static CLIENT_ACCESS: OnceLock<Result<SocketAccess, String>> = OnceLock::new();
+    let access = CLIENT_ACCESS
+        .get_or_init(|| SocketAccess::from_config(&UnifiedConfig::load().ipc))
+        .as_ref()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.clone()))?;
+    access.connect(path).await
+}
+
+async fn with_timeout<T>(operation: impl std::future::Future<Output = io::Result<T>>) -> io::Result<T> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, operation)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "IPC handshake timed out"))?
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
+}
+
+fn random_nonce() -> io::Result<[u8; NONCE_LEN]> {
+    let mut nonce = [0u8; NONCE_LEN];
+    fs::File::open("/dev/urandom")?.read_exact(&mut nonce)?;
+    Ok(nonce)
+}
+
+fn handshake_digest(secret: &str, nonce: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(secret.as_bytes());
+    hasher.update(nonce);
+    hasher.finalize().into()
+}
+
+/// Compare secrets in time independent of where they differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Whether a browser Origin is a page served from this machine
+pub fn is_local_origin(origin: &str) -> bool {
+    let Some((_, rest)) = origin.split_once("://") else {
+        return false;
+    };
+    let host = if rest.starts_with('[') {
+        rest.split(']').next().map(|host| format!("{}]", host))
+    } else {
+        rest.split([':', '/']).next().map(str::to_string)
+    };
+    matches!(host.as_deref(), Some("localhost" | "127.0.0.1" | "[::1]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn handshake_with(server_secret: &str, client_secret: &str) -> (io::Result<PeerIdentity>, io::Result<()>) {
+        let (mut server_end, mut client_end) = UnixStream::pair().unwrap();
+        let server = SocketAccess::new(Vec::new(), Some(server_secret.to_string()));
+        let client = SocketAccess::new(Vec::new(), Some(client_secret.to_string()));
+        tokio::join!(server.authorize(&mut server_end), client.handshake(&mut client_end))
+    }
+
+    #[test]
+    fn test_is_local_origin() {
+        assert!(is_local_origin("http://localhost:3000"));
+        assert!(is_local_origin("http://127.0.0.1"));
+        assert!(is_local_origin("https://[::1]:8080/"));
+        assert!(!is_local_origin("http://localhost.evil.com"));
+        assert!(!is_local_origin("https://example.com"));
+        assert!(!is_local_origin("null"));
+    }
+
+    #[tokio::test]
+    async fn test_shared_secret_handshake() {
+        let (server, client) = handshake_with("s3cret", "s3cret").await;
+        assert_eq!(server.unwrap().uid, current_uid());
+        client.unwrap();
+
+        let (server, client) = handshake_with("s3cret", "guess").await;
+        assert_eq!(server.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(client.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_bind_sets_permissions() {
+        let dir = std::env::temp_dir().join(format!("gemini-ipc-test-{}", std::process::id()));
+        let path = dir.join("sockets").join("test.sock");
+        drop(bind(&path).unwrap());
+        // The stale socket left behind is replaced
+        let listener = bind(&path).unwrap();
+
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        // Nothing is left behind from staging the socket
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        let mut stream = SocketAccess::default().connect(&path).await.unwrap();
+        let (mut accepted, _) = listener.accept().await.unwrap();
+        assert!(SocketAccess::default().authorize(&mut accepted).await.is_ok());
+        stream.shutdown().await.unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
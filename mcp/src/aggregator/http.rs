@@ -0,0 +1,242 @@
+// Streamable HTTP transport of the aggregator, served on 127.0.0.1 only.
+//
+// Clients POST JSON-RPC messages to `/mcp` and receive the responses as JSON; no
+// SSE streams are opened, so GET is answered with 405. `initialize` starts a
+// session whose ID is returned in the `Mcp-Session-Id` header and must be sent
+// with every later request; DELETE ends it. Every request must carry the IPC shared
+// secret as a bearer token, since any local user can reach the port, and requests
+// from browser pages on other origins are refused to prevent DNS rebinding.
+
+use super::{handle_message, is_initialize, parse_error_response, AggregatorBackend};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use gemini_ipc::security::{constant_time_eq, is_local_origin};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use uuid::Uuid;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+// Sessions kept at once; starting another ends the least recently used
+const MAX_SESSIONS: usize = 64;
+
+type Rejection = (StatusCode, &'static str);
+
+/// Creates the backend answering requests of one session; the argument identifies
+/// the client in audit log entries
+pub type BackendFactory = Arc<dyn Fn(String) -> Arc<dyn AggregatorBackend> + Send + Sync>;
+
+#[derive(Clone)]
+struct HttpState {
+    backend_for: BackendFactory,
+    token: Arc<str>,
+    // Open sessions and when each was last used
+    sessions: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// Serve the aggregator at `http://127.0.0.1:<port>/mcp` until the task is dropped,
+/// to clients sending `Authorization: Bearer <token>`
+pub async fn serve(port: u16, token: &str, backend_for: BackendFactory) -> Result<(), String> {
+    if token.is_empty() {
+        return Err("The MCP HTTP endpoint requires a bearer token".to_string());
+    }
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let state = HttpState {
+        backend_for,
+        token: token.into(),
+        sessions: Arc::default(),
+    };
+    let app = Router::new()
+        .route(
+            "/mcp",
+            post(handle_post).get(handle_get).delete(handle_delete),
+        )
+        .with_state(state);
+
+    let server = axum::Server::try_bind(&addr)
+        .map_err(|e| format!("Failed to bind MCP HTTP endpoint on {}: {}", addr, e))?;
+    info!("Serving MCP over Streamable HTTP at http://{}/mcp", addr);
+    server
+        .serve(app.into_make_service())
+        .await
+        .map_err(|e| format!("MCP HTTP endpoint failed: {}", e))
+}
+
+async fn handle_post(State(state): State<HttpState>, headers: HeaderMap, body: Bytes) -> Response {
+    if let Err(rejection) = check_request(&state, &headers) {
+        return rejection.into_response();
+    }
+
+    let message: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(parse_error_response(&e.to_string())))
+                .into_response()
+        }
+    };
+
+    let session_id = if is_initialize(&message) {
+        let session_id = Uuid::new_v4().to_string();
+        if let Ok(mut sessions) = state.sessions.lock() {
+            start_session(&mut sessions, session_id.clone());
+        }
+        info!("Started MCP HTTP session {}", session_id);
+        session_id
+    } else {
+        match session_from_headers(&state, &headers) {
+            Ok(session_id) => session_id,
+            Err(rejection) => return rejection.into_response(),
+        }
+    };
+
+    let backend = (state.backend_for)(format!("http session {}", &session_id[..8]));
+    let response = handle_message(backend.as_ref(), message).await;
+
+    let mut response = match response {
+        Some(response) => (StatusCode::OK, Json(response)).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&session_id) {
+        response.headers_mut().insert(SESSION_HEADER, value);
+    }
+    response
+}
+
+async fn handle_get(State(state): State<HttpState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = check_request(&state, &headers) {
+        return rejection.into_response();
+    }
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        "This server does not open SSE streams",
+    )
+        .into_response()
+}
+
+async fn handle_delete(State(state): State<HttpState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = check_request(&state, &headers) {
+        return rejection.into_response();
+    }
+    match session_from_headers(&state, &headers) {
+        Ok(session_id) => {
+            if let Ok(mut sessions) = state.sessions.lock() {
+                sessions.remove(&session_id);
+            }
+            info!("Ended MCP HTTP session {}", session_id);
+            StatusCode::OK.into_response()
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+// The session of a request; unknown sessions get 404 so the client starts a new one
+fn session_from_headers(state: &HttpState, headers: &HeaderMap) -> Result<String, Rejection> {
+    let Some(session_id) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+        return Err((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"));
+    };
+    let known = state
+        .sessions
+        .lock()
+        .map(|mut sessions| match sessions.get_mut(session_id) {
+            Some(last_used) => {
+                *last_used = Instant::now();
+                true
+            }
+            None => false,
+        })
+        .unwrap_or(false);
+    if known {
+        Ok(session_id.to_string())
+    } else {
+        debug!("Request for unknown MCP HTTP session {}", session_id);
+        Err((StatusCode::NOT_FOUND, "Unknown MCP session"))
+    }
+}
+
+// Record a new session, ending the least recently used one when there are too many
+fn start_session(sessions: &mut HashMap<String, Instant>, session_id: String) {
+    if sessions.len() >= MAX_SESSIONS {
+        let oldest = sessions
+            .iter()
+            .min_by_key(|(_, last_used)| **last_used)
+            .map(|(id, _)| id.clone());
+        if let Some(oldest) = oldest {
+            debug!("Ending least recently used MCP HTTP session {}", oldest);
+            sessions.remove(&oldest);
+        }
+    }
+    sessions.insert(session_id, Instant::now());
+}
+
+fn check_request(state: &HttpState, headers: &HeaderMap) -> Result<(), Rejection> {
+    check_origin(headers)?;
+    check_token(&state.token, headers)
+}
+
+fn check_token(token: &str, headers: &HeaderMap) -> Result<(), Rejection> {
+    let presented = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(presented) if constant_time_eq(presented.as_bytes(), token.as_bytes()) => Ok(()),
+        _ => {
+            warn!("Rejected MCP HTTP request without a valid bearer token");
+            Err((StatusCode::UNAUTHORIZED, "Missing or invalid bearer token"))
+        }
+    }
+}
+
+// Requests without an Origin header come from non-browser clients and are accepted
+fn check_origin(headers: &HeaderMap) -> Result<(), Rejection> {
+    let Some(origin) = headers.get("origin") else {
+        return Ok(());
+    };
+    let allowed = origin.to_str().ok().is_some_and(is_local_origin);
+    if allowed {
+        Ok(())
+    } else {
+        Err((StatusCode::FORBIDDEN, "Origin not allowed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_token() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("authorization", HeaderValue::from_static(value));
+            headers
+        };
+        assert!(check_token("secret", &headers("Bearer secret")).is_ok());
+        assert!(check_token("secret", &headers("Bearer wrong")).is_err());
+        assert!(check_token("secret", &headers("secret")).is_err());
+        assert!(check_token("secret", &HeaderMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_sessions_are_bounded() {
+        let start = Instant::now();
+        let mut sessions: HashMap<String, Instant> = (0..MAX_SESSIONS)
+            .map(|i| (i.to_string(), start + std::time::Duration::from_secs(i as u64)))
+            .collect();
+        // Using the first session makes the second the least recently used
+        sessions.insert("0".to_string(), start + std::time::Duration::from_secs(1000));
+        start_session(&mut sessions, "new".to_string());
+        assert_eq!(sessions.len(), MAX_SESSIONS);
+        assert!(sessions.contains_key("0") && sessions.contains_key("new"));
+        assert!(!sessions.contains_key("1"));
+    }
+}
@@ -0,0 +1,363 @@
+// MCP server side of mcp-hostd.
+//
+// External MCP clients (editors, other agents) talk to mcp-hostd as if it were a
+// single MCP server exposing the combined `server/tool` namespace of every
+// configured server. Requests reach it through the `mcp-bridge` stdio binary,
+// which forwards them over the daemon socket, or through the Streamable HTTP
+// endpoint in `http`. Tool calls go through the same policy and audit log as
+// calls from our own clients.
+
+pub mod http;
+
+use async_trait::async_trait;
+use gemini_core::rpc_types::{
+    GetPromptResult, JsonRpcError, Prompt, ReadResourceResult, Request, ResourceTemplate,
+    Response, ServerCapabilities, ToolCallResult,
+};
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Protocol revision answered when the client asks for one we do not know
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// What the aggregator needs from mcp-hostd to answer MCP requests.
+/// All names are qualified as `server/name`.
+#[async_trait]
+pub trait AggregatorBackend: Send + Sync {
+    /// Combined tools and resources of all servers
+    async fn capabilities(&self) -> ServerCapabilities;
+
+    /// Run a tool through the tool policy and audit log. Failures and refusals are
+    /// returned as error results, so the client's model can see them.
+    async fn call_tool(&self, server: &str, tool: &str, args: Value) -> ToolCallResult;
+
+    async fn list_resource_templates(&self) -> Vec<ResourceTemplate>;
+
+    async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, String>;
+
+    async fn list_prompts(&self) -> Vec<Prompt>;
+
+    async fn get_prompt(
+        &self,
+        server: &str,
+        prompt: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult, String>;
+}
+
+/// Answer a JSON-RPC message or batch from an MCP client.
+/// Returns `None` when nothing is to be sent back (notifications and responses).
+pub async fn handle_message(backend: &dyn AggregatorBackend, message: Value) -> Option<Value> {
+    match message {
+        Value::Array(messages) if messages.is_empty() => Some(error_response(
+            Value::Null,
+            rpc_error(-32600, "Empty batch".to_string()),
+        )),
+        Value::Array(messages) => {
+            let mut responses = Vec::new();
+            for message in messages {
+                if let Some(response) = handle_single(backend, message).await {
+                    responses.push(response);
+                }
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        message => handle_single(backend, message).await,
+    }
+}
+
+/// True if the message (or any message of a batch) is an `initialize` request
+pub fn is_initialize(message: &Value) -> bool {
+    match message {
+        Value::Array(messages) => messages.iter().any(is_initialize),
+        message => message.get("method").and_then(Value::as_str) == Some("initialize"),
+    }
+}
+
+/// Error response for a message that is not valid JSON
+pub fn parse_error_response(error: &str) -> Value {
+    error_response(Value::Null, rpc_error(-32700, format!("Parse error: {}", error)))
+}
+
+async fn handle_single(backend: &dyn AggregatorBackend, message: Value) -> Option<Value> {
+    // Clients answer no requests of ours, so responses are dropped
+    if message.get("method").is_none()
+        && (message.get("result").is_some() || message.get("error").is_some())
+    {
+        debug!("Ignoring response from MCP client");
+        return None;
+    }
+
+    let request: Request = match serde_json::from_value(message) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                rpc_error(-32600, format!("Invalid request: {}", e)),
+            ))
+        }
+    };
+
+    let Some(id) = request.id.clone() else {
+        debug!("Received '{}' notification from MCP client", request.method);
+        return None;
+    };
+
+    debug!("Handling '{}' request from MCP client", request.method);
+    let params = request.params.unwrap_or_else(|| json!({}));
+    let outcome = match request.method.as_str() {
+        "initialize" => Ok(initialize(&params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(list_tools(backend).await),
+        "tools/call" => call_tool(backend, &params).await,
+        "resources/list" => Ok(list_resources(backend).await),
+        "resources/templates/list" => Ok(json!({
+            "resourceTemplates": backend.list_resource_templates().await
+        })),
+        "resources/read" => read_resource(backend, &params).await,
+        "prompts/list" => Ok(json!({ "prompts": backend.list_prompts().await })),
+        "prompts/get" => get_prompt(backend, &params).await,
+        method => Err(rpc_error(-32601, format!("Method not found: {}", method))),
+    };
+
+    Some(match outcome {
+        Ok(result) => serde_json::to_value(Response {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        })
+        .unwrap_or(Value::Null),
+        Err(error) => {
+            warn!("MCP client request '{}' failed: {}", request.method, error.message);
+            error_response(id, error)
+        }
+    })
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let protocol_version = requested
+        .filter(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(version))
+        .unwrap_or(PROTOCOL_VERSION);
+
+    json!({
+        "protocolVersion": protocol_version,
+        "capabilities": {
+            "tools": {},
+            "resources": {},
+            "prompts": {},
+        },
+        "serverInfo": {
+            "name": "mcp-hostd",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "instructions": "Tools, resources and prompts of all MCP servers configured for the Gemini suite, named `server/name`.",
+    })
+}
+
+async fn list_tools(backend: &dyn AggregatorBackend) -> Value {
+    let tools: Vec<Value> = backend
+        .capabilities()
+        .await
+        .tools
+        .into_iter()
+        .map(|tool| {
+            let mut entry = json!({
+                "name": tool.name,
+                "inputSchema": tool.parameters.unwrap_or_else(|| json!({ "type": "object" })),
+            });
+            if let Some(description) = tool.description {
+                entry["description"] = Value::String(description);
+            }
+            if let Some(annotations) = tool.annotations {
+                entry["annotations"] = json!(annotations);
+            }
+            entry
+        })
+        .collect();
+    json!({ "tools": tools })
+}
+
+async fn call_tool(backend: &dyn AggregatorBackend, params: &Value) -> Result<Value, JsonRpcError> {
+    let name = params
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| rpc_error(-32602, "Missing tool name".to_string()))?;
+    let (server, tool) = split_qualified_name(name)?;
+    let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+    let result = backend.call_tool(server, tool, args).await;
+    serde_json::to_value(result)
+        .map_err(|e| rpc_error(-32603, format!("Failed to serialize tool result: {}", e)))
+}
+
+async fn list_resources(backend: &dyn AggregatorBackend) -> Value {
+    // Only resources with a URI can be read back through `resources/read`
+    let resources: Vec<_> = backend
+        .capabilities()
+        .await
+        .resources
+        .into_iter()
+        .filter(|resource| resource.uri.is_some())
+        .collect();
+    json!({ "resources": resources })
+}
+
+async fn read_resource(
+    backend: &dyn AggregatorBackend,
+    params: &Value,
+) -> Result<Value, JsonRpcError> {
+    let uri = params
+        .get("uri")
+        .and_then(Value::as_str)
+        .ok_or_else(|| rpc_error(-32602, "Missing resource URI".to_string()))?;
+    let result = backend
+        .read_resource(uri)
+        .await
+        .map_err(|e| rpc_error(-32002, e))?;
+    serde_json::to_value(result)
+        .map_err(|e| rpc_error(-32603, format!("Failed to serialize resource: {}", e)))
+}
+
+async fn get_prompt(backend: &dyn AggregatorBackend, params: &Value) -> Result<Value, JsonRpcError> {
+    let name = params
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| rpc_error(-32602, "Missing prompt name".to_string()))?;
+    let (server, prompt) = split_qualified_name(name)?;
+    let arguments: HashMap<String, String> = match params.get("arguments") {
+        None | Some(Value::Null) => HashMap::new(),
+        Some(arguments) => serde_json::from_value(arguments.clone())
+            .map_err(|e| rpc_error(-32602, format!("Invalid prompt arguments: {}", e)))?,
+    };
+
+    let result = backend
+        .get_prompt(server, prompt, arguments)
+        .await
+        .map_err(|e| rpc_error(-32603, e))?;
+    serde_json::to_value(result)
+        .map_err(|e| rpc_error(-32603, format!("Failed to serialize prompt: {}", e)))
+}
+
+fn split_qualified_name(name: &str) -> Result<(&str, &str), JsonRpcError> {
+    name.split_once('/')
+        .filter(|(server, item)| !server.is_empty() && !item.is_empty())
+        .ok_or_else(|| rpc_error(-32602, format!("'{}' must be named as server/name", name)))
+}
+
+fn error_response(id: Value, error: JsonRpcError) -> Value {
+    serde_json::to_value(Response {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(error),
+    })
+    .unwrap_or(Value::Null)
+}
+
+fn rpc_error(code: i64, message: String) -> JsonRpcError {
+    JsonRpcError {
+        code,
+        message,
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gemini_core::rpc_types::{Tool, ToolContent};
+
+    struct TestBackend;
+
+    #[async_trait]
+    impl AggregatorBackend for TestBackend {
+        async fn capabilities(&self) -> ServerCapabilities {
+            ServerCapabilities {
+                tools: vec![Tool {
+                    name: "filesystem/read_file".to_string(),
+                    description: Some("Read a file".to_string()),
+                    parameters: None,
+                    annotations: None,
+                }],
+                ..Default::default()
+            }
+        }
+
+        async fn call_tool(&self, server: &str, tool: &str, args: Value) -> ToolCallResult {
+            ToolCallResult {
+                content: vec![ToolContent::Text {
+                    text: format!("{}/{} {}", server, tool, args["path"]),
+                }],
+                structured_content: None,
+                is_error: false,
+            }
+        }
+
+        async fn list_resource_templates(&self) -> Vec<ResourceTemplate> {
+            Vec::new()
+        }
+
+        async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, String> {
+            Err(format!("No MCP server provides resource '{}'", uri))
+        }
+
+        async fn list_prompts(&self) -> Vec<Prompt> {
+            Vec::new()
+        }
+
+        async fn get_prompt(
+            &self,
+            _server: &str,
+            _prompt: &str,
+            _arguments: HashMap<String, String>,
+        ) -> Result<GetPromptResult, String> {
+            Ok(GetPromptResult::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_message() {
+        let backend = TestBackend;
+
+        let init = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "protocolVersion": "2024-11-05", "capabilities": {} }
+        });
+        assert!(is_initialize(&init));
+        let response = handle_message(&backend, init).await.unwrap();
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "mcp-hostd");
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert_eq!(handle_message(&backend, notification).await, None);
+
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": 2, "method": "tools/list" },
+            {
+                "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                "params": { "name": "filesystem/read_file", "arguments": { "path": "/a" } }
+            },
+            { "jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": { "name": "read_file" } },
+            { "jsonrpc": "2.0", "id": 5, "method": "resources/read", "params": { "uri": "file:///x" } },
+            { "jsonrpc": "2.0", "id": 6, "method": "unknown/method" }
+        ]);
+        let responses = handle_message(&backend, batch).await.unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 5);
+        assert_eq!(responses[0]["result"]["tools"][0]["name"], "filesystem/read_file");
+        assert_eq!(responses[0]["result"]["tools"][0]["inputSchema"]["type"], "object");
+        assert_eq!(responses[1]["id"], 3);
+        assert_eq!(
+            responses[1]["result"]["content"][0]["text"],
+            "filesystem/read_file \"/a\""
+        );
+        assert_eq!(responses[2]["error"]["code"], -32602);
+        assert_eq!(responses[3]["error"]["code"], -32002);
+        assert_eq!(responses[4]["error"]["code"], -32601);
+    }
+}
@@ -0,0 +1,457 @@
+// Append-only audit log of the tool calls handled by mcp-hostd.
+//
+// Each line of the JSONL file is an `AuditEntry`. Entries are numbered and
+// hash-chained: `hash` is the last field of the line and the SHA-256 of the
+// previous entry's hash followed by the bytes of the line before it, so editing,
+// reordering or removing an entry breaks every hash after it. `verify` walks the
+// chain over the line text as written. Entries removed from the end of the log
+// leave a valid chain behind and cannot be detected.
+
+use crate::policy::glob_matches;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// `prev_hash` of the first entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const REDACTED: &str = "<redacted>";
+
+/// How a recorded tool call ended
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    /// Failed to run, or the tool reported an error (`isError`)
+    Error,
+    /// Refused by the tool policy
+    Denied,
+    /// Refused until the user approves it
+    ApprovalRequired,
+}
+
+impl AuditOutcome {
+    /// Outcome of a call that returned `result`; tools report failures in it with
+    /// `isError`
+    pub fn of_result(result: &Value) -> Self {
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            Self::Error
+        } else {
+            Self::Success
+        }
+    }
+}
+
+/// One line of the audit log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Connection that sent the call
+    pub client: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    pub server: String,
+    pub tool: String,
+    /// Call arguments with redacted values replaced
+    pub arguments: Value,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// SHA-256 of the serialized result of calls that returned one, including
+    /// results the tool marked as errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_digest: Option<String>,
+    pub prev_hash: String,
+    /// Must stay the last field: it is appended to the line it hashes
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Hash of the serialized entry `unhashed`, without its `hash` field, chained
+    /// to `prev_hash`
+    pub fn compute_hash(prev_hash: &str, unhashed: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        hasher.update(unhashed.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+// The end of a line written for an entry with the given hash
+fn hash_suffix(hash: &str) -> String {
+    format!(",\"hash\":\"{}\"}}", hash)
+}
+
+/// A tool call to record
+#[derive(Debug)]
+pub struct AuditRecord<'a> {
+    pub client: &'a str,
+    pub session: Option<&'a str>,
+    pub server: &'a str,
+    pub tool: &'a str,
+    pub arguments: &'a Value,
+    pub outcome: AuditOutcome,
+    pub error: Option<&'a str>,
+    pub duration: Duration,
+    pub result: Option<&'a Value>,
+}
+
+struct ChainState {
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+}
+
+/// Replaces the values of arguments whose names match the `redact-arguments`
+/// globs, for the audit log and anywhere else arguments are logged
+#[derive(Debug, Clone, Default)]
+pub struct ArgumentRedactor {
+    patterns: Vec<String>,
+}
+
+impl ArgumentRedactor {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|pattern| pattern.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// A copy of `arguments` with redacted values replaced
+    pub fn redacted(&self, arguments: &Value) -> Value {
+        let mut arguments = arguments.clone();
+        self.redact(&mut arguments);
+        arguments
+    }
+
+    fn redact(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let key = key.to_lowercase();
+                    if self
+                        .patterns
+                        .iter()
+                        .any(|pattern| glob_matches(pattern, &key))
+                    {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            _ => {}
+        }
+    }
+}
+
+/// Writer for the audit log
+pub struct AuditLog {
+    path: PathBuf,
+    redactor: ArgumentRedactor,
+    state: Mutex<ChainState>,
+}
+
+impl AuditLog {
+    /// Open the log for appending, continuing the chain of existing entries
+    pub fn open(path: &Path, redact_arguments: Vec<String>) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create audit log directory: {}", e))?;
+        }
+
+        let (next_seq, last_hash) = match read_last_entry(path)? {
+            Some(entry) => (entry.seq + 1, entry.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| format!("Failed to open audit log '{}': {}", path.display(), e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            redactor: ArgumentRedactor::new(redact_arguments),
+            state: Mutex::new(ChainState {
+                file,
+                next_seq,
+                last_hash,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry for a tool call
+    pub fn record(&self, record: AuditRecord<'_>) -> Result<(), String> {
+        self.append(self.entry(record))
+    }
+
+    /// The entry for a tool call, numbered and chained when it is appended
+    pub fn entry(&self, record: AuditRecord<'_>) -> AuditEntry {
+        let result_digest = record.result.map(|result| {
+            let serialized = serde_json::to_string(result).unwrap_or_default();
+            format!("sha256:{:x}", Sha256::digest(serialized.as_bytes()))
+        });
+        AuditEntry {
+            seq: 0,
+            timestamp: Utc::now(),
+            client: record.client.to_string(),
+            session: record.session.map(str::to_string),
+            server: record.server.to_string(),
+            tool: record.tool.to_string(),
+            arguments: self.redactor.redacted(record.arguments),
+            outcome: record.outcome,
+            error: record.error.map(str::to_string),
+            duration_ms: record.duration.as_millis() as u64,
+            result_digest,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// Number, chain and write an entry made by `entry`. This blocks on the file,
+    /// so async callers run it on a blocking thread.
+    pub fn append(&self, mut entry: AuditEntry) -> Result<(), String> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| "Audit log lock poisoned".to_string())?;
+        entry.seq = state.next_seq;
+        entry.prev_hash = state.last_hash.clone();
+        entry.hash = String::new();
+
+        // The hash covers the exact bytes written, so verifying does not depend on
+        // the entry serializing the same way after being parsed
+        let unhashed = serde_json::to_string(&entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        entry.hash = AuditEntry::compute_hash(&entry.prev_hash, &unhashed);
+        let mut line = unhashed;
+        line.pop();
+        line.push_str(&hash_suffix(&entry.hash));
+        line.push('\n');
+        // A single write keeps concurrent readers from seeing half an entry
+        state
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|_| state.file.flush())
+            .map_err(|e| format!("Failed to write audit log '{}': {}", self.path.display(), e))?;
+
+        state.next_seq += 1;
+        state.last_hash = entry.hash;
+        Ok(())
+    }
+}
+
+/// Result of checking the hash chain of an audit log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditVerification {
+    /// Entries that were checked successfully
+    pub valid_entries: usize,
+    /// Line number (1-based) and description of the first broken entry
+    pub error: Option<(usize, String)>,
+}
+
+/// Read all entries of an audit log
+pub fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open audit log '{}': {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read audit log: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|e| format!("Line {}: invalid audit entry: {}", index + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Check the numbering and hash chain of every entry
+pub fn verify(path: &Path) -> Result<AuditVerification, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open audit log '{}': {}", path.display(), e))?;
+
+    let mut valid_entries = 0;
+    let mut expected_prev = GENESIS_HASH.to_string();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|e| format!("Failed to read audit log: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let broken = |reason: String| AuditVerification {
+            valid_entries,
+            error: Some((line_number, reason)),
+        };
+        let entry: AuditEntry = match serde_json::from_str(&line) {
+            Ok(entry) => entry,
+            Err(e) => return Ok(broken(format!("invalid entry: {}", e))),
+        };
+        if entry.seq != valid_entries as u64 {
+            return Ok(broken(format!(
+                "expected entry {} but found {}",
+                valid_entries, entry.seq
+            )));
+        }
+        if entry.prev_hash != expected_prev {
+            return Ok(broken("previous hash does not match the preceding entry".to_string()));
+        }
+        let Some(unhashed) = line.strip_suffix(&hash_suffix(&entry.hash)) else {
+            return Ok(broken("entry does not end with its hash".to_string()));
+        };
+        if AuditEntry::compute_hash(&entry.prev_hash, &format!("{}}}", unhashed)) != entry.hash {
+            return Ok(broken("entry hash does not match its contents".to_string()));
+        }
+
+        expected_prev = entry.hash;
+        valid_entries += 1;
+    }
+
+    Ok(AuditVerification {
+        valid_entries,
+        error: None,
+    })
+}
+
+fn read_last_entry(path: &Path) -> Result<Option<AuditEntry>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read audit log '{}': {}", path.display(), e)),
+    };
+    match content.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => serde_json::from_str(line).map(Some).map_err(|e| {
+            format!(
+                "Last entry of audit log '{}' is invalid ({}); run `gemini-manager audit verify`",
+                path.display(),
+                e
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn record_call(log: &AuditLog, tool: &str) {
+        log.record(AuditRecord {
+            client: "conn-1",
+            session: Some("session-1"),
+            server: "filesystem",
+            tool,
+            arguments: &json!({ "path": "/tmp/a", "auth": { "api_token": "hunter2" } }),
+            outcome: AuditOutcome::Success,
+            error: None,
+            duration: Duration::from_millis(5),
+            result: Some(&json!({ "ok": true })),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_audit_chain_and_tampering() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(&path, vec!["*token*".to_string()]).unwrap();
+        record_call(&log, "read_file");
+        drop(log);
+        // Reopening continues the existing chain
+        let log = AuditLog::open(&path, vec!["*token*".to_string()]).unwrap();
+        record_call(&log, "write_file");
+
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].seq, 1);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(entries[0].arguments["auth"]["api_token"], REDACTED);
+        assert_eq!(entries[0].arguments["path"], "/tmp/a");
+        assert_eq!(
+            verify(&path).unwrap(),
+            AuditVerification {
+                valid_entries: 2,
+                error: None
+            }
+        );
+
+        let tampered = fs::read_to_string(&path)
+            .unwrap()
+            .replacen("read_file", "list_directory", 1);
+        fs::write(&path, tampered).unwrap();
+        let verification = verify(&path).unwrap();
+        assert_eq!(verification.valid_entries, 0);
+        assert_eq!(verification.error.unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_audit_chain_with_floats() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::open(&path, Vec::new()).unwrap();
+        // Floats that do not survive a parse and re-serialize without float_roundtrip
+        for value in [9.930361573109431e-73, 3.7747769216861355e-54, 7.661036008484923e-59] {
+            let result = json!({ "score": value });
+            log.record(AuditRecord {
+                client: "conn-1",
+                session: None,
+                server: "stats",
+                tool: "mean",
+                arguments: &json!({ "values": [value, -value] }),
+                outcome: AuditOutcome::Success,
+                error: None,
+                duration: Duration::from_millis(1),
+                result: Some(&result),
+            })
+            .unwrap();
+        }
+
+        assert_eq!(
+            verify(&path).unwrap(),
+            AuditVerification {
+                valid_entries: 3,
+                error: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_entries_of_failed_results() {
+        assert_eq!(AuditOutcome::of_result(&json!({ "content": [] })), AuditOutcome::Success);
+        assert_eq!(
+            AuditOutcome::of_result(&json!({ "content": [], "isError": true })),
+            AuditOutcome::Error
+        );
+
+        let redactor = ArgumentRedactor::new(vec!["*TOKEN*".to_string()]);
+        assert_eq!(
+            redactor.redacted(&json!([{ "Api_Token": "hunter2", "path": "/tmp" }])),
+            json!([{ "Api_Token": REDACTED, "path": "/tmp" }])
+        );
+    }
+}
@@ -0,0 +1,53 @@
+// Command-execution MCP server over stdio.
+//
+// Logs go to stderr (RUST_LOG), so they never mix with the protocol on stdout.
+
+use clap::Parser;
+use gemini_mcp::servers::command::{
+    CommandOptions, CommandServer, DEFAULT_MAX_OUTPUT_BYTES, DEFAULT_TIMEOUT_SECS,
+};
+use gemini_mcp::servers::serve_stdio;
+use log::error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(about = "MCP server running commands, in the foreground or as background jobs")]
+struct Args {
+    /// Executable that may run, as a bare name looked up on PATH or an absolute
+    /// path; repeat for several. Without any, every command may run.
+    #[arg(long = "allow", env = "COMMAND_MCP_ALLOW", value_delimiter = ',')]
+    allowed_commands: Vec<String>,
+
+    /// Time limit of foreground commands, in seconds
+    #[arg(long, env = "COMMAND_MCP_TIMEOUT_SECS", default_value_t = DEFAULT_TIMEOUT_SECS)]
+    timeout_secs: u64,
+
+    /// Largest amount of output kept from a command, in bytes
+    #[arg(long, env = "COMMAND_MCP_MAX_OUTPUT_BYTES", default_value_t = DEFAULT_MAX_OUTPUT_BYTES)]
+    max_output_bytes: usize,
+
+    /// Working directory of commands that do not name one
+    #[arg(long, env = "COMMAND_MCP_WORKING_DIR")]
+    working_dir: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    if let Some(dir) = args.working_dir.as_ref().filter(|dir| !dir.is_dir()) {
+        eprintln!("command-mcp: {} is not a directory", dir.display());
+        std::process::exit(2);
+    }
+    let server = CommandServer::new(CommandOptions {
+        allowed_commands: args.allowed_commands,
+        timeout: Duration::from_secs(args.timeout_secs),
+        max_output_bytes: args.max_output_bytes,
+        working_dir: args.working_dir,
+    });
+    if let Err(e) = serve_stdio(server).await {
+        error!("Stdio session failed: {}", e);
+        std::process::exit(1);
+    }
+}
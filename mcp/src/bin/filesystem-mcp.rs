@@ -0,0 +1,49 @@
+// Filesystem MCP server over stdio.
+//
+// Logs go to stderr (RUST_LOG), so they never mix with the protocol on stdout.
+
+use clap::Parser;
+use gemini_mcp::servers::filesystem::{FilesystemOptions, FilesystemServer, DEFAULT_MAX_FILE_SIZE};
+use gemini_mcp::servers::serve_stdio;
+use log::error;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(about = "MCP server giving tools access to files below a set of root directories")]
+struct Args {
+    /// Directory the tools may access; repeat for several. Without any, the
+    /// client's roots are used, or else the working directory.
+    #[arg(long = "root", env = "FILESYSTEM_MCP_ROOTS", value_delimiter = ':')]
+    roots: Vec<PathBuf>,
+
+    /// Only offer the tools that do not modify files
+    #[arg(long, env = "FILESYSTEM_MCP_READ_ONLY")]
+    read_only: bool,
+
+    /// Largest file, in bytes, that may be read or written whole
+    #[arg(long, env = "FILESYSTEM_MCP_MAX_FILE_SIZE", default_value_t = DEFAULT_MAX_FILE_SIZE)]
+    max_file_size: u64,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    let options = FilesystemOptions {
+        roots: args.roots,
+        read_only: args.read_only,
+        max_file_size: args.max_file_size,
+    };
+    let server = match FilesystemServer::new(options) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("filesystem-mcp: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = serve_stdio(server).await {
+        error!("Stdio session failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,148 @@
+// Stdio bridge to the MCP server mode of mcp-hostd.
+//
+// Editors and other MCP clients launch `mcp-bridge` as a stdio MCP server. Every
+// JSON-RPC line read from stdin is forwarded to the running daemon as a
+// `DaemonRequest::McpMessage`, and the daemon's response is written to stdout as
+// one line. Logs go to stderr so they never mix with the protocol.
+
+use clap::Parser;
+use gemini_core::config::UnifiedConfig;
+use gemini_ipc::daemon_messages::{DaemonRequest, DaemonResponse};
+use gemini_ipc::framing::{Hello, IpcClient};
+use gemini_mcp::aggregator::parse_error_response;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+#[derive(Parser, Debug)]
+#[command(about = "Expose the MCP servers managed by mcp-hostd to MCP clients over stdio")]
+struct Args {
+    /// Path of the mcp-hostd socket (defaults to the configured or standard location)
+    #[arg(long)]
+    socket: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct McpMessageOutput {
+    message: Value,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    let socket_path = args
+        .socket
+        .or_else(|| UnifiedConfig::load().mcp.mcp_host_socket_path)
+        .unwrap_or_else(default_socket_path);
+    info!("Bridging stdio to mcp-hostd at {}", socket_path.display());
+
+    let mut stdin = BufReader::new(io::stdin()).lines();
+    let mut stdout = io::stdout();
+    let mut connection: Option<IpcClient> = None;
+
+    loop {
+        let line = match stdin.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                error!("Failed to read from stdin: {}", e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => forward(&socket_path, &mut connection, message).await,
+            Err(e) => Some(parse_error_response(&e.to_string())),
+        };
+
+        if let Some(response) = response {
+            let mut output = response.to_string();
+            output.push('\n');
+            if let Err(e) = stdout.write_all(output.as_bytes()).await {
+                error!("Failed to write to stdout: {}", e);
+                break;
+            }
+            let _ = stdout.flush().await;
+        }
+    }
+    debug!("Stdin closed, exiting");
+}
+
+// Send a message to the daemon, reconnecting once if the daemon was restarted
+async fn forward(
+    socket_path: &PathBuf,
+    connection: &mut Option<IpcClient>,
+    message: Value,
+) -> Option<Value> {
+    let request = DaemonRequest::McpMessage {
+        message: message.clone(),
+    };
+    let mut result = Err("not connected".to_string());
+    for attempt in 0..2 {
+        if connection.is_none() {
+            match IpcClient::connect(socket_path, Hello::new("mcp-bridge")).await {
+                Ok(client) => *connection = Some(client),
+                Err(e) => {
+                    result = Err(format!(
+                        "Cannot connect to mcp-hostd at {}: {}",
+                        socket_path.display(),
+                        e
+                    ));
+                    break;
+                }
+            }
+        }
+        let Some(client) = connection.as_ref() else {
+            break;
+        };
+        result = client
+            .request::<Value>(&request)
+            .await
+            .map_err(|e| format!("Request to mcp-hostd failed: {}", e));
+        // Only retry when the connection was lost, not when the daemon answered
+        // with an error frame
+        if result.is_ok() || !client.is_closed() {
+            break;
+        }
+        *connection = None;
+        if attempt == 0 {
+            warn!("Lost connection to mcp-hostd, reconnecting");
+        }
+    }
+
+    match result {
+        Ok(response) => match DaemonResponse::decode_value::<McpMessageOutput>(response) {
+            Ok(Ok(output)) => (!output.message.is_null()).then_some(output.message),
+            Ok(Err(error)) => error_for(&message, &error.message),
+            Err(e) => error_for(&message, &format!("Invalid response from mcp-hostd: {}", e)),
+        },
+        Err(e) => {
+            error!("{}", e);
+            error_for(&message, &e)
+        }
+    }
+}
+
+// JSON-RPC error for a request the daemon could not answer; notifications get none
+fn error_for(message: &Value, error: &str) -> Option<Value> {
+    let id = message.get("id").filter(|id| !id.is_null())?;
+    Some(json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": -32603, "message": error },
+    }))
+}
+
+fn default_socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("gemini-cli")
+        .join("mcp-hostd.sock")
+}
@@ -1,35 +1,153 @@
+use gemini_ipc::framing::{self, ErrorCode, Hello};
+use gemini_ipc::security::{self, SocketAccess};
 use gemini_ipc::daemon_messages::{
-    BrokerCapabilities, DaemonRequest, DaemonResponse, DaemonResult, ToolDefinition,
+    BrokerCapabilities, DaemonErrorCode, DaemonRequest, DaemonResponse, DaemonResult,
+    ResourceContents, ResourceUpdate, ResourceUpdates, ResponsePayload, ServerList, ServerState,
+    ServerStatus, ToolCallCheck, ToolCallDecision, ToolDefinition,
 };
-use gemini_mcp::{load_mcp_servers, McpHost};
+use async_trait::async_trait;
+use gemini_mcp::aggregator::{self, AggregatorBackend};
+use gemini_mcp::audit::{ArgumentRedactor, AuditLog, AuditOutcome, AuditRecord};
+use gemini_mcp::config::{get_audit_log_path, get_mcp_config_path};
+use gemini_mcp::memory_tools;
+use gemini_mcp::sampling::GeminiSamplingHandler;
+use gemini_mcp::{
+    load_mcp_servers, ApprovalTokens, ConfigReload, McpHost, McpServerConfig, PolicyDecision,
+    ResourceUpdated, ToolPolicy,
+};
+use gemini_core::client::GeminiClient;
 use gemini_core::config::{self, UnifiedConfig};
 use gemini_memory::schema::{EmbeddingModelVariant, self};
 use gemini_memory::MemoryStore;
 use gemini_memory::broker::McpHostInterface;
 use log::{debug, error, info, warn};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde_json::{json, Value};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{UnixListener, UnixStream};
+use tokio::net::UnixStream;
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::sync::mpsc;
-use tokio::time::{sleep, Duration};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{sleep, Duration, Instant};
 use std::str::FromStr;
-use gemini_core::rpc_types::{ServerCapabilities, Tool as CoreTool};
+use gemini_core::rpc_types::{
+    GetPromptResult, ListPromptsResult, ListResourceTemplatesResult, Prompt, ReadResourceResult,
+    ResourceTemplate, ServerCapabilities, ToolCallResult, ToolContent,
+};
+use std::collections::HashMap;
 
 // Helper function to determine the socket path
 fn get_socket_path() -> Result<PathBuf, String> {
-    let base_dir = dirs::runtime_dir()
-        .or_else(dirs::data_local_dir)
-        .ok_or_else(|| "Could not determine runtime or data local directory".to_string())?;
-    let socket_dir = base_dir.join("gemini-cli");
-    fs::create_dir_all(&socket_dir)
+    let socket_dir = security::socket_dir()
         .map_err(|e| format!("Failed to create socket directory: {}", e))?;
     Ok(socket_dir.join("mcp-hostd.sock"))
 }
 
+// Servers disabled in mcp_servers.json are left out entirely
+fn load_enabled_servers() -> Result<Vec<McpServerConfig>, String> {
+    let configs = load_mcp_servers()?;
+    Ok(configs.into_iter().filter(|config| config.enabled).collect())
+}
+
+// Wait after a change before reloading, so an editor's write and rename are applied once
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Re-read mcp_servers.json and apply it to the running host. A file that fails to
+// load leaves the current servers untouched.
+async fn reload_server_configs(host: &McpHost, trigger: &str) -> Result<ConfigReload, String> {
+    info!("Reloading MCP server configuration ({})", trigger);
+    match load_enabled_servers() {
+        Ok(configs) => Ok(host.reload_configs(configs).await),
+        Err(e) => {
+            error!("Keeping the current MCP server configuration: {}", e);
+            Err(e)
+        }
+    }
+}
+
+// Watch the directory of mcp_servers.json rather than the file itself, since
+// editors often replace the file instead of writing it in place
+fn watch_server_config(reload_tx: mpsc::Sender<&'static str>) -> Option<RecommendedWatcher> {
+    let config_path = match get_mcp_config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Not watching MCP server configuration: {}", e);
+            return None;
+        }
+    };
+    let (Some(config_dir), Some(file_name)) = (config_path.parent(), config_path.file_name())
+    else {
+        return None;
+    };
+    let file_name = file_name.to_os_string();
+
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) => {
+                let config_changed = !matches!(event.kind, EventKind::Access(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == Some(file_name.as_os_str()));
+                if config_changed {
+                    // A reload already queued will pick up this change too
+                    let _ = reload_tx.try_send("configuration file changed");
+                }
+            }
+            Err(e) => warn!("Error watching MCP server configuration: {}", e),
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Not watching MCP server configuration: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = watcher.watch(config_dir, RecursiveMode::NonRecursive) {
+        warn!(
+            "Not watching MCP server configuration in {}: {}",
+            config_dir.display(),
+            e
+        );
+        return None;
+    }
+    info!("Watching {} for changes", config_path.display());
+    Some(watcher)
+}
+
+// Apply configuration changes one at a time, coalescing changes that arrive together
+fn spawn_config_reloader(host: Arc<McpHost>, mut reload_rx: mpsc::Receiver<&'static str>) {
+    tokio::spawn(async move {
+        while let Some(trigger) = reload_rx.recv().await {
+            sleep(CONFIG_RELOAD_DEBOUNCE).await;
+            while reload_rx.try_recv().is_ok() {}
+            let _ = reload_server_configs(&host, trigger).await;
+        }
+    });
+}
+
+fn to_ipc_status(status: gemini_mcp::ServerStatus) -> ServerStatus {
+    ServerStatus {
+        name: status.name,
+        state: match status.state {
+            gemini_mcp::ServerState::Running => ServerState::Running,
+            gemini_mcp::ServerState::Initializing => ServerState::Initializing,
+            gemini_mcp::ServerState::Stopped => ServerState::Stopped,
+            gemini_mcp::ServerState::Failed => ServerState::Failed,
+        },
+        transport: status.transport.to_string(),
+        eager: status.eager,
+        tools: status.tools,
+        resources: status.resources,
+        prompts: status.prompts,
+        pid: status.pid,
+        uptime_secs: status.uptime.map(|uptime| uptime.as_secs()),
+        last_error: status.last_error,
+    }
+}
+
 // Extension trait to add embedding and broker capabilities functions
 trait McpHostExtensions {
     async fn generate_embedding(&self, text: &str, model_variant: &str)
@@ -77,9 +195,9 @@ impl McpHostExtensions for Arc<McpHost> {
 
         // Add internally handled memory tools if the store exists
         if internal_memory_store.is_some() {
-            let internal_tools = get_internal_memory_tool_definitions();
+            let internal_tools = memory_tools::tool_definitions();
             all_caps.tools.extend(internal_tools);
-            info!("Added {} internal memory tools to broker capabilities", get_internal_memory_tool_definitions().len());
+            info!("Added {} internal memory tools to broker capabilities", memory_tools::tool_definitions().len());
         }
 
         // Convert to broker capabilities format
@@ -99,6 +217,72 @@ impl McpHostExtensions for Arc<McpHost> {
 struct DaemonState {
     host: Arc<McpHost>,
     memory_store: Option<Arc<MemoryStore>>,
+    // Resource updates not yet collected by this connection
+    resource_updates: Arc<tokio::sync::Mutex<broadcast::Receiver<ResourceUpdated>>>,
+    tool_policy: Arc<ToolPolicy>,
+    // Tokens issued for calls the policy asks about, redeemed when they run
+    approvals: Arc<ApprovalTokens>,
+    audit_log: Option<Arc<AuditLog>>,
+    // Hides sensitive argument values from the audit log and the debug log
+    redactor: Arc<ArgumentRedactor>,
+    // Identifies the connection in audit log entries
+    client: String,
+    // Stops the daemon when a client requests shutdown
+    shutdown: mpsc::Sender<()>,
+}
+
+// External MCP clients see the same tools as our own clients, and their calls go
+// through the policy and audit log. Calls that need approval are refused, since
+// these clients have no way to ask our user.
+#[async_trait]
+impl AggregatorBackend for DaemonState {
+    async fn capabilities(&self) -> ServerCapabilities {
+        let mut caps = self.host.get_all_capabilities().await;
+        if self.memory_store.is_some() {
+            caps.tools.extend(memory_tools::tool_definitions());
+        }
+        caps
+    }
+
+    async fn call_tool(&self, server: &str, tool: &str, args: Value) -> ToolCallResult {
+        let response = execute_audited_tool(self, server, tool, args, None, None).await;
+        match response.payload {
+            ResponsePayload::Result(DaemonResult::ExecutionOutput(output)) => {
+                ToolCallResult::from_value(output)
+            }
+            ResponsePayload::Result(other) => {
+                ToolCallResult::from_value(serde_json::to_value(other).unwrap_or(Value::Null))
+            }
+            ResponsePayload::Error(error) => ToolCallResult {
+                content: vec![ToolContent::Text {
+                    text: error.message,
+                }],
+                structured_content: None,
+                is_error: true,
+            },
+        }
+    }
+
+    async fn list_resource_templates(&self) -> Vec<ResourceTemplate> {
+        self.host.list_resource_templates().await
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult, String> {
+        self.host.read_resource(None, uri).await.map(|(_, result)| result)
+    }
+
+    async fn list_prompts(&self) -> Vec<Prompt> {
+        self.host.list_prompts().await
+    }
+
+    async fn get_prompt(
+        &self,
+        server: &str,
+        prompt: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult, String> {
+        self.host.get_prompt(server, prompt, arguments).await
+    }
 }
 
 #[tokio::main]
@@ -116,17 +300,8 @@ async fn main() {
         }
     };
 
-    // Clean up existing socket file if it exists
-    if socket_path.exists() {
-        info!("Removing existing socket file: {}", socket_path.display());
-        if let Err(e) = fs::remove_file(&socket_path) {
-            error!("Failed to remove existing socket file: {}", e);
-            std::process::exit(1);
-        }
-    }
-
     // Load MCP server configurations for McpHost
-    let mcp_server_configs = match load_mcp_servers() {
+    let mcp_server_configs = match load_enabled_servers() {
         Ok(configs) => configs,
         Err(e) => {
             error!("Failed to load MCP server configurations: {}", e);
@@ -135,6 +310,63 @@ async fn main() {
         }
     };
 
+    let unified_config = UnifiedConfig::load();
+
+    let socket_access = match SocketAccess::from_config(&unified_config.ipc) {
+        Ok(access) => {
+            if access.requires_handshake() {
+                info!("IPC clients must complete the shared-secret handshake");
+            }
+            access
+        }
+        Err(e) => {
+            error!("Failed to load IPC access settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Compile the tool policy before any server is launched; a broken policy must not
+    // silently fall back to allowing everything
+    let tool_policy = match ToolPolicy::new(&unified_config.mcp.tool_policy) {
+        Ok(policy) => {
+            info!("Loaded tool policy with {} rules", policy.len());
+            Arc::new(policy)
+        }
+        Err(e) => {
+            error!("Failed to load tool policy: {}", e);
+            let _ = fs::remove_file(&socket_path);
+            std::process::exit(1);
+        }
+    };
+
+    let audit_log = if unified_config.mcp.audit.enabled {
+        let audit_path = unified_config
+            .mcp
+            .audit
+            .path
+            .clone()
+            .or_else(get_audit_log_path);
+        let Some(audit_path) = audit_path else {
+            error!("Could not determine the audit log location; set mcp.audit.path");
+            let _ = fs::remove_file(&socket_path);
+            std::process::exit(1);
+        };
+        match AuditLog::open(&audit_path, unified_config.mcp.audit.redact_arguments.clone()) {
+            Ok(log) => {
+                info!("Recording tool calls in audit log {}", audit_path.display());
+                Some(Arc::new(log))
+            }
+            Err(e) => {
+                error!("Failed to open audit log: {}", e);
+                let _ = fs::remove_file(&socket_path);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        info!("Audit log disabled");
+        None
+    };
+
     // Create MCP Host (implements McpHostInterface)
     let mcp_host = match McpHost::new(mcp_server_configs).await {
         Ok(host) => Arc::new(host),
@@ -146,8 +378,28 @@ async fn main() {
     };
     info!("MCP Host initialization completed.");
 
+    // Let servers with a sampling policy request completions through the Gemini API
+    match GeminiClient::new(unified_config.gemini_api.clone()) {
+        Ok(client) => {
+            mcp_host.set_sampling_handler(Arc::new(GeminiSamplingHandler::new(client)));
+            info!("Sampling requests from MCP servers will be answered by Gemini.");
+        }
+        Err(e) => warn!("Sampling disabled, Gemini client unavailable: {}", e),
+    }
+
+    // Each connection collects resource updates from its own subscriber; this one logs them
+    let mut updates_rx = mcp_host.resource_updates();
+    tokio::spawn(async move {
+        loop {
+            match updates_rx.recv().await {
+                Ok(update) => info!("Resource '{}' updated on server '{}'", update.uri, update.server),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
     // --- Initialize MemoryStore --- 
-    let unified_config = UnifiedConfig::load();
 
     // Directly access fields, assuming unified_config.memory is not Option
     let db_path_opt: Option<PathBuf> = unified_config.memory.db_path.clone();
@@ -185,7 +437,7 @@ async fn main() {
     // --- End MemoryStore Init --- 
 
     // Bind the Unix listener
-    let listener = match UnixListener::bind(&socket_path) {
+    let listener = match security::bind(&socket_path) {
         Ok(listener) => {
             info!("IPC Listener bound to {}", socket_path.display());
             listener
@@ -201,6 +453,43 @@ async fn main() {
     // Set up shutdown channel
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
 
+    // Connections get a copy of this state naming their client
+    let base_state = DaemonState {
+        host: Arc::clone(&mcp_host),
+        memory_store: memory_store_instance.clone(),
+        resource_updates: Arc::new(tokio::sync::Mutex::new(mcp_host.resource_updates())),
+        tool_policy: tool_policy.clone(),
+        approvals: Arc::default(),
+        audit_log: audit_log.clone(),
+        redactor: Arc::new(ArgumentRedactor::new(
+            unified_config.mcp.audit.redact_arguments.clone(),
+        )),
+        client: String::new(),
+        shutdown: shutdown_tx.clone(),
+    };
+
+    // Serve the combined namespace to external MCP clients over HTTP when enabled.
+    // Any local user can reach the port, so clients must present the shared secret.
+    let http_port = unified_config.mcp.aggregator.http_port;
+    let http_token = socket_access.shared_secret().map(str::to_string);
+    if let (Some(_), None) = (http_port, &http_token) {
+        error!("Not serving MCP over HTTP: set `shared-secret` in [ipc] for clients to authenticate with");
+    }
+    if let (Some(port), Some(token)) = (http_port, http_token) {
+        let http_state = base_state.clone();
+        let backend_for: aggregator::http::BackendFactory = Arc::new(move |client| {
+            Arc::new(DaemonState {
+                client,
+                ..http_state.clone()
+            }) as Arc<dyn AggregatorBackend>
+        });
+        tokio::spawn(async move {
+            if let Err(e) = aggregator::http::serve(port, &token, backend_for).await {
+                error!("{}", e);
+            }
+        });
+    }
+
     // Set up signal handlers for graceful shutdown
     let mut sigint = match signal(SignalKind::interrupt()) {
         Ok(signal) => signal,
@@ -237,21 +526,55 @@ async fn main() {
         let _ = shutdown_tx_term.send(()).await;
     });
 
+    // Apply edits to mcp_servers.json without restarting the daemon, when the file
+    // changes or on SIGHUP
+    let (reload_tx, reload_rx) = mpsc::channel::<&'static str>(1);
+    spawn_config_reloader(Arc::clone(&mcp_host), reload_rx);
+    let _config_watcher = watch_server_config(reload_tx.clone());
+    match signal(SignalKind::hangup()) {
+        Ok(mut sighup) => {
+            tokio::spawn(async move {
+                while sighup.recv().await.is_some() {
+                    info!("Received SIGHUP");
+                    let _ = reload_tx.try_send("SIGHUP");
+                }
+            });
+        }
+        Err(e) => warn!("Failed to set up SIGHUP handler: {}", e),
+    }
+
     info!("MCP Host Daemon running. Accepting IPC connections...");
 
     // Main loop: Accept connections and listen for shutdown signal
+    let mut connection_count: u64 = 0;
     loop {
         tokio::select! {
             // Accept new IPC connection
-            Ok((stream, _addr)) = listener.accept() => {
-                info!("Accepted new IPC connection");
-                let state = DaemonState {
-                    host: Arc::clone(&mcp_host),
-                    memory_store: memory_store_instance.clone(),
-                };
+            Ok((mut stream, _addr)) = listener.accept() => {
+                connection_count += 1;
+                let connection = connection_count;
+                let access = socket_access.clone();
+                let base_state = base_state.clone();
 
-                // Spawn a task to handle this client connection
+                // Spawn a task to check and then handle this client connection
                 tokio::spawn(async move {
+                    let peer = match access.authorize(&mut stream).await {
+                        Ok(peer) => peer,
+                        Err(e) => {
+                            warn!("Rejected IPC connection conn-{}: {}", connection, e);
+                            return;
+                        }
+                    };
+                    let client = match peer.pid {
+                        Some(pid) => format!("conn-{} (pid {})", connection, pid),
+                        None => format!("conn-{}", connection),
+                    };
+                    info!("Accepted new IPC connection {}", client);
+                    let state = DaemonState {
+                        client,
+                        resource_updates: Arc::new(tokio::sync::Mutex::new(base_state.host.resource_updates())),
+                        ..base_state
+                    };
                     handle_client(stream, state).await;
                 });
             }
@@ -287,75 +610,246 @@ async fn main() {
     info!("MCP Host Daemon terminated");
 }
 
-/// Handles communication with a single connected client.
-async fn handle_client(mut stream: UnixStream, state: DaemonState) {
+/// Handles communication with a single connected client, answering its requests concurrently.
+async fn handle_client(stream: UnixStream, state: DaemonState) {
     info!("Client handler started for connection.");
-    let (mut reader, mut writer) = stream.split();
+    let result = framing::serve(stream, Hello::new("mcp-hostd"), move |request| {
+        let state = state.clone();
+        async move {
+            let daemon_request: DaemonRequest = match request.parse() {
+                Ok(daemon_request) => daemon_request,
+                Err(e) => {
+                    warn!("Failed to deserialize request from client: {}", e);
+                    if let Err(e) = request.fail(ErrorCode::InvalidRequest, format!("Invalid request format: {}", e)) {
+                        warn!("Failed to send deserialization error to client: {}", e);
+                    }
+                    return;
+                }
+            };
+            debug!("Received request {}: {:?}", request.id, daemon_request);
 
-    loop {
-        // 1. Read message length (u32)
-        let msg_len = match reader.read_u32().await {
-            Ok(len) => len,
-            Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                info!("Client disconnected gracefully.");
-                break;
-            }
-            Err(e) => {
-                warn!("Failed to read message length from client: {}", e);
-                break;
+            let response = process_request(daemon_request, state).await;
+            match request.respond(&response) {
+                Ok(()) => debug!("Successfully sent response: {:?}", response),
+                Err(e) => warn!("Failed to send response to client: {}", e),
             }
-        };
-
-        if msg_len == 0 {
-            warn!("Received message with length 0. Closing connection.");
-            break;
         }
+    })
+    .await;
 
-        debug!("Received message header with length: {} bytes", msg_len);
+    match result {
+        Ok(()) => info!("Client disconnected gracefully."),
+        Err(e) => warn!("Client connection failed: {}", e),
+    }
+}
 
-        // 2. Read the message payload
-        let mut buffer = vec![0u8; msg_len as usize];
-        if let Err(e) = reader.read_exact(&mut buffer).await {
-            warn!("Failed to read message payload from client: {}", e);
-            break;
+/// Checks a tool call against the policy and runs it on its server or the internal memory store.
+async fn execute_tool_request(
+    host: &Arc<McpHost>,
+    memory_store: &Option<Arc<MemoryStore>>,
+    tool_policy: &ToolPolicy,
+    server: &str,
+    tool: &str,
+    args: Value,
+    approved: bool,
+) -> DaemonResponse {
+    info!("Executing tool '{}' on server '{}'", tool, server);
+
+    // Every client goes through the tool policy, including calls to internal tools
+    match tool_policy.evaluate(server, tool, &args) {
+        PolicyDecision::Allow => {}
+        PolicyDecision::Ask { .. } if approved => {
+            info!("Tool '{}/{}' approved by the user", server, tool);
+        }
+        PolicyDecision::Ask { rationale } => {
+            info!("Tool '{}/{}' requires approval", server, tool);
+            let mut message = format!("Tool '{}/{}' requires the user's approval", server, tool);
+            if let Some(rationale) = rationale {
+                message = format!("{}: {}", message, rationale);
+            }
+            return DaemonResponse::error_with_code(message, DaemonErrorCode::ApprovalRequired);
+        }
+        PolicyDecision::Deny { rationale } => {
+            warn!("Tool '{}/{}' denied by policy: {}", server, tool, rationale);
+            return DaemonResponse::error_with_code(
+                format!("Tool call denied by policy: {}", rationale),
+                DaemonErrorCode::PolicyDenied,
+            );
         }
+    }
 
-        // 3. Deserialize request
-        let request: DaemonRequest = match serde_json::from_slice(&buffer) {
-            Ok(req) => req,
-            Err(e) => {
-                warn!("Failed to deserialize request from client: {}", e);
-                // Send an error response back
-                let response = DaemonResponse::error(format!("Invalid request format: {}", e));
-                if write_response(&mut writer, &response).await.is_err() {
-                    warn!("Failed to send deserialization error response to client.");
+    // --- Intercept memory-store-mcp calls --- 
+    if server == memory_tools::SERVER_NAME {
+        if let Some(ref memory_store_arc) = memory_store { // Use ref memory_store_arc
+            // Handle memory operations internally
+            debug!("Intercepted call for internal memory store: {}", tool);
+            match memory_tools::handle_tool(tool, args, memory_store_arc).await {
+                Ok(result_value) => {
+                     debug!(
+                        "Internal memory tool execution succeeded with result: {}",
+                        serde_json::to_string(&result_value).unwrap_or_else(|_| "<unserializable>".to_string())
+                    );
+                     return DaemonResponse::success(DaemonResult::ExecutionOutput(result_value));
+                }
+                Err(e) => {
+                    error!("Internal memory tool '{}' failed: {}", tool, e);
+                    return DaemonResponse::error(format!("Internal memory tool error: {}", e));
                 }
-                continue; // Or break, depending on desired behavior
             }
-        };
+        } else {
+             error!("Received request for memory-store-mcp, but internal store is not initialized.");
+             return DaemonResponse::error("Internal memory store not available".to_string());
+        }
+    }
+    // --- End Intercept --- 
+
+    // If not intercepted, proceed with standard MCP call
+    match host.execute_tool(server, tool, args).await {
+        Ok(result_value) => {
+            debug!(
+                "Tool execution succeeded with result: {}",
+                serde_json::to_string(&result_value)
+                    .unwrap_or_else(|_| "unable to serialize".to_string())
+            );
+            DaemonResponse::success(DaemonResult::ExecutionOutput(result_value))
+        }
+        Err(e) => {
+            error!(
+                "Tool execution failed: {} on server {} - Error: {}",
+                tool, server, e
+            );
+            DaemonResponse::error(format!("Tool execution error: {}", e))
+        }
+    }
+}
 
-        debug!("Received request: {:?}", request);
+/// Checks a tool call against the policy and the server's `auto_execute` list,
+/// issuing an approval token for calls the policy asks about.
+async fn check_tool_call(state: &DaemonState, server: &str, tool: &str, args: &Value) -> ToolCallCheck {
+    match state.tool_policy.evaluate(server, tool, args) {
+        PolicyDecision::Allow => {
+            let decision = if state.host.is_auto_execute(server, tool).await {
+                ToolCallDecision::Allow
+            } else {
+                ToolCallDecision::Confirm
+            };
+            ToolCallCheck {
+                decision,
+                rationale: None,
+                approval_token: None,
+            }
+        }
+        PolicyDecision::Ask { rationale } => ToolCallCheck {
+            decision: ToolCallDecision::Ask,
+            rationale,
+            approval_token: Some(state.approvals.issue(server, tool, args)),
+        },
+        PolicyDecision::Deny { rationale } => ToolCallCheck {
+            decision: ToolCallDecision::Deny,
+            rationale: Some(rationale),
+            approval_token: None,
+        },
+    }
+}
 
-        // 4. Process request and generate response
-        let response = process_request(request, state.clone()).await;
+/// Runs a tool call and records it in the audit log.
+async fn execute_audited_tool(
+    state: &DaemonState,
+    server: &str,
+    tool: &str,
+    args: Value,
+    approval_token: Option<&str>,
+    session_id: Option<&str>,
+) -> DaemonResponse {
+    let started = Instant::now();
+    debug!(
+        "Arguments of tool '{}/{}': {}",
+        server,
+        tool,
+        state.redactor.redacted(&args)
+    );
+    // Only a token issued for exactly this call approves it
+    let approved = approval_token
+        .is_some_and(|token| state.approvals.redeem(token, server, tool, &args));
+    let audit_args = state.audit_log.as_ref().map(|_| args.clone());
+    let response = execute_tool_request(
+        &state.host,
+        &state.memory_store,
+        &state.tool_policy,
+        server,
+        tool,
+        args,
+        approved,
+    )
+    .await;
 
-        // 5. Send response
-        match write_response(&mut writer, &response).await {
-            Ok(_) => debug!("Successfully sent response: {:?}", response),
-            Err(e) => {
-                warn!("Failed to send response to client: {}", e);
-                break;
+    if let (Some(audit_log), Some(arguments)) = (&state.audit_log, audit_args) {
+        let (outcome, error, result) = match &response.payload {
+            ResponsePayload::Result(DaemonResult::ExecutionOutput(output)) => {
+                let outcome = AuditOutcome::of_result(output);
+                let error = (outcome == AuditOutcome::Error).then_some("Tool reported an error");
+                (outcome, error, Some(output))
+            }
+            ResponsePayload::Result(_) => (AuditOutcome::Success, None, None),
+            ResponsePayload::Error(error) => {
+                let outcome = match error.code {
+                    Some(DaemonErrorCode::PolicyDenied) => AuditOutcome::Denied,
+                    Some(DaemonErrorCode::ApprovalRequired) => AuditOutcome::ApprovalRequired,
+                    None => AuditOutcome::Error,
+                };
+                (outcome, Some(error.message.as_str()), None)
             }
+        };
+        let record = AuditRecord {
+            client: &state.client,
+            session: session_id,
+            server,
+            tool,
+            arguments: &arguments,
+            outcome,
+            error,
+            duration: started.elapsed(),
+            result,
+        };
+        // Writing blocks on the file, so it runs off the async workers
+        let entry = audit_log.entry(record);
+        let audit_log = Arc::clone(audit_log);
+        match tokio::task::spawn_blocking(move || audit_log.append(entry)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Failed to record tool call in audit log: {}", e),
+            Err(e) => error!("Failed to record tool call in audit log: {}", e),
         }
     }
+    response
+}
 
-    info!("Client handler finished for connection.");
+/// Drains the resource updates a connection received since it last asked, once each.
+fn take_resource_updates(receiver: &mut broadcast::Receiver<ResourceUpdated>) -> Vec<ResourceUpdate> {
+    let mut updates = Vec::new();
+    loop {
+        match receiver.try_recv() {
+            Ok(update) => {
+                let update = ResourceUpdate {
+                    server: update.server,
+                    uri: update.uri,
+                };
+                if !updates.contains(&update) {
+                    updates.push(update);
+                }
+            }
+            Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                warn!("Dropped {} resource update notifications for this client", skipped);
+            }
+            Err(_) => return updates,
+        }
+    }
 }
 
 /// Processes a deserialized DaemonRequest and returns a DaemonResponse.
 async fn process_request(request: DaemonRequest, state: DaemonState) -> DaemonResponse {
-    let host = state.host;
-    let memory_store = state.memory_store;
+    let host = &state.host;
+    let memory_store = &state.memory_store;
+    let resource_updates = &state.resource_updates;
 
     match request {
         DaemonRequest::GetCapabilities => {
@@ -369,63 +863,36 @@ async fn process_request(request: DaemonRequest, state: DaemonState) -> DaemonRe
             );
             // Manually add internal memory tools if store exists
             if memory_store.is_some() {
-                let internal_tools = get_internal_memory_tool_definitions();
+                let internal_tools = memory_tools::tool_definitions();
                 let count = internal_tools.len();
                 caps.tools.extend(internal_tools);
                  info!("Added {} internal memory tools to reported capabilities", count);
             }
             DaemonResponse::success(DaemonResult::Capabilities(caps)) // Return the modified caps
         }
-        DaemonRequest::ExecuteTool { server, tool, args } => {
-            info!(
-                "Executing tool '{}' on server '{}' with args: {}",
-                tool,
-                server,
-                serde_json::to_string(&args).unwrap_or_else(|_| "unable to serialize".to_string())
-            );
-
-            // --- Intercept memory-store-mcp calls --- 
-            if server == "memory-store-mcp" {
-                if let Some(ref memory_store_arc) = memory_store { // Use ref memory_store_arc
-                    // Handle memory operations internally
-                    debug!("Intercepted call for internal memory store: {}", tool);
-                    match handle_internal_memory_tool(&tool, args, &*memory_store_arc).await { // Dereference Arc
-                        Ok(result_value) => {
-                             debug!(
-                                "Internal memory tool execution succeeded with result: {}",
-                                serde_json::to_string(&result_value).unwrap_or_else(|_| "<unserializable>".to_string())
-                            );
-                             return DaemonResponse::success(DaemonResult::ExecutionOutput(result_value));
-                        }
-                        Err(e) => {
-                            error!("Internal memory tool '{}' failed: {}", tool, e);
-                            return DaemonResponse::error(format!("Internal memory tool error: {}", e));
-                        }
-                    }
-                } else {
-                     error!("Received request for memory-store-mcp, but internal store is not initialized.");
-                     return DaemonResponse::error("Internal memory store not available".to_string());
-                }
-            }
-            // --- End Intercept --- 
-
-            // If not intercepted, proceed with standard MCP call
-            match host.execute_tool(&server, &tool, args).await {
-                Ok(result_value) => {
-                    debug!(
-                        "Tool execution succeeded with result: {}",
-                        serde_json::to_string(&result_value)
-                            .unwrap_or_else(|_| "unable to serialize".to_string())
-                    );
-                    DaemonResponse::success(DaemonResult::ExecutionOutput(result_value))
-                }
-                Err(e) => {
-                    error!(
-                        "Tool execution failed: {} on server {} - Error: {}",
-                        tool, server, e
-                    );
-                    DaemonResponse::error(format!("Tool execution error: {}", e))
-                }
+        DaemonRequest::ExecuteTool {
+            server,
+            tool,
+            args,
+            approval_token,
+            session_id,
+        } => {
+            execute_audited_tool(
+                &state,
+                &server,
+                &tool,
+                args,
+                approval_token.as_deref(),
+                session_id.as_deref(),
+            )
+            .await
+        }
+        DaemonRequest::CheckToolCall { server, tool, args } => {
+            let check = check_tool_call(&state, &server, &tool, &args).await;
+            debug!("Checked tool '{}/{}': {:?}", server, tool, check.decision);
+            match serde_json::to_value(check) {
+                Ok(output) => DaemonResponse::success(DaemonResult::ExecutionOutput(output)),
+                Err(e) => DaemonResponse::error(format!("Error serializing tool check: {}", e)),
             }
         }
         DaemonRequest::GenerateEmbedding {
@@ -447,7 +914,7 @@ async fn process_request(request: DaemonRequest, state: DaemonState) -> DaemonRe
         }
         DaemonRequest::GetBrokerCapabilities => {
             info!("Getting broker capabilities");
-            match host.get_broker_capabilities(&memory_store).await {
+            match host.get_broker_capabilities(memory_store).await {
                 Ok(caps) => {
                     info!(
                         "Retrieved broker capabilities with {} tools",
@@ -460,164 +927,147 @@ async fn process_request(request: DaemonRequest, state: DaemonState) -> DaemonRe
                     DaemonResponse::error(format!("Error getting broker capabilities: {}", e))
                 }
             }
-        } // Handle other request types here in the future
+        }
+        DaemonRequest::SetWorkspaceRoots { roots } => {
+            debug!("Setting workspace roots: {:?}", roots);
+            let changed = host.set_workspace_roots(roots).await;
+            DaemonResponse::success(DaemonResult::ExecutionOutput(json!({ "changed": changed })))
+        }
+        DaemonRequest::ListPrompts => {
+            let prompts = host.list_prompts().await;
+            info!("Listing {} prompts", prompts.len());
+            DaemonResponse::success(DaemonResult::Prompts(ListPromptsResult { prompts }))
+        }
+        DaemonRequest::GetPrompt {
+            server,
+            prompt,
+            arguments,
+        } => {
+            info!("Expanding prompt '{}' on server '{}'", prompt, server);
+            match host.get_prompt(&server, &prompt, arguments).await {
+                Ok(result) => DaemonResponse::success(DaemonResult::Prompt(result)),
+                Err(e) => {
+                    error!("Failed to get prompt: {}", e);
+                    DaemonResponse::error(format!("Error getting prompt: {}", e))
+                }
+            }
+        }
+        DaemonRequest::ListResourceTemplates => {
+            let resource_templates = host.list_resource_templates().await;
+            info!("Listing {} resource templates", resource_templates.len());
+            DaemonResponse::success(DaemonResult::ResourceTemplates(ListResourceTemplatesResult {
+                resource_templates,
+            }))
+        }
+        DaemonRequest::ReadResource { server, uri } => {
+            info!("Reading resource '{}'", uri);
+            match host.read_resource(server.as_deref(), &uri).await {
+                Ok((server, result)) => DaemonResponse::success(DaemonResult::ResourceContents(
+                    ResourceContents {
+                        server,
+                        contents: result.contents,
+                    },
+                )),
+                Err(e) => {
+                    error!("Failed to read resource: {}", e);
+                    DaemonResponse::error(format!("Error reading resource: {}", e))
+                }
+            }
+        }
+        DaemonRequest::SubscribeResource { server, uri } => {
+            match host.subscribe_resource(&server, &uri).await {
+                Ok(()) => {
+                    DaemonResponse::success(DaemonResult::ExecutionOutput(json!({ "subscribed": true })))
+                }
+                Err(e) => {
+                    error!("Failed to subscribe to resource: {}", e);
+                    DaemonResponse::error(format!("Error subscribing to resource: {}", e))
+                }
+            }
+        }
+        DaemonRequest::UnsubscribeResource { server, uri } => {
+            match host.unsubscribe_resource(&server, &uri).await {
+                Ok(()) => {
+                    DaemonResponse::success(DaemonResult::ExecutionOutput(json!({ "subscribed": false })))
+                }
+                Err(e) => {
+                    error!("Failed to unsubscribe from resource: {}", e);
+                    DaemonResponse::error(format!("Error unsubscribing from resource: {}", e))
+                }
+            }
+        }
+        DaemonRequest::TakeResourceUpdates => {
+            let updates = take_resource_updates(&mut *resource_updates.lock().await);
+            debug!("Returning {} resource updates", updates.len());
+            DaemonResponse::success(DaemonResult::ResourceUpdates(ResourceUpdates { updates }))
+        }
+        DaemonRequest::McpMessage { message } => {
+            let response = aggregator::handle_message(&state, message).await;
+            DaemonResponse::success(DaemonResult::ExecutionOutput(json!({ "message": response })))
+        }
+        DaemonRequest::ListServers => {
+            let servers = host.list_servers().await.into_iter().map(to_ipc_status).collect();
+            DaemonResponse::success(DaemonResult::Servers(ServerList { servers }))
+        }
+        DaemonRequest::StartServer { name } => {
+            info!("Starting server '{}' on request", name);
+            server_status_response(host.start_server(&name).await, "starting")
+        }
+        DaemonRequest::StopServer { name } => {
+            server_status_response(host.stop_server(&name).await, "stopping")
+        }
+        DaemonRequest::RestartServer { name } => {
+            info!("Restarting server '{}' on request", name);
+            server_status_response(host.restart_server(&name).await, "restarting")
+        }
+        DaemonRequest::ReloadConfig => {
+            match reload_server_configs(host, &format!("requested by {}", state.client)).await {
+                Ok(reload) => DaemonResponse::success(DaemonResult::ExecutionOutput(json!({
+                    "added": reload.added,
+                    "removed": reload.removed,
+                    "restarted": reload.restarted,
+                    "updated": reload.updated,
+                }))),
+                Err(e) => DaemonResponse::error(format!("Error reloading configuration: {}", e)),
+            }
+        }
+        DaemonRequest::GetResource {
+            server,
+            name,
+            params,
+        } => {
+            if params.is_some() {
+                debug!("Ignoring params of resource '{}': resources/read takes only a URI", name);
+            }
+            match host.get_resource(&server, &name).await {
+                Ok(resource) => {
+                    DaemonResponse::success(DaemonResult::ExecutionOutput(json!({ "resource": resource })))
+                }
+                Err(e) => {
+                    error!("Failed to get resource: {}", e);
+                    DaemonResponse::error(format!("Error getting resource: {}", e))
+                }
+            }
+        }
+        DaemonRequest::Shutdown => {
+            info!("Shutdown requested by {}", state.client);
+            // The main loop only stops accepting connections, so this response is
+            // still written while the servers are being shut down
+            let _ = state.shutdown.try_send(());
+            DaemonResponse::success(DaemonResult::ExecutionOutput(json!({ "shutdown": true })))
+        }
     }
 }
 
-/// Serializes and writes a DaemonResponse to the client stream with a length prefix.
-async fn write_response<W: AsyncWriteExt + Unpin>(
-    writer: &mut W,
-    response: &DaemonResponse,
-) -> Result<(), std::io::Error> {
-    let response_bytes = match serde_json::to_vec(response) {
-        Ok(bytes) => bytes,
+fn server_status_response(
+    result: Result<gemini_mcp::ServerStatus, String>,
+    action: &str,
+) -> DaemonResponse {
+    match result {
+        Ok(status) => DaemonResponse::success(DaemonResult::Server(to_ipc_status(status))),
         Err(e) => {
-            error!("Failed to serialize response: {}", e);
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Failed to serialize response: {}", e),
-            ));
+            error!("Failed {} server: {}", action, e);
+            DaemonResponse::error(format!("Error {} server: {}", action, e))
         }
-    };
-
-    let response_len = response_bytes.len() as u32;
-    debug!("Sending response with length: {} bytes", response_len);
-
-    // Write the length prefix first
-    if let Err(e) = writer.write_u32(response_len).await {
-        error!("Failed to write response length: {}", e);
-        return Err(e);
-    }
-
-    // Then write the response data
-    if let Err(e) = writer.write_all(&response_bytes).await {
-        error!("Failed to write response data: {}", e);
-        return Err(e);
-    }
-
-    // Flush to ensure all data is sent
-    if let Err(e) = writer.flush().await {
-        error!("Failed to flush response: {}", e);
-        return Err(e);
-    }
-
-    debug!("Response successfully written and flushed");
-    Ok(())
-}
-
-// --- NEW HELPER FUNCTION --- 
-// Defines the tools handled internally by the embedded MemoryStore
-fn get_internal_memory_tool_definitions() -> Vec<CoreTool> {
-    vec![
-        CoreTool {
-            name: "memory-store-mcp/store_memory".to_string(),
-            description: Some("Stores a memory item (key, content, tags).".to_string()),
-            parameters: None, // Define schema if needed
-        },
-         CoreTool {
-            name: "memory-store-mcp/list_all_memories".to_string(),
-            description: Some("Lists all stored memory items.".to_string()),
-            parameters: None,
-        },
-        CoreTool {
-            name: "memory-store-mcp/retrieve_memory_by_key".to_string(),
-            description: Some("Retrieves a memory item by its key.".to_string()),
-            parameters: None,
-        },
-        CoreTool {
-            name: "memory-store-mcp/retrieve_memory_by_tag".to_string(),
-            description: Some("Retrieves memory items matching a specific tag.".to_string()),
-            parameters: None,
-        },
-        CoreTool {
-            name: "memory-store-mcp/delete_memory_by_key".to_string(),
-            description: Some("Deletes a memory item by its key.".to_string()),
-            parameters: None,
-        },
-        CoreTool {
-            name: "memory-store-mcp/semantic_search".to_string(),
-            description: Some("Performs semantic search over memories.".to_string()),
-            parameters: None, // TODO: Define input schema (query_embedding, k, filter)
-        },
-        // Add other internal tools like add_vector_memory if implemented
-    ]
-}
-
-// --- NEW FUNCTION: Handle Internal Memory Tool Calls --- 
-async fn handle_internal_memory_tool(
-    tool_name: &str,
-    args: Value,
-    memory_store: &MemoryStore,
-) -> Result<Value, String> {
-    match tool_name {
-        "store_memory" => {
-            let key = args["key"].as_str().ok_or("Missing string arg: key")?.to_string();
-            let content = args["content"].as_str().ok_or("Missing string arg: content")?.to_string();
-            let tags: Vec<String> = args["tags"].as_array()
-                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
-                .unwrap_or_default();
-            // Embeddings/metadata might be handled separately or passed if available
-            // For now, basic implementation
-            memory_store.add_memory(&key, &content, tags, None, None, None).await
-                 .map_err(|e| format!("Failed to store memory: {}", e))?;
-            Ok(json!({ "success": true, "message": format!("Memory stored with key: {}", key) }))
-        }
-        "list_all_memories" => {
-             let memories = memory_store.get_all_memories().await
-                 .map_err(|e| format!("Failed to list memories: {}", e))?;
-             // Convert memories to JSON compatible format (consider timestamp formatting)
-             let result_memories = memories.into_iter().map(|m| json!({
-                 "key": m.key,
-                 "content": m.value, // Assuming value is String
-                 "tags": m.tags,
-                 "timestamp": m.timestamp, // Keep as number or format?
-                 // Add other fields as needed
-             })).collect::<Vec<_>>();
-             Ok(json!({ "memories": result_memories }))
-        }
-         "retrieve_memory_by_key" => {
-            let key = args["key"].as_str().ok_or("Missing string arg: key")?;
-            match memory_store.get_by_key(key).await {
-                Ok(Some(mem)) => Ok(json!({
-                     "memory": {
-                         "key": mem.key,
-                         "content": mem.value,
-                         "tags": mem.tags,
-                         "timestamp": mem.timestamp
-                    }
-                 })),
-                Ok(None) => Err(format!("Memory with key '{}' not found", key)),
-                Err(e) => Err(format!("Failed to retrieve memory by key: {}", e)),
-            }
-        }
-        "retrieve_memory_by_tag" => {
-             let tag = args["tag"].as_str().ok_or("Missing string arg: tag")?;
-             let memories = memory_store.get_by_tag(tag).await
-                 .map_err(|e| format!("Failed to retrieve memory by tag: {}", e))?;
-             let result_memories = memories.into_iter().map(|m| json!({
-                 "key": m.key,
-                 "content": m.value,
-                 "tags": m.tags,
-                 "timestamp": m.timestamp
-             })).collect::<Vec<_>>();
-             Ok(json!({ "memories": result_memories }))
-        }
-        "delete_memory_by_key" => {
-             let key = args["key"].as_str().ok_or("Missing string arg: key")?;
-             let count = memory_store.delete_by_key(key).await
-                 .map_err(|e| format!("Failed to delete memory: {}", e))?;
-             Ok(json!({ "success": true, "message": format!("Memory with key '{}' deleted (count: {})", key, count) }))
-        }
-        "semantic_search" => {
-            // TODO: Implement semantic search call
-            // 1. Extract query embedding from args
-            // 2. Extract k, filters etc from args
-            // 3. Call memory_store.query_memories(...)
-            // 4. Format results
-            warn!("Internal call to unimplemented tool: semantic_search");
-            Err("semantic_search not yet implemented internally".to_string())
-        }
-        // Add other memory tools here (e.g., add_vector_memory)
-        _ => Err(format!("Unknown internal memory tool: {}", tool_name)),
     }
 }
@@ -2,7 +2,10 @@ use std::io;
 use std::path::PathBuf;
 
 // Re-export the McpServerConfig and McpTransport from core
-pub use gemini_core::config::{McpServerConfig, McpTransport};
+pub use gemini_core::config::{
+    McpSamplingPolicy, McpSandboxConfig, McpSandboxFilesystem, McpSeccompPreset, McpServerConfig,
+    McpStartup, McpTransport,
+};
 
 pub fn get_config_dir() -> Result<PathBuf, String> {
     // Use gemini-core's function to get the config directory
@@ -24,6 +27,16 @@ pub fn get_mcp_config_path() -> io::Result<PathBuf> {
         .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))
 }
 
+/// Default location of the mcp-hostd audit log
+pub fn get_audit_log_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("gemini-suite").join("audit.jsonl"))
+}
+
+/// Location of the cached server capabilities used to list tools of lazy servers without launching them
+pub fn get_capabilities_cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("gemini-suite").join("mcp_capabilities.json"))
+}
+
 pub fn load_mcp_servers() -> Result<Vec<McpServerConfig>, String> {
     // Use gemini-core's function to load MCP servers
     gemini_core::config::load_mcp_servers(None)
@@ -97,6 +110,71 @@ mod tests {
         assert_eq!(servers[0].auto_execute, vec!["tool1"]);
     }
 
+    #[test]
+    fn test_load_mcp_servers_lazy_startup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lazy_mcp_servers.json");
+        let content = r#"
+        [
+          {
+            "name": "LazyServer",
+            "enabled": true,
+            "transport": "stdio",
+            "command": ["lazy_cmd"],
+            "startup": "lazy",
+            "idleTimeout": 300
+          },
+          {
+            "name": "EagerServer",
+            "enabled": true,
+            "transport": "stdio",
+            "command": ["eager_cmd"]
+          }
+        ]
+        "#;
+        fs::write(&path, content).unwrap();
+
+        let servers = load_mcp_servers_from_path(&path).unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].startup, McpStartup::Lazy);
+        assert_eq!(servers[0].idle_timeout, Some(300));
+        assert_eq!(servers[1].startup, McpStartup::Eager);
+        assert_eq!(servers[1].idle_timeout, None);
+    }
+
+    #[test]
+    fn test_load_mcp_servers_request_policy() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("policy_mcp_servers.json");
+        let content = r#"
+        [
+          {
+            "name": "Embedding",
+            "enabled": true,
+            "transport": "stdio",
+            "command": ["embed_cmd"],
+            "timeout": 120,
+            "toolTimeouts": { "embed_batch": 600 },
+            "maxConcurrentRequests": 2,
+            "idempotentTools": ["embed"],
+            "retry": { "maxAttempts": 4 }
+          }
+        ]
+        "#;
+        fs::write(&path, content).unwrap();
+
+        let servers = load_mcp_servers_from_path(&path).unwrap();
+        let policy = &servers[0].requests;
+        assert_eq!(policy.timeout_for("embed"), Some(120));
+        assert_eq!(policy.timeout_for("embed_batch"), Some(600));
+        assert_eq!(policy.max_concurrent_requests, Some(2));
+        assert!(policy.is_idempotent("embed"));
+        assert!(!policy.is_idempotent("embed_batch"));
+        let retry = policy.retry.as_ref().unwrap();
+        assert_eq!(retry.max_attempts, 4);
+        assert_eq!(retry.backoff_ms, 500);
+    }
+
     #[test]
     fn test_load_mcp_servers_invalid_json() {
         let dir = tempdir().unwrap();
@@ -105,6 +183,65 @@ mod tests {
         assert!(load_mcp_servers_from_path(&path).is_err());
     }
 
+    #[test]
+    fn test_load_mcp_servers_secret_references() {
+        let dir = tempdir().unwrap();
+        let token_path = dir.path().join("token");
+        fs::write(&token_path, "file-secret\n").unwrap();
+        let path = dir.path().join("secret_mcp_servers.json");
+        let content = format!(
+            r#"{{ "mcpServers": {{ "api": {{
+                "command": "api_cmd",
+                "env": {{
+                    "TOKEN": "${{file:{}}}",
+                    "KEY": "key-${{cmd:printf cmd-secret}}",
+                    "PLAIN": "$${{literal}}"
+                }}
+            }} }} }}"#,
+            token_path.display()
+        );
+        fs::write(&path, content).unwrap();
+
+        let servers = gemini_core::config::load_mcp_servers(Some(&path)).unwrap();
+        let env = &servers[0].env;
+        assert_eq!(env["TOKEN"], "file-secret");
+        assert_eq!(env["KEY"], "key-cmd-secret");
+        assert_eq!(env["PLAIN"], "${literal}");
+        let debug = format!("{:?}", servers[0]);
+        assert!(!debug.contains("file-secret") && !debug.contains("key-cmd-secret"));
+
+        // Saving writes the references back instead of the resolved values
+        let saved_path = dir.path().join("saved_mcp_servers.json");
+        gemini_core::config::save_mcp_servers(&servers, Some(&saved_path)).unwrap();
+        let saved = fs::read_to_string(&saved_path).unwrap();
+        assert!(saved.contains("key-${cmd:printf cmd-secret}"));
+        assert!(!saved.contains("file-secret"));
+
+        fs::write(
+            &path,
+            r#"{ "mcpServers": { "broken": { "command": "x", "env": { "T": "${GEMINI_TEST_UNSET_VARIABLE}" } } } }"#,
+        )
+        .unwrap();
+        let error = gemini_core::config::load_mcp_servers(Some(&path))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'broken'"));
+        assert!(error.contains("GEMINI_TEST_UNSET_VARIABLE"));
+    }
+
+    #[test]
+    fn test_load_mcp_servers_record_any_transport() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("record_mcp_servers.json");
+        fs::write(
+            &path,
+            r#"[{ "name": "remote", "enabled": true, "transport": { "websocket": { "url": "ws://localhost:1" } }, "record": "remote.jsonl" }]"#,
+        )
+        .unwrap();
+        let servers = gemini_core::config::load_mcp_servers(Some(&path)).unwrap();
+        assert_eq!(servers[0].record.as_deref(), Some("remote.jsonl"));
+    }
+
     // Helper for testing to avoid dependency on actual config dir
     fn load_mcp_servers_from_path(
         config_path: &std::path::Path,
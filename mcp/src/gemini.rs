@@ -1,8 +1,12 @@
 // This module handles conversion between MCP capabilities and Gemini function calling formats,
 // and processing function calls.
 
+use crate::tool_names::ToolNameRegistry;
 use colored::Colorize;
-use gemini_core::rpc_types::{Resource, Tool};
+use gemini_core::rpc_types::{
+    EmbeddedResource, GetPromptResult, Resource, Tool, ToolCallResult, ToolContent,
+};
+use gemini_core::types::{Content, Part};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -38,11 +42,19 @@ pub struct FunctionCall {
     pub arguments: Value,
 }
 
-/// Converts MCP Tool capabilities to Gemini function definitions
+/// Converts MCP Tool capabilities to Gemini function definitions.
+/// Function names come from a `ToolNameRegistry` built from the same tools,
+/// which resolves them back to their server and tool.
 pub fn convert_mcp_tools_to_gemini_functions(tools: &[Tool]) -> Vec<FunctionDef> {
+    let names = ToolNameRegistry::new(tools);
     let mut functions = Vec::new();
 
     for tool in tools {
+        let Some(gemini_function_name) = names.function_name(&tool.name) else {
+            debug!("Skipping tool without a server prefix: {}", tool.name);
+            continue;
+        };
+
         // Use the parameters field if available, otherwise create a minimal schema
         let parameters = if let Some(params) = &tool.parameters {
             // Sanitize the JSON schema to remove fields not supported by Gemini
@@ -61,12 +73,8 @@ pub fn convert_mcp_tools_to_gemini_functions(tools: &[Tool]) -> Vec<FunctionDef>
             .clone()
             .unwrap_or_else(|| "No description provided".to_string());
 
-        // Format function name for Gemini compatibility
-        // Replace slashes with dots to maintain namespacing without violating Gemini's naming rules
-        let gemini_function_name = tool.name.clone().replace("/", ".");
-
         functions.push(FunctionDef {
-            name: gemini_function_name,
+            name: gemini_function_name.to_string(),
             description: Some(description),
             parameters,
         });
@@ -123,6 +131,7 @@ pub fn build_mcp_system_prompt(tools: &[Tool], resources: &[Resource]) -> String
 
     // Add Tools Section
     if !tools.is_empty() {
+        let names = ToolNameRegistry::new(tools);
         prompt.push_str("## Available Tools\n\n");
         for tool in tools {
             let description = tool
@@ -130,8 +139,8 @@ pub fn build_mcp_system_prompt(tools: &[Tool], resources: &[Resource]) -> String
                 .clone()
                 .unwrap_or_else(|| "No description provided".to_string());
 
-            // Use the dot-notation for display to match what Gemini will use
-            let display_name = tool.name.replace("/", ".");
+            // Use the function name Gemini will see
+            let display_name = names.function_name(&tool.name).unwrap_or(&tool.name);
             prompt.push_str(&format!("* **{}**: {}\n", display_name, description));
         }
         prompt.push('\n');
@@ -145,7 +154,13 @@ pub fn build_mcp_system_prompt(tools: &[Tool], resources: &[Resource]) -> String
                 .description
                 .clone()
                 .unwrap_or_else(|| "No description provided".to_string());
-            prompt.push_str(&format!("* **{}**: {}\n", resource.name, resource_desc));
+            match &resource.uri {
+                Some(uri) => prompt.push_str(&format!(
+                    "* **{}** (`{}`): {}\n",
+                    resource.name, uri, resource_desc
+                )),
+                None => prompt.push_str(&format!("* **{}**: {}\n", resource.name, resource_desc)),
+            }
         }
         prompt.push('\n');
     }
@@ -247,28 +262,200 @@ pub fn generate_gemini_function_declarations(tools: &[Tool]) -> Option<Vec<Funct
     }
 }
 
-/// Process a detected function call from Gemini and ask for user confirmation
+/// Gemini parts produced from a single MCP tool result
+#[derive(Debug, Clone)]
+pub struct ToolResultParts {
+    /// The `functionResponse` part answering the model's function call
+    pub function_response: Part,
+    /// Images, audio and binary resources returned by the tool, as inline-data parts
+    pub attachments: Vec<Part>,
+}
+
+/// Structured error payload for a failed tool call, visible to the model
+pub fn tool_error_response(message: &str) -> Value {
+    json!({ "error": { "message": message } })
+}
+
+/// Map an MCP tool result to a `functionResponse` part plus inline-data parts.
+///
+/// Standard `tools/call` results are split by content type: text is joined into
+/// `output`, embedded text resources and resource links are listed under
+/// `resources`, and binary content becomes attachments referenced from the
+/// response. Results with `isError` set are reported as a structured error.
+/// Free-form results from older servers are passed through unchanged.
+pub fn tool_result_to_parts(function_name: &str, result: &Value) -> ToolResultParts {
+    let Some(call_result) = ToolCallResult::from_standard(result) else {
+        let response = if result.is_object() {
+            result.clone()
+        } else {
+            json!({ "output": result })
+        };
+        return ToolResultParts {
+            function_response: Part::function_response(function_name.to_string(), response),
+            attachments: Vec::new(),
+        };
+    };
+
+    let mut attachments = Vec::new();
+    let mut attachment_refs = Vec::new();
+    let mut resources = Vec::new();
+
+    for content in &call_result.content {
+        match content {
+            ToolContent::Text { .. } | ToolContent::Unknown => {}
+            ToolContent::Image { data, mime_type } | ToolContent::Audio { data, mime_type } => {
+                attachment_refs.push(json!({
+                    "index": attachments.len(),
+                    "mimeType": mime_type,
+                }));
+                attachments.push(Part::inline_data(mime_type.clone(), data.clone()));
+            }
+            ToolContent::Resource { resource } => match (&resource.text, &resource.blob) {
+                (Some(text), _) => resources.push(json!({
+                    "uri": resource.uri,
+                    "mimeType": resource.mime_type,
+                    "text": text,
+                })),
+                (None, Some(blob)) => {
+                    let mime_type = resource
+                        .mime_type
+                        .clone()
+                        .unwrap_or_else(|| "application/octet-stream".to_string());
+                    attachment_refs.push(json!({
+                        "index": attachments.len(),
+                        "mimeType": mime_type,
+                        "uri": resource.uri,
+                    }));
+                    attachments.push(Part::inline_data(mime_type, blob.clone()));
+                }
+                (None, None) => resources.push(json!({ "uri": resource.uri })),
+            },
+            ToolContent::ResourceLink {
+                uri,
+                name,
+                description,
+                mime_type,
+            } => resources.push(json!({
+                "uri": uri,
+                "name": name,
+                "description": description,
+                "mimeType": mime_type,
+            })),
+        }
+    }
+
+    let text = call_result.text();
+    let mut response = serde_json::Map::new();
+    if call_result.is_error {
+        let message = if text.is_empty() {
+            "Tool reported an error without details".to_string()
+        } else {
+            text
+        };
+        response.insert("error".to_string(), json!({ "message": message }));
+    } else if !text.is_empty() {
+        response.insert("output".to_string(), Value::String(text));
+    }
+    if let Some(structured) = call_result.structured_content {
+        response.insert("structuredContent".to_string(), structured);
+    }
+    if !resources.is_empty() {
+        response.insert("resources".to_string(), Value::Array(resources));
+    }
+    if !attachment_refs.is_empty() {
+        response.insert("attachments".to_string(), Value::Array(attachment_refs));
+    }
+
+    debug!(
+        "Mapped result of '{}' to a function response with {} attachment(s)",
+        function_name,
+        attachments.len()
+    );
+
+    ToolResultParts {
+        function_response: Part::function_response(function_name.to_string(), Value::Object(response)),
+        attachments,
+    }
+}
+
+/// Map an expanded MCP prompt to Gemini conversation contents.
+///
+/// Assistant messages become `model` turns. Embedded text resources are inlined
+/// as text under their URI, binary content becomes inline data and resource
+/// links are mentioned by URI. Content types Gemini cannot represent are dropped.
+pub fn prompt_to_contents(prompt: &GetPromptResult) -> Vec<Content> {
+    prompt
+        .messages
+        .iter()
+        .filter_map(|message| {
+            let part = match &message.content {
+                ToolContent::Text { text } => Part::text(text.clone()),
+                ToolContent::Image { data, mime_type } | ToolContent::Audio { data, mime_type } => {
+                    Part::inline_data(mime_type.clone(), data.clone())
+                }
+                ToolContent::Resource { resource } => match (&resource.text, &resource.blob) {
+                    (Some(text), _) => Part::text(format!("Resource {}:\n{}", resource.uri, text)),
+                    (None, Some(blob)) => Part::inline_data(
+                        resource
+                            .mime_type
+                            .clone()
+                            .unwrap_or_else(|| "application/octet-stream".to_string()),
+                        blob.clone(),
+                    ),
+                    (None, None) => Part::text(format!("Resource {}", resource.uri)),
+                },
+                ToolContent::ResourceLink { uri, .. } => Part::text(format!("Resource {}", uri)),
+                ToolContent::Unknown => return None,
+            };
+            let role = if message.role == "assistant" { "model" } else { "user" };
+            Some(Content {
+                parts: vec![part],
+                role: Some(role.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Map the contents of a resource read with `resources/read` to Gemini parts.
+/// Text contents are labelled with their URI; binary contents become inline data.
+pub fn resource_contents_to_parts(contents: &[EmbeddedResource]) -> Vec<Part> {
+    contents
+        .iter()
+        .map(|content| match (&content.text, &content.blob) {
+            (Some(text), _) => Part::text(format!("Contents of {}:\n{}", content.uri, text)),
+            (None, Some(blob)) => Part::inline_data(
+                content
+                    .mime_type
+                    .clone()
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                blob.clone(),
+            ),
+            (None, None) => Part::text(format!("Resource {} is empty", content.uri)),
+        })
+        .collect()
+}
+
+/// Process a detected function call from Gemini and ask for user confirmation.
+/// `names` must be built from the tools the function declarations came from.
 pub async fn process_function_call(
     function_call: &FunctionCall,
+    names: &ToolNameRegistry,
     mcp_host: &crate::host::McpHost,
 ) -> Result<Value, String> {
-    // Extract the server and tool name from the qualified name
-    // Convert dots back to slashes for MCP host compatibility
-    let qualified_name = &function_call.name.replace(".", "/");
+    // Look up the server and tool the function name was generated for
+    let qualified_name = names
+        .qualified_name(&function_call.name)
+        .ok_or_else(|| format!("Unknown function: {}", function_call.name))?;
     if std::env::var("DEBUG").is_ok() {
         println!(
-            "[DEBUG] Processing function call: original name='{}', converted name='{}'",
+            "[DEBUG] Processing function call: original name='{}', resolved name='{}'",
             &function_call.name, qualified_name
         );
     }
 
-    // Parse the qualified name into server and tool parts
-    let parts: Vec<&str> = qualified_name.splitn(2, "/").collect();
-    if parts.len() != 2 {
+    let Some((server_name, tool_name)) = qualified_name.split_once('/') else {
         return Err(format!("Invalid qualified tool name: {}", qualified_name));
-    }
-    let server_name = parts[0];
-    let tool_name = parts[1];
+    };
 
     // Check if this tool is in the auto-execute list for this server
     let should_auto_execute = mcp_host.is_auto_execute(server_name, tool_name).await;
@@ -319,3 +506,158 @@ pub async fn process_function_call(
 
     Ok(Value::Null)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_of(parts: &ToolResultParts) -> &Value {
+        &parts.function_response.function_response.as_ref().unwrap().response
+    }
+
+    #[test]
+    fn test_tool_result_to_parts_text() {
+        let result = json!({
+            "content": [
+                { "type": "text", "text": "first" },
+                { "type": "text", "text": "second" }
+            ],
+            "structuredContent": { "count": 2 }
+        });
+        let parts = tool_result_to_parts("server__tool", &result);
+
+        let function_response = parts.function_response.function_response.as_ref().unwrap();
+        assert_eq!(function_response.name, "server__tool");
+        assert_eq!(
+            function_response.response,
+            json!({ "output": "first\nsecond", "structuredContent": { "count": 2 } })
+        );
+        assert!(parts.attachments.is_empty());
+    }
+
+    #[test]
+    fn test_tool_result_to_parts_media() {
+        let result = json!({
+            "content": [
+                { "type": "image", "data": "aW1n", "mimeType": "image/png" },
+                { "type": "audio", "data": "YXVk", "mimeType": "audio/wav" },
+                { "type": "resource", "resource": { "uri": "file:///a.bin", "blob": "AAE=" } }
+            ]
+        });
+        let parts = tool_result_to_parts("tool", &result);
+
+        assert_eq!(
+            response_of(&parts),
+            &json!({ "attachments": [
+                { "index": 0, "mimeType": "image/png" },
+                { "index": 1, "mimeType": "audio/wav" },
+                { "index": 2, "mimeType": "application/octet-stream", "uri": "file:///a.bin" }
+            ] })
+        );
+        let blobs: Vec<_> = parts
+            .attachments
+            .iter()
+            .map(|part| {
+                let blob = part.inline_data.as_ref().unwrap();
+                (blob.mime_type.as_str(), blob.data.as_str())
+            })
+            .collect();
+        assert_eq!(
+            blobs,
+            [
+                ("image/png", "aW1n"),
+                ("audio/wav", "YXVk"),
+                ("application/octet-stream", "AAE=")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tool_result_to_parts_resources() {
+        let result = json!({
+            "content": [
+                { "type": "resource", "resource": { "uri": "file:///a.rs", "mimeType": "text/x-rust", "text": "fn main() {}" } },
+                { "type": "resource", "resource": { "uri": "file:///empty" } },
+                { "type": "resource_link", "uri": "file:///b.rs", "name": "b.rs" },
+                { "type": "video", "data": "dmlk" }
+            ]
+        });
+        let parts = tool_result_to_parts("tool", &result);
+
+        assert_eq!(
+            response_of(&parts),
+            &json!({ "resources": [
+                { "uri": "file:///a.rs", "mimeType": "text/x-rust", "text": "fn main() {}" },
+                { "uri": "file:///empty" },
+                { "uri": "file:///b.rs", "name": "b.rs", "description": null, "mimeType": null }
+            ] })
+        );
+        assert!(parts.attachments.is_empty());
+    }
+
+    #[test]
+    fn test_tool_result_to_parts_error() {
+        let result = json!({
+            "content": [{ "type": "text", "text": "file not found" }],
+            "isError": true
+        });
+        let parts = tool_result_to_parts("tool", &result);
+        assert_eq!(response_of(&parts), &json!({ "error": { "message": "file not found" } }));
+
+        let result = json!({ "content": [], "isError": true });
+        let parts = tool_result_to_parts("tool", &result);
+        assert_eq!(
+            response_of(&parts),
+            &json!({ "error": { "message": "Tool reported an error without details" } })
+        );
+    }
+
+    #[test]
+    fn test_tool_result_to_parts_free_form() {
+        let parts = tool_result_to_parts("tool", &json!({ "files": ["a.rs"] }));
+        assert_eq!(response_of(&parts), &json!({ "files": ["a.rs"] }));
+
+        let parts = tool_result_to_parts("tool", &json!("done"));
+        assert_eq!(response_of(&parts), &json!({ "output": "done" }));
+    }
+
+    #[test]
+    fn test_prompt_to_contents() {
+        let prompt: GetPromptResult = serde_json::from_value(json!({
+            "description": "Review a change",
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": "Review this diff" } },
+                { "role": "assistant", "content": { "type": "text", "text": "Which file?" } },
+                { "role": "user", "content": { "type": "image", "data": "aW1n", "mimeType": "image/png" } },
+                { "role": "user", "content": { "type": "resource", "resource": { "uri": "file:///a.rs", "text": "fn main() {}" } } },
+                { "role": "user", "content": { "type": "resource", "resource": { "uri": "file:///a.bin", "blob": "AAE=" } } },
+                { "role": "user", "content": { "type": "resource_link", "uri": "file:///b.rs", "name": "b.rs" } },
+                { "role": "user", "content": { "type": "video", "data": "dmlk" } }
+            ]
+        }))
+        .unwrap();
+
+        let contents = prompt_to_contents(&prompt);
+        assert_eq!(contents.len(), 6, "unknown content types are dropped");
+
+        let roles: Vec<_> = contents.iter().map(|c| c.role.as_deref().unwrap()).collect();
+        assert_eq!(roles, ["user", "model", "user", "user", "user", "user"]);
+
+        assert_eq!(contents[0].parts[0].text.as_deref(), Some("Review this diff"));
+        assert_eq!(contents[1].parts[0].text.as_deref(), Some("Which file?"));
+        let image = contents[2].parts[0].inline_data.as_ref().unwrap();
+        assert_eq!((image.mime_type.as_str(), image.data.as_str()), ("image/png", "aW1n"));
+        assert_eq!(
+            contents[3].parts[0].text.as_deref(),
+            Some("Resource file:///a.rs:\nfn main() {}")
+        );
+        let blob = contents[4].parts[0].inline_data.as_ref().unwrap();
+        assert_eq!(blob.mime_type, "application/octet-stream");
+        assert_eq!(contents[5].parts[0].text.as_deref(), Some("Resource file:///b.rs"));
+    }
+
+    #[test]
+    fn test_prompt_to_contents_without_messages() {
+        assert!(prompt_to_contents(&GetPromptResult::default()).is_empty());
+    }
+}
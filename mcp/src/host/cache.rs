@@ -0,0 +1,247 @@
+// Result cache for tools marked cacheable in a server's `cache` config.
+//
+// Entries are keyed on server, tool and the canonical JSON of the arguments, so
+// argument order does not matter. A server's entries are dropped when it is
+// launched or stopped, when it reports that its tool list changed and after a
+// call to one of its tools that is neither cacheable nor marked read-only.
+
+use gemini_core::config::McpCacheConfig;
+use gemini_core::rpc_types::Tool;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct CachedResult {
+    value: Value,
+    inserted: Instant,
+    expires: Instant,
+}
+
+// Cached results and counters of one server
+#[derive(Debug, Default)]
+struct ServerCache {
+    // Keyed on (tool, canonical arguments)
+    entries: HashMap<(String, String), CachedResult>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Hit/miss counters of a server's cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ResultCache {
+    servers: Mutex<HashMap<String, ServerCache>>,
+}
+
+impl ResultCache {
+    /// Cached result of a call, counting a hit or a miss
+    pub(crate) fn get(&self, server: &str, tool: &str, args: &Value) -> Option<Value> {
+        let mut servers = self.servers.lock().ok()?;
+        let cache = servers.entry(server.to_string()).or_default();
+        let key = (tool.to_string(), canonical_json(args));
+
+        let now = Instant::now();
+        match cache.entries.get(&key) {
+            Some(cached) if cached.expires > now => {
+                cache.hits += 1;
+                Some(cached.value.clone())
+            }
+            Some(_) => {
+                cache.entries.remove(&key);
+                cache.misses += 1;
+                None
+            }
+            None => {
+                cache.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Store a result if it is within the configured size limit
+    pub(crate) fn insert(
+        &self,
+        server: &str,
+        tool: &str,
+        args: &Value,
+        value: &Value,
+        ttl: Duration,
+        config: &McpCacheConfig,
+    ) {
+        let size = serde_json::to_string(value).map(|s| s.len()).unwrap_or(usize::MAX);
+        if size > config.max_entry_bytes || config.max_entries == 0 {
+            return;
+        }
+        let Ok(mut servers) = self.servers.lock() else {
+            return;
+        };
+        let cache = servers.entry(server.to_string()).or_default();
+
+        let now = Instant::now();
+        cache.entries.retain(|_, cached| cached.expires > now);
+        while cache.entries.len() >= config.max_entries {
+            let oldest = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.inserted)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => cache.entries.remove(&key),
+                None => break,
+            };
+        }
+
+        cache.entries.insert(
+            (tool.to_string(), canonical_json(args)),
+            CachedResult {
+                value: value.clone(),
+                inserted: now,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    /// Drop every cached result of a server, keeping its counters
+    pub(crate) fn invalidate(&self, server: &str) {
+        if let Ok(mut servers) = self.servers.lock() {
+            if let Some(cache) = servers.get_mut(server) {
+                cache.entries.clear();
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self, server: &str) -> CacheStats {
+        let Ok(servers) = self.servers.lock() else {
+            return CacheStats::default();
+        };
+        servers
+            .get(server)
+            .map(|cache| CacheStats {
+                entries: cache.entries.len(),
+                hits: cache.hits,
+                misses: cache.misses,
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Whether a call to a tool that is not cached may change what cached tools
+/// return: true unless the server annotates the tool as read-only
+pub(crate) fn may_write(tools: &[Tool], tool_name: &str) -> bool {
+    !tools
+        .iter()
+        .any(|tool| tool.name == tool_name && tool.is_read_only())
+}
+
+// JSON with object keys sorted at every level
+fn canonical_json(value: &Value) -> String {
+    fn write(value: &Value, out: &mut String) {
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                out.push('{');
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&Value::String(key.clone()).to_string());
+                    out.push(':');
+                    write(&map[key], out);
+                }
+                out.push('}');
+            }
+            Value::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write(item, out);
+                }
+                out.push(']');
+            }
+            other => out.push_str(&other.to_string()),
+        }
+    }
+
+    let mut out = String::new();
+    write(value, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gemini_core::rpc_types::ToolAnnotations;
+    use serde_json::json;
+
+    fn config(max_entries: usize) -> McpCacheConfig {
+        McpCacheConfig {
+            tools: HashMap::from([("read_file".to_string(), 60)]),
+            max_entries,
+            max_entry_bytes: 64,
+        }
+    }
+
+    #[test]
+    fn test_result_cache() {
+        let cache = ResultCache::default();
+        let config = config(2);
+        let ttl = Duration::from_secs(60);
+        let args = json!({ "path": "/a", "options": { "x": 1, "y": 2 } });
+        let reordered = json!({ "options": { "y": 2, "x": 1 }, "path": "/a" });
+
+        assert_eq!(cache.get("fs", "read_file", &args), None);
+        cache.insert("fs", "read_file", &args, &json!("contents"), ttl, &config);
+        assert_eq!(cache.get("fs", "read_file", &reordered), Some(json!("contents")));
+
+        // Too large to cache
+        let large = json!("x".repeat(100));
+        cache.insert("fs", "read_file", &json!({ "path": "/big" }), &large, ttl, &config);
+        assert_eq!(cache.get("fs", "read_file", &json!({ "path": "/big" })), None);
+
+        // The oldest entry is evicted once the server holds max_entries results
+        cache.insert("fs", "read_file", &json!({ "path": "/b" }), &json!("b"), ttl, &config);
+        cache.insert("fs", "read_file", &json!({ "path": "/c" }), &json!("c"), ttl, &config);
+        assert_eq!(cache.get("fs", "read_file", &args), None);
+        assert_eq!(
+            cache.stats("fs"),
+            CacheStats {
+                entries: 2,
+                hits: 1,
+                misses: 3
+            }
+        );
+
+        cache.invalidate("fs");
+        assert_eq!(cache.get("fs", "read_file", &json!({ "path": "/b" })), None);
+        assert_eq!(cache.stats("fs").entries, 0);
+    }
+
+    #[test]
+    fn test_may_write() {
+        let tool = |name: &str, read_only: Option<bool>| Tool {
+            name: name.to_string(),
+            description: None,
+            parameters: None,
+            annotations: Some(ToolAnnotations {
+                read_only_hint: read_only,
+                ..ToolAnnotations::default()
+            }),
+        };
+        let tools = [tool("list_directory", Some(true)), tool("write_file", Some(false)), tool("stat", None)];
+        assert!(!may_write(&tools, "list_directory"));
+        assert!(may_write(&tools, "write_file"));
+        assert!(may_write(&tools, "stat"));
+        assert!(may_write(&tools, "unlisted"));
+    }
+}
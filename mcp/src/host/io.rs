@@ -1,6 +1,44 @@
-// The IO module handles the low-level transport details for MCP servers
+// Low-level framing shared by the transports that are bridged to the stdio
+// message loop in `types.rs` (replay and WebSocket): JSON-RPC messages framed
+// with Content-Length headers, as MCP servers use on stdio.
 
-// Note: IO functionality is now handled directly in types.rs
-// - BufReader and BufWriter are used for stdin/stdout communication
-// - Message formatting follows JSON-RPC over stdio protocol
-// - Content-Length headers are used to frame messages
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+// Read one Content-Length framed message; `None` at the end of the stream
+pub(crate) async fn read_message<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<Option<String>, String> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("Failed to read headers: {}", e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = content_length.ok_or("Message without Content-Length")?;
+    let mut content = vec![0; length];
+    reader
+        .read_exact(&mut content)
+        .await
+        .map_err(|e| format!("Failed to read content: {}", e))?;
+    String::from_utf8(content)
+        .map(Some)
+        .map_err(|e| format!("Invalid UTF-8 in message: {}", e))
+}
+
+// Frame a message as the stdio message loop reads it
+pub(crate) fn frame_message(text: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", text.len(), text)
+}
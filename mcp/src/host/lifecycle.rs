@@ -0,0 +1,130 @@
+// Lifecycle bookkeeping for lazily started and idle-stopped MCP servers.
+//
+// The host tracks when each server was last used and how many requests are
+// in flight, so the idle reaper never stops a server mid-request. Capabilities
+// reported by a server are remembered (and persisted to a small cache file) so
+// that listing tools does not force a lazy server to launch.
+
+use gemini_core::rpc_types::ServerCapabilities;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// How often the idle reaper checks for servers to stop
+pub(crate) const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ServerActivity {
+    pub last_used: Instant,
+    pub in_flight: usize,
+}
+
+impl Default for ServerActivity {
+    fn default() -> Self {
+        Self {
+            last_used: Instant::now(),
+            in_flight: 0,
+        }
+    }
+}
+
+pub(crate) type ActivityMap = Arc<Mutex<HashMap<String, ServerActivity>>>;
+
+// Marks a server as busy for as long as the guard is alive
+pub(crate) struct ActivityGuard {
+    activity: ActivityMap,
+    server_name: String,
+}
+
+impl ActivityGuard {
+    pub(crate) fn new(activity: &ActivityMap, server_name: &str) -> Self {
+        if let Ok(mut map) = activity.lock() {
+            let entry = map.entry(server_name.to_string()).or_default();
+            entry.in_flight += 1;
+            entry.last_used = Instant::now();
+        }
+        Self {
+            activity: activity.clone(),
+            server_name: server_name.to_string(),
+        }
+    }
+}
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        if let Ok(mut map) = self.activity.lock() {
+            let entry = map.entry(self.server_name.clone()).or_default();
+            entry.in_flight = entry.in_flight.saturating_sub(1);
+            entry.last_used = Instant::now();
+        }
+    }
+}
+
+// Reset the idle clock for a server, e.g. right after it starts
+pub(crate) fn touch(activity: &ActivityMap, server_name: &str) {
+    if let Ok(mut map) = activity.lock() {
+        map.entry(server_name.to_string()).or_default().last_used = Instant::now();
+    }
+}
+
+// Returns true if the server has no requests in flight and has been unused for longer than `timeout`
+pub(crate) fn is_idle(activity: &ActivityMap, server_name: &str, timeout: Duration) -> bool {
+    match activity.lock() {
+        Ok(map) => match map.get(server_name) {
+            Some(entry) => entry.in_flight == 0 && entry.last_used.elapsed() >= timeout,
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+// Load capabilities remembered from previous runs
+pub(crate) fn load_capabilities_cache(path: Option<&PathBuf>) -> HashMap<String, ServerCapabilities> {
+    let Some(path) = path else {
+        return HashMap::new();
+    };
+    if !path.exists() {
+        return HashMap::new();
+    }
+    match std::fs::read_to_string(path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(cache) => cache,
+            Err(e) => {
+                warn!("Ignoring unreadable MCP capabilities cache {:?}: {}", path, e);
+                HashMap::new()
+            }
+        },
+        Err(e) => {
+            warn!("Failed to read MCP capabilities cache {:?}: {}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
+// Persist remembered capabilities; failures are logged but not fatal
+pub(crate) fn save_capabilities_cache(
+    path: Option<&PathBuf>,
+    cache: &HashMap<String, ServerCapabilities>,
+) {
+    let Some(path) = path else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            warn!("Failed to create MCP cache directory {:?}: {}", parent, e);
+            return;
+        }
+    }
+    match serde_json::to_string_pretty(cache) {
+        Ok(content) => {
+            if let Err(e) = std::fs::write(path, content) {
+                warn!("Failed to write MCP capabilities cache {:?}: {}", path, e);
+            } else {
+                debug!("Saved MCP capabilities cache to {:?}", path);
+            }
+        }
+        Err(e) => warn!("Failed to serialize MCP capabilities cache: {}", e),
+    }
+}
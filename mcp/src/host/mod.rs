@@ -1,31 +1,67 @@
 mod active_server;
+mod cache;
 mod io;
+mod lifecycle;
 mod message_handler;
+mod recording;
+mod sandbox;
+mod server_requests;
+mod status;
+mod websocket;
 pub(crate) mod types;
 
 // Use types from the module
-use self::types::ActiveServer;
+use self::lifecycle::{ActivityGuard, ActivityMap, IDLE_CHECK_INTERVAL};
+use self::server_requests::{ClientState, ServerRequestRouter};
+use self::status::LastErrors;
+pub use self::server_requests::ResourceUpdated;
+pub use self::status::{ConfigReload, ServerState, ServerStatus};
+use self::types::{ActiveServer, InitFuture};
 
 // Main host implementation
 use crate::config::{McpServerConfig, McpTransport};
 // Import specific types from gemini_core
-use crate::rpc::{self, create_log_notification};
+use crate::rpc::{self, create_log_notification, create_roots_changed_notification};
+use crate::sampling::SamplingHandler;
 use async_trait::async_trait; // Needed for trait implementation
-use gemini_core::{JsonRpcError, Request, Response, ServerCapabilities}; // Removed RpcTool, Resource - not directly used here?
+use gemini_core::{
+    GetPromptResult, JsonRpcError, ListPromptsResult, ListResourceTemplatesResult, Prompt,
+    ReadResourceResult, Request, ResourceTemplate, Response, ServerCapabilities, ToolCallResult,
+}; // Removed RpcTool, Resource - not directly used here?
 use gemini_memory::broker::{self as memory_broker, McpHostInterface};
 use log::{debug, error, info, warn};
 use serde_json::{self, json, Value};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
+
+// Tool call timeout when neither the config nor the environment sets one
+const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 30;
 
 // Need Clone for task spawning
 #[derive(Debug, Clone)]
 pub struct McpHost {
-    servers: Arc<Mutex<HashMap<String, ActiveServer>>>, // Keyed by server name
+    servers: Arc<Mutex<HashMap<String, ActiveServer>>>, // Running servers, keyed by server name
     next_request_id: Arc<AtomicU64>,                    // Use atomic for thread-safe incrementing
+    configs: Arc<Mutex<HashMap<String, McpServerConfig>>>, // All configured servers, running or not
+    // Capabilities last reported by each server, used to list tools of stopped servers
+    known_capabilities: Arc<Mutex<HashMap<String, ServerCapabilities>>>,
+    capabilities_cache_path: Option<PathBuf>,
+    activity: ActivityMap,
+    // Serializes on-demand launches so concurrent callers start a server only once
+    launch_locks: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+    // Per-server queues for servers with max_concurrent_requests
+    request_limits: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+    // Sampling handler, workspace roots and notification channels shared with the servers' routers
+    client_state: Arc<ClientState>,
+    // Resources subscribed to per server, re-subscribed when a server is relaunched
+    resource_subscriptions: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    last_errors: LastErrors,
+    reload_lock: Arc<Mutex<()>>,
+    closed: Arc<AtomicBool>,
 }
 
 impl McpHost {
@@ -40,110 +76,65 @@ impl McpHost {
         // Added more debug info to help diagnose initialization issues
         info!("Creating MCP Host with {} server configurations", configs.len());
 
+        let capabilities_cache_path = crate::config::get_capabilities_cache_path();
+        let known_capabilities = lifecycle::load_capabilities_cache(capabilities_cache_path.as_ref());
+
         let host = McpHost {
             servers: Arc::new(Mutex::new(HashMap::new())),
             next_request_id: Arc::new(AtomicU64::new(1)), // Start IDs from 1
+            configs: Arc::new(Mutex::new(
+                configs.iter().map(|c| (c.name.clone(), c.clone())).collect(),
+            )),
+            known_capabilities: Arc::new(Mutex::new(known_capabilities)),
+            capabilities_cache_path,
+            activity: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            launch_locks: Arc::new(Mutex::new(HashMap::new())),
+            request_limits: Arc::new(Mutex::new(HashMap::new())),
+            client_state: Arc::new(ClientState::default()),
+            resource_subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            last_errors: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            reload_lock: Arc::new(Mutex::new(())),
+            closed: Arc::new(AtomicBool::new(false)),
         };
 
         let mut init_tasks = Vec::new();
         let mut servers_map = HashMap::new(); // Temp map to build servers before locking
 
         let mut failed_count = 0;
-        for config_orig in configs {
-            // Clone the config and immediately destructure it to avoid partial moves
-            let McpServerConfig {
-                name: server_name,
-                enabled: _enabled, // Not directly used in this loop, but needed for destructuring
-                transport,
-                command,
-                args,
-                env,
-                auto_execute,
-            } = config_orig.clone();
+        for config in configs {
+            let server_name = config.name.clone();
 
-            info!("Preparing to launch server: '{}'", server_name);
+            if !config.startup.is_eager() {
+                info!(
+                    "Server '{}' uses lazy startup; it will be launched on first use",
+                    server_name
+                );
+                continue;
+            }
 
-            // Create a new config instance from the cloned parts for launching servers
-            // This is needed because ActiveServer::launch_* expects a McpServerConfig
-            let launch_config = McpServerConfig {
-                name: server_name.clone(),
-                enabled: _enabled,            // Must match the original
-                transport: transport.clone(), // Clone transport for the new instance
-                command: command.clone(),
-                args: args.clone(),
-                env: env.clone(),
-                auto_execute: auto_execute.clone(),
-            };
+            info!("Preparing to launch server: '{}'", server_name);
 
             if std::env::var("DEBUG").is_ok() {
                 println!(
                     "Starting MCP server '{}' with transport {:?}",
-                    server_name, transport
+                    server_name, config.transport
                 );
             }
 
-            info!("Launching server '{}' with transport {:?}", server_name, transport);
+            info!("Launching server '{}' with transport {:?}", server_name, config.transport);
 
-            let init_future = match transport {
-                McpTransport::Stdio => {
-                    match ActiveServer::launch_stdio(&host.next_request_id, launch_config.clone())
-                        .await
-                    {
-                        Ok((server, init_future)) => {
-                            info!("Server '{}' process launched successfully, awaiting initialization", server_name);
-                            servers_map.insert(server_name.clone(), server);
-                            init_future
-                        }
-                        Err(e) => {
-                            eprintln!("MCP Server '{}' initialization failed: {}", server_name, e);
-                            failed_count += 1;
-                            continue;
-                        }
-                    }
+            match host.launch(config).await {
+                Ok((server, init_future)) => {
+                    info!("Server '{}' process launched successfully, awaiting initialization", server_name);
+                    servers_map.insert(server_name.clone(), server);
+                    init_tasks.push((server_name, init_future));
                 }
-                McpTransport::SSE { url, headers } => {
-                    match ActiveServer::launch_sse(
-                        &host.next_request_id,
-                        launch_config.clone(),
-                        url.clone(),
-                        headers.clone(),
-                    )
-                    .await
-                    {
-                        Ok((server, init_future)) => {
-                            servers_map.insert(server_name.clone(), server);
-                            init_future
-                        }
-                        Err(e) => {
-                            eprintln!("MCP Server '{}' initialization failed: {}", server_name, e);
-                            failed_count += 1;
-                            continue;
-                        }
-                    }
-                }
-                McpTransport::WebSocket { url, headers } => {
-                    match ActiveServer::launch_websocket(
-                        &host.next_request_id,
-                        launch_config.clone(),
-                        url.clone(),
-                        headers.clone(),
-                    )
-                    .await
-                    {
-                        Ok((server, init_future)) => {
-                            servers_map.insert(server_name.clone(), server);
-                            init_future
-                        }
-                        Err(e) => {
-                            eprintln!("MCP Server '{}' initialization failed: {}", server_name, e);
-                            failed_count += 1;
-                            continue;
-                        }
-                    }
+                Err(e) => {
+                    eprintln!("MCP Server '{}' initialization failed: {}", server_name, e);
+                    status::record_launch_error(&host.last_errors, &server_name, &e);
+                    failed_count += 1;
                 }
-            };
-
-            init_tasks.push((server_name, init_future));
+            }
         }
 
         info!("All servers launched, waiting for initialization responses ({} servers)", init_tasks.len());
@@ -159,6 +150,7 @@ impl McpHost {
                         }
                         Err(e) => {
                             eprintln!("Initialization error: Server '{}' init failed with error: {}", server_name, e.message);
+                            status::record_launch_error(&host.last_errors, &server_name, &e.message);
                             failed_count += 1;
                         }
                     }
@@ -168,11 +160,22 @@ impl McpHost {
                         "Initialization error: Server '{}' init timed out after {:?}",
                         server_name, elapsed
                     );
+                    status::record_launch_error(
+                        &host.last_errors,
+                        &server_name,
+                        &format!("init timed out after {:?}", elapsed),
+                    );
                     failed_count += 1;
                 }
             }
         }
 
+        // Remember what each server reported so it can be listed while stopped
+        for (server_name, server) in servers_map.iter() {
+            lifecycle::touch(&host.activity, server_name);
+            host.remember_capabilities(server_name, server).await;
+        }
+
         // Move servers to the host's map
         {
             info!("Moving {} servers to host's server map", servers_map.len());
@@ -189,27 +192,271 @@ impl McpHost {
             // NOTE: We no longer return Err here. Initialization proceeds.
         }
 
+        host.spawn_idle_reaper();
+
         info!("MCP Host initialization completed");
         // Always return Ok. Failures are logged as warnings.
         Ok(host)
     }
 
-    // Gets combined capabilities from all *initialized* servers
-    pub async fn get_all_capabilities(&self) -> ServerCapabilities {
+    /// Set the handler that answers `sampling/createMessage` requests from servers
+    /// with a `sampling` policy. Without a handler such requests are rejected.
+    pub fn set_sampling_handler(&self, handler: Arc<dyn SamplingHandler>) {
+        self.client_state.sampling.set_handler(Some(handler));
+    }
+
+    /// Replace the client workspace directories reported through `roots/list`.
+    /// Running servers are sent `notifications/roots/list_changed` if the set changed.
+    pub async fn set_workspace_roots(&self, roots: Vec<PathBuf>) -> bool {
+        if !self.client_state.workspace_roots.set(roots) {
+            return false;
+        }
+        info!(
+            "Workspace roots changed to {:?}",
+            self.client_state.workspace_roots.get()
+        );
+
         let servers = self.servers.lock().await;
+        for (name, server) in servers.iter() {
+            let notification = create_roots_changed_notification();
+            if let Err(e) = server.send_notification(notification).await {
+                warn!("Failed to notify server '{}' of changed roots: {}", name, e);
+            }
+        }
+        true
+    }
+
+    // Launch a server process/connection for the configured transport
+    async fn launch(&self, config: McpServerConfig) -> Result<(ActiveServer, InitFuture), String> {
+        let next_request_id = &self.next_request_id;
+        let router = ServerRequestRouter::new(&config, self.client_state.clone());
+        match config.transport.clone() {
+            McpTransport::Stdio => ActiveServer::launch_stdio(next_request_id, config, router).await,
+            McpTransport::SSE { url, headers } => {
+                ActiveServer::launch_sse(next_request_id, config, url, headers, router).await
+            }
+            McpTransport::WebSocket { url, headers } => {
+                ActiveServer::launch_websocket(next_request_id, config, url, headers, router).await
+            }
+            McpTransport::Replay { recording, realtime } => {
+                ActiveServer::launch_replay(next_request_id, config, recording, realtime, router).await
+            }
+        }
+    }
+
+    // Launch a single server, wait for it to initialize and register it as running
+    async fn start_from_config(&self, config: McpServerConfig) -> Result<ActiveServer, String> {
+        let server_name = config.name.clone();
+        info!("Starting MCP server '{}' on demand", server_name);
+
+        let (server, init_future) = match self.launch(config).await {
+            Ok(launched) => launched,
+            Err(e) => {
+                status::record_launch_error(&self.last_errors, &server_name, &e);
+                return Err(e);
+            }
+        };
+
+        let init_result = match init_future.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(format!(
+                "Server '{}' init failed with error: {}",
+                server_name, e.message
+            )),
+            Err(elapsed) => Err(format!(
+                "Server '{}' init timed out after {:?}",
+                server_name, elapsed
+            )),
+        };
+
+        if let Err(e) = init_result {
+            error!("{}", e);
+            status::record_launch_error(&self.last_errors, &server_name, &e);
+            server.set_shutdown().await;
+            if let Some(mut process) = server.take_process().await {
+                Self::kill_process(&mut process, &server_name).await;
+            }
+            return Err(e);
+        }
+
+        lifecycle::touch(&self.activity, &server_name);
+        status::clear_error(&self.last_errors, &server_name);
+        // Results cached from a previous run of the server may no longer hold
+        self.client_state.result_cache.invalidate(&server_name);
+        self.servers
+            .lock()
+            .await
+            .insert(server_name.clone(), server.clone());
+        self.remember_capabilities(&server_name, &server).await;
+        self.restore_subscriptions(&server_name, &server).await;
+
+        info!("Server '{}' started", server_name);
+        Ok(server)
+    }
+
+    // Get a ready server, launching it first if it is configured but not running.
+    // A server whose last launch failed is not launched again until its backoff
+    // has passed.
+    async fn ensure_server(&self, server_name: &str) -> Result<ActiveServer, String> {
+        let running = self.servers.lock().await.get(server_name).cloned();
+        if running.is_some() {
+            return Self::find_ready_server(&self.servers, server_name).await;
+        }
+
+        let config = self
+            .configs
+            .lock()
+            .await
+            .get(server_name)
+            .cloned()
+            .ok_or_else(|| format!("Server '{}' not found", server_name))?;
+
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(format!(
+                "Server '{}' is not running and the MCP host is shutting down",
+                server_name
+            ));
+        }
+
+        let launch_lock = self
+            .launch_locks
+            .lock()
+            .await
+            .entry(server_name.to_string())
+            .or_default()
+            .clone();
+        let _launching = launch_lock.lock().await;
+
+        // Another caller may have started the server while we waited for the lock
+        if self.servers.lock().await.contains_key(server_name) {
+            return Self::find_ready_server(&self.servers, server_name).await;
+        }
+
+        if let Some(left) = status::launch_backoff(&self.last_errors, server_name) {
+            return Err(format!(
+                "Server '{}' failed to start ({}); retrying in {}s",
+                server_name,
+                status::last_error(&self.last_errors, server_name).unwrap_or_default(),
+                left.as_secs().max(1)
+            ));
+        }
+
+        self.start_from_config(config).await
+    }
+
+    // Record the capabilities of a running server and persist them
+    async fn remember_capabilities(&self, server_name: &str, server: &ActiveServer) {
+        let caps = server.capabilities.lock().await.clone();
+        if let Some(caps) = caps {
+            let snapshot = {
+                let mut known = self.known_capabilities.lock().await;
+                known.insert(server_name.to_string(), caps);
+                known.clone()
+            };
+            lifecycle::save_capabilities_cache(self.capabilities_cache_path.as_ref(), &snapshot);
+        }
+    }
+
+    // Periodically stop servers that have been idle longer than their idle_timeout
+    fn spawn_idle_reaper(&self) {
+        let host = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if host.closed.load(Ordering::SeqCst) {
+                    debug!("Idle reaper exiting, MCP host is closed");
+                    break;
+                }
+                host.stop_idle_servers().await;
+            }
+        });
+    }
+
+    async fn stop_idle_servers(&self) {
+        let timeouts: Vec<(String, Duration)> = self
+            .configs
+            .lock()
+            .await
+            .values()
+            .filter_map(|c| c.idle_timeout.map(|t| (c.name.clone(), Duration::from_secs(t))))
+            .collect();
+
+        if timeouts.is_empty() {
+            return;
+        }
+
+        // Remove idle servers under the lock so no new request can pick them up
+        let idle_servers = {
+            let mut servers = self.servers.lock().await;
+            let mut idle = Vec::new();
+            for (server_name, timeout) in timeouts {
+                if servers.contains_key(&server_name)
+                    && lifecycle::is_idle(&self.activity, &server_name, timeout)
+                {
+                    if let Some(server) = servers.remove(&server_name) {
+                        idle.push((server_name, timeout, server));
+                    }
+                }
+            }
+            idle
+        };
+
+        for (server_name, timeout, server) in idle_servers {
+            info!(
+                "Stopping MCP server '{}' after {}s without requests",
+                server_name,
+                timeout.as_secs()
+            );
+            self.stop_active_server(&server_name, &server).await;
+        }
+    }
+
+    // Gets combined capabilities from all configured servers.
+    // Stopped servers report the capabilities remembered from their last run;
+    // lazy servers that have never run are launched to discover their tools.
+    pub async fn get_all_capabilities(&self) -> ServerCapabilities {
+        let mut server_names: Vec<String> = self.configs.lock().await.keys().cloned().collect();
+        server_names.sort();
+
         let mut combined_caps = ServerCapabilities::default();
         debug!(
             "[DEBUG get_all_capabilities] Checking capabilities for {} servers",
-            servers.len()
+            server_names.len()
         );
 
-        for (server_name, server) in servers.iter() {
+        for server_name in server_names {
             debug!(
                 "[DEBUG get_all_capabilities] Checking server: {}",
                 server_name
             );
-            let server_capabilities_lock = server.capabilities.lock().await;
-            let maybe_caps = server_capabilities_lock.as_ref();
+            let running = self.servers.lock().await.get(&server_name).cloned();
+
+            let maybe_caps = match running {
+                Some(server) => server.capabilities.lock().await.clone(),
+                None => {
+                    let known = self.known_capabilities.lock().await.get(&server_name).cloned();
+                    match known {
+                        Some(caps) => {
+                            debug!(
+                                "[DEBUG get_all_capabilities] Using remembered capabilities for stopped server: {}",
+                                server_name
+                            );
+                            Some(caps)
+                        }
+                        None => match self.ensure_server(&server_name).await {
+                            Ok(server) => server.capabilities.lock().await.clone(),
+                            Err(e) => {
+                                warn!(
+                                    "Could not start server '{}' to discover its capabilities: {}",
+                                    server_name, e
+                                );
+                                None
+                            }
+                        },
+                    }
+                }
+            };
 
             if let Some(caps) = maybe_caps {
                 debug!(
@@ -235,6 +482,12 @@ impl McpHost {
                     resource.name = format!("{}/{}", server_name, resource.name);
                     combined_caps.resources.push(resource);
                 }
+
+                // Add prompts advertised at initialization (if any)
+                for mut prompt in caps.prompts.iter().cloned() {
+                    prompt.name = format!("{}/{}", server_name, prompt.name);
+                    combined_caps.prompts.push(prompt);
+                }
             } else {
                 debug!(
                     "[DEBUG get_all_capabilities] No capabilities found for server: {}",
@@ -273,8 +526,13 @@ impl McpHost {
         }
     }
 
-    // Helper method to determine the appropriate timeout for different servers/tools
-    fn get_tool_timeout(&self, server_name: &str, tool_name: &str) -> Duration {
+    // Helper method to determine the appropriate timeout for different servers/tools.
+    // Env vars take precedence over mcp_servers.json so a timeout can be raised without editing config:
+    // GEMINI_MCP_TIMEOUT_<SERVER>_<TOOL>, GEMINI_MCP_TIMEOUT_<SERVER>, then the configured per-tool
+    // timeout, the configured server timeout, GEMINI_MCP_TOOL_TIMEOUT and finally the 30s default.
+    fn get_tool_timeout(config: &McpServerConfig, tool_name: &str) -> Duration {
+        let server_name = &config.name;
+
         // Check if there's an environment variable for this specific server/tool
         let env_var_name = format!(
             "GEMINI_MCP_TIMEOUT_{}_{}",
@@ -303,34 +561,177 @@ impl McpHost {
             }
         }
 
+        // Per-tool or per-server timeout from the server config
+        if let Some(timeout_secs) = config.requests.timeout_for(tool_name) {
+            debug!(
+                "Using configured timeout of {}s for {}/{}",
+                timeout_secs, server_name, tool_name
+            );
+            return Duration::from_secs(timeout_secs);
+        }
+
         // Server-specific defaults
-        match server_name {
-            "embedding" => {
-                // Embedding operations can take longer, especially for large texts
-                debug!("Using extended timeout of 120s for embedding server");
-                Duration::from_secs(120) // 2 minutes for embedding operations
-            }
-            _ => {
-                // Check global timeout env var
-                if let Ok(timeout_str) = std::env::var("GEMINI_MCP_TOOL_TIMEOUT") {
-                    if let Ok(timeout_secs) = timeout_str.parse::<u64>() {
-                        return Duration::from_secs(timeout_secs);
-                    }
+        if server_name == "embedding" {
+            // Embedding operations can take longer, especially for large texts
+            debug!("Using extended timeout of 120s for embedding server");
+            return Duration::from_secs(120);
+        }
+
+        // Check global timeout env var
+        if let Ok(timeout_str) = std::env::var("GEMINI_MCP_TOOL_TIMEOUT") {
+            if let Ok(timeout_secs) = timeout_str.parse::<u64>() {
+                return Duration::from_secs(timeout_secs);
+            }
+        }
+
+        // Default timeout for other servers
+        Duration::from_secs(DEFAULT_TOOL_TIMEOUT_SECS)
+    }
+
+    // Get the queue limiting concurrent requests to a server, if it has one
+    async fn request_limit(&self, config: &McpServerConfig) -> Option<Arc<Semaphore>> {
+        let max_in_flight = config.requests.max_concurrent_requests?.max(1);
+        let mut limits = self.request_limits.lock().await;
+        Some(
+            limits
+                .entry(config.name.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(max_in_flight)))
+                .clone(),
+        )
+    }
+
+    // Send a request to a server, honouring its timeout and concurrency limit.
+    // Idempotent tools are retried with exponential backoff when the request
+    // times out or the connection fails; error responses are never retried.
+    async fn send_with_policy(
+        &self,
+        server: &ActiveServer,
+        tool_name: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Response, String> {
+        let server_name = &server.config.name;
+
+        // Get the appropriate timeout for this server/tool
+        let timeout = Self::get_tool_timeout(&server.config, tool_name);
+        info!(
+            "Using {}s timeout for {}/{}",
+            timeout.as_secs(),
+            server_name,
+            tool_name
+        );
+
+        let retry = if server.config.requests.is_idempotent(tool_name) {
+            server.config.requests.retry.clone()
+        } else {
+            None
+        };
+        let max_attempts = retry.as_ref().map(|r| r.max_attempts.max(1)).unwrap_or(1);
+
+        let mut attempt = 1;
+        loop {
+            match self.send_once(server, method, params.clone(), timeout).await {
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < max_attempts => {
+                    let backoff_ms = retry.as_ref().map(|r| r.backoff_ms).unwrap_or_default();
+                    let backoff =
+                        Duration::from_millis(backoff_ms.saturating_mul(1 << (attempt - 1).min(16)));
+                    warn!(
+                        "Attempt {}/{} for {}/{} failed: {}. Retrying in {:?}",
+                        attempt, max_attempts, server_name, tool_name, e, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
                 }
-                // Default timeout for other servers
-                Duration::from_secs(30)
+                Err(e) => return Err(e),
             }
         }
     }
 
+    // Single attempt: wait for a free slot, then send and wait for the response before the deadline
+    async fn send_once(
+        &self,
+        server: &ActiveServer,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Response, String> {
+        let server_name = &server.config.name;
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        // Requests beyond the server's limit queue here until a slot frees up
+        let _permit = match self.request_limit(&server.config).await {
+            Some(limit) => match tokio::time::timeout_at(deadline, limit.acquire_owned()).await {
+                Ok(Ok(permit)) => Some(permit),
+                Ok(Err(_)) => {
+                    return Err(format!("Request queue for server '{}' is closed", server_name))
+                }
+                Err(_) => {
+                    return Err(format!(
+                        "Timeout waiting in queue for server '{}' ({} requests already in flight)",
+                        server_name,
+                        server.config.requests.max_concurrent_requests.unwrap_or_default()
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        // Get next request ID
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+
+        let request = Request::new(
+            Some(serde_json::to_value(request_id).unwrap()),
+            method.into(),
+            Some(params),
+        );
+
+        // Send request, with timeout for response
+        let result = match tokio::time::timeout_at(deadline, server.send_request(request)).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => Err(format!("Error from server '{}': {:?}", server_name, e)),
+            Err(_) => Err(format!("Timeout waiting for response from server '{}'", server_name)),
+        };
+        if let Err(e) = &result {
+            status::record_error(&self.last_errors, server_name, e);
+        }
+        result
+    }
+
     pub async fn execute_tool(
         &self,
         server_name: &str,
         tool_name: &str,
         args: Value,
     ) -> Result<Value, String> {
-        // First, find the server
-        let server = Self::find_ready_server(&self.servers, server_name).await?;
+        // Serve cacheable tools from the result cache when possible
+        let cache_config = self
+            .configs
+            .lock()
+            .await
+            .get(server_name)
+            .and_then(|config| config.cache.clone());
+        let cache_ttl = cache_config
+            .as_ref()
+            .and_then(|cache| cache.ttl_for(tool_name))
+            .map(Duration::from_secs);
+        let cache_args = match cache_ttl {
+            Some(_) => {
+                let cache = &self.client_state.result_cache;
+                if let Some(result) = cache.get(server_name, tool_name, &args) {
+                    debug!("Serving {}/{} from the result cache", server_name, tool_name);
+                    return Ok(result);
+                }
+                Some(args.clone())
+            }
+            None => None,
+        };
+
+        // Keep the server from being stopped as idle while this request runs
+        let _activity = ActivityGuard::new(&self.activity, server_name);
+
+        // First, find the server (launching it if it is not running)
+        let server = self.ensure_server(server_name).await?;
 
         if std::env::var("DEBUG").is_ok() {
             // Get current time with milliseconds for logging
@@ -344,32 +745,30 @@ impl McpHost {
             arguments: args,
         };
 
-        // Get next request ID
-        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
-
-        let request = Request::new(
-            Some(serde_json::to_value(request_id).unwrap()),
-            "mcp/tool/execute".into(),
-            Some(serde_json::to_value(params).unwrap()),
-        );
-
-        // Get the appropriate timeout for this server/tool
-        let timeout = self.get_tool_timeout(server_name, tool_name);
-        info!(
-            "Using {}s timeout for {}/{}",
-            timeout.as_secs(),
-            server_name,
-            tool_name
-        );
+        // Cached results of the server may be stale once a tool that is neither
+        // cacheable nor read-only has run, whether or not it succeeded
+        let may_write = cache_config.is_some() && cache_ttl.is_none() && {
+            let capabilities = server.capabilities.lock().await;
+            let tools = capabilities.as_ref().map(|caps| caps.tools.as_slice()).unwrap_or_default();
+            cache::may_write(tools, tool_name)
+        };
 
-        // Send request, with timeout for response
-        let response = tokio::time::timeout(timeout, server.send_request(request))
-            .await
-            .map_err(|_| format!("Timeout waiting for response from server '{}'", server_name))?
-            .map_err(|e| format!("Error from server '{}': {:?}", server_name, e))?;
+        let response = self
+            .send_with_policy(
+                &server,
+                tool_name,
+                "mcp/tool/execute",
+                serde_json::to_value(params).unwrap(),
+            )
+            .await;
+        if may_write {
+            debug!("Dropping cached results of '{}' after {}", server_name, tool_name);
+            self.client_state.result_cache.invalidate(server_name);
+        }
+        let response = response?;
 
         // Parse response
-        match response.result() {
+        let result = match response.result() {
             Ok(result_value) => {
                 // Modify this block to handle both formats: with and without 'result' field
                 if let Some(result) = result_value.get("result") {
@@ -390,61 +789,561 @@ impl McpHost {
                 "Error from server '{}' executing tool '{}': {}",
                 server_name, tool_name, error.message
             )),
+        }?;
+
+        // Tool-level errors are not cached, so the next call tries again
+        if let (Some(cache), Some(ttl), Some(args)) = (&cache_config, cache_ttl, cache_args) {
+            if result.get("isError").and_then(Value::as_bool) != Some(true) {
+                self.client_state
+                    .result_cache
+                    .insert(server_name, tool_name, &args, &result, ttl, cache);
+            }
         }
+        Ok(result)
     }
 
-    // Get a resource from a specific server
-    pub async fn get_resource(
+    /// Execute a tool and return its result as MCP content blocks.
+    /// Free-form results from older servers are wrapped as structured content.
+    pub async fn call_tool(
         &self,
         server_name: &str,
-        resource_name: &str,
-        params: Option<Value>, // Add params if needed by spec/servers
-    ) -> Result<Value, String> {
-        // First, find the server
+        tool_name: &str,
+        args: Value,
+    ) -> Result<ToolCallResult, String> {
+        let result = ToolCallResult::from_value(self.execute_tool(server_name, tool_name, args).await?);
+        if result.is_error {
+            warn!(
+                "Tool {}/{} reported an error: {}",
+                server_name,
+                tool_name,
+                result.text()
+            );
+        }
+        Ok(result)
+    }
+
+    /// Reserve a request ID for `send_request`
+    pub fn allocate_request_id(&self) -> u64 {
+        self.next_request_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Send a request to a server as is and wait up to `timeout` for its response,
+    /// JSON-RPC errors included. There is no result cache, queueing or retry; this
+    /// is meant for diagnostics such as `mcp-tester`. `id` must come from
+    /// `allocate_request_id`.
+    pub async fn send_request(
+        &self,
+        server_name: &str,
+        id: u64,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Response, String> {
+        let _activity = ActivityGuard::new(&self.activity, server_name);
+        let server = self.ensure_server(server_name).await?;
+        let request = Request::new(Some(json!(id)), method.into(), Some(params));
+        match tokio::time::timeout(timeout, server.send_request(request)).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => Err(format!("Error from server '{}': {}", server_name, e.message)),
+            Err(_) => Err(format!("Timeout waiting for response from server '{}'", server_name)),
+        }
+    }
+
+    /// Send a notification to a running server
+    pub async fn send_notification(
+        &self,
+        server_name: &str,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<(), String> {
         let server = Self::find_ready_server(&self.servers, server_name).await?;
+        server
+            .send_notification(rpc::Notification::new(method.to_string(), params))
+            .await
+    }
 
-        // Then, get the resource
-        let params = rpc::GetResourceParams {
-            name: resource_name.to_string(),
-            params,
+    /// Get a resource from a specific server with `resources/read`. `resource_name`
+    /// is the name of a resource the server advertises, or the resource URI itself.
+    pub async fn get_resource(&self, server_name: &str, resource_name: &str) -> Result<Value, String> {
+        let running = self.servers.lock().await.get(server_name).cloned();
+        let caps = match running {
+            Some(server) => server.capabilities.lock().await.clone(),
+            None => self.known_capabilities.lock().await.get(server_name).cloned(),
         };
+        let uri = caps
+            .and_then(|caps| {
+                caps.resources
+                    .into_iter()
+                    .find(|resource| resource.name == resource_name)
+                    .and_then(|resource| resource.uri)
+            })
+            .unwrap_or_else(|| resource_name.to_string());
 
-        // Get next request ID
-        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (_, result) = self.read_resource(Some(server_name), &uri).await?;
+        serde_json::to_value(result).map_err(|e| format!("Failed to serialize resource '{}': {}", uri, e))
+    }
 
-        let request = Request::new(
-            Some(serde_json::to_value(request_id).unwrap()),
-            "resource/get".into(),
-            Some(serde_json::to_value(params).unwrap()),
-        );
+    /// List the prompt templates of all servers, named `server/prompt`.
+    /// Running servers that declared the prompts capability are asked with
+    /// `prompts/list`; other servers report the prompts remembered from their
+    /// last run and are not launched.
+    pub async fn list_prompts(&self) -> Vec<Prompt> {
+        let mut server_names: Vec<String> = self.configs.lock().await.keys().cloned().collect();
+        server_names.sort();
+
+        let mut prompts = Vec::new();
+        for server_name in server_names {
+            let running = self.servers.lock().await.get(&server_name).cloned();
+            let server_prompts = match running {
+                Some(server) => {
+                    let declared = server
+                        .capabilities
+                        .lock()
+                        .await
+                        .as_ref()
+                        .map(|caps| caps.prompts.clone())
+                        .unwrap_or_default();
+                    // Servers without the prompts capability are not asked
+                    if declared.is_empty() {
+                        continue;
+                    }
+                    match self.fetch_prompts(&server).await {
+                        Ok(server_prompts) => server_prompts,
+                        Err(e) => {
+                            debug!("Server '{}' did not list prompts: {}", server_name, e);
+                            declared
+                        }
+                    }
+                }
+                None => self
+                    .known_capabilities
+                    .lock()
+                    .await
+                    .get(&server_name)
+                    .map(|caps| caps.prompts.clone())
+                    .unwrap_or_default(),
+            };
 
-        // Get the appropriate timeout for this server/resource
-        let timeout = self.get_tool_timeout(server_name, &format!("resource_{}", resource_name));
-        info!(
-            "Using {}s timeout for {}/{} resource",
-            timeout.as_secs(),
-            server_name,
-            resource_name
-        );
+            for mut prompt in server_prompts {
+                prompt.name = format!("{}/{}", server_name, prompt.name);
+                prompts.push(prompt);
+            }
+        }
+        prompts
+    }
 
-        // Send request, with timeout for response
-        let response = tokio::time::timeout(timeout, server.send_request(request))
+    // Ask a running server for its prompts
+    async fn fetch_prompts(&self, server: &ActiveServer) -> Result<Vec<Prompt>, String> {
+        let _activity = ActivityGuard::new(&self.activity, &server.config.name);
+        let response = self
+            .send_with_policy(server, "prompts/list", "prompts/list", json!({}))
+            .await?;
+        let result = response.result().map_err(|e| e.message)?;
+        serde_json::from_value::<ListPromptsResult>(result)
+            .map(|list| list.prompts)
+            .map_err(|e| format!("Invalid prompts/list result: {}", e))
+    }
+
+    /// Expand a prompt template on a server into conversation messages
+    pub async fn get_prompt(
+        &self,
+        server_name: &str,
+        prompt_name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult, String> {
+        // Keep the server from being stopped as idle while this request runs
+        let _activity = ActivityGuard::new(&self.activity, server_name);
+
+        // First, find the server (launching it if it is not running)
+        let server = self.ensure_server(server_name).await?;
+
+        let response = self
+            .send_with_policy(
+                &server,
+                "prompts/get",
+                "prompts/get",
+                json!({ "name": prompt_name, "arguments": arguments }),
+            )
+            .await?;
+
+        match response.result() {
+            Ok(result_value) => serde_json::from_value(result_value).map_err(|e| {
+                format!(
+                    "Server '{}' returned an invalid result for prompt '{}': {}",
+                    server_name, prompt_name, e
+                )
+            }),
+            Err(error) => Err(format!(
+                "Error from server '{}' getting prompt '{}': {}",
+                server_name, prompt_name, error.message
+            )),
+        }
+    }
+
+    /// List the resource templates of all running servers, named `server/template`
+    pub async fn list_resource_templates(&self) -> Vec<ResourceTemplate> {
+        let mut running: Vec<(String, ActiveServer)> = self
+            .servers
+            .lock()
             .await
-            .map_err(|_| format!("Timeout waiting for response from server '{}'", server_name))?
-            .map_err(|e| format!("Error from server '{}': {:?}", server_name, e))?;
+            .iter()
+            .map(|(name, server)| (name.clone(), server.clone()))
+            .collect();
+        running.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut templates = Vec::new();
+        for (server_name, server) in running {
+            match self.fetch_resource_templates(&server).await {
+                Ok(server_templates) => {
+                    for mut template in server_templates {
+                        template.name = format!("{}/{}", server_name, template.name);
+                        templates.push(template);
+                    }
+                }
+                Err(e) => debug!(
+                    "Server '{}' did not list resource templates: {}",
+                    server_name, e
+                ),
+            }
+        }
+        templates
+    }
+
+    // Ask a running server for its resource templates
+    async fn fetch_resource_templates(
+        &self,
+        server: &ActiveServer,
+    ) -> Result<Vec<ResourceTemplate>, String> {
+        let _activity = ActivityGuard::new(&self.activity, &server.config.name);
+        let response = self
+            .send_with_policy(
+                server,
+                "resources/templates/list",
+                "resources/templates/list",
+                json!({}),
+            )
+            .await?;
+        let result = response.result().map_err(|e| e.message)?;
+        serde_json::from_value::<ListResourceTemplatesResult>(result)
+            .map(|list| list.resource_templates)
+            .map_err(|e| format!("Invalid resources/templates/list result: {}", e))
+    }
+
+    /// Find the server providing a resource URI: servers advertising the exact URI
+    /// come first, then running servers with a matching resource template.
+    pub async fn find_resource_server(&self, uri: &str) -> Result<String, String> {
+        let mut server_names: Vec<String> = self.configs.lock().await.keys().cloned().collect();
+        server_names.sort();
+
+        for server_name in &server_names {
+            let running = self.servers.lock().await.get(server_name).cloned();
+            let caps = match running {
+                Some(server) => server.capabilities.lock().await.clone(),
+                None => self.known_capabilities.lock().await.get(server_name).cloned(),
+            };
+            let advertised = caps.is_some_and(|caps| {
+                caps.resources
+                    .iter()
+                    .any(|resource| resource.uri.as_deref() == Some(uri))
+            });
+            if advertised {
+                return Ok(server_name.clone());
+            }
+        }
+
+        for template in self.list_resource_templates().await {
+            if template.matches(uri) {
+                if let Some((server_name, _)) = template.name.split_once('/') {
+                    return Ok(server_name.to_string());
+                }
+            }
+        }
+
+        Err(format!("No MCP server provides resource '{}'", uri))
+    }
+
+    /// Read a resource by URI with `resources/read`. Without a server name the
+    /// server is found with [`McpHost::find_resource_server`].
+    pub async fn read_resource(
+        &self,
+        server_name: Option<&str>,
+        uri: &str,
+    ) -> Result<(String, ReadResourceResult), String> {
+        let server_name = match server_name {
+            Some(name) => name.to_string(),
+            None => self.find_resource_server(uri).await?,
+        };
+
+        // Keep the server from being stopped as idle while this request runs
+        let _activity = ActivityGuard::new(&self.activity, &server_name);
+
+        // First, find the server (launching it if it is not running)
+        let server = self.ensure_server(&server_name).await?;
+
+        let response = self
+            .send_with_policy(&server, "resources/read", "resources/read", json!({ "uri": uri }))
+            .await?;
 
-        // Parse response
         match response.result() {
-            Ok(result_value) => Ok(result_value),
+            Ok(result_value) => serde_json::from_value(result_value)
+                .map(|result| (server_name.clone(), result))
+                .map_err(|e| {
+                    format!(
+                        "Server '{}' returned an invalid result for resource '{}': {}",
+                        server_name, uri, e
+                    )
+                }),
             Err(error) => Err(format!(
-                "Error from server '{}' getting resource '{}': {}",
-                server_name, resource_name, error.message
+                "Error from server '{}' reading resource '{}': {}",
+                server_name, uri, error.message
             )),
         }
     }
 
+    /// Subscribe to updates of a resource. Updates are delivered through
+    /// [`McpHost::resource_updates`]; the subscription survives server restarts.
+    pub async fn subscribe_resource(&self, server_name: &str, uri: &str) -> Result<(), String> {
+        let _activity = ActivityGuard::new(&self.activity, server_name);
+        let server = self.ensure_server(server_name).await?;
+        self.send_subscription(&server, "resources/subscribe", uri)
+            .await?;
+        self.resource_subscriptions
+            .lock()
+            .await
+            .entry(server_name.to_string())
+            .or_default()
+            .insert(uri.to_string());
+        info!("Subscribed to resource '{}' on server '{}'", uri, server_name);
+        Ok(())
+    }
+
+    /// Stop receiving updates for a resource
+    pub async fn unsubscribe_resource(&self, server_name: &str, uri: &str) -> Result<(), String> {
+        let removed = self
+            .resource_subscriptions
+            .lock()
+            .await
+            .get_mut(server_name)
+            .is_some_and(|uris| uris.remove(uri));
+        if !removed {
+            return Err(format!(
+                "Not subscribed to resource '{}' on server '{}'",
+                uri, server_name
+            ));
+        }
+
+        // A stopped server has no subscription to cancel
+        let running = self.servers.lock().await.get(server_name).cloned();
+        if let Some(server) = running {
+            let _activity = ActivityGuard::new(&self.activity, server_name);
+            self.send_subscription(&server, "resources/unsubscribe", uri)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Receive `notifications/resources/updated` from all servers
+    pub fn resource_updates(&self) -> tokio::sync::broadcast::Receiver<ResourceUpdated> {
+        self.client_state.resource_updates.subscribe()
+    }
+
+    async fn send_subscription(
+        &self,
+        server: &ActiveServer,
+        method: &str,
+        uri: &str,
+    ) -> Result<(), String> {
+        let response = self
+            .send_with_policy(server, method, method, json!({ "uri": uri }))
+            .await?;
+        response.result().map(|_| ()).map_err(|e| {
+            format!(
+                "Error from server '{}' for {} '{}': {}",
+                server.config.name, method, uri, e.message
+            )
+        })
+    }
+
+    // Re-establish resource subscriptions after a server was (re)started
+    async fn restore_subscriptions(&self, server_name: &str, server: &ActiveServer) {
+        let uris: Vec<String> = match self.resource_subscriptions.lock().await.get(server_name) {
+            Some(uris) => uris.iter().cloned().collect(),
+            None => return,
+        };
+        for uri in uris {
+            if let Err(e) = self
+                .send_subscription(server, "resources/subscribe", &uri)
+                .await
+            {
+                warn!("Failed to restore subscription: {}", e);
+            }
+        }
+    }
+
+    /// Status of every configured server, sorted by name
+    pub async fn list_servers(&self) -> Vec<ServerStatus> {
+        let mut names: Vec<String> = self.configs.lock().await.keys().cloned().collect();
+        names.sort();
+        let mut statuses = Vec::with_capacity(names.len());
+        for name in names {
+            if let Ok(status) = self.server_status(&name).await {
+                statuses.push(status);
+            }
+        }
+        statuses
+    }
+
+    /// Status of a configured server
+    pub async fn server_status(&self, server_name: &str) -> Result<ServerStatus, String> {
+        let config = self
+            .configs
+            .lock()
+            .await
+            .get(server_name)
+            .cloned()
+            .ok_or_else(|| format!("Server '{}' not found", server_name))?;
+        let running = self.servers.lock().await.get(server_name).cloned();
+        let mut last_error = status::last_error(&self.last_errors, server_name);
+
+        let (state, caps, pid, uptime) = match running {
+            Some(server) => {
+                let caps = server.capabilities.lock().await.clone();
+                let state = if let Some(exit) = server.exit_status().await {
+                    last_error = Some(format!("Server process exited ({})", exit));
+                    ServerState::Failed
+                } else if caps.is_some() {
+                    ServerState::Running
+                } else if last_error.is_some() {
+                    ServerState::Failed
+                } else {
+                    ServerState::Initializing
+                };
+                let pid = server.pid().await;
+                (state, caps, pid, Some(server.started_at.elapsed()))
+            }
+            None => {
+                let state = if last_error.is_some() {
+                    ServerState::Failed
+                } else {
+                    ServerState::Stopped
+                };
+                (state, None, None, None)
+            }
+        };
+        let caps = match caps {
+            Some(caps) => Some(caps),
+            None => self.known_capabilities.lock().await.get(server_name).cloned(),
+        };
+
+        Ok(ServerStatus {
+            name: server_name.to_string(),
+            state,
+            transport: status::transport_name(&config.transport),
+            eager: config.startup.is_eager(),
+            tools: caps.as_ref().map(|c| c.tools.len()).unwrap_or_default(),
+            resources: caps.as_ref().map(|c| c.resources.len()).unwrap_or_default(),
+            prompts: caps.as_ref().map(|c| c.prompts.len()).unwrap_or_default(),
+            pid,
+            uptime,
+            last_error,
+        })
+    }
+
+    /// Start a configured server unless it is already running, even if its last
+    /// launch failed moments ago
+    pub async fn start_server(&self, server_name: &str) -> Result<ServerStatus, String> {
+        status::reset_launch_backoff(&self.last_errors, server_name);
+        self.ensure_server(server_name).await?;
+        self.server_status(server_name).await
+    }
+
+    /// Stop a running server. It is launched again on its next request; disable it
+    /// in the configuration to keep it stopped.
+    pub async fn stop_server(&self, server_name: &str) -> Result<ServerStatus, String> {
+        let server = self.servers.lock().await.remove(server_name);
+        match server {
+            Some(server) => {
+                info!("Stopping MCP server '{}' on request", server_name);
+                self.stop_active_server(server_name, &server).await;
+            }
+            None => debug!("Server '{}' is not running", server_name),
+        }
+        status::clear_error(&self.last_errors, server_name);
+        self.server_status(server_name).await
+    }
+
+    /// Stop a server if it is running and start it again
+    pub async fn restart_server(&self, server_name: &str) -> Result<ServerStatus, String> {
+        self.stop_server(server_name).await?;
+        self.start_server(server_name).await
+    }
+
+    /// Replace the server configurations, leaving unaffected servers running.
+    /// Removed servers are stopped. Servers whose launch settings changed are
+    /// restarted if they were running, and added or restarted servers with eager
+    /// startup are started; lazy ones start on their next request. Other changes
+    /// apply to running servers in place.
+    pub async fn reload_configs(&self, configs: Vec<McpServerConfig>) -> ConfigReload {
+        // One reload at a time, so servers are never started from a superseded config
+        let _reloading = self.reload_lock.lock().await;
+        let new_configs: HashMap<String, McpServerConfig> =
+            configs.into_iter().map(|c| (c.name.clone(), c)).collect();
+        let reload = {
+            let mut current = self.configs.lock().await;
+            let reload = status::diff_configs(&current, &new_configs);
+            *current = new_configs.clone();
+            reload
+        };
+        if reload.is_empty() {
+            info!("MCP server configuration unchanged");
+            return reload;
+        }
+        let mut relaunch = Vec::new();
+        for server_name in reload.removed.iter().chain(&reload.restarted) {
+            let server = self.servers.lock().await.remove(server_name);
+            if let Some(server) = server {
+                self.stop_active_server(server_name, &server).await;
+                relaunch.push(server_name.clone());
+            }
+            // Queues are sized from the old max_concurrent_requests
+            self.request_limits.lock().await.remove(server_name);
+            status::clear_error(&self.last_errors, server_name);
+        }
+        for server_name in &reload.removed {
+            self.known_capabilities.lock().await.remove(server_name);
+            self.resource_subscriptions.lock().await.remove(server_name);
+            if let Ok(mut activity) = self.activity.lock() {
+                activity.remove(server_name);
+            }
+        }
+
+        for server_name in &reload.updated {
+            let config = &new_configs[server_name];
+            if let Some(server) = self.servers.lock().await.get_mut(server_name) {
+                server.config = config.clone();
+            }
+            self.request_limits.lock().await.remove(server_name);
+            self.client_state.result_cache.invalidate(server_name);
+        }
+
+        for server_name in reload.added.iter().chain(&reload.restarted) {
+            let config = &new_configs[server_name];
+            if !config.startup.is_eager() && !relaunch.contains(server_name) {
+                continue;
+            }
+            if let Err(e) = self.ensure_server(server_name).await {
+                warn!("Failed to start reloaded server '{}': {}", server_name, e);
+            }
+        }
+        info!("Applied MCP server configuration: {}", reload);
+        reload
+    }
+
     // Shutdown all servers
     pub async fn shutdown(&self) {
+        // Stop the idle reaper and prevent further on-demand launches
+        self.closed.store(true, Ordering::SeqCst);
+
         let mut servers_lock = self.servers.lock().await;
 
         if servers_lock.is_empty() {
@@ -455,83 +1354,89 @@ impl McpHost {
         info!("Shutting down {} MCP servers...", servers_lock.len());
 
         // Take the servers out of the map (to appease the borrow checker)
-        let mut servers = std::mem::take(&mut *servers_lock);
+        let servers = std::mem::take(&mut *servers_lock);
+        drop(servers_lock);
 
-        for (server_name, server) in servers.iter_mut() {
-            // Don't hold the capabilities lock during shutdown
-            let should_shutdown = {
-                let caps = server.capabilities.lock().await;
-                caps.is_some() // Only shutdown if initialized
-            };
+        for (server_name, server) in servers.iter() {
+            self.stop_active_server(server_name, server).await;
+        }
 
-            if should_shutdown {
-                info!("Shutting down MCP server '{}'", server_name);
+        // Clean up any remaining resources
+        info!("MCP host shutdown complete");
+    }
 
-                // Send shutdown request if possible
-                let request = Request::new(
-                    Some(json!(self.next_request_id.fetch_add(1, Ordering::SeqCst))),
-                    "shutdown".into(),
-                    None,
-                );
+    // Gracefully stop a single server that has already been removed from the running map
+    async fn stop_active_server(&self, server_name: &str, server: &ActiveServer) {
+        self.client_state.result_cache.invalidate(server_name);
 
-                // Try to send shutdown, with a longer timeout (5 seconds)
-                match tokio::time::timeout(Duration::from_secs(5), server.send_request(request))
-                    .await
-                {
-                    Ok(send_result) => match send_result {
-                        Ok(response) => {
-                            info!(
-                                "Received shutdown response from MCP server '{}': {:?}",
-                                server_name, response
-                            );
+        // Don't hold the capabilities lock during shutdown
+        let should_shutdown = {
+            let caps = server.capabilities.lock().await;
+            caps.is_some() // Only shutdown if initialized
+        };
 
-                            // Wait a moment for graceful shutdown
-                            tokio::time::sleep(Duration::from_secs(1)).await;
+        if should_shutdown {
+            info!("Shutting down MCP server '{}'", server_name);
 
-                            // Send exit notification
-                            let exit_notification =
-                                crate::rpc::Notification::new("exit".into(), None);
+            // Send shutdown request if possible
+            let request = Request::new(
+                Some(json!(self.next_request_id.fetch_add(1, Ordering::SeqCst))),
+                "shutdown".into(),
+                None,
+            );
 
-                            match server.send_notification(exit_notification).await {
-                                Ok(_) => {
-                                    info!("Sent exit notification to MCP server '{}'", server_name)
-                                }
-                                Err(e) => warn!(
-                                    "Failed to send exit notification to MCP server '{}': {}",
-                                    server_name, e
-                                ),
-                            }
+            // Try to send shutdown, with a longer timeout (5 seconds)
+            match tokio::time::timeout(Duration::from_secs(5), server.send_request(request)).await
+            {
+                Ok(send_result) => match send_result {
+                    Ok(response) => {
+                        info!(
+                            "Received shutdown response from MCP server '{}': {:?}",
+                            server_name, response
+                        );
 
-                            // Additional grace period
-                            tokio::time::sleep(Duration::from_millis(500)).await;
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to send shutdown request to MCP server '{}': {:?}",
+                        // Wait a moment for graceful shutdown
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+
+                        // Send exit notification
+                        let exit_notification = crate::rpc::Notification::new("exit".into(), None);
+
+                        match server.send_notification(exit_notification).await {
+                            Ok(_) => {
+                                info!("Sent exit notification to MCP server '{}'", server_name)
+                            }
+                            Err(e) => warn!(
+                                "Failed to send exit notification to MCP server '{}': {}",
                                 server_name, e
-                            );
+                            ),
                         }
-                    },
-                    Err(_) => {
+
+                        // Additional grace period
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                    }
+                    Err(e) => {
                         error!(
-                            "Timeout sending shutdown request to MCP server '{}'",
-                            server_name
+                            "Failed to send shutdown request to MCP server '{}': {:?}",
+                            server_name, e
                         );
                     }
+                },
+                Err(_) => {
+                    error!(
+                        "Timeout sending shutdown request to MCP server '{}'",
+                        server_name
+                    );
                 }
             }
+        }
 
-            // Interrupt any blocked receiving/dispatching tasks by setting shutdown flag
-            server.set_shutdown().await;
+        // Interrupt any blocked receiving/dispatching tasks by setting shutdown flag
+        server.set_shutdown().await;
 
-            // Kill the process if using Stdio
-            if let Some(mut process) = server.take_process().await {
-                Self::kill_process(&mut process, server_name).await;
-            }
+        // Kill the process if using Stdio
+        if let Some(mut process) = server.take_process().await {
+            Self::kill_process(&mut process, server_name).await;
         }
-
-        // Clean up any remaining resources
-        info!("MCP host shutdown complete");
     }
 
     // Helper to kill child processes
@@ -546,22 +1451,54 @@ impl McpHost {
 
     // Get system information from all servers (for status)
     pub async fn get_system_info(&self) -> Result<String, String> {
-        let servers = self.servers.lock().await;
-        let mut output = String::new();
-        output.push_str(&format!("{} MCP servers connected:\n", servers.len()));
+        let configs = self.configs.lock().await.clone();
+        let servers = self.servers.lock().await.clone();
+        let known = self.known_capabilities.lock().await.clone();
 
-        for (name, server) in servers.iter() {
-            let caps = server.capabilities.lock().await;
-            if let Some(caps) = caps.as_ref() {
-                output.push_str(&format!(
-                    "- {} [{}]: {} tools, {} resources\n",
-                    name,
-                    "READY",
-                    caps.tools.len(),
-                    caps.resources.len()
-                ));
+        let mut names: Vec<&String> = configs.keys().collect();
+        names.sort();
+
+        let mut output = String::new();
+        output.push_str(&format!(
+            "{} MCP servers connected ({} configured):\n",
+            servers.len(),
+            configs.len()
+        ));
+
+        for name in names {
+            let config = &configs[name];
+            if let Some(server) = servers.get(name) {
+                let caps = server.capabilities.lock().await;
+                if let Some(caps) = caps.as_ref() {
+                    output.push_str(&format!(
+                        "- {} [{}]: {} tools, {} resources\n",
+                        name,
+                        "READY",
+                        caps.tools.len(),
+                        caps.resources.len()
+                    ));
+                    if config.cache.is_some() {
+                        let stats = self.client_state.result_cache.stats(name);
+                        output.push_str(&format!(
+                            "  result cache: {} entries, {} hits, {} misses\n",
+                            stats.entries, stats.hits, stats.misses
+                        ));
+                    }
+                } else {
+                    output.push_str(&format!("- {} [{}]\n", name, "INITIALIZING"));
+                }
             } else {
-                output.push_str(&format!("- {} [{}]\n", name, "INITIALIZING"));
+                let startup = if config.startup.is_eager() { "eager" } else { "lazy" };
+                match known.get(name) {
+                    Some(caps) => output.push_str(&format!(
+                        "- {} [{}] ({}): {} tools remembered\n",
+                        name,
+                        "STOPPED",
+                        startup,
+                        caps.tools.len()
+                    )),
+                    None => output.push_str(&format!("- {} [{}] ({})\n", name, "STOPPED", startup)),
+                }
             }
         }
 
@@ -583,81 +1520,39 @@ impl McpHost {
 
     // Check if a tool is configured to auto-execute
     pub async fn is_auto_execute(&self, server_name: &str, tool_name: &str) -> bool {
-        let servers = self.servers.lock().await;
+        let configs = self.configs.lock().await;
 
-        if let Some(server) = servers.get(server_name) {
-            server.config.auto_execute.contains(&tool_name.to_string())
+        if let Some(config) = configs.get(server_name) {
+            config.auto_execute.contains(&tool_name.to_string())
         } else {
             false
         }
     }
 
-    // Add a tool to the auto-execute list
+    // Run a tool without confirmation for as long as this host runs. The config
+    // file is left alone: it holds `${...}` references that the loaded configs have
+    // resolved, and tools meant to run in every session belong in it by hand.
     pub async fn add_to_auto_execute(
         &self,
         server_name: &str,
         tool_name: &str,
     ) -> Result<(), String> {
-        let mut servers = self.servers.lock().await;
-
-        if let Some(server) = servers.get_mut(server_name) {
-            // Check if it's not already there
-            if !server.config.auto_execute.contains(&tool_name.to_string()) {
-                // Add the tool to the auto-execute list
-                server.config.auto_execute.push(tool_name.to_string());
-
-                // Update the config file
-                // Create a closure to avoid holding the lock during file I/O
-                let config_clone = server.config.clone();
-                drop(servers); // Release the lock
-
-                // Now write the updated config to disk
-                let config_path = crate::config::get_mcp_config_path()
-                    .map_err(|e| format!("Error finding MCP config path: {}", e))?;
-
-                let mut all_configs = if config_path.exists() {
-                    let content = std::fs::read_to_string(&config_path)
-                        .map_err(|e| format!("Error reading MCP config file: {}", e))?;
-
-                    if content.trim().is_empty() {
-                        Vec::new()
-                    } else {
-                        serde_json::from_str::<Vec<McpServerConfig>>(&content)
-                            .map_err(|e| format!("Error parsing MCP config file: {}", e))?
-                    }
-                } else {
-                    Vec::new()
-                };
-
-                // Update the server config in the list
-                let mut found = false;
-                for config in all_configs.iter_mut() {
-                    if config.name == config_clone.name {
-                        *config = config_clone.clone();
-                        found = true;
-                        break;
-                    }
-                }
-
-                if !found {
-                    all_configs.push(config_clone);
-                }
-
-                // Write the updated configs
-                let content = serde_json::to_string_pretty(&all_configs)
-                    .map_err(|e| format!("Error serializing MCP configs: {}", e))?;
-
-                std::fs::write(&config_path, content)
-                    .map_err(|e| format!("Error writing MCP config file: {}", e))?;
+        let mut configs = self.configs.lock().await;
+        let Some(config) = configs.get_mut(server_name) else {
+            return Err(format!("Server '{}' not found", server_name));
+        };
+        if config.auto_execute.iter().any(|name| name == tool_name) {
+            return Ok(());
+        }
+        config.auto_execute.push(tool_name.to_string());
+        let auto_execute = config.auto_execute.clone();
+        drop(configs);
 
-                Ok(())
-            } else {
-                // Already in the list, no action needed
-                Ok(())
-            }
-        } else {
-            Err(format!("Server '{}' not found", server_name))
+        // Keep the running server's copy in sync
+        if let Some(server) = self.servers.lock().await.get_mut(server_name) {
+            server.config.auto_execute = auto_execute;
         }
+        Ok(())
     }
 }
 
@@ -713,7 +1608,10 @@ impl McpHostInterface for McpHost {
                 data: None,
             })?;
 
-        let active_server = McpHost::find_ready_server(&self.servers, server_name)
+        let _activity = ActivityGuard::new(&self.activity, server_name);
+
+        let active_server = self
+            .ensure_server(server_name)
             .await
             .map_err(|e| JsonRpcError {
                 code: -32603,
@@ -0,0 +1,465 @@
+// Recording of the JSON-RPC traffic between the host and a server, and a
+// replay transport that stands in for the server from such a recording.
+//
+// A recording is a JSON Lines file with one frame per message: when it was
+// sent, how long after the start of the recording, in which direction, and
+// the message itself. Servers are recorded when `record` is set in their
+// configuration, each launch appending to the file, or into a new file per
+// launch in `GEMINI_MCP_RECORD_DIR` when that is set.
+//
+// The replay answers each message from the host with what the server sent in
+// reply to the matching recorded message. A message matches the first
+// recorded one with the same method (preferring one with the same params), so
+// request ids and the order of unrelated calls may differ from the recording.
+// Along with the response, the replay sends the notifications and requests the
+// server sent before the host's next message. Requests without a recorded
+// counterpart fail with an error so the host never waits on them.
+
+use super::io::{frame_message, read_message};
+use crate::config::{McpServerConfig, McpTransport};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+// Directory servers without a configured `record` file are recorded into
+pub(crate) const RECORD_DIR_ENV: &str = "GEMINI_MCP_RECORD_DIR";
+
+// Error code for requests the recording has no response to
+const NOT_RECORDED: i64 = -32000;
+
+const REPLAY_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Direction {
+    ToServer,
+    ToHost,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Frame {
+    pub timestamp: DateTime<Utc>,
+    // Milliseconds since the start of the recording
+    pub offset_ms: u64,
+    pub direction: Direction,
+    // The JSON-RPC message; anything that is not JSON is kept as a string
+    pub message: Value,
+}
+
+impl Frame {
+    fn method(&self) -> Option<&str> {
+        self.message.get("method").and_then(Value::as_str)
+    }
+
+    fn id(&self) -> Option<&Value> {
+        self.message.get("id").filter(|id| !id.is_null())
+    }
+
+    // The message as it was sent
+    fn text(&self) -> String {
+        match &self.message {
+            Value::String(text) => text.clone(),
+            message => message.to_string(),
+        }
+    }
+}
+
+// Appends the frames of one server session to a recording file. Frames are
+// written by a separate task so recording never blocks the server's reader.
+pub(crate) struct Recorder {
+    started: Instant,
+    lines: mpsc::UnboundedSender<String>,
+}
+
+impl Recorder {
+    // Open a recording for appending, so the sessions of earlier launches are
+    // kept. The file is only readable by the user as it may hold secrets.
+    pub(crate) fn create(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+        let (lines, mut lines_rx) = mpsc::unbounded_channel::<String>();
+        let path = path.to_path_buf();
+        tokio::spawn(async move {
+            let mut file = tokio::fs::File::from_std(file);
+            while let Some(line) = lines_rx.recv().await {
+                // Lines are written whole so a crash leaves a readable file
+                if let Err(e) = file.write_all(line.as_bytes()).await {
+                    warn!("Failed to write to recording {}: {}", path.display(), e);
+                }
+            }
+            if let Err(e) = file.flush().await {
+                warn!("Failed to write to recording {}: {}", path.display(), e);
+            }
+        });
+        Ok(Self {
+            started: Instant::now(),
+            lines,
+        })
+    }
+
+    // Record one message
+    pub(crate) fn record(&self, direction: Direction, text: &str) {
+        let frame = Frame {
+            timestamp: Utc::now(),
+            offset_ms: self.started.elapsed().as_millis() as u64,
+            direction,
+            message: serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())),
+        };
+        let mut line = match serde_json::to_string(&frame) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize recorded frame: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+        // The writer only stops once every recorder handle is gone
+        let _ = self.lines.send(line);
+    }
+}
+
+// The recorder for a server's session, if it is to be recorded
+pub(crate) fn recorder_for(config: &McpServerConfig) -> Option<Arc<Recorder>> {
+    // A replay could otherwise overwrite the recording it is reading
+    if matches!(config.transport, McpTransport::Replay { .. }) {
+        return None;
+    }
+    let path = match &config.record {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let dir = std::env::var_os(RECORD_DIR_ENV)?;
+            PathBuf::from(dir).join(format!(
+                "{}-{}.jsonl",
+                config.name,
+                Utc::now().format("%Y%m%dT%H%M%S%.3f")
+            ))
+        }
+    };
+    match Recorder::create(&path) {
+        Ok(recorder) => {
+            info!("Recording MCP server '{}' to {}", config.name, path.display());
+            Some(Arc::new(recorder))
+        }
+        Err(e) => {
+            warn!("Not recording MCP server '{}': {}", config.name, e);
+            None
+        }
+    }
+}
+
+// Read a recording, skipping blank lines
+pub(crate) fn load(path: &Path) -> Result<Vec<Frame>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read recording {}: {}", path.display(), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("Invalid frame at {}:{}: {}", path.display(), index + 1, e))
+        })
+        .collect()
+}
+
+// What the replay sends in answer to one message from the host
+#[derive(Debug, PartialEq)]
+struct Reply {
+    // Delay relative to the matched message in the recording
+    delay: Duration,
+    text: String,
+}
+
+// Matching state of a replay
+struct Replay {
+    frames: Vec<Frame>,
+    consumed: Vec<bool>,
+}
+
+impl Replay {
+    fn new(frames: Vec<Frame>) -> Self {
+        let consumed = vec![false; frames.len()];
+        Self { frames, consumed }
+    }
+
+    // The replies to a message from the host
+    fn answer(&mut self, message: &Value) -> Vec<Reply> {
+        let method = message.get("method").and_then(Value::as_str);
+        let live_id = message.get("id").filter(|id| !id.is_null());
+        let Some(index) = self.find(method, live_id, message.get("params")) else {
+            return match (method, live_id) {
+                (Some(method), Some(id)) => {
+                    warn!("Replay: no recorded response for '{}'", method);
+                    let error = json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": NOT_RECORDED,
+                            "message": format!("No recorded response for '{}'", method),
+                        },
+                    });
+                    vec![Reply {
+                        delay: Duration::ZERO,
+                        text: error.to_string(),
+                    }]
+                }
+                _ => {
+                    debug!("Replay: ignoring unrecorded message {}", message);
+                    Vec::new()
+                }
+            };
+        };
+        self.consumed[index] = true;
+
+        // The response to a request may come after later messages from the host
+        let response = match (method, live_id) {
+            (Some(_), Some(_)) => {
+                let recorded_id = self.frames[index].id().cloned();
+                (index + 1..self.frames.len()).find(|&i| {
+                    let frame = &self.frames[i];
+                    !self.consumed[i]
+                        && frame.direction == Direction::ToHost
+                        && frame.method().is_none()
+                        && frame.id() == recorded_id.as_ref()
+                })
+            }
+            _ => None,
+        };
+        let next_from_host = (index + 1..self.frames.len())
+            .find(|&i| self.frames[i].direction == Direction::ToServer)
+            .unwrap_or(self.frames.len());
+        let mut replies: Vec<usize> = (index + 1..next_from_host)
+            .filter(|&i| !self.consumed[i] && self.frames[i].method().is_some())
+            .collect();
+        if let Some(response) = response {
+            replies.push(response);
+            replies.sort_unstable();
+        }
+
+        let start = self.frames[index].offset_ms;
+        replies
+            .into_iter()
+            .map(|i| {
+                self.consumed[i] = true;
+                let frame = &self.frames[i];
+                let text = match (Some(i) == response, live_id) {
+                    (true, Some(id)) => {
+                        let mut message = frame.message.clone();
+                        message["id"] = id.clone();
+                        message.to_string()
+                    }
+                    _ => frame.text(),
+                };
+                Reply {
+                    delay: Duration::from_millis(frame.offset_ms.saturating_sub(start)),
+                    text,
+                }
+            })
+            .collect()
+    }
+
+    // The recorded message a message from the host stands for: requests and
+    // notifications by method, responses to server requests by id
+    fn find(&self, method: Option<&str>, id: Option<&Value>, params: Option<&Value>) -> Option<usize> {
+        let candidates: Vec<usize> = (0..self.frames.len())
+            .filter(|&i| {
+                let frame = &self.frames[i];
+                !self.consumed[i]
+                    && frame.direction == Direction::ToServer
+                    && frame.method() == method
+                    && match method {
+                        Some(_) => frame.id().is_some() == id.is_some(),
+                        None => frame.id() == id,
+                    }
+            })
+            .collect();
+        candidates
+            .iter()
+            .copied()
+            .find(|&i| self.frames[i].message.get("params") == params)
+            .or_else(|| candidates.first().copied())
+    }
+}
+
+// Start a replay of `frames`, returning the streams the host reads from and
+// writes to. With `realtime`, replies are delayed as long as they were when
+// recorded.
+pub(crate) fn spawn_replay(
+    server_name: &str,
+    frames: Vec<Frame>,
+    realtime: bool,
+) -> (impl AsyncRead + Send + Unpin, impl AsyncWrite + Send + Unpin) {
+    let (host_side, replay_side) = tokio::io::duplex(REPLAY_BUFFER_SIZE);
+    let (replay_reader, mut replay_writer) = tokio::io::split(replay_side);
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<String>();
+
+    let server_name_writer = server_name.to_string();
+    tokio::spawn(async move {
+        while let Some(text) = reply_rx.recv().await {
+            let framed = frame_message(&text);
+            if let Err(e) = replay_writer.write_all(framed.as_bytes()).await {
+                debug!("Replay({}): host stopped reading: {}", server_name_writer, e);
+                break;
+            }
+        }
+    });
+
+    let server_name = server_name.to_string();
+    tokio::spawn(async move {
+        let mut replay = Replay::new(frames);
+        let mut reader = BufReader::new(replay_reader);
+        loop {
+            let text = match read_message(&mut reader).await {
+                Ok(Some(text)) => text,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Replay({}): {}", server_name, e);
+                    break;
+                }
+            };
+            let message: Value = match serde_json::from_str(&text) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Replay({}): ignoring invalid message from host: {}", server_name, e);
+                    continue;
+                }
+            };
+            let replies = replay.answer(&message);
+            if realtime {
+                let reply_tx = reply_tx.clone();
+                tokio::spawn(async move {
+                    let received = tokio::time::Instant::now();
+                    for reply in replies {
+                        tokio::time::sleep_until(received + reply.delay).await;
+                        let _ = reply_tx.send(reply.text);
+                    }
+                });
+            } else {
+                for reply in replies {
+                    let _ = reply_tx.send(reply.text);
+                }
+            }
+        }
+        debug!("Replay({}): host closed the connection", server_name);
+    });
+
+    tokio::io::split(host_side)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn frame(offset_ms: u64, direction: Direction, message: Value) -> Frame {
+        Frame {
+            timestamp: Utc::now(),
+            offset_ms,
+            direction,
+            message,
+        }
+    }
+
+    fn session() -> Vec<Frame> {
+        vec![
+            frame(0, Direction::ToServer, json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})),
+            frame(5, Direction::ToHost, json!({"jsonrpc": "2.0", "id": 1, "result": {"capabilities": {}}})),
+            frame(6, Direction::ToServer, json!({"jsonrpc": "2.0", "method": "notifications/initialized"})),
+            frame(10, Direction::ToServer, json!({"jsonrpc": "2.0", "id": 2, "method": "mcp/tool/execute", "params": {"name": "read", "args": {"path": "a"}}})),
+            frame(11, Direction::ToServer, json!({"jsonrpc": "2.0", "id": 3, "method": "mcp/tool/execute", "params": {"name": "read", "args": {"path": "b"}}})),
+            frame(12, Direction::ToHost, json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {"level": "info"}})),
+            frame(20, Direction::ToHost, json!({"jsonrpc": "2.0", "id": 3, "result": "b"})),
+            frame(30, Direction::ToHost, json!({"jsonrpc": "2.0", "id": 2, "result": "a"})),
+        ]
+    }
+
+    // The frames of a recording once its writer has written `count` of them
+    async fn wait_for_frames(path: &Path, count: usize) -> Vec<Frame> {
+        for _ in 0..100 {
+            match load(path) {
+                Ok(frames) if frames.len() == count => return frames,
+                _ => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+        panic!("{} frames were not recorded", count);
+    }
+
+    fn texts(replies: Vec<Reply>) -> Vec<Value> {
+        replies
+            .into_iter()
+            .map(|reply| serde_json::from_str(&reply.text).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_replay_matching() {
+        let mut replay = Replay::new(session());
+
+        let replies = replay.answer(&json!({"jsonrpc": "2.0", "id": 7, "method": "initialize", "params": {"x": 1}}));
+        assert_eq!(replies[0].delay, Duration::from_millis(5));
+        assert_eq!(texts(replies), vec![json!({"jsonrpc": "2.0", "id": 7, "result": {"capabilities": {}}})]);
+        assert!(replay.answer(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).is_empty());
+
+        // Calls are matched by params before order
+        let replies = replay.answer(&json!({"jsonrpc": "2.0", "id": 8, "method": "mcp/tool/execute", "params": {"name": "read", "args": {"path": "b"}}}));
+        assert_eq!(replies.iter().map(|r| r.delay.as_millis()).collect::<Vec<_>>(), vec![1, 9]);
+        let replies = texts(replies);
+        assert_eq!(replies[0]["method"], "notifications/message");
+        assert_eq!(replies[1], json!({"jsonrpc": "2.0", "id": 8, "result": "b"}));
+
+        let replies = replay.answer(&json!({"jsonrpc": "2.0", "id": 9, "method": "mcp/tool/execute", "params": {"name": "read", "args": {"path": "c"}}}));
+        assert_eq!(texts(replies), vec![json!({"jsonrpc": "2.0", "id": 9, "result": "a"})]);
+
+        // Nothing recorded is left for a further call
+        let replies = texts(replay.answer(&json!({"jsonrpc": "2.0", "id": 10, "method": "mcp/tool/execute", "params": {}})));
+        assert_eq!(replies[0]["id"], 10);
+        assert_eq!(replies[0]["error"]["code"], NOT_RECORDED);
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(Direction::ToServer, r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#);
+        recorder.record(Direction::ToHost, r#"{"jsonrpc":"2.0","id":1,"result":{}}"#);
+        drop(recorder);
+        let frames = wait_for_frames(&path, 2).await;
+
+        // A relaunch appends to the recording instead of replacing it
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(Direction::ToHost, "not json");
+        drop(recorder);
+        assert_eq!(wait_for_frames(&path, 3).await[..2], frames[..]);
+        let frames = load(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(frames[1].direction, Direction::ToHost);
+        assert_eq!(frames[2].message, json!("not json"));
+
+        let (output, mut input) = spawn_replay("test", frames, false);
+        let request = r#"{"jsonrpc":"2.0","id":42,"method":"ping"}"#;
+        input
+            .write_all(format!("Content-Length: {}\r\n\r\n{}", request.len(), request).as_bytes())
+            .await
+            .unwrap();
+        let mut output = BufReader::new(output);
+        let response = read_message(&mut output).await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&response).unwrap(), json!({"jsonrpc": "2.0", "id": 42, "result": {}}));
+    }
+}
@@ -0,0 +1,644 @@
+// Sandboxing for stdio MCP server processes.
+//
+// Everything that can fail for lack of kernel support is probed in the host
+// before the server is spawned, so that unavailable features are reported in
+// the daemon log (or refused in strict mode). The restrictions themselves are
+// applied in the child between fork and exec, in this order: resource limits,
+// network namespace, Landlock filesystem rules, seccomp filter. The child only
+// uses data prepared up front and plain system calls.
+
+use crate::config::{McpSandboxConfig, McpSeccompPreset};
+use log::{info, warn};
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+
+// Directories every sandboxed server may read, so that dynamically linked
+// executables and interpreters can start
+const SYSTEM_READ_ONLY_PATHS: &[&str] = &[
+    "/usr", "/lib", "/lib64", "/lib32", "/bin", "/sbin", "/etc", "/opt", "/proc", "/sys",
+];
+// Device nodes such as /dev/null and /dev/urandom
+const SYSTEM_READ_WRITE_PATHS: &[&str] = &["/dev"];
+
+// Landlock filesystem access rights (linux/landlock.h)
+const LANDLOCK_ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const LANDLOCK_ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const LANDLOCK_ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const LANDLOCK_ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const LANDLOCK_ACCESS_FS_REFER: u64 = 1 << 13;
+const LANDLOCK_ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+// All rights defined by the first Landlock ABI
+const LANDLOCK_ACCESS_FS_ABI_1: u64 = (1 << 13) - 1;
+// Rights that may be granted on a regular file rather than a directory
+const LANDLOCK_ACCESS_FILE: u64 = LANDLOCK_ACCESS_FS_EXECUTE
+    | LANDLOCK_ACCESS_FS_WRITE_FILE
+    | LANDLOCK_ACCESS_FS_READ_FILE
+    | LANDLOCK_ACCESS_FS_TRUNCATE;
+const LANDLOCK_READ_ONLY: u64 =
+    LANDLOCK_ACCESS_FS_EXECUTE | LANDLOCK_ACCESS_FS_READ_FILE | LANDLOCK_ACCESS_FS_READ_DIR;
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+
+#[repr(C)]
+struct LandlockRulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct LandlockPathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+// Offsets into struct seccomp_data
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const SECCOMP_DATA_ARG0: u32 = 16;
+
+const AUDIT_ARCH_X86_64: u32 = 0xC000_003E;
+// Set in the numbers of x32 system calls, which pass the x86_64 architecture check
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_CURRENT: Option<u32> = Some(AUDIT_ARCH_X86_64);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH_CURRENT: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH_CURRENT: Option<u32> = None;
+
+// System calls blocked by every seccomp preset
+const BLOCKED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_kexec_file_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_acct,
+    libc::SYS_quotactl,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_userfaultfd,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_io_uring_setup,
+];
+
+// How the server is cut off from the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NetworkIsolation {
+    // The host may create network namespaces directly
+    NetNamespace,
+    // An unprivileged user namespace is needed to create the network namespace
+    UserAndNetNamespace,
+}
+
+#[derive(Debug)]
+struct UserNamespaceMaps {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct LandlockRules {
+    handled_access: u64,
+    rules: Vec<(CString, u64)>,
+}
+
+// Restrictions to apply in the child process, prepared by `prepare`
+#[derive(Debug, Default)]
+pub(crate) struct SandboxPlan {
+    cpu_seconds: Option<u64>,
+    memory_bytes: Option<u64>,
+    open_files: Option<u64>,
+    network: Option<NetworkIsolation>,
+    user_maps: Option<UserNamespaceMaps>,
+    landlock: Option<LandlockRules>,
+    seccomp: Option<Vec<libc::sock_filter>>,
+}
+
+impl SandboxPlan {
+    fn is_empty(&self) -> bool {
+        self.cpu_seconds.is_none()
+            && self.memory_bytes.is_none()
+            && self.open_files.is_none()
+            && self.network.is_none()
+            && self.landlock.is_none()
+            && self.seccomp.is_none()
+    }
+}
+
+/// Configure `cmd` to run the server inside its sandbox. Features the kernel does not
+/// support are logged and skipped, or refused if the sandbox is `strict`.
+pub(crate) fn apply(
+    cmd: &mut Command,
+    server_name: &str,
+    executable: &str,
+    sandbox: &McpSandboxConfig,
+) -> Result<(), String> {
+    if let Some(allowlist) = &sandbox.env_allowlist {
+        cmd.env_clear();
+        for name in allowlist {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+    }
+    if let Some(dir) = &sandbox.working_dir {
+        cmd.current_dir(dir);
+    }
+
+    let plan = prepare(server_name, executable, sandbox)?;
+    if plan.is_empty() {
+        return Ok(());
+    }
+
+    // SAFETY: the closure only issues system calls on data prepared above; it does
+    // not allocate or take locks between fork and exec.
+    unsafe {
+        cmd.pre_exec(move || enforce(&plan));
+    }
+    Ok(())
+}
+
+// Decide which restrictions can be applied on this kernel
+fn prepare(
+    server_name: &str,
+    executable: &str,
+    sandbox: &McpSandboxConfig,
+) -> Result<SandboxPlan, String> {
+    let mut plan = SandboxPlan {
+        cpu_seconds: sandbox.cpu_seconds,
+        memory_bytes: sandbox.memory_mb.map(|mb| mb.saturating_mul(1024 * 1024)),
+        open_files: sandbox.open_files,
+        ..Default::default()
+    };
+    let mut applied = Vec::new();
+    if plan.cpu_seconds.is_some() || plan.memory_bytes.is_some() || plan.open_files.is_some() {
+        applied.push("resource limits");
+    }
+
+    let unavailable = |feature: &str, reason: String| -> Result<(), String> {
+        if sandbox.strict {
+            Err(format!(
+                "Server '{}': sandbox {} is unavailable on this system ({}); refusing to start in strict mode",
+                server_name, feature, reason
+            ))
+        } else {
+            warn!(
+                "Server '{}': sandbox {} is unavailable on this system ({}); starting without it",
+                server_name, feature, reason
+            );
+            Ok(())
+        }
+    };
+
+    if sandbox.no_network {
+        match network_support() {
+            Ok(isolation) => {
+                if *isolation == NetworkIsolation::UserAndNetNamespace {
+                    // SAFETY: getuid and getgid cannot fail
+                    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+                    plan.user_maps = Some(UserNamespaceMaps {
+                        uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+                        gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+                    });
+                }
+                plan.network = Some(*isolation);
+                applied.push("network isolation");
+            }
+            Err(reason) => unavailable("network isolation", reason.clone())?,
+        }
+    }
+
+    if let Some(filesystem) = &sandbox.filesystem {
+        match landlock_abi() {
+            Ok(abi) => {
+                let mut handled_access = LANDLOCK_ACCESS_FS_ABI_1;
+                if *abi >= 2 {
+                    handled_access |= LANDLOCK_ACCESS_FS_REFER;
+                }
+                if *abi >= 3 {
+                    handled_access |= LANDLOCK_ACCESS_FS_TRUNCATE;
+                }
+
+                let mut read_only: Vec<PathBuf> =
+                    SYSTEM_READ_ONLY_PATHS.iter().map(PathBuf::from).collect();
+                read_only.extend(resolve_executable(executable));
+                read_only.extend(filesystem.read_only.iter().cloned());
+                let mut read_write: Vec<PathBuf> =
+                    SYSTEM_READ_WRITE_PATHS.iter().map(PathBuf::from).collect();
+                read_write.extend(filesystem.read_write.iter().cloned());
+
+                let mut rules = Vec::new();
+                for (paths, access) in [
+                    (read_only, LANDLOCK_READ_ONLY),
+                    (read_write, handled_access),
+                ] {
+                    for path in paths {
+                        rules.push((path_cstring(&path)?, access & handled_access));
+                    }
+                }
+                plan.landlock = Some(LandlockRules {
+                    handled_access,
+                    rules,
+                });
+                applied.push("filesystem restrictions");
+            }
+            Err(reason) => unavailable("filesystem restrictions (Landlock)", reason.clone())?,
+        }
+    }
+
+    if let Some(preset) = sandbox.seccomp {
+        match seccomp_support() {
+            Ok(arch) => {
+                plan.seccomp = Some(seccomp_filter(*arch, preset));
+                applied.push("seccomp filter");
+            }
+            Err(reason) => unavailable("seccomp filter", reason.clone())?,
+        }
+    }
+
+    if !applied.is_empty() {
+        info!(
+            "Server '{}': sandbox enabled with {}",
+            server_name,
+            applied.join(", ")
+        );
+    }
+    Ok(plan)
+}
+
+// Runs in the child between fork and exec
+fn enforce(plan: &SandboxPlan) -> io::Result<()> {
+    set_rlimit(libc::RLIMIT_CPU, plan.cpu_seconds)?;
+    set_rlimit(libc::RLIMIT_AS, plan.memory_bytes)?;
+    set_rlimit(libc::RLIMIT_NOFILE, plan.open_files)?;
+
+    match plan.network {
+        Some(NetworkIsolation::NetNamespace) => check(unsafe { libc::unshare(libc::CLONE_NEWNET) })?,
+        Some(NetworkIsolation::UserAndNetNamespace) => {
+            check(unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) })?;
+            if let Some(maps) = &plan.user_maps {
+                write_proc_file(c"/proc/self/setgroups", b"deny")?;
+                write_proc_file(c"/proc/self/uid_map", &maps.uid_map)?;
+                write_proc_file(c"/proc/self/gid_map", &maps.gid_map)?;
+            }
+        }
+        None => {}
+    }
+
+    if plan.landlock.is_some() || plan.seccomp.is_some() {
+        // Required to install Landlock rules and seccomp filters without privileges
+        check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+    }
+
+    if let Some(landlock) = &plan.landlock {
+        restrict_filesystem(landlock)?;
+    }
+
+    if let Some(filter) = &plan.seccomp {
+        let program = libc::sock_fprog {
+            len: filter.len() as libc::c_ushort,
+            filter: filter.as_ptr() as *mut libc::sock_filter,
+        };
+        check(unsafe {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+                &program as *const libc::sock_fprog as libc::c_ulong,
+                0,
+                0,
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(target_env = "gnu")]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type RlimitResource = libc::c_int;
+
+fn set_rlimit(resource: RlimitResource, limit: Option<u64>) -> io::Result<()> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    let rlimit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    check(unsafe { libc::setrlimit(resource, &rlimit) })
+}
+
+fn write_proc_file(path: &std::ffi::CStr, contents: &[u8]) -> io::Result<()> {
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    check(fd)?;
+    let written = unsafe { libc::write(fd, contents.as_ptr().cast(), contents.len()) };
+    unsafe { libc::close(fd) };
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn restrict_filesystem(landlock: &LandlockRules) -> io::Result<()> {
+    let attr = LandlockRulesetAttr {
+        handled_access_fs: landlock.handled_access,
+    };
+    let ruleset_fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const LandlockRulesetAttr,
+            std::mem::size_of::<LandlockRulesetAttr>(),
+            0u32,
+        )
+    } as libc::c_int;
+    check(ruleset_fd)?;
+
+    for (path, access) in &landlock.rules {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+        if fd < 0 {
+            // Paths that do not exist (e.g. /lib32) simply grant nothing
+            continue;
+        }
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        let is_dir = unsafe { libc::fstat(fd, &mut stat) } == 0
+            && (stat.st_mode & libc::S_IFMT) == libc::S_IFDIR;
+        let allowed_access = if is_dir {
+            *access
+        } else {
+            access & LANDLOCK_ACCESS_FILE
+        };
+        let rule = LandlockPathBeneathAttr {
+            allowed_access,
+            parent_fd: fd,
+        };
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset_fd,
+                LANDLOCK_RULE_PATH_BENEATH,
+                &rule as *const LandlockPathBeneathAttr,
+                0u32,
+            )
+        };
+        unsafe { libc::close(fd) };
+        if result < 0 {
+            let error = io::Error::last_os_error();
+            unsafe { libc::close(ruleset_fd) };
+            return Err(error);
+        }
+    }
+
+    let result = unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset_fd, 0u32) };
+    unsafe { libc::close(ruleset_fd) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn path_cstring(path: &Path) -> Result<CString, String> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| format!("Sandbox path {:?} contains a NUL byte", path))
+}
+
+// Find the server executable so it stays readable under Landlock
+fn resolve_executable(executable: &str) -> Option<PathBuf> {
+    let path = Path::new(executable);
+    let found = if path.components().count() > 1 {
+        Some(path.to_path_buf())
+    } else {
+        std::env::var_os("PATH").and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(executable))
+                .find(|candidate| candidate.is_file())
+        })
+    };
+    found.and_then(|path| path.canonicalize().ok())
+}
+
+// Kernel support is probed once per host process
+fn network_support() -> &'static Result<NetworkIsolation, String> {
+    static SUPPORT: OnceLock<Result<NetworkIsolation, String>> = OnceLock::new();
+    SUPPORT.get_or_init(probe_network_namespaces)
+}
+
+fn landlock_abi() -> &'static Result<i64, String> {
+    static ABI: OnceLock<Result<i64, String>> = OnceLock::new();
+    ABI.get_or_init(|| {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<LandlockRulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 0 {
+            let error = io::Error::last_os_error();
+            Err(match error.raw_os_error() {
+                Some(libc::ENOSYS) => "kernel built without Landlock".to_string(),
+                Some(libc::EOPNOTSUPP) => "Landlock is disabled at boot".to_string(),
+                _ => error.to_string(),
+            })
+        } else {
+            Ok(abi)
+        }
+    })
+}
+
+fn seccomp_support() -> &'static Result<u32, String> {
+    static SUPPORT: OnceLock<Result<u32, String>> = OnceLock::new();
+    SUPPORT.get_or_init(|| {
+        let arch = AUDIT_ARCH_CURRENT
+            .ok_or_else(|| "no seccomp preset for this CPU architecture".to_string())?;
+        if unsafe { libc::prctl(libc::PR_GET_SECCOMP, 0, 0, 0, 0) } < 0 {
+            return Err("kernel built without seccomp".to_string());
+        }
+        Ok(arch)
+    })
+}
+
+// Try the namespace setups in a short-lived child so the host itself is unaffected
+fn probe_network_namespaces() -> Result<NetworkIsolation, String> {
+    // SAFETY: the child only calls unshare and _exit, which are async-signal-safe
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        return Err(format!("probe failed: {}", io::Error::last_os_error()));
+    }
+    if pid == 0 {
+        unsafe {
+            if libc::unshare(libc::CLONE_NEWNET) == 0 {
+                libc::_exit(0);
+            }
+            if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) == 0 {
+                libc::_exit(1);
+            }
+            libc::_exit(2);
+        }
+    }
+
+    let mut status = 0;
+    if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        return Err(format!("probe failed: {}", io::Error::last_os_error()));
+    }
+    match libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status)) {
+        Some(0) => Ok(NetworkIsolation::NetNamespace),
+        Some(1) => Ok(NetworkIsolation::UserAndNetNamespace),
+        _ => Err("network namespaces require privileges and unprivileged user namespaces are disabled"
+            .to_string()),
+    }
+}
+
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+// Build the BPF program for a preset: kill on foreign architectures and x32 calls, fail blocked
+// calls with EPERM and, for `strict`, fail non-Unix sockets with EAFNOSUPPORT
+fn seccomp_filter(arch: u32, preset: McpSeccompPreset) -> Vec<libc::sock_filter> {
+    let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+    let jump_eq = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+    let ret = libc::BPF_RET | libc::BPF_K;
+
+    let mut filter = vec![
+        bpf_stmt(load, SECCOMP_DATA_ARCH),
+        bpf_jump(jump_eq, arch, 1, 0),
+        bpf_stmt(ret, libc::SECCOMP_RET_KILL_PROCESS),
+        bpf_stmt(load, SECCOMP_DATA_NR),
+    ];
+    if arch == AUDIT_ARCH_X86_64 {
+        // Otherwise `nr | X32_SYSCALL_BIT` would reach the same calls past the table
+        filter.extend([
+            bpf_jump(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, X32_SYSCALL_BIT, 0, 1),
+            bpf_stmt(ret, libc::SECCOMP_RET_KILL_PROCESS),
+        ]);
+    }
+    for syscall in BLOCKED_SYSCALLS {
+        filter.push(bpf_jump(jump_eq, *syscall as u32, 0, 1));
+        filter.push(bpf_stmt(ret, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    }
+    if preset == McpSeccompPreset::Strict {
+        filter.extend([
+            bpf_jump(jump_eq, libc::SYS_socket as u32, 0, 3),
+            bpf_stmt(load, SECCOMP_DATA_ARG0),
+            bpf_jump(jump_eq, libc::AF_UNIX as u32, 1, 0),
+            bpf_stmt(ret, libc::SECCOMP_RET_ERRNO | libc::EAFNOSUPPORT as u32),
+        ]);
+    }
+    filter.push(bpf_stmt(ret, libc::SECCOMP_RET_ALLOW));
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seccomp_filter_layout() {
+        let arch = AUDIT_ARCH_X86_64;
+        let default = seccomp_filter(arch, McpSeccompPreset::Default);
+        assert_eq!(default.len(), 6 + 2 * BLOCKED_SYSCALLS.len() + 1);
+        assert_eq!(default[1].k, arch);
+        assert_eq!(default[4].k, X32_SYSCALL_BIT);
+
+        let aarch64 = seccomp_filter(0xC000_00B7, McpSeccompPreset::Default);
+        assert_eq!(aarch64.len(), default.len() - 2);
+        assert_eq!(default.last().unwrap().k, libc::SECCOMP_RET_ALLOW);
+
+        let strict = seccomp_filter(arch, McpSeccompPreset::Strict);
+        assert_eq!(strict.len(), default.len() + 4);
+        assert_eq!(
+            strict[strict.len() - 2].k,
+            libc::SECCOMP_RET_ERRNO | libc::EAFNOSUPPORT as u32
+        );
+    }
+
+    // Blocked calls fail with EPERM and their x32 aliases kill the process
+    #[test]
+    fn test_seccomp_filter_blocks_calls() {
+        let Ok(arch) = *seccomp_support() else {
+            return;
+        };
+        let plan = SandboxPlan {
+            seccomp: Some(seccomp_filter(arch, McpSeccompPreset::Default)),
+            ..SandboxPlan::default()
+        };
+
+        // SAFETY: the child only calls prctl, syscall and _exit
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0, "fork failed: {}", io::Error::last_os_error());
+        if pid == 0 {
+            unsafe {
+                if enforce(&plan).is_err() {
+                    libc::_exit(10);
+                }
+                let result = libc::syscall(libc::SYS_acct, std::ptr::null::<libc::c_char>());
+                if result != -1 || *libc::__errno_location() != libc::EPERM {
+                    libc::_exit(11);
+                }
+                if arch == AUDIT_ARCH_X86_64 {
+                    libc::syscall(
+                        libc::SYS_acct | X32_SYSCALL_BIT as libc::c_long,
+                        std::ptr::null::<libc::c_char>(),
+                    );
+                    libc::_exit(12);
+                }
+                libc::_exit(0);
+            }
+        }
+
+        let mut status = 0;
+        assert!(unsafe { libc::waitpid(pid, &mut status, 0) } == pid);
+        if arch == AUDIT_ARCH_X86_64 {
+            assert!(
+                libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGSYS,
+                "x32 call was not killed, status {:#x}",
+                status
+            );
+        } else {
+            assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// Handles requests and notifications that MCP servers send to the host, i.e.
+// the client side of the protocol. One router is created per launched server so
+// that per-server permissions (such as the sampling allowlist) and configured
+// roots are applied consistently.
+
+use super::cache::ResultCache;
+use crate::config::{McpSamplingPolicy, McpServerConfig};
+use crate::sampling::{CreateMessageParams, SamplingHandler};
+use gemini_core::rpc_types::{JsonRpcError, Request, Response};
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::broadcast;
+
+// JSON-RPC error code used by MCP when the client declines a request
+const REQUEST_DECLINED: i64 = -1;
+
+// Resource update notifications buffered per subscriber before the oldest are dropped
+const RESOURCE_UPDATES_CAPACITY: usize = 256;
+
+/// A subscribed resource changed on a server (`notifications/resources/updated`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceUpdated {
+    pub server: String,
+    pub uri: String,
+}
+
+// Host-side state shared by the routers of all servers
+#[derive(Debug)]
+pub(crate) struct ClientState {
+    pub sampling: SamplingState,
+    pub workspace_roots: WorkspaceRoots,
+    pub resource_updates: broadcast::Sender<ResourceUpdated>,
+    pub result_cache: ResultCache,
+}
+
+impl Default for ClientState {
+    fn default() -> Self {
+        Self {
+            sampling: SamplingState::default(),
+            workspace_roots: WorkspaceRoots::default(),
+            resource_updates: broadcast::channel(RESOURCE_UPDATES_CAPACITY).0,
+            result_cache: ResultCache::default(),
+        }
+    }
+}
+
+// Sampling handler and per-server token usage, shared by all routers of a host
+#[derive(Default)]
+pub(crate) struct SamplingState {
+    handler: RwLock<Option<Arc<dyn SamplingHandler>>>,
+    tokens_used: Mutex<HashMap<String, u64>>,
+}
+
+impl std::fmt::Debug for SamplingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let has_handler = self.handler.read().map(|h| h.is_some()).unwrap_or(false);
+        f.debug_struct("SamplingState")
+            .field("has_handler", &has_handler)
+            .field("tokens_used", &self.tokens_used)
+            .finish()
+    }
+}
+
+impl SamplingState {
+    pub(crate) fn set_handler(&self, handler: Option<Arc<dyn SamplingHandler>>) {
+        if let Ok(mut current) = self.handler.write() {
+            *current = handler;
+        }
+    }
+
+    fn handler(&self) -> Option<Arc<dyn SamplingHandler>> {
+        self.handler.read().ok().and_then(|h| h.clone())
+    }
+
+    fn tokens_used(&self, server_name: &str) -> u64 {
+        self.tokens_used
+            .lock()
+            .map(|usage| usage.get(server_name).copied().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    fn charge(&self, server_name: &str, tokens: u64) {
+        if let Ok(mut usage) = self.tokens_used.lock() {
+            *usage.entry(server_name.to_string()).or_default() += tokens;
+        }
+    }
+}
+
+// Workspace directories of the current client session, shared by all routers of a host
+#[derive(Debug, Default)]
+pub(crate) struct WorkspaceRoots {
+    roots: RwLock<Vec<PathBuf>>,
+}
+
+impl WorkspaceRoots {
+    // Replace the workspace roots; returns true if they changed
+    pub(crate) fn set(&self, roots: Vec<PathBuf>) -> bool {
+        match self.roots.write() {
+            Ok(mut current) if *current != roots => {
+                *current = roots;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn get(&self) -> Vec<PathBuf> {
+        self.roots.read().map(|r| r.clone()).unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ServerRequestRouter {
+    server_name: String,
+    sampling_policy: Option<McpSamplingPolicy>,
+    configured_roots: Vec<String>,
+    state: Arc<ClientState>,
+}
+
+impl ServerRequestRouter {
+    pub(crate) fn new(config: &McpServerConfig, state: Arc<ClientState>) -> Self {
+        Self {
+            server_name: config.name.clone(),
+            sampling_policy: config.sampling.clone(),
+            configured_roots: config.roots.clone(),
+            state,
+        }
+    }
+
+    // Client capabilities advertised to the server in `initialize`
+    pub(crate) fn client_capabilities(&self) -> Value {
+        let mut capabilities = serde_json::Map::new();
+        capabilities.insert("roots".to_string(), json!({ "listChanged": true }));
+        if self.sampling_policy.is_some() {
+            capabilities.insert("sampling".to_string(), json!({}));
+        }
+        Value::Object(capabilities)
+    }
+
+    // Answer a request sent by the server
+    pub(crate) async fn handle(&self, request: Request) -> Response {
+        debug!(
+            "Handling '{}' request from server '{}'",
+            request.method, self.server_name
+        );
+        let id = request.id.clone().unwrap_or(Value::Null);
+        let outcome = match request.method.as_str() {
+            "ping" => Ok(json!({})),
+            "roots/list" => Ok(self.list_roots()),
+            "sampling/createMessage" => self.create_message(request.params).await,
+            method => Err(rpc_error(-32601, format!("Method not found: {}", method))),
+        };
+
+        match outcome {
+            Ok(result) => Response {
+                jsonrpc: "2.0".to_string(),
+                id,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => {
+                warn!(
+                    "Request '{}' from server '{}' failed: {}",
+                    request.method, self.server_name, error.message
+                );
+                Response {
+                    jsonrpc: "2.0".to_string(),
+                    id,
+                    result: None,
+                    error: Some(error),
+                }
+            }
+        }
+    }
+
+    // Configured roots first, then the client's workspace; duplicates are dropped
+    fn list_roots(&self) -> Value {
+        let mut uris: Vec<String> = Vec::new();
+        let mut roots = Vec::new();
+        let configured = self.configured_roots.iter().map(|root| expand_root(root));
+        for path in configured.chain(self.state.workspace_roots.get()) {
+            let uri = file_uri(&path);
+            if uris.contains(&uri) {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+            roots.push(json!({ "uri": uri, "name": name }));
+            uris.push(uri);
+        }
+        json!({ "roots": roots })
+    }
+
+    // Act on a notification sent by the server
+    pub(crate) fn handle_notification(&self, method: &str, params: Option<&Value>) {
+        match method {
+            "notifications/resources/updated" => {
+                let Some(uri) = params.and_then(|p| p.get("uri")).and_then(Value::as_str) else {
+                    warn!(
+                        "Server '{}' sent a resource update without a URI",
+                        self.server_name
+                    );
+                    return;
+                };
+                debug!("Resource '{}' updated on server '{}'", uri, self.server_name);
+                // No receivers is not an error; nobody is watching updates right now
+                let _ = self.state.resource_updates.send(ResourceUpdated {
+                    server: self.server_name.clone(),
+                    uri: uri.to_string(),
+                });
+            }
+            "notifications/tools/list_changed" => {
+                info!(
+                    "Tool list of server '{}' changed, dropping its cached results",
+                    self.server_name
+                );
+                self.state.result_cache.invalidate(&self.server_name);
+            }
+            _ => debug!(
+                "Ignoring '{}' notification from server '{}'",
+                method, self.server_name
+            ),
+        }
+    }
+
+    async fn create_message(&self, params: Option<Value>) -> Result<Value, JsonRpcError> {
+        let Some(policy) = &self.sampling_policy else {
+            return Err(rpc_error(
+                REQUEST_DECLINED,
+                format!("Sampling is not enabled for server '{}'", self.server_name),
+            ));
+        };
+        let handler = self.state.sampling.handler().ok_or_else(|| {
+            rpc_error(-32603, "No completion handler is configured on this host".to_string())
+        })?;
+        let mut params: CreateMessageParams =
+            serde_json::from_value(params.unwrap_or(Value::Null))
+                .map_err(|e| rpc_error(-32602, format!("Invalid sampling params: {}", e)))?;
+
+        // Apply the per-request cap, then whatever is left of the budget
+        let mut max_tokens = params.max_tokens.min(policy.max_tokens);
+        if let Some(budget) = policy.token_budget {
+            let remaining = budget.saturating_sub(self.state.sampling.tokens_used(&self.server_name));
+            if remaining == 0 {
+                return Err(rpc_error(
+                    REQUEST_DECLINED,
+                    format!(
+                        "Sampling token budget of {} exhausted for server '{}'",
+                        budget, self.server_name
+                    ),
+                ));
+            }
+            max_tokens = max_tokens.min(u32::try_from(remaining).unwrap_or(u32::MAX));
+        }
+        if max_tokens < params.max_tokens {
+            info!(
+                "Clamping sampling request from '{}' from {} to {} tokens",
+                self.server_name, params.max_tokens, max_tokens
+            );
+        }
+        params.max_tokens = max_tokens;
+
+        let output = handler
+            .create_message(&self.server_name, params)
+            .await
+            .map_err(|e| rpc_error(-32603, e))?;
+
+        self.state.sampling.charge(
+            &self.server_name,
+            output.tokens_used.unwrap_or(u64::from(max_tokens)),
+        );
+
+        serde_json::to_value(output.result)
+            .map_err(|e| rpc_error(-32603, format!("Failed to serialize sampling result: {}", e)))
+    }
+}
+
+// Resolve a configured root, which may be a path, `~/...` or a `file://` URI
+fn expand_root(root: &str) -> PathBuf {
+    let root = root.strip_prefix("file://").unwrap_or(root);
+    match root.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(root)),
+        None => PathBuf::from(root),
+    }
+}
+
+// Build a `file://` URI, percent-encoding everything but unreserved characters and `/`
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn rpc_error(code: i64, message: String) -> JsonRpcError {
+    JsonRpcError {
+        code,
+        message,
+        data: None,
+    }
+}
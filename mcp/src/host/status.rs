@@ -0,0 +1,289 @@
+// Status reporting and configuration reloads for management clients.
+//
+// The host keeps the last launch or connection error of each server so that a
+// server that failed to start can be told apart from one that was simply never
+// used, and so that it is not relaunched on every request while it keeps
+// failing. Reloading compares the new configuration with the current one by value:
+// only servers whose launch settings changed are restarted, while changes to
+// request handling (timeouts, limits, caching, idle timeout) apply in place.
+
+use crate::config::{McpServerConfig, McpTransport};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Lifecycle state of a configured server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerState {
+    /// Initialized and accepting requests
+    Running,
+    /// Launched, waiting for the `initialize` response
+    Initializing,
+    /// Not running; launched on its next request
+    Stopped,
+    /// Failed to start, or its process exited while registered as running
+    Failed,
+}
+
+/// Status of a configured server
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub name: String,
+    pub state: ServerState,
+    /// `stdio`, `sse` or `websocket`
+    pub transport: &'static str,
+    /// Whether the server is launched with the host rather than on first use
+    pub eager: bool,
+    /// Counts reported by the running server, or remembered from its last run
+    pub tools: usize,
+    pub resources: usize,
+    pub prompts: usize,
+    /// Process ID of a running stdio server
+    pub pid: Option<u32>,
+    /// Time since the running server was launched
+    pub uptime: Option<Duration>,
+    pub last_error: Option<String>,
+}
+
+/// Servers affected by a configuration reload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigReload {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Servers whose command, environment, transport or other launch settings changed
+    pub restarted: Vec<String>,
+    /// Servers whose other settings changed, applied without a restart
+    pub updated: Vec<String>,
+}
+
+impl ConfigReload {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.restarted.is_empty()
+            && self.updated.is_empty()
+    }
+}
+
+impl std::fmt::Display for ConfigReload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no changes");
+        }
+        let groups = [
+            ("added", &self.added),
+            ("removed", &self.removed),
+            ("restarted", &self.restarted),
+            ("updated", &self.updated),
+        ];
+        let parts: Vec<String> = groups
+            .iter()
+            .filter(|(_, names)| !names.is_empty())
+            .map(|(label, names)| format!("{} {}", label, names.join(", ")))
+            .collect();
+        f.write_str(&parts.join("; "))
+    }
+}
+
+// Last launch or connection error of each server, cleared when it starts again
+pub(crate) type LastErrors = Arc<Mutex<HashMap<String, LastError>>>;
+
+// First wait before a server that failed to launch is launched on demand again,
+// doubled on each further failure up to the maximum
+const LAUNCH_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_LAUNCH_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+pub(crate) struct LastError {
+    message: String,
+    // Launches that failed in a row, and when the last one did
+    launch_failures: u32,
+    failed_at: Instant,
+}
+
+pub(crate) fn record_error(errors: &LastErrors, server_name: &str, error: &str) {
+    if let Ok(mut errors) = errors.lock() {
+        errors
+            .entry(server_name.to_string())
+            .and_modify(|last| last.message = error.to_string())
+            .or_insert_with(|| LastError {
+                message: error.to_string(),
+                launch_failures: 0,
+                failed_at: Instant::now(),
+            });
+    }
+}
+
+// Record a failed launch, delaying the next launch on demand
+pub(crate) fn record_launch_error(errors: &LastErrors, server_name: &str, error: &str) {
+    if let Ok(mut errors) = errors.lock() {
+        let last = errors
+            .entry(server_name.to_string())
+            .or_insert_with(|| LastError {
+                message: String::new(),
+                launch_failures: 0,
+                failed_at: Instant::now(),
+            });
+        last.message = error.to_string();
+        last.launch_failures = last.launch_failures.saturating_add(1);
+        last.failed_at = Instant::now();
+    }
+}
+
+// Time left before a server that failed to launch may be launched on demand again
+pub(crate) fn launch_backoff(errors: &LastErrors, server_name: &str) -> Option<Duration> {
+    let errors = errors.lock().ok()?;
+    let last = errors.get(server_name).filter(|last| last.launch_failures > 0)?;
+    let backoff = LAUNCH_BACKOFF
+        .saturating_mul(1 << (last.launch_failures - 1).min(16))
+        .min(MAX_LAUNCH_BACKOFF);
+    backoff
+        .checked_sub(last.failed_at.elapsed())
+        .filter(|left| !left.is_zero())
+}
+
+// Allow an immediate launch, keeping the error for the status
+pub(crate) fn reset_launch_backoff(errors: &LastErrors, server_name: &str) {
+    if let Ok(mut errors) = errors.lock() {
+        if let Some(last) = errors.get_mut(server_name) {
+            last.launch_failures = 0;
+        }
+    }
+}
+
+pub(crate) fn clear_error(errors: &LastErrors, server_name: &str) {
+    if let Ok(mut errors) = errors.lock() {
+        errors.remove(server_name);
+    }
+}
+
+pub(crate) fn last_error(errors: &LastErrors, server_name: &str) -> Option<String> {
+    Some(errors.lock().ok()?.get(server_name)?.message.clone())
+}
+
+pub(crate) fn transport_name(transport: &McpTransport) -> &'static str {
+    match transport {
+        McpTransport::Stdio => "stdio",
+        McpTransport::SSE { .. } => "sse",
+        McpTransport::WebSocket { .. } => "websocket",
+        McpTransport::Replay { .. } => "replay",
+    }
+}
+
+// Settings fixed when a server is launched: its process, connection, recording,
+// and the sampling and roots policies its request router was built with
+fn launch_settings(config: &McpServerConfig) -> Option<serde_json::Value> {
+    serde_json::to_value((
+        &config.transport,
+        &config.command,
+        &config.args,
+        &config.env,
+        &config.sandbox,
+        &config.sampling,
+        &config.roots,
+        &config.record,
+    ))
+    .ok()
+}
+
+// Compare two configurations by their serialized form, which covers every field
+pub(crate) fn diff_configs(
+    current: &HashMap<String, McpServerConfig>,
+    new: &HashMap<String, McpServerConfig>,
+) -> ConfigReload {
+    let mut reload = ConfigReload::default();
+    for (name, config) in new {
+        match current.get(name) {
+            None => reload.added.push(name.clone()),
+            Some(old) if launch_settings(old) != launch_settings(config) => {
+                reload.restarted.push(name.clone())
+            }
+            Some(old) => {
+                if serde_json::to_value(old).ok() != serde_json::to_value(config).ok() {
+                    reload.updated.push(name.clone());
+                }
+            }
+        }
+    }
+    reload.removed = current
+        .keys()
+        .filter(|name| !new.contains_key(*name))
+        .cloned()
+        .collect();
+    reload.added.sort();
+    reload.removed.sort();
+    reload.restarted.sort();
+    reload.updated.sort();
+    reload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(name: &str, command: &str) -> McpServerConfig {
+        McpServerConfig {
+            name: name.to_string(),
+            command: vec![command.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn by_name(configs: Vec<McpServerConfig>) -> HashMap<String, McpServerConfig> {
+        configs.into_iter().map(|c| (c.name.clone(), c)).collect()
+    }
+
+    #[test]
+    fn test_diff_configs() {
+        let current = by_name(vec![
+            server("kept", "a"),
+            server("tuned", "b"),
+            server("moved", "c"),
+            server("dropped", "d"),
+        ]);
+        let mut tuned = server("tuned", "b");
+        tuned.idle_timeout = Some(60);
+        let mut moved = server("moved", "c");
+        moved.env.insert("PORT".to_string(), "8080".to_string());
+        let new = by_name(vec![server("kept", "a"), tuned, moved, server("new", "e")]);
+
+        let reload = diff_configs(&current, &new);
+        assert_eq!(reload.added, vec!["new"]);
+        assert_eq!(reload.removed, vec!["dropped"]);
+        assert_eq!(reload.restarted, vec!["moved"]);
+        assert_eq!(reload.updated, vec!["tuned"]);
+        assert_eq!(
+            reload.to_string(),
+            "added new; removed dropped; restarted moved; updated tuned"
+        );
+
+        assert!(diff_configs(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_launch_backoff() {
+        let errors = LastErrors::default();
+        assert_eq!(launch_backoff(&errors, "a"), None);
+
+        // Request errors do not hold back launches
+        record_error(&errors, "a", "request failed");
+        assert_eq!(launch_backoff(&errors, "a"), None);
+
+        record_launch_error(&errors, "a", "not found");
+        let first = launch_backoff(&errors, "a").unwrap();
+        assert!(first > LAUNCH_BACKOFF / 2 && first <= LAUNCH_BACKOFF);
+        record_launch_error(&errors, "a", "not found");
+        assert!(launch_backoff(&errors, "a").unwrap() > LAUNCH_BACKOFF);
+        assert_eq!(last_error(&errors, "a").as_deref(), Some("not found"));
+        for _ in 0..40 {
+            record_launch_error(&errors, "a", "not found");
+        }
+        assert!(launch_backoff(&errors, "a").unwrap() <= MAX_LAUNCH_BACKOFF);
+
+        reset_launch_backoff(&errors, "a");
+        assert_eq!(launch_backoff(&errors, "a"), None);
+        assert_eq!(last_error(&errors, "a").as_deref(), Some("not found"));
+        clear_error(&errors, "a");
+        assert_eq!(last_error(&errors, "a"), None);
+    }
+}
@@ -0,0 +1,174 @@
+// WebSocket transport: each text frame carries one JSON-RPC message.
+//
+// The connection is bridged to the Content-Length framed streams the stdio
+// message loop reads and writes, so WebSocket servers share its request
+// handling, the routing of server-to-client requests and recording.
+
+use super::io::{frame_message, read_message};
+use futures::{SinkExt, StreamExt};
+use log::{debug, warn};
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const BRIDGE_BUFFER_SIZE: usize = 64 * 1024;
+
+// Connect to a WebSocket server, returning the streams the host reads from and
+// writes to
+pub(crate) async fn open(
+    server_name: &str,
+    url: &str,
+    headers: Option<&HashMap<String, String>>,
+) -> Result<(impl AsyncRead + Send + Unpin, impl AsyncWrite + Send + Unpin), String> {
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| format!("Server '{}': invalid WebSocket URL '{}': {}", server_name, url, e))?;
+    for (name, value) in headers.into_iter().flatten() {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("Server '{}': invalid header name '{}': {}", server_name, name, e))?;
+        // Header values usually carry credentials, so they are not echoed back
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("Server '{}': invalid value for header '{}'", server_name, name))?;
+        request.headers_mut().insert(name, value);
+    }
+
+    let (socket, _) = tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(request))
+        .await
+        .map_err(|_| format!("Server '{}': timed out connecting to {}", server_name, url))?
+        .map_err(|e| format!("Server '{}': failed to connect to {}: {}", server_name, url, e))?;
+    let (mut sink, mut source) = socket.split();
+
+    let (host_side, bridge_side) = tokio::io::duplex(BRIDGE_BUFFER_SIZE);
+    let (bridge_reader, mut bridge_writer) = tokio::io::split(bridge_side);
+
+    // Server to host; dropping the writer ends the host's stream when the socket closes
+    let server_name_source = server_name.to_string();
+    tokio::spawn(async move {
+        while let Some(message) = source.next().await {
+            let text = match message {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Binary(bytes)) => match String::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(_) => {
+                        warn!("WebSocket({}): ignoring non-UTF-8 binary message", server_name_source);
+                        continue;
+                    }
+                },
+                Ok(Message::Close(_)) => break,
+                // Pings are answered by the socket itself
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("WebSocket({}): connection failed: {}", server_name_source, e);
+                    break;
+                }
+            };
+            if let Err(e) = bridge_writer.write_all(frame_message(&text).as_bytes()).await {
+                debug!("WebSocket({}): host stopped reading: {}", server_name_source, e);
+                break;
+            }
+        }
+        debug!("WebSocket({}): server closed the connection", server_name_source);
+    });
+
+    // Host to server
+    let server_name = server_name.to_string();
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(bridge_reader);
+        loop {
+            match read_message(&mut reader).await {
+                Ok(Some(text)) => {
+                    if let Err(e) = sink.send(Message::Text(text)).await {
+                        warn!("WebSocket({}): failed to send: {}", server_name, e);
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("WebSocket({}): {}", server_name, e);
+                    break;
+                }
+            }
+        }
+        let _ = sink.close().await;
+        debug!("WebSocket({}): host closed the connection", server_name);
+    });
+
+    Ok(tokio::io::split(host_side))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::server_requests::{ClientState, ServerRequestRouter};
+    use super::super::types::ActiveServer;
+    use super::*;
+    use crate::config::McpServerConfig;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+    use tokio_tungstenite::WebSocketStream;
+
+    async fn next_json(socket: &mut WebSocketStream<TcpStream>) -> Value {
+        loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)] // The handshake callback signature is fixed by tungstenite
+    async fn test_websocket_server_requests_are_routed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        // Asks the host for its roots before answering `initialize`
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut authorization = None;
+            let mut socket =
+                tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response: Response| {
+                    authorization = request
+                        .headers()
+                        .get("authorization")
+                        .map(|value| value.to_str().unwrap().to_string());
+                    Ok(response)
+                })
+                .await
+                .unwrap();
+            let initialize = next_json(&mut socket).await;
+            assert_eq!(initialize["method"], "initialize");
+            assert!(initialize["params"]["capabilities"]["roots"].is_object());
+
+            let roots_request = json!({ "jsonrpc": "2.0", "id": "roots", "method": "roots/list" });
+            socket.send(Message::Text(roots_request.to_string())).await.unwrap();
+            let roots = next_json(&mut socket).await;
+
+            let result = json!({ "jsonrpc": "2.0", "id": initialize["id"], "result": { "capabilities": {} } });
+            socket.send(Message::Text(result.to_string())).await.unwrap();
+            (authorization, roots)
+        });
+
+        let config = McpServerConfig {
+            name: "remote".to_string(),
+            roots: vec!["/srv/project".to_string()],
+            ..Default::default()
+        };
+        let router = ServerRequestRouter::new(&config, Arc::new(ClientState::default()));
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer secret".to_string())]);
+        let (_server, init) = ActiveServer::launch_websocket(&Arc::default(), config, url, Some(headers), router)
+            .await
+            .unwrap();
+        assert!(matches!(init.await, Ok(Ok(()))));
+
+        let (authorization, roots) = server.await.unwrap();
+        assert_eq!(authorization.as_deref(), Some("Bearer secret"));
+        assert_eq!(roots["id"], "roots");
+        assert_eq!(roots["result"]["roots"][0]["uri"], "file:///srv/project");
+    }
+}
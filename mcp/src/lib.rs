@@ -3,23 +3,33 @@
 // - Tool dispatch functionality
 // - Function call handling
 
+pub mod aggregator;
+pub mod audit;
 pub mod config;
 pub mod gemini;
 pub mod host;
+pub mod memory_tools;
+pub mod policy;
 // pub mod ipc; // Removed, now handled by the dedicated `ipc` crate
 pub mod rpc;
+pub mod servers;
+pub mod sampling;
+pub mod tool_names;
 
 // Re-export main types and functions for convenience
-pub use host::McpHost;
+pub use host::{ConfigReload, McpHost, ResourceUpdated, ServerState, ServerStatus};
+pub use policy::{ApprovalTokens, PolicyDecision, ToolPolicy};
+pub use tool_names::ToolNameRegistry;
 // Re-export gemini types and functions
 pub use gemini::{
     build_mcp_system_prompt, convert_mcp_tools_to_gemini_functions,
     generate_gemini_function_declarations, parse_function_calls, process_function_call,
-    sanitize_json_schema, FunctionCall, FunctionDef, FunctionParameter,
+    prompt_to_contents, resource_contents_to_parts, sanitize_json_schema, tool_error_response,
+    tool_result_to_parts, FunctionCall, FunctionDef, FunctionParameter, ToolResultParts,
 };
 // Remove re-export of types now in core
 // pub use rpc::{ServerCapabilities, Tool, Resource};
-pub use config::{get_mcp_config_path, load_mcp_servers, McpServerConfig, McpTransport};
+pub use config::{get_mcp_config_path, load_mcp_servers, McpServerConfig, McpStartup, McpTransport};
 
 
 // Modules will be added in Phase 4
@@ -0,0 +1,399 @@
+// Memory tools answered by mcp-hostd from its embedded `MemoryStore`.
+//
+// They are offered to clients as the tools of a `memory-store-mcp` server, but
+// calls to that server never leave the daemon. Results are JSON objects, with
+// memories as `{key, content, tags, timestamp, created_at, source}`, plus the
+// similarity `score` in `semantic_search` results.
+//
+// Times are accepted as RFC 3339 timestamps, `YYYY-MM-DD` dates (midnight UTC)
+// or Unix seconds, and relative windows as a number with a unit, e.g. `90m`,
+// `24h`, `7d` or `2w`.
+
+use crate::servers::{optional_str, optional_u64, required_str};
+use chrono::{DateTime, NaiveDate, Utc};
+use gemini_core::rpc_types::{Tool, ToolAnnotations};
+use gemini_memory::{Memory, MemoryStore};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Server name under which the internal memory tools are offered
+pub const SERVER_NAME: &str = "memory-store-mcp";
+
+const DEFAULT_SEARCH_RESULTS: u64 = 5;
+const MAX_SEARCH_RESULTS: u64 = 50;
+const DEFAULT_TIME_RESULTS: u64 = 20;
+
+// Tools that change the store; the others are annotated as read-only
+const WRITE_TOOLS: &[&str] = &["store_memory", "update_memory", "delete_memory_by_key"];
+
+/// Definitions of the internal memory tools, named `memory-store-mcp/<tool>`
+pub fn tool_definitions() -> Vec<Tool> {
+    let key = json!({ "type": "string", "description": "Unique key of the memory, e.g. 'user.preferred_editor'" });
+    let tags = |description: &str| json!({ "type": "array", "items": { "type": "string" }, "description": description });
+    let time = |description: &str| {
+        json!({
+            "type": "string",
+            "description": format!("{}, as an RFC 3339 timestamp, a YYYY-MM-DD date or Unix seconds", description),
+        })
+    };
+    let tool = |name: &str, description: &str, parameters: Value| Tool {
+        name: format!("{}/{}", SERVER_NAME, name),
+        description: Some(description.to_string()),
+        parameters: Some(parameters),
+        annotations: Some(ToolAnnotations {
+            read_only_hint: Some(!WRITE_TOOLS.contains(&name)),
+            ..Default::default()
+        }),
+    };
+
+    vec![
+        tool(
+            "store_memory",
+            "Store a new memory. Use update_memory to change an existing one.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "key": key,
+                    "content": { "type": "string", "description": "What to remember" },
+                    "tags": tags("Labels to find the memory by"),
+                    "source": { "type": "string", "description": "Where the memory comes from, e.g. 'user'" },
+                },
+                "required": ["key", "content"],
+            }),
+        ),
+        tool(
+            "update_memory",
+            "Change the content or tags of an existing memory. Omitted fields keep their value.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "key": key,
+                    "content": { "type": "string", "description": "New content" },
+                    "tags": tags("New tags, replacing the current ones"),
+                },
+                "required": ["key"],
+            }),
+        ),
+        tool(
+            "list_all_memories",
+            "List every stored memory.",
+            json!({ "type": "object", "properties": {} }),
+        ),
+        tool(
+            "retrieve_memory_by_key",
+            "Retrieve the memory stored under a key.",
+            json!({ "type": "object", "properties": { "key": key }, "required": ["key"] }),
+        ),
+        tool(
+            "retrieve_memory_by_tag",
+            "Retrieve the memories carrying a tag.",
+            json!({
+                "type": "object",
+                "properties": { "tag": { "type": "string", "description": "Tag to look for" } },
+                "required": ["tag"],
+            }),
+        ),
+        tool(
+            "delete_memory_by_key",
+            "Delete the memory stored under a key.",
+            json!({ "type": "object", "properties": { "key": key }, "required": ["key"] }),
+        ),
+        tool(
+            "semantic_search",
+            "Find the memories closest in meaning to a query, most similar first, each with a similarity \
+             score between 0 and 1.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "What to look for, in natural language" },
+                    "k": { "type": "integer", "description": format!("Number of results (default {}, at most {})", DEFAULT_SEARCH_RESULTS, MAX_SEARCH_RESULTS) },
+                    "tags": tags("Only memories carrying all of these tags"),
+                    "since": time("Only memories stored at or after this time"),
+                    "until": time("Only memories stored at or before this time"),
+                    "min_score": { "type": "number", "description": "Drop results less similar than this score" },
+                },
+                "required": ["query"],
+            }),
+        ),
+        tool(
+            "search_memories_by_time",
+            "List memories stored in a time window, newest first.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "within": { "type": "string", "description": "Window ending now, e.g. '24h' or '7d'" },
+                    "since": time("Start of the window"),
+                    "until": time("End of the window, now by default"),
+                    "tags": tags("Only memories carrying all of these tags"),
+                    "limit": { "type": "integer", "description": format!("Number of results (default {})", DEFAULT_TIME_RESULTS) },
+                },
+            }),
+        ),
+    ]
+}
+
+/// Run an internal memory tool, named without the server prefix
+pub async fn handle_tool(tool_name: &str, args: Value, store: &MemoryStore) -> Result<Value, String> {
+    match tool_name {
+        "store_memory" => {
+            let key = required_str(&args, "key")?;
+            let content = required_str(&args, "content")?;
+            let source = optional_str(&args, "source").map(str::to_string);
+            store
+                .add_memory(key, content, string_list(&args, "tags")?.unwrap_or_default(), None, source, None)
+                .await
+                .map_err(|e| format!("Failed to store memory: {}", e))?;
+            Ok(json!({ "success": true, "message": format!("Memory stored with key: {}", key) }))
+        }
+        "update_memory" => {
+            let key = required_str(&args, "key")?;
+            let content = optional_str(&args, "content");
+            let tags = string_list(&args, "tags")?;
+            if content.is_none() && tags.is_none() {
+                return Err("Nothing to update: give content, tags or both".to_string());
+            }
+            let existing = store
+                .get_by_key(key)
+                .await
+                .map_err(|e| format!("Failed to retrieve memory by key: {}", e))?
+                .ok_or_else(|| format!("Memory with key '{}' not found; use store_memory to create it", key))?;
+            store
+                .update_memory(
+                    key,
+                    content.unwrap_or(&existing.value),
+                    tags.unwrap_or(existing.tags),
+                    existing.session_id,
+                    existing.source,
+                    existing.related_keys,
+                )
+                .await
+                .map_err(|e| format!("Failed to update memory: {}", e))?;
+            Ok(json!({ "success": true, "message": format!("Memory with key '{}' updated", key) }))
+        }
+        "list_all_memories" => {
+            let memories = store
+                .get_all_memories()
+                .await
+                .map_err(|e| format!("Failed to list memories: {}", e))?;
+            Ok(json!({ "memories": memories.iter().map(memory_json).collect::<Vec<_>>() }))
+        }
+        "retrieve_memory_by_key" => {
+            let key = required_str(&args, "key")?;
+            match store.get_by_key(key).await {
+                Ok(Some(memory)) => Ok(json!({ "memory": memory_json(&memory) })),
+                Ok(None) => Err(format!("Memory with key '{}' not found", key)),
+                Err(e) => Err(format!("Failed to retrieve memory by key: {}", e)),
+            }
+        }
+        "retrieve_memory_by_tag" => {
+            let tag = required_str(&args, "tag")?;
+            let memories = store
+                .get_by_tag(tag)
+                .await
+                .map_err(|e| format!("Failed to retrieve memory by tag: {}", e))?;
+            Ok(json!({ "memories": memories.iter().map(memory_json).collect::<Vec<_>>() }))
+        }
+        "delete_memory_by_key" => {
+            let key = required_str(&args, "key")?;
+            let count = store
+                .delete_by_key(key)
+                .await
+                .map_err(|e| format!("Failed to delete memory: {}", e))?;
+            Ok(json!({ "success": true, "message": format!("Memory with key '{}' deleted (count: {})", key, count) }))
+        }
+        "semantic_search" => semantic_search(&args, store).await,
+        "search_memories_by_time" => search_by_time(&args, store).await,
+        _ => Err(format!("Unknown internal memory tool: {}", tool_name)),
+    }
+}
+
+async fn semantic_search(args: &Value, store: &MemoryStore) -> Result<Value, String> {
+    let query = required_str(args, "query")?;
+    let k = optional_u64(args, "k").unwrap_or(DEFAULT_SEARCH_RESULTS).clamp(1, MAX_SEARCH_RESULTS);
+    let tags = string_list(args, "tags")?;
+    let since = optional_time(args, "since")?;
+    let until = optional_time(args, "until")?;
+    let min_score = args.get("min_score").and_then(Value::as_f64).map(|score| score as f32);
+
+    let memories = store
+        .search_memories(Some(query), tags.as_ref(), k as usize, since, until)
+        .await
+        .map_err(|e| format!("Failed to search memories: {}", e))?;
+    // Without embeddings the store can only apply the filters, and nothing is scored
+    let ranked = memories.iter().any(|memory| memory.confidence_score.is_some());
+    let results: Vec<Value> = memories
+        .iter()
+        .filter(|memory| match (min_score, memory.confidence_score) {
+            (Some(min), Some(score)) => score >= min,
+            _ => true,
+        })
+        .map(|memory| {
+            let mut result = memory_json(memory);
+            result["score"] = json!(memory.confidence_score);
+            result
+        })
+        .collect();
+
+    let mut result = json!({ "memories": results });
+    if !ranked {
+        result["note"] = json!("Embeddings are unavailable, so results are not ranked by similarity");
+    }
+    Ok(result)
+}
+
+async fn search_by_time(args: &Value, store: &MemoryStore) -> Result<Value, String> {
+    let now = SystemTime::now();
+    let since = match optional_str(args, "within") {
+        Some(within) => {
+            if args.get("since").is_some() {
+                return Err("Give either 'within' or 'since', not both".to_string());
+            }
+            let window = parse_window(within)?;
+            Some(now.checked_sub(window).unwrap_or(UNIX_EPOCH))
+        }
+        None => optional_time(args, "since")?,
+    };
+    let until = optional_time(args, "until")?.unwrap_or(now);
+    let tags = string_list(args, "tags")?.unwrap_or_default();
+    let limit = optional_u64(args, "limit").unwrap_or(DEFAULT_TIME_RESULTS) as usize;
+
+    let mut memories = store
+        .get_in_range(since.unwrap_or(UNIX_EPOCH), until)
+        .await
+        .map_err(|e| format!("Failed to search memories by time: {}", e))?;
+    memories.retain(|memory| tags.iter().all(|tag| memory.tags.contains(tag)));
+    memories.sort_by_key(|memory| std::cmp::Reverse(memory.timestamp));
+    let total = memories.len();
+    memories.truncate(limit);
+    Ok(json!({
+        "memories": memories.iter().map(memory_json).collect::<Vec<_>>(),
+        "total": total,
+    }))
+}
+
+fn memory_json(memory: &Memory) -> Value {
+    let created_at = DateTime::<Utc>::from_timestamp(memory.timestamp as i64, 0).map(|time| time.to_rfc3339());
+    json!({
+        "key": memory.key,
+        "content": memory.value,
+        "tags": memory.tags,
+        "timestamp": memory.timestamp,
+        "created_at": created_at,
+        "source": memory.source,
+    })
+}
+
+// An optional array of strings
+fn string_list(args: &Value, name: &str) -> Result<Option<Vec<String>>, String> {
+    match args.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(Some)
+            .ok_or_else(|| format!("'{}' must be a list of strings", name)),
+        Some(_) => Err(format!("'{}' must be a list of strings", name)),
+    }
+}
+
+fn optional_time(args: &Value, name: &str) -> Result<Option<SystemTime>, String> {
+    match args.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => parse_time(value).map(Some).map_err(|e| format!("Invalid '{}': {}", name, e)),
+    }
+}
+
+fn parse_time(value: &Value) -> Result<SystemTime, String> {
+    let seconds = match value {
+        Value::Number(number) => number.as_i64().ok_or("expected whole Unix seconds")?,
+        Value::String(text) => {
+            let text = text.trim();
+            if let Ok(seconds) = text.parse::<i64>() {
+                seconds
+            } else if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+                time.timestamp()
+            } else if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+                date.and_hms_opt(0, 0, 0).map(|time| time.and_utc().timestamp()).unwrap_or_default()
+            } else {
+                return Err(format!("'{}' is not a timestamp, date or Unix time", text));
+            }
+        }
+        _ => return Err("expected a string or a number".to_string()),
+    };
+    let seconds = u64::try_from(seconds).map_err(|_| "times before 1970 are not supported".to_string())?;
+    Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn parse_window(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (amount, unit) = text.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("'{}' is not a window like '24h' or '7d'", text))?;
+    let unit_seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(format!("Unknown unit in '{}'; use s, m, h, d or w", text)),
+    };
+    Ok(Duration::from_secs(amount.saturating_mul(unit_seconds)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_arguments() {
+        let at = |seconds: u64| UNIX_EPOCH + Duration::from_secs(seconds);
+        assert_eq!(parse_time(&json!(86_400)).unwrap(), at(86_400));
+        assert_eq!(parse_time(&json!("1970-01-02")).unwrap(), at(86_400));
+        assert_eq!(parse_time(&json!("1970-01-02T01:00:00+01:00")).unwrap(), at(86_400));
+        assert!(parse_time(&json!("yesterday")).is_err());
+        assert!(parse_time(&json!(-5)).is_err());
+
+        assert_eq!(parse_window("90m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_window("7d").unwrap(), Duration::from_secs(604_800));
+        assert!(parse_window("7 fortnights").is_err());
+        assert!(parse_window("h").is_err());
+    }
+
+    #[test]
+    fn test_tool_definitions_have_schemas() {
+        for tool in tool_definitions() {
+            assert!(tool.name.starts_with("memory-store-mcp/"));
+            let schema = tool.parameters.expect("every memory tool has a schema");
+            assert_eq!(schema["type"], "object");
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let name = required.as_str().unwrap();
+                assert!(schema["properties"].get(name).is_some(), "{}: {}", tool.name, name);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_memory_replaces_the_stored_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(Some(dir.path().join("memory.lancedb")), None, None)
+            .await
+            .unwrap();
+        handle_tool("store_memory", json!({ "key": "editor", "content": "vim", "tags": ["tools"] }), &store)
+            .await
+            .unwrap();
+        handle_tool("update_memory", json!({ "key": "editor", "content": "helix" }), &store)
+            .await
+            .unwrap();
+
+        let memories = store.get_all_memories().await.unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].value, "helix");
+        assert_eq!(memories[0].tags, vec!["tools"]);
+        assert!(handle_tool("update_memory", json!({ "key": "missing", "content": "x" }), &store)
+            .await
+            .is_err());
+    }
+}
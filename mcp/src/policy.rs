@@ -0,0 +1,480 @@
+// Tool execution policy enforced by mcp-hostd.
+//
+// Rules come from the `[mcp.tool-policy]` section of the unified config. They are
+// checked in order against `server/tool`; the first match decides whether the call
+// is allowed, denied or needs the user's approval, and its argument constraints
+// turn an allowed or asked call into a denial when they are violated. Calls the
+// policy asks about run only with an approval token that mcp-hostd issued when the
+// call was checked.
+
+use gemini_core::config::{ToolArgumentConstraint, ToolPolicyAction, ToolPolicyConfig};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How long an approval token stays valid; longer than clients wait for the user
+const APPROVAL_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Outcome of checking a tool call against the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    Allow,
+    /// The call may run once the user approves it
+    Ask { rationale: Option<String> },
+    /// The call must not run; the rationale is returned to the model
+    Deny { rationale: String },
+}
+
+/// Compiled tool execution policy
+#[derive(Debug, Default)]
+pub struct ToolPolicy {
+    default: ToolPolicyAction,
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    tool: String,
+    action: ToolPolicyAction,
+    rationale: Option<String>,
+    arguments: Vec<CompiledConstraint>,
+}
+
+#[derive(Debug)]
+struct CompiledConstraint {
+    argument: String,
+    path_under: Vec<PathBuf>,
+    matches: Vec<Regex>,
+    optional: bool,
+}
+
+impl ToolPolicy {
+    /// Compile the configured rules, failing on invalid regular expressions
+    pub fn new(config: &ToolPolicyConfig) -> Result<Self, String> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let arguments = rule
+                    .arguments
+                    .iter()
+                    .map(|constraint| compile_constraint(&rule.tool, constraint))
+                    .collect::<Result<_, _>>()?;
+                Ok(CompiledRule {
+                    tool: rule.tool.clone(),
+                    action: rule.action,
+                    rationale: rule.rationale.clone(),
+                    arguments,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            default: config.default,
+            rules,
+        })
+    }
+
+    /// Number of configured rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check a call of `tool` on `server` with the given arguments
+    pub fn evaluate(&self, server: &str, tool: &str, args: &Value) -> PolicyDecision {
+        let qualified_name = format!("{}/{}", server, tool);
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| glob_matches(&rule.tool, &qualified_name))
+        else {
+            return match self.default {
+                ToolPolicyAction::Allow => PolicyDecision::Allow,
+                ToolPolicyAction::Ask => PolicyDecision::Ask { rationale: None },
+                ToolPolicyAction::Deny => PolicyDecision::Deny {
+                    rationale: format!("'{}' is not allowed by the tool policy", qualified_name),
+                },
+            };
+        };
+
+        if rule.action == ToolPolicyAction::Deny {
+            return PolicyDecision::Deny {
+                rationale: rule.rationale.clone().unwrap_or_else(|| {
+                    format!("'{}' is denied by policy rule '{}'", qualified_name, rule.tool)
+                }),
+            };
+        }
+
+        for constraint in &rule.arguments {
+            if let Err(violation) = constraint.check(args) {
+                let rationale = match &rule.rationale {
+                    Some(rationale) => format!("{} ({})", rationale, violation),
+                    None => format!("'{}' violates policy rule '{}': {}", qualified_name, rule.tool, violation),
+                };
+                return PolicyDecision::Deny { rationale };
+            }
+        }
+
+        match rule.action {
+            ToolPolicyAction::Ask => PolicyDecision::Ask {
+                rationale: rule.rationale.clone(),
+            },
+            _ => PolicyDecision::Allow,
+        }
+    }
+}
+
+/// Approvals of calls the policy asks about. A token is issued when a client checks
+/// such a call and can run that call once, with exactly the checked arguments.
+#[derive(Debug, Default)]
+pub struct ApprovalTokens {
+    issued: Mutex<HashMap<String, IssuedApproval>>,
+}
+
+#[derive(Debug)]
+struct IssuedApproval {
+    server: String,
+    tool: String,
+    arguments: Value,
+    issued_at: Instant,
+}
+
+impl ApprovalTokens {
+    /// Issue a token for one call of `tool` on `server` with `arguments`
+    pub fn issue(&self, server: &str, tool: &str, arguments: &Value) -> String {
+        let token = Uuid::new_v4().to_string();
+        let mut issued = self.issued.lock().unwrap_or_else(PoisonError::into_inner);
+        issued.retain(|_, approval| approval.issued_at.elapsed() < APPROVAL_TOKEN_TTL);
+        issued.insert(
+            token.clone(),
+            IssuedApproval {
+                server: server.to_string(),
+                tool: tool.to_string(),
+                arguments: arguments.clone(),
+                issued_at: Instant::now(),
+            },
+        );
+        token
+    }
+
+    /// Use up a token, returning whether it was issued for this call and is still valid
+    pub fn redeem(&self, token: &str, server: &str, tool: &str, arguments: &Value) -> bool {
+        let mut issued = self.issued.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(approval) = issued.remove(token) else {
+            return false;
+        };
+        approval.issued_at.elapsed() < APPROVAL_TOKEN_TTL
+            && approval.server == server
+            && approval.tool == tool
+            && approval.arguments == *arguments
+    }
+}
+
+fn compile_constraint(
+    rule_tool: &str,
+    constraint: &ToolArgumentConstraint,
+) -> Result<CompiledConstraint, String> {
+    let matches = constraint
+        .matches
+        .iter()
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| {
+                format!(
+                    "Invalid pattern '{}' for argument '{}' in policy rule '{}': {}",
+                    pattern, constraint.argument, rule_tool, e
+                )
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let path_under = constraint
+        .path_under
+        .iter()
+        .map(|path| resolve_path(&expand_path(path)))
+        .collect();
+
+    Ok(CompiledConstraint {
+        argument: constraint.argument.clone(),
+        path_under,
+        matches,
+        optional: constraint.optional,
+    })
+}
+
+impl CompiledConstraint {
+    fn check(&self, args: &Value) -> Result<(), String> {
+        let value = self
+            .argument
+            .split('.')
+            .try_fold(args, |value, key| value.get(key));
+        let values: Vec<&Value> = match value {
+            // A missing argument would let the server pick a value nobody checked
+            None | Some(Value::Null) if self.optional => return Ok(()),
+            None | Some(Value::Null) => {
+                return Err(format!("argument '{}' is required", self.argument))
+            }
+            Some(Value::Array(items)) => items.iter().collect(),
+            Some(value) => vec![value],
+        };
+
+        for value in values {
+            let Some(value) = value.as_str() else {
+                return Err(format!("argument '{}' must be a string", self.argument));
+            };
+            if !self.matches.is_empty() && !self.matches.iter().any(|re| re.is_match(value)) {
+                return Err(format!(
+                    "argument '{}' value '{}' is not in the allowed list",
+                    self.argument, value
+                ));
+            }
+            if !self.path_under.is_empty() {
+                let path = expand_path(value);
+                if !path.is_absolute() {
+                    return Err(format!(
+                        "argument '{}' must be an absolute path, got '{}'",
+                        self.argument, value
+                    ));
+                }
+                let path = resolve_path(&path);
+                if !self.path_under.iter().any(|root| path.starts_with(root)) {
+                    return Err(format!(
+                        "argument '{}' path '{}' is outside the allowed directories",
+                        self.argument, value
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// `*` matches any sequence of characters (including `/`), `?` a single character
+pub(crate) fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Expand a leading `~` and `$VAR`/`${VAR}` references
+fn expand_path(path: &str) -> PathBuf {
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => match dirs::home_dir() {
+            Some(home) => format!("{}{}", home.display(), rest),
+            None => path.to_string(),
+        },
+        _ => path.to_string(),
+    };
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path.as_str();
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        match std::env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&rest[start..rest.len() - remainder.len()]),
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
+}
+
+// Remove `.` and `..` components, then resolve symlinks in the longest existing prefix
+fn resolve_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    let mut existing = normalized.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(resolved, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_owned());
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gemini_core::config::ToolPolicyRule;
+    use serde_json::json;
+
+    fn rule(tool: &str, action: ToolPolicyAction) -> ToolPolicyRule {
+        ToolPolicyRule {
+            tool: tool.to_string(),
+            action,
+            rationale: None,
+            arguments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "filesystem/read_file"));
+        assert!(glob_matches("filesystem/*", "filesystem/read_file"));
+        assert!(glob_matches("*/delete_*", "filesystem/delete_file"));
+        assert!(glob_matches("command/execute_?ommand", "command/execute_command"));
+        assert!(!glob_matches("filesystem/*", "command/execute_command"));
+        assert!(!glob_matches("*/delete_*", "filesystem/read_file"));
+    }
+
+    #[test]
+    fn test_policy_rules_and_constraints() {
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("projects");
+        std::fs::create_dir(&allowed).unwrap();
+
+        let mut write_rule = rule("filesystem/*", ToolPolicyAction::Allow);
+        write_rule.rationale = Some("Files are limited to the projects directory".to_string());
+        write_rule.arguments.push(ToolArgumentConstraint {
+            argument: "path".to_string(),
+            path_under: vec![allowed.display().to_string()],
+            matches: Vec::new(),
+            optional: false,
+        });
+        let mut command_rule = rule("command/execute_command", ToolPolicyAction::Ask);
+        command_rule.arguments.push(ToolArgumentConstraint {
+            argument: "command".to_string(),
+            path_under: Vec::new(),
+            matches: vec!["(ls|git status)( .*)?".to_string()],
+            optional: false,
+        });
+        let config = ToolPolicyConfig {
+            default: ToolPolicyAction::Deny,
+            rules: vec![rule("*/delete_*", ToolPolicyAction::Deny), write_rule, command_rule],
+        };
+        let policy = ToolPolicy::new(&config).unwrap();
+
+        let inside = allowed.join("notes.txt").display().to_string();
+        let escape = format!("{}/../secret", allowed.display());
+        assert_eq!(
+            policy.evaluate("filesystem", "read_file", &json!({ "path": inside })),
+            PolicyDecision::Allow
+        );
+        assert!(matches!(
+            policy.evaluate("filesystem", "read_file", &json!({ "path": escape })),
+            PolicyDecision::Deny { rationale } if rationale.starts_with("Files are limited")
+        ));
+        assert!(matches!(
+            policy.evaluate("filesystem", "delete_file", &json!({ "path": inside })),
+            PolicyDecision::Deny { .. }
+        ));
+        assert_eq!(
+            policy.evaluate("command", "execute_command", &json!({ "command": "ls -la" })),
+            PolicyDecision::Ask { rationale: None }
+        );
+        assert!(matches!(
+            policy.evaluate("command", "execute_command", &json!({ "command": "rm -rf /" })),
+            PolicyDecision::Deny { .. }
+        ));
+        assert!(matches!(
+            policy.evaluate("memory", "store", &json!({})),
+            PolicyDecision::Deny { .. }
+        ));
+    }
+
+    #[test]
+    fn test_missing_constrained_argument() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut constraint = ToolArgumentConstraint {
+            argument: "path".to_string(),
+            path_under: vec![dir.path().display().to_string()],
+            matches: Vec::new(),
+            optional: false,
+        };
+        let mut required = rule("filesystem/*", ToolPolicyAction::Allow);
+        required.arguments.push(constraint.clone());
+        constraint.optional = true;
+        let mut optional = rule("search/*", ToolPolicyAction::Allow);
+        optional.arguments.push(constraint);
+        let policy = ToolPolicy::new(&ToolPolicyConfig {
+            default: ToolPolicyAction::Allow,
+            rules: vec![required, optional],
+        })
+        .unwrap();
+
+        for args in [json!({}), json!({ "path": null })] {
+            assert!(matches!(
+                policy.evaluate("filesystem", "read_file", &args),
+                PolicyDecision::Deny { rationale } if rationale.contains("argument 'path' is required")
+            ));
+            assert_eq!(policy.evaluate("search", "grep", &args), PolicyDecision::Allow);
+        }
+    }
+
+    #[test]
+    fn test_approval_tokens() {
+        let tokens = ApprovalTokens::default();
+        let args = json!({ "command": "ls" });
+
+        let token = tokens.issue("shell", "run", &args);
+        assert!(tokens.redeem(&token, "shell", "run", &args));
+        assert!(!tokens.redeem(&token, "shell", "run", &args), "tokens are single-use");
+
+        let token = tokens.issue("shell", "run", &args);
+        assert!(!tokens.redeem(&token, "shell", "run", &json!({ "command": "rm -rf /" })));
+        let token = tokens.issue("shell", "run", &args);
+        assert!(!tokens.redeem(&token, "shell", "exec", &args));
+        assert!(!tokens.redeem("forged", "shell", "run", &args));
+    }
+}
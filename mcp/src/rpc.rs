@@ -84,7 +84,7 @@ pub(crate) struct ServerInfo {
 // `mcp/tool/execute` request parameters
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct ExecuteToolParams {
+pub struct ExecuteToolParams {
     // Workaround: Use "name" on the wire for backward compatibility
     #[serde(rename = "name")]
     pub tool_name: String,
@@ -121,15 +121,6 @@ pub(crate) struct CancelParams {
     pub id: Value, // The request id to cancel
 }
 
-// `resource/get` request parameters
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
-pub(crate) struct GetResourceParams {
-    pub name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub params: Option<Value>, // Optional parameters for resource retrieval
-}
-
 // Helper function to create a log message notification
 pub(crate) fn create_log_notification(message: &str, level: i32) -> Notification {
     let params = Some(json!({
@@ -138,3 +129,8 @@ pub(crate) fn create_log_notification(message: &str, level: i32) -> Notification
     }));
     Notification::new("window/logMessage".to_string(), params)
 }
+
+// Helper function to tell a server that the client's roots changed
+pub(crate) fn create_roots_changed_notification() -> Notification {
+    Notification::new("notifications/roots/list_changed".to_string(), None)
+}
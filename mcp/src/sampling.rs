@@ -0,0 +1,165 @@
+// MCP sampling support: servers asking the host for an LLM completion
+// through `sampling/createMessage`.
+//
+// The host only forwards requests from servers that have a `sampling` policy
+// in mcp_servers.json, clamps `maxTokens` to the policy and tracks a per-server
+// token budget. The completion itself is produced by a pluggable
+// `SamplingHandler`; `GeminiSamplingHandler` uses the Gemini API.
+
+use async_trait::async_trait;
+use gemini_core::client::GeminiClient;
+use gemini_core::rpc_types::ToolContent;
+use gemini_core::types::{Content, GenerateContentRequest, GenerationConfig, Part};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Parameters of a `sampling/createMessage` request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    pub max_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+/// A message in a sampling conversation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SamplingMessage {
+    pub role: String, // "user" or "assistant"
+    pub content: ToolContent,
+}
+
+/// Result of a `sampling/createMessage` request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: String,
+    pub content: ToolContent,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+/// A completion produced by a [`SamplingHandler`]
+#[derive(Debug, Clone)]
+pub struct SamplingOutput {
+    pub result: CreateMessageResult,
+    /// Tokens consumed, if the backend reports them; charged against the server's budget
+    pub tokens_used: Option<u64>,
+}
+
+/// Produces completions for MCP servers that request sampling
+#[async_trait]
+pub trait SamplingHandler: Send + Sync {
+    async fn create_message(
+        &self,
+        server_name: &str,
+        params: CreateMessageParams,
+    ) -> Result<SamplingOutput, String>;
+}
+
+/// Sampling handler backed by the Gemini API
+pub struct GeminiSamplingHandler {
+    client: GeminiClient,
+}
+
+impl GeminiSamplingHandler {
+    pub fn new(client: GeminiClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl SamplingHandler for GeminiSamplingHandler {
+    async fn create_message(
+        &self,
+        server_name: &str,
+        params: CreateMessageParams,
+    ) -> Result<SamplingOutput, String> {
+        let mut contents = Vec::with_capacity(params.messages.len());
+        for message in params.messages {
+            let part = match message.content {
+                ToolContent::Text { text } => Part::text(text),
+                ToolContent::Image { data, mime_type } | ToolContent::Audio { data, mime_type } => {
+                    Part::inline_data(mime_type, data)
+                }
+                other => {
+                    return Err(format!(
+                        "Unsupported content in sampling message: {:?}",
+                        other
+                    ))
+                }
+            };
+            let role = if message.role == "assistant" { "model" } else { "user" };
+            contents.push(Content {
+                parts: vec![part],
+                role: Some(role.to_string()),
+            });
+        }
+
+        let request = GenerateContentRequest {
+            contents,
+            system_instruction: params.system_prompt.map(|prompt| Content {
+                parts: vec![Part::text(prompt)],
+                role: Some("system".to_string()),
+            }),
+            tools: None,
+            generation_config: Some(GenerationConfig {
+                temperature: params.temperature,
+                max_output_tokens: Some(params.max_tokens as i32),
+                stop_sequences: if params.stop_sequences.is_empty() {
+                    None
+                } else {
+                    Some(params.stop_sequences)
+                },
+                ..Default::default()
+            }),
+        };
+
+        debug!("Forwarding sampling request from '{}' to Gemini", server_name);
+        let response = self
+            .client
+            .generate_content(request)
+            .await
+            .map_err(|e| format!("Gemini request failed: {}", e))?;
+        let text = self
+            .client
+            .extract_text_from_response(&response)
+            .map_err(|e| format!("Gemini returned no text: {}", e))?;
+
+        let stop_reason = response
+            .candidates
+            .first()
+            .and_then(|c| c.finish_reason.as_deref())
+            .map(|reason| match reason {
+                "MAX_TOKENS" => "maxTokens".to_string(),
+                _ => "endTurn".to_string(),
+            });
+        let tokens_used = response
+            .usage_metadata
+            .as_ref()
+            .and_then(|u| u.total_token_count.or(u.candidates_token_count));
+
+        Ok(SamplingOutput {
+            result: CreateMessageResult {
+                role: "assistant".to_string(),
+                content: ToolContent::Text { text },
+                model: self.client.model_name().to_string(),
+                stop_reason,
+            },
+            tokens_used,
+        })
+    }
+}
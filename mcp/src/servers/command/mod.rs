@@ -0,0 +1,618 @@
+// Command-execution MCP server.
+//
+// `execute_command` runs an executable with arguments (or a line of `sh` with
+// `shell`), streaming each output line as a progress notification when the client
+// sent a progress token. Output is capped at `max_output_bytes`, and a timeout or
+// a cancelled call kills the command's whole process group. Commands started with
+// `background` return a job ID at once and are followed with `poll_job`,
+// `list_jobs` and `kill_job`. Finished jobs are forgotten once polled, or
+// `FINISHED_JOB_RETENTION` after they end.
+//
+// When an allowlist is configured, only the listed executables may run: bare
+// names are looked up on PATH by the server, paths must match exactly, and `env`
+// may only set the variables in `RESTRICTED_ENV`. Many others (`LD_PRELOAD`,
+// `BASH_ENV`, `PATH`, `GIT_SSH_COMMAND`, `PYTHONSTARTUP`, ...) run code of the
+// caller's choosing inside an allowed command.
+
+mod process;
+
+use super::{error_result, optional_bool, optional_str, optional_u64, required_str, ToolContext, ToolServer};
+use async_trait::async_trait;
+use gemini_core::rpc_types::{Tool, ToolAnnotations, ToolCallResult, ToolContent};
+use log::{debug, info};
+use process::{describe_status, Output, ProcessGroup, Spec};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Default time limit of a command
+pub const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+/// Default cap on the output kept from a command, in bytes
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 100 * 1024;
+
+// Variables `env` may set while commands are restricted: locale, terminal and
+// output settings that do not make a program load or run anything
+const RESTRICTED_ENV: &[&str] = &[
+    "LANG", "LANGUAGE", "LC_ALL", "LC_CTYPE", "LC_MESSAGES", "LC_NUMERIC", "LC_TIME",
+    "TZ", "TERM", "COLUMNS", "LINES", "NO_COLOR", "FORCE_COLOR", "CLICOLOR", "CI",
+    "RUST_BACKTRACE", "RUST_LOG",
+];
+
+// Longest a `poll_job` call waits for a job to finish
+const MAX_POLL_WAIT: Duration = Duration::from_secs(60);
+
+// How long a finished job that is never polled stays listed
+const FINISHED_JOB_RETENTION: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone)]
+pub struct CommandOptions {
+    /// Executables that may run, as bare names or absolute paths; empty allows any
+    pub allowed_commands: Vec<String>,
+    /// Time limit of foreground commands; background jobs only get one if asked
+    pub timeout: Duration,
+    pub max_output_bytes: usize,
+    /// Working directory of commands that do not name one
+    pub working_dir: Option<PathBuf>,
+}
+
+impl Default for CommandOptions {
+    fn default() -> Self {
+        Self {
+            allowed_commands: Vec::new(),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            working_dir: None,
+        }
+    }
+}
+
+pub struct CommandServer {
+    options: CommandOptions,
+    jobs: Arc<Mutex<HashMap<String, Arc<Job>>>>,
+    next_job: AtomicU64,
+}
+
+// A background command
+struct Job {
+    command_line: String,
+    started: Instant,
+    group: ProcessGroup,
+    killed: AtomicBool,
+    // Output not yet returned by `poll_job`, and the final status once known
+    output: Mutex<Output>,
+    finished: watch::Sender<Option<String>>,
+    ended: OnceLock<Instant>,
+}
+
+impl Job {
+    fn status(&self) -> String {
+        match self.finished.borrow().as_ref() {
+            Some(status) => format!("finished ({})", status),
+            None => format!("running for {}s", self.started.elapsed().as_secs()),
+        }
+    }
+}
+
+// A validated `execute_command` call
+struct Invocation {
+    spec: Spec,
+    command_line: String,
+    timeout: Option<Duration>,
+    max_output_bytes: usize,
+}
+
+impl CommandServer {
+    pub fn new(options: CommandOptions) -> Self {
+        Self {
+            options,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            next_job: AtomicU64::new(1),
+        }
+    }
+
+    fn invocation(&self, arguments: &Value) -> Result<Invocation, String> {
+        let command = required_str(arguments, "command")?;
+        let args = match arguments.get("args") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(args)) => args
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or("'args' must be a list of strings")?,
+            Some(_) => return Err("'args' must be a list of strings".to_string()),
+        };
+        let env = match arguments.get("env") {
+            None | Some(Value::Null) => HashMap::new(),
+            Some(Value::Object(env)) => env
+                .iter()
+                .map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+                .collect::<Option<HashMap<_, _>>>()
+                .ok_or("'env' values must be strings")?,
+            Some(_) => return Err("'env' must be an object of strings".to_string()),
+        };
+
+        let cwd = match optional_str(arguments, "cwd").or_else(|| optional_str(arguments, "working_dir")) {
+            Some(cwd) => PathBuf::from(cwd),
+            None => match &self.options.working_dir {
+                Some(dir) => dir.clone(),
+                None => std::env::current_dir().map_err(|e| format!("Cannot determine the working directory: {}", e))?,
+            },
+        };
+        if !cwd.is_dir() {
+            return Err(format!("Working directory {} does not exist", cwd.display()));
+        }
+
+        let (program_name, args, command_line) = if optional_bool(arguments, "shell") {
+            ("sh", vec!["-c".to_string(), command.to_string()], command.to_string())
+        } else {
+            let command_line = std::iter::once(command)
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" ");
+            (command, args, command_line)
+        };
+        let program = resolve_program(program_name, &cwd)?;
+        self.check_allowed(program_name, &program, &env)?;
+
+        let timeout = match optional_u64(arguments, "timeout_secs") {
+            Some(0) => return Err("'timeout_secs' must be positive".to_string()),
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None if optional_bool(arguments, "background") => None,
+            None => Some(self.options.timeout),
+        };
+        let max_output_bytes = optional_u64(arguments, "max_output_bytes")
+            .map_or(self.options.max_output_bytes, |max| (max as usize).min(self.options.max_output_bytes));
+
+        Ok(Invocation {
+            spec: Spec {
+                program,
+                args,
+                cwd,
+                env,
+            },
+            command_line,
+            timeout,
+            max_output_bytes,
+        })
+    }
+
+    fn check_allowed(&self, name: &str, program: &Path, env: &HashMap<String, String>) -> Result<(), String> {
+        let allowed = &self.options.allowed_commands;
+        if allowed.is_empty() {
+            return Ok(());
+        }
+        // A bare name only matches when it was looked up on PATH, so "./git" or
+        // "/tmp/git" do not pass for "git"
+        let canonical = std::fs::canonicalize(program).ok();
+        let permitted = allowed.iter().any(|entry| {
+            if entry.contains('/') {
+                canonical.is_some() && std::fs::canonicalize(entry).ok() == canonical
+            } else {
+                !name.contains('/') && entry == name
+            }
+        });
+        if !permitted {
+            return Err(format!("'{}' is not in the list of allowed commands", name));
+        }
+        if let Some(key) = env.keys().find(|key| !RESTRICTED_ENV.contains(&key.as_str())) {
+            return Err(format!("Setting {} is not allowed while commands are restricted", key));
+        }
+        Ok(())
+    }
+
+    async fn execute(&self, invocation: Invocation, context: &ToolContext) -> ToolCallResult {
+        info!("Running {}", invocation.command_line);
+        let (mut child, group) = match process::spawn(&invocation.spec) {
+            Ok(spawned) => spawned,
+            Err(e) => return error_result(format!("Failed to start {}: {}", invocation.command_line, e)),
+        };
+
+        let mut output = Output::new(invocation.max_output_bytes);
+        let mut lines = 0u64;
+        let run = async {
+            process::read_lines(&mut child, invocation.max_output_bytes, |stream, line| {
+                lines += 1;
+                context.progress(lines as f64, None, Some(&format!("{}: {}", stream.label(), line.trim_end())));
+                output.push(stream, &line);
+            })
+            .await;
+            child.wait().await
+        };
+        let status = match invocation.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, run).await.ok(),
+            None => Some(run.await),
+        };
+
+        let (summary, exit_code, timed_out) = match status {
+            Some(Ok(status)) => {
+                group.disarm();
+                (describe_status(&status), status.code(), false)
+            }
+            Some(Err(e)) => return error_result(format!("Failed to wait for {}: {}", invocation.command_line, e)),
+            None => {
+                group.kill();
+                let _ = child.wait().await;
+                let secs = invocation.timeout.unwrap_or_default().as_secs();
+                (format!("timed out after {}s and was killed", secs), None, true)
+            }
+        };
+        debug!("{} ended: {}", invocation.command_line, summary);
+
+        ToolCallResult {
+            content: vec![ToolContent::Text {
+                text: format!("Command {}\n{}", summary, output.render()),
+            }],
+            structured_content: Some(json!({
+                "exit_code": exit_code,
+                "success": exit_code == Some(0),
+                "timed_out": timed_out,
+                "stdout": output.stdout,
+                "stderr": output.stderr,
+                "truncated_bytes": output.dropped,
+            })),
+            is_error: timed_out,
+        }
+    }
+
+    fn start_job(&self, invocation: Invocation) -> Result<String, String> {
+        let (mut child, group) = process::spawn(&invocation.spec)
+            .map_err(|e| format!("Failed to start {}: {}", invocation.command_line, e))?;
+        let id = format!("job-{}", self.next_job.fetch_add(1, Ordering::Relaxed));
+        info!("Started {} as {}", invocation.command_line, id);
+        let job = Arc::new(Job {
+            command_line: invocation.command_line,
+            started: Instant::now(),
+            group,
+            killed: AtomicBool::new(false),
+            output: Mutex::new(Output::new(invocation.max_output_bytes)),
+            finished: watch::channel(None).0,
+            ended: OnceLock::new(),
+        });
+        {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            prune_jobs(&mut jobs, FINISHED_JOB_RETENTION);
+            jobs.insert(id.clone(), Arc::clone(&job));
+        }
+
+        let timeout = invocation.timeout;
+        let max_line = invocation.max_output_bytes;
+        tokio::spawn(async move {
+            let run = async {
+                process::read_lines(&mut child, max_line, |stream, line| {
+                    if let Ok(mut output) = job.output.lock() {
+                        output.push(stream, &line);
+                    }
+                })
+                .await;
+                child.wait().await
+            };
+            let status = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, run).await.ok(),
+                None => Some(run.await),
+            };
+            let summary = match status {
+                Some(Ok(status)) if job.killed.load(Ordering::Relaxed) => {
+                    format!("killed on request, {}", describe_status(&status))
+                }
+                Some(Ok(status)) => {
+                    job.group.disarm();
+                    describe_status(&status)
+                }
+                Some(Err(e)) => format!("failed to wait for the process: {}", e),
+                None => {
+                    job.group.kill();
+                    let _ = child.wait().await;
+                    format!("timed out after {}s and was killed", timeout.unwrap_or_default().as_secs())
+                }
+            };
+            info!("{} ended: {}", job.command_line, summary);
+            let _ = job.ended.set(Instant::now());
+            job.finished.send_replace(Some(summary));
+        });
+        Ok(id)
+    }
+
+    fn job(&self, arguments: &Value) -> Result<(String, Arc<Job>), String> {
+        let id = required_str(arguments, "job_id")?;
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs.get(id).ok_or_else(|| format!("No job '{}'", id))?;
+        Ok((id.to_string(), Arc::clone(job)))
+    }
+
+    async fn poll_job(&self, arguments: &Value) -> Result<String, String> {
+        let (id, job) = self.job(arguments)?;
+        let wait = Duration::from_secs(optional_u64(arguments, "wait_secs").unwrap_or(0)).min(MAX_POLL_WAIT);
+        if !wait.is_zero() {
+            let mut finished = job.finished.subscribe();
+            let _ = tokio::time::timeout(wait, finished.wait_for(Option::is_some)).await;
+        }
+
+        let status = job.status();
+        let output = job.output.lock().map(|mut output| output.take()).unwrap_or_default();
+        // A finished job is forgotten once its last output has been returned
+        if job.finished.borrow().is_some() {
+            self.jobs.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        }
+        let rendered = output.render();
+        Ok(format!(
+            "{}: {} is {}\n{}",
+            id,
+            job.command_line,
+            status,
+            if rendered.is_empty() { "(no new output)\n" } else { &rendered }
+        ))
+    }
+
+    fn list_jobs(&self) -> String {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        prune_jobs(&mut jobs, FINISHED_JOB_RETENTION);
+        if jobs.is_empty() {
+            return "No background jobs".to_string();
+        }
+        let mut lines: Vec<String> = jobs
+            .iter()
+            .map(|(id, job)| format!("{}: {} ({})", id, job.command_line, job.status()))
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+
+    fn kill_job(&self, arguments: &Value) -> Result<String, String> {
+        let (id, job) = self.job(arguments)?;
+        if job.finished.borrow().is_some() {
+            return Ok(format!("{} has already {}", id, job.status()));
+        }
+        job.killed.store(true, Ordering::Relaxed);
+        job.group.kill();
+        Ok(format!("Killed {} ({}); poll it for its remaining output", id, job.command_line))
+    }
+}
+
+// Forget jobs that finished more than `retention` ago without being polled
+fn prune_jobs(jobs: &mut HashMap<String, Arc<Job>>, retention: Duration) {
+    jobs.retain(|id, job| {
+        let expired = job.ended.get().is_some_and(|ended| ended.elapsed() >= retention);
+        if expired {
+            debug!("Forgetting {}, which finished without being polled", id);
+        }
+        !expired
+    });
+}
+
+// Find the executable a command names: paths are taken relative to the working
+// directory, bare names are looked up on PATH
+fn resolve_program(name: &str, cwd: &Path) -> Result<PathBuf, String> {
+    let is_executable = |path: &Path| {
+        std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    if name.contains('/') {
+        let path = cwd.join(name);
+        return if is_executable(&path) {
+            Ok(path)
+        } else {
+            Err(format!("{} is not an executable file", path.display()))
+        };
+    }
+    std::env::var_os("PATH")
+        .and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(name))
+                .find(|path| is_executable(path))
+        })
+        .ok_or_else(|| format!("Command not found: {}", name))
+}
+
+#[async_trait]
+impl ToolServer for CommandServer {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn instructions(&self) -> Option<String> {
+        let mut instructions = "Commands run without a shell unless `shell` is set. Use `background` for \
+                                long-running commands and follow them with poll_job."
+            .to_string();
+        if !self.options.allowed_commands.is_empty() {
+            instructions.push_str(&format!(
+                " Only these executables may run: {}.",
+                self.options.allowed_commands.join(", ")
+            ));
+        }
+        Some(instructions)
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        let job_id = json!({ "type": "string", "description": "ID returned when the job was started" });
+        vec![
+            Tool {
+                name: "execute_command".to_string(),
+                description: Some(
+                    "Run a command and return its exit code and output. Output lines are streamed as \
+                     progress notifications while it runs."
+                        .to_string(),
+                ),
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "string", "description": "Executable to run, or a shell command line when shell is set" },
+                        "args": { "type": "array", "items": { "type": "string" }, "description": "Arguments of the executable" },
+                        "shell": { "type": "boolean", "description": "Run command with sh -c, allowing pipes and redirections" },
+                        "cwd": { "type": "string", "description": "Working directory" },
+                        "env": { "type": "object", "additionalProperties": { "type": "string" }, "description": "Extra environment variables" },
+                        "timeout_secs": { "type": "integer", "description": format!("Time limit, {}s by default for foreground commands", self.options.timeout.as_secs()) },
+                        "max_output_bytes": { "type": "integer", "description": format!("Output kept, at most {} bytes", self.options.max_output_bytes) },
+                        "background": { "type": "boolean", "description": "Return a job ID at once instead of waiting" },
+                    },
+                    "required": ["command"],
+                })),
+                annotations: None,
+            },
+            Tool {
+                name: "poll_job".to_string(),
+                description: Some("Report a background job's status and the output it produced since the last poll.".to_string()),
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "job_id": job_id,
+                        "wait_secs": { "type": "integer", "description": "Wait up to this long for the job to finish (at most 60)" },
+                    },
+                    "required": ["job_id"],
+                })),
+                annotations: None,
+            },
+            Tool {
+                name: "list_jobs".to_string(),
+                description: Some("List background jobs and their status.".to_string()),
+                parameters: Some(json!({ "type": "object", "properties": {} })),
+                annotations: Some(ToolAnnotations {
+                    read_only_hint: Some(true),
+                    ..Default::default()
+                }),
+            },
+            Tool {
+                name: "kill_job".to_string(),
+                description: Some("Kill a background job and every process it started.".to_string()),
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": { "job_id": job_id },
+                    "required": ["job_id"],
+                })),
+                annotations: None,
+            },
+        ]
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value, context: ToolContext) -> ToolCallResult {
+        let outcome = match name {
+            "execute_command" => match self.invocation(&arguments) {
+                Ok(invocation) if optional_bool(&arguments, "background") => self
+                    .start_job(invocation)
+                    .map(|id| format!("Started {}; check on it with poll_job", id)),
+                Ok(invocation) => return self.execute(invocation, &context).await,
+                Err(e) => Err(e),
+            },
+            "poll_job" => self.poll_job(&arguments).await,
+            "list_jobs" => Ok(self.list_jobs()),
+            "kill_job" => self.kill_job(&arguments),
+            _ => Err(format!("Unknown tool '{}'", name)),
+        };
+        match outcome {
+            Ok(text) => super::text_result(text),
+            Err(e) => error_result(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn context() -> (ToolContext, mpsc::UnboundedReceiver<Value>) {
+        let (outgoing, notifications) = mpsc::unbounded_channel();
+        let roots = watch::channel(Some(Vec::new())).1;
+        (ToolContext::new(outgoing, Some(json!("token")), roots), notifications)
+    }
+
+    #[tokio::test]
+    async fn test_execute_command() {
+        let server = CommandServer::new(CommandOptions {
+            max_output_bytes: 8,
+            ..CommandOptions::default()
+        });
+        let (context, mut notifications) = context();
+        let arguments = json!({ "command": "printf 'one\\ntwo\\nthree\\n'; exit 3", "shell": true });
+        let result = server.call_tool("execute_command", arguments, context.clone()).await;
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["exit_code"], 3);
+        assert_eq!(structured["stdout"], "one\ntwo\n");
+        assert_eq!(structured["truncated_bytes"], 6);
+        let first = notifications.recv().await.unwrap();
+        assert_eq!(first["params"]["message"], "stdout: one");
+
+        // A timeout kills the whole group, including the backgrounded sleep
+        let arguments = json!({ "command": "sleep 30 & echo $!; wait", "shell": true, "timeout_secs": 1 });
+        let result = server.call_tool("execute_command", arguments, context).await;
+        assert!(result.is_error);
+        let pid: i32 = result.structured_content.unwrap()["stdout"].as_str().unwrap().trim().parse().unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Gone, or a zombie waiting for an init process that does not reap
+        let state = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        assert!(state.is_empty() || state.contains(") Z "), "{}", state);
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_and_background_jobs() {
+        let server = CommandServer::new(CommandOptions {
+            allowed_commands: vec!["echo".to_string(), "sleep".to_string()],
+            ..CommandOptions::default()
+        });
+        let (context, _notifications) = context();
+        let denied = server
+            .call_tool("execute_command", json!({ "command": "ls -l", "shell": true }), context.clone())
+            .await;
+        assert!(denied.is_error);
+        assert!(denied.text().contains("not in the list of allowed commands"));
+        for key in ["LD_PRELOAD", "BASH_ENV", "PATH", "GIT_SSH_COMMAND", "PYTHONSTARTUP"] {
+            let arguments = json!({ "command": "echo", "env": { key: "/tmp/x" } });
+            let result = server.call_tool("execute_command", arguments, context.clone()).await;
+            assert!(result.is_error, "{} was accepted", key);
+        }
+        let locale = json!({ "command": "echo", "args": ["hi"], "env": { "LANG": "C" } });
+        assert!(!server.call_tool("execute_command", locale, context.clone()).await.is_error);
+
+        let started = server
+            .call_tool("execute_command", json!({ "command": "sleep", "args": ["30"], "background": true }), context.clone())
+            .await;
+        assert_eq!(started.text(), "Started job-1; check on it with poll_job");
+        assert!(server.list_jobs().contains("job-1: sleep 30 (running"));
+        server.call_tool("kill_job", json!({ "job_id": "job-1" }), context.clone()).await;
+        let polled = server
+            .call_tool("poll_job", json!({ "job_id": "job-1", "wait_secs": 5 }), context.clone())
+            .await;
+        assert!(polled.text().contains("killed on request"), "{}", polled.text());
+        assert_eq!(server.list_jobs(), "No background jobs");
+
+        // Jobs that finish without being polled are forgotten after a while
+        server
+            .call_tool("execute_command", json!({ "command": "echo", "background": true }), context)
+            .await;
+        let (_, job) = server.job(&json!({ "job_id": "job-2" })).unwrap();
+        job.finished.subscribe().wait_for(Option::is_some).await.unwrap();
+        let mut jobs = server.jobs.lock().unwrap();
+        prune_jobs(&mut jobs, Duration::from_secs(60));
+        assert!(jobs.contains_key("job-2"));
+        prune_jobs(&mut jobs, Duration::ZERO);
+        assert!(jobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lines_are_cut_at_the_cap() {
+        let mut reader = Some(tokio::io::BufReader::new(
+            tokio::io::AsyncReadExt::take(tokio::io::repeat(b'x'), 250),
+        ));
+        let mut buffer = Vec::new();
+        let mut lengths = Vec::new();
+        while let Some(line) = process::next_line(&mut reader, &mut buffer, 100).await {
+            lengths.push(line.len());
+        }
+        assert_eq!(lengths, [100, 100, 50]);
+
+        // Output without any newline is kept up to the cap and the rest dropped
+        let server = CommandServer::new(CommandOptions {
+            max_output_bytes: 1024,
+            ..CommandOptions::default()
+        });
+        let arguments = json!({ "command": "head -c 1000000 /dev/zero | tr '\\0' x", "shell": true });
+        let result = server.call_tool("execute_command", arguments, context().0).await;
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["stdout"].as_str().unwrap().len(), 1024);
+        assert_eq!(structured["truncated_bytes"], 1_000_000 - 1024);
+    }
+}
@@ -0,0 +1,223 @@
+// Child processes of the command server.
+//
+// Every command runs in its own process group, so a timeout or a cancelled call
+// kills whatever the command started along with it. The group is killed when its
+// `ProcessGroup` is dropped without being disarmed, which covers tool calls
+// aborted mid-flight.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+
+/// What to run
+#[derive(Debug, Clone)]
+pub(crate) struct Spec {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub env: HashMap<String, String>,
+}
+
+/// Kills a process group when dropped, unless disarmed
+pub(crate) struct ProcessGroup {
+    pgid: i32,
+    armed: AtomicBool,
+}
+
+impl ProcessGroup {
+    /// Kill the group once; it is not signalled again on drop, when its id may
+    /// already belong to another group
+    pub(crate) fn kill(&self) {
+        if !self.armed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        // SAFETY: killpg has no memory-safety preconditions; a group that is
+        // already gone just yields ESRCH
+        unsafe {
+            libc::killpg(self.pgid, libc::SIGKILL);
+        }
+    }
+
+    /// Leave the group running on drop, once its leader has exited normally
+    pub(crate) fn disarm(&self) {
+        self.armed.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Start `spec` as the leader of a new process group, with stdin closed and
+/// stdout and stderr piped
+pub(crate) fn spawn(spec: &Spec) -> io::Result<(Child, ProcessGroup)> {
+    let child = Command::new(&spec.program)
+        .args(&spec.args)
+        .current_dir(&spec.cwd)
+        .envs(&spec.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()?;
+    let pgid = child
+        .id()
+        .ok_or_else(|| io::Error::other("the process exited before it could be tracked"))?;
+    let group = ProcessGroup {
+        pgid: pgid as i32,
+        armed: AtomicBool::new(true),
+    };
+    Ok((child, group))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+/// Pass each line the child writes to `on_line` until both pipes close. Lines longer
+/// than `max_line` bytes are passed on in pieces of that size.
+pub(crate) async fn read_lines(
+    child: &mut Child,
+    max_line: usize,
+    mut on_line: impl FnMut(Stream, String),
+) {
+    let max_line = max_line.max(1);
+    let mut stdout = child.stdout.take().map(BufReader::new);
+    let mut stderr = child.stderr.take().map(BufReader::new);
+    let (mut out_line, mut err_line) = (Vec::new(), Vec::new());
+    while stdout.is_some() || stderr.is_some() {
+        tokio::select! {
+            line = next_line(&mut stdout, &mut out_line, max_line), if stdout.is_some() => match line {
+                Some(line) => on_line(Stream::Stdout, line),
+                None => stdout = None,
+            },
+            line = next_line(&mut stderr, &mut err_line, max_line), if stderr.is_some() => match line {
+                Some(line) => on_line(Stream::Stderr, line),
+                None => stderr = None,
+            },
+        }
+    }
+}
+
+// Partial lines stay in `buffer` if the read is interrupted by the other pipe. The
+// buffer never grows past `max_line`, however much a child writes without a newline.
+pub(crate) async fn next_line<R: AsyncRead + Unpin>(
+    reader: &mut Option<BufReader<R>>,
+    buffer: &mut Vec<u8>,
+    max_line: usize,
+) -> Option<String> {
+    let reader = reader.as_mut()?;
+    loop {
+        let available = reader.fill_buf().await.unwrap_or_default();
+        if available.is_empty() {
+            return (!buffer.is_empty()).then(|| take_line(buffer));
+        }
+        let room = max_line.saturating_sub(buffer.len()).min(available.len());
+        let (used, complete) = match available[..room].iter().position(|&b| b == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (room, buffer.len() + room >= max_line),
+        };
+        buffer.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        if complete {
+            return Some(take_line(buffer));
+        }
+    }
+}
+
+fn take_line(buffer: &mut Vec<u8>) -> String {
+    let line = String::from_utf8_lossy(buffer).into_owned();
+    buffer.clear();
+    line
+}
+
+/// Output kept from a command, bounded by `max_bytes` across both streams
+#[derive(Debug, Default)]
+pub(crate) struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    /// Bytes discarded once the limit was reached
+    pub dropped: usize,
+    max_bytes: usize,
+}
+
+impl Output {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn push(&mut self, stream: Stream, line: &str) {
+        let room = self.max_bytes.saturating_sub(self.stdout.len() + self.stderr.len());
+        let mut keep = line.len().min(room);
+        while !line.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        self.dropped += line.len() - keep;
+        let target = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+        target.push_str(&line[..keep]);
+    }
+
+    /// Hand over what was collected and start again empty
+    pub(crate) fn take(&mut self) -> Output {
+        Output {
+            stdout: std::mem::take(&mut self.stdout),
+            stderr: std::mem::take(&mut self.stderr),
+            dropped: std::mem::take(&mut self.dropped),
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    /// Output as shown to the model
+    pub(crate) fn render(&self) -> String {
+        let mut text = String::new();
+        for (label, content) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !content.is_empty() {
+                text.push_str(&format!("[{}]\n{}", label, content));
+                if !content.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+        }
+        if self.dropped > 0 {
+            text.push_str(&format!(
+                "(output truncated: {} bytes over the {} byte limit were dropped)\n",
+                self.dropped, self.max_bytes
+            ));
+        }
+        text
+    }
+}
+
+/// How a command ended
+pub(crate) fn describe_status(status: &ExitStatus) -> String {
+    use std::os::unix::process::ExitStatusExt;
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit code {}", code),
+        (None, Some(signal)) => format!("killed by signal {}", signal),
+        _ => "unknown exit status".to_string(),
+    }
+}
@@ -0,0 +1,254 @@
+// Confinement of paths to the server's root directories.
+//
+// Paths are resolved with symlinks followed, so a link inside a root that points
+// outside of it is refused. Paths that do not exist yet are resolved through
+// their nearest existing ancestor, and may not climb back out with `..`. Files
+// are opened for writing relative to their root without following symlinks, so
+// a path swapped for a link after it was resolved is refused.
+
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+
+pub(crate) struct Jail {
+    roots: Vec<PathBuf>,
+}
+
+impl Jail {
+    pub(crate) fn new(roots: &[PathBuf]) -> Result<Self, String> {
+        let roots = roots
+            .iter()
+            .map(|root| {
+                fs::canonicalize(root)
+                    .map_err(|e| format!("Root directory {} is not accessible: {}", root.display(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if roots.is_empty() {
+            return Err("No root directories are configured".to_string());
+        }
+        Ok(Self { roots })
+    }
+
+    pub(crate) fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Resolve an existing path, following symlinks
+    pub(crate) fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let requested = self.absolute(path);
+        let resolved = fs::canonicalize(&requested)
+            .map_err(|e| format!("Cannot access {}: {}", requested.display(), e))?;
+        self.check(&requested, resolved)
+    }
+
+    /// Resolve a file that is about to be written, which need not exist yet
+    pub(crate) fn resolve_new(&self, path: &str) -> Result<PathBuf, String> {
+        let requested = self.absolute(path);
+        if fs::symlink_metadata(&requested).is_ok() {
+            return self.resolve(path);
+        }
+
+        // Resolve the nearest existing ancestor and append the missing components
+        let mut existing = requested.as_path();
+        let mut missing = Vec::new();
+        while fs::symlink_metadata(existing).is_err() {
+            let name = existing
+                .file_name()
+                .ok_or_else(|| format!("Cannot resolve {}", requested.display()))?;
+            missing.push(name);
+            existing = existing
+                .parent()
+                .ok_or_else(|| format!("Cannot resolve {}", requested.display()))?;
+        }
+        if requested
+            .strip_prefix(existing)
+            .map(|rest| rest.components().any(|c| !matches!(c, Component::Normal(_))))
+            .unwrap_or(true)
+        {
+            return Err(format!("{} escapes the allowed directories", requested.display()));
+        }
+        let mut resolved = fs::canonicalize(existing)
+            .map_err(|e| format!("Cannot access {}: {}", existing.display(), e))?;
+        resolved.extend(missing.iter().rev());
+        self.check(&requested, resolved)
+    }
+
+    /// Resolve a directory entry itself rather than what a symlink points to, for
+    /// moving and deleting. The roots themselves are refused.
+    pub(crate) fn resolve_entry(&self, path: &str) -> Result<PathBuf, String> {
+        let requested = self.absolute(path);
+        let name = match requested.components().next_back() {
+            Some(Component::Normal(name)) => name.to_owned(),
+            _ => return Err(format!("{} does not name a file", requested.display())),
+        };
+        let parent = requested
+            .parent()
+            .ok_or_else(|| format!("{} does not name a file", requested.display()))?;
+        let parent = fs::canonicalize(parent)
+            .map_err(|e| format!("Cannot access {}: {}", parent.display(), e))?;
+        let resolved = self.check(&requested, parent.join(name))?;
+        if self.roots.contains(&resolved) {
+            return Err(format!("{} is a root directory", resolved.display()));
+        }
+        Ok(resolved)
+    }
+
+    /// Open a resolved path for writing with the `open(2)` `flags` given. The path is
+    /// walked from its root one directory at a time without following symlinks, so
+    /// a file or directory swapped for a link after it was resolved is refused
+    /// before anything is created.
+    pub(crate) fn open_resolved(&self, path: &Path, flags: libc::c_int) -> Result<fs::File, String> {
+        let root = self
+            .roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.as_os_str().len())
+            .ok_or_else(|| format!("{} is outside the allowed directories", path.display()))?;
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let mut names = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => names.push(
+                    CString::new(name.as_bytes())
+                        .map_err(|_| format!("{} contains a NUL byte", path.display()))?,
+                ),
+                _ => return Err(format!("{} is not a resolved path", path.display())),
+            }
+        }
+        let Some(file_name) = names.pop() else {
+            return Err(format!("{} does not name a file", path.display()));
+        };
+
+        let open_error = |e: io::Error| match e.raw_os_error() {
+            Some(libc::ELOOP) | Some(libc::ENOTDIR) => {
+                format!("{} changed to a symlink while it was being opened", path.display())
+            }
+            _ => format!("Cannot open {}: {}", path.display(), e),
+        };
+        let root_name = CString::new(root.as_os_str().as_bytes())
+            .map_err(|_| format!("{} contains a NUL byte", root.display()))?;
+        let directory_flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        // SAFETY: the name is a valid NUL-terminated string
+        let mut directory = owned_fd(unsafe { libc::open(root_name.as_ptr(), directory_flags) })
+            .map_err(open_error)?;
+        for name in &names {
+            // SAFETY: the directory fd is open and the name is NUL-terminated
+            directory = owned_fd(unsafe { libc::openat(directory.as_raw_fd(), name.as_ptr(), directory_flags) })
+                .map_err(open_error)?;
+        }
+        // SAFETY: as above; the mode is only used when O_CREAT creates the file
+        let file = owned_fd(unsafe {
+            libc::openat(
+                directory.as_raw_fd(),
+                file_name.as_ptr(),
+                flags | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+                0o666 as libc::c_uint,
+            )
+        })
+        .map_err(open_error)?;
+        Ok(fs::File::from(file))
+    }
+
+    /// Whether a resolved path is inside one of the roots
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// Path relative to the root containing it, for display
+    pub(crate) fn display(&self, path: &Path) -> String {
+        let relative = self
+            .roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .filter(|_| self.roots.len() == 1);
+        match relative {
+            Some(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+            Some(relative) => relative.display().to_string(),
+            None => path.display().to_string(),
+        }
+    }
+
+    // Relative paths are taken from the first root
+    fn absolute(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.roots[0].join(path)
+        }
+    }
+
+    fn check(&self, requested: &Path, resolved: PathBuf) -> Result<PathBuf, String> {
+        if self.contains(&resolved) {
+            Ok(resolved)
+        } else {
+            Err(format!("{} is outside the allowed directories", requested.display()))
+        }
+    }
+}
+
+// Take ownership of a descriptor returned by open(2), or the error it reported
+fn owned_fd(fd: libc::c_int) -> io::Result<OwnedFd> {
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        // SAFETY: the descriptor was just opened and nothing else owns it
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jail_confines_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("sub/file.txt"), "x").unwrap();
+        fs::write(outside.join("secret.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+
+        let jail = Jail::new(std::slice::from_ref(&root)).unwrap();
+        let root = fs::canonicalize(&root).unwrap();
+        assert_eq!(jail.resolve("sub/file.txt").unwrap(), root.join("sub/file.txt"));
+        assert!(jail.resolve("../outside/secret.txt").is_err());
+        assert!(jail.resolve("escape/secret.txt").is_err());
+        assert!(jail.resolve(outside.join("secret.txt").to_str().unwrap()).is_err());
+
+        assert_eq!(jail.resolve_new("sub/new/deep.txt").unwrap(), root.join("sub/new/deep.txt"));
+        assert!(jail.resolve_new("sub/new/../../../outside/x").is_err());
+        assert!(jail.resolve_new("escape/new.txt").is_err());
+
+        // The link itself may be removed, but not the root
+        assert_eq!(jail.resolve_entry("escape").unwrap(), root.join("escape"));
+        assert!(jail.resolve_entry(root.to_str().unwrap()).is_err());
+        assert!(jail.resolve_entry("..").is_err());
+
+        // A file swapped for a link after it was resolved is not written through
+        let flags = libc::O_WRONLY | libc::O_CREAT;
+        let resolved = jail.resolve_new("sub/swapped.txt").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), &resolved).unwrap();
+        assert!(jail.open_resolved(&resolved, flags).is_err());
+        assert_eq!(fs::read_to_string(outside.join("secret.txt")).unwrap(), "x");
+        assert!(jail.open_resolved(&root.join("sub/file.txt"), flags).is_ok());
+
+        // Nor is a file created in a directory swapped for a link
+        fs::create_dir(root.join("moved")).unwrap();
+        let resolved = jail.resolve_new("moved/new.txt").unwrap();
+        fs::remove_dir(root.join("moved")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("moved")).unwrap();
+        assert!(jail.open_resolved(&resolved, flags).is_err());
+        assert!(!outside.join("new.txt").exists());
+
+        let created = jail.open_resolved(&root.join("sub/created.txt"), flags);
+        assert!(created.is_ok());
+        assert!(root.join("sub/created.txt").is_file());
+    }
+}
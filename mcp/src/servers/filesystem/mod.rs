@@ -0,0 +1,594 @@
+// Filesystem MCP server.
+//
+// Every path is confined to the server's root directories (see `jail`): the
+// `--root` directories, else the roots listed by the client, else the working
+// directory. Reads and writes are limited to `max_file_size` bytes, and in
+// read-only mode the tools that modify files are neither listed nor callable.
+
+mod jail;
+mod search;
+
+use super::{
+    error_result, optional_bool, optional_str, optional_u64, required_str, text_result,
+    ToolContext, ToolServer,
+};
+use async_trait::async_trait;
+use gemini_core::rpc_types::{Tool, ToolAnnotations, ToolCallResult};
+use jail::Jail;
+use regex::RegexBuilder;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+
+/// Default limit on the size of files read or written, in bytes
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+// Cap on the entries returned by `list_directory` and `glob`
+const MAX_LISTED_ENTRIES: usize = 1000;
+
+const DEFAULT_MAX_GREP_RESULTS: u64 = 200;
+
+const WRITE_TOOLS: &[&str] = &["write_file", "edit_file", "move_file", "delete_file"];
+
+#[derive(Debug, Clone)]
+pub struct FilesystemOptions {
+    /// Directories the tools may access; when empty, the client's roots are used
+    pub roots: Vec<PathBuf>,
+    pub read_only: bool,
+    pub max_file_size: u64,
+}
+
+impl Default for FilesystemOptions {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            read_only: false,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        }
+    }
+}
+
+pub struct FilesystemServer {
+    options: FilesystemOptions,
+}
+
+impl FilesystemServer {
+    /// Fails if a configured root is not an accessible directory
+    pub fn new(options: FilesystemOptions) -> Result<Self, String> {
+        for root in &options.roots {
+            if !root.is_dir() {
+                return Err(format!("Root {} is not a directory", root.display()));
+            }
+        }
+        Ok(Self { options })
+    }
+
+    async fn jail(&self, context: &ToolContext) -> Result<Jail, String> {
+        if !self.options.roots.is_empty() {
+            return Jail::new(&self.options.roots);
+        }
+        let client_roots = context.client_roots().await;
+        if !client_roots.is_empty() {
+            return Jail::new(&client_roots);
+        }
+        let cwd = std::env::current_dir()
+            .map_err(|e| format!("Cannot determine the working directory: {}", e))?;
+        Jail::new(&[cwd])
+    }
+
+    fn run(&self, name: &str, arguments: &Value, jail: &Jail) -> Result<String, String> {
+        if self.options.read_only && WRITE_TOOLS.contains(&name) {
+            return Err(format!("'{}' is not available: the server is read-only", name));
+        }
+        match name {
+            "list_roots" => Ok(jail
+                .roots()
+                .iter()
+                .map(|root| root.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "list_directory" => list_directory(jail, optional_str(arguments, "path").unwrap_or(".")),
+            "read_file" => self.read_file(jail, arguments),
+            "write_file" => self.write_file(jail, arguments),
+            "edit_file" => self.edit_file(jail, arguments),
+            "move_file" => move_file(jail, arguments),
+            "delete_file" => delete_file(jail, arguments),
+            "glob" => glob(jail, arguments),
+            "grep" => self.grep(jail, arguments),
+            _ => Err(format!("Unknown tool '{}'", name)),
+        }
+    }
+
+    fn read_file(&self, jail: &Jail, arguments: &Value) -> Result<String, String> {
+        let path = jail.resolve(required_str(arguments, "path")?)?;
+        let offset = optional_u64(arguments, "offset").map(|o| o.max(1) as usize);
+        let limit = optional_u64(arguments, "limit").map(|l| l as usize);
+        let size = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+        // Line ranges are read line by line, so only whole reads are bounded by size
+        if offset.is_none() && limit.is_none() && size > self.options.max_file_size {
+            return Err(format!(
+                "{} is {} bytes, over the {} byte limit; read a range with offset and limit",
+                jail.display(&path),
+                size,
+                self.options.max_file_size
+            ));
+        }
+        if search::is_binary(&path) {
+            return Err(format!("{} is a binary file", jail.display(&path)));
+        }
+        let text_error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::InvalidData => format!("{} is not valid UTF-8 text", jail.display(&path)),
+            _ => format!("Cannot read {}: {}", jail.display(&path), e),
+        };
+        if offset.is_none() && limit.is_none() {
+            return fs::read_to_string(&path).map_err(text_error);
+        }
+
+        let mut reader = BufReader::new(fs::File::open(&path).map_err(text_error)?);
+        for _ in 1..offset.unwrap_or(1) {
+            if reader.skip_until(b'\n').map_err(text_error)? == 0 {
+                break;
+            }
+        }
+        // Lines are read at most up to the size limit, so one without a newline in a
+        // large file is not loaded whole
+        let mut selected = Vec::new();
+        for _ in 0..limit.unwrap_or(usize::MAX) {
+            let remaining = self.options.max_file_size.saturating_sub(selected.len() as u64);
+            let mut line = Vec::new();
+            let read = (&mut reader)
+                .take(remaining + 1)
+                .read_until(b'\n', &mut line)
+                .map_err(text_error)?;
+            if read == 0 || read as u64 > remaining {
+                break;
+            }
+            if line.ends_with(b"\n") {
+                line.pop();
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+            }
+            if selected.len() + line.len() + 1 > self.options.max_file_size as usize {
+                break;
+            }
+            selected.extend_from_slice(&line);
+            selected.push(b'\n');
+        }
+        String::from_utf8(selected).map_err(|_| format!("{} is not valid UTF-8 text", jail.display(&path)))
+    }
+
+    fn write_file(&self, jail: &Jail, arguments: &Value) -> Result<String, String> {
+        let path = jail.resolve_new(required_str(arguments, "path")?)?;
+        let content = required_str(arguments, "content")?;
+        let append = optional_bool(arguments, "append");
+        let existing = if append {
+            fs::metadata(&path).map(|m| m.len()).unwrap_or(0)
+        } else {
+            0
+        };
+        if existing + content.len() as u64 > self.options.max_file_size {
+            return Err(format!(
+                "Writing {} would exceed the {} byte limit",
+                jail.display(&path),
+                self.options.max_file_size
+            ));
+        }
+        if path.is_dir() {
+            return Err(format!("{} is a directory", jail.display(&path)));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+        }
+        let flags = libc::O_WRONLY | libc::O_CREAT | if append { libc::O_APPEND } else { 0 };
+        let mut file = jail.open_resolved(&path, flags)?;
+        // Truncated only once the opened file is known to be inside the roots
+        let written = if append {
+            file.write_all(content.as_bytes())
+        } else {
+            file.set_len(0).and_then(|_| file.write_all(content.as_bytes()))
+        };
+        written.map_err(|e| format!("Cannot write {}: {}", jail.display(&path), e))?;
+        Ok(format!(
+            "{} {} bytes to {}",
+            if append { "Appended" } else { "Wrote" },
+            content.len(),
+            jail.display(&path)
+        ))
+    }
+
+    fn edit_file(&self, jail: &Jail, arguments: &Value) -> Result<String, String> {
+        let path = jail.resolve(required_str(arguments, "path")?)?;
+        let size = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+        if size > self.options.max_file_size {
+            return Err(format!(
+                "{} is over the {} byte limit",
+                jail.display(&path),
+                self.options.max_file_size
+            ));
+        }
+        let original = fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read {}: {}", jail.display(&path), e))?;
+
+        let edits = match arguments.get("edits").and_then(Value::as_array) {
+            Some(edits) => edits.clone(),
+            None => vec![arguments.clone()],
+        };
+        let mut updated = original.clone();
+        for (index, edit) in edits.iter().enumerate() {
+            let old_text = required_str(edit, "old_text")?;
+            let new_text = required_str(edit, "new_text")?;
+            if old_text.is_empty() {
+                return Err(format!("Edit {}: old_text is empty", index + 1));
+            }
+            let occurrences = updated.matches(old_text).count();
+            updated = match occurrences {
+                0 => return Err(format!("Edit {}: old_text was not found", index + 1)),
+                1 => updated.replacen(old_text, new_text, 1),
+                _ if optional_bool(edit, "replace_all") => updated.replace(old_text, new_text),
+                n => {
+                    return Err(format!(
+                        "Edit {}: old_text occurs {} times; add context to make it unique or set replace_all",
+                        index + 1,
+                        n
+                    ))
+                }
+            };
+        }
+        if updated.len() as u64 > self.options.max_file_size {
+            return Err(format!("The edited file would exceed the {} byte limit", self.options.max_file_size));
+        }
+
+        let diff = diffy::create_patch(&original, &updated).to_string();
+        if optional_bool(arguments, "dry_run") {
+            return Ok(format!("Dry run, {} was not changed:\n{}", jail.display(&path), diff));
+        }
+        let mut file = jail.open_resolved(&path, libc::O_WRONLY)?;
+        file.set_len(0)
+            .and_then(|_| file.write_all(updated.as_bytes()))
+            .map_err(|e| format!("Cannot write {}: {}", jail.display(&path), e))?;
+        Ok(format!("Edited {}:\n{}", jail.display(&path), diff))
+    }
+
+    fn grep(&self, jail: &Jail, arguments: &Value) -> Result<String, String> {
+        let regex = RegexBuilder::new(required_str(arguments, "pattern")?)
+            .case_insensitive(optional_bool(arguments, "case_insensitive"))
+            .build()
+            .map_err(|e| format!("Invalid pattern: {}", e))?;
+        let base = jail.resolve(optional_str(arguments, "path").unwrap_or("."))?;
+        let options = search::GrepOptions {
+            regex: &regex,
+            include: optional_str(arguments, "include"),
+            max_file_size: self.options.max_file_size,
+            max_results: optional_u64(arguments, "max_results").unwrap_or(DEFAULT_MAX_GREP_RESULTS) as usize,
+        };
+        let (matches, truncated) = search::grep(jail, &base, &options);
+        if matches.is_empty() {
+            return Ok("No matches".to_string());
+        }
+        let mut output: Vec<String> = matches
+            .iter()
+            .map(|m| format!("{}:{}: {}", jail.display(&m.path), m.line_number, m.line))
+            .collect();
+        if truncated {
+            output.push(format!("(stopped after {} matches)", matches.len()));
+        }
+        Ok(output.join("\n"))
+    }
+}
+
+fn list_directory(jail: &Jail, path: &str) -> Result<String, String> {
+    let dir = jail.resolve(path)?;
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Cannot list {}: {}", jail.display(&dir), e))?;
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut lines: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let kind = match entry.file_type() {
+                Ok(t) if t.is_symlink() => "[link]",
+                Ok(t) if t.is_dir() => "[dir] ",
+                _ => "[file]",
+            };
+            format!("{} {}", kind, entry.file_name().to_string_lossy())
+        })
+        .collect();
+    if lines.is_empty() {
+        return Ok(format!("{} is empty", jail.display(&dir)));
+    }
+    let total = lines.len();
+    lines.truncate(MAX_LISTED_ENTRIES);
+    if total > MAX_LISTED_ENTRIES {
+        lines.push(format!("({} more entries)", total - MAX_LISTED_ENTRIES));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn move_file(jail: &Jail, arguments: &Value) -> Result<String, String> {
+    let source = jail.resolve_entry(required_str(arguments, "source")?)?;
+    let destination = jail.resolve_new(required_str(arguments, "destination")?)?;
+    if fs::symlink_metadata(&destination).is_ok() && !optional_bool(arguments, "overwrite") {
+        return Err(format!(
+            "{} already exists; set overwrite to replace it",
+            jail.display(&destination)
+        ));
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    fs::rename(&source, &destination).map_err(|e| {
+        format!(
+            "Cannot move {} to {}: {}",
+            jail.display(&source),
+            jail.display(&destination),
+            e
+        )
+    })?;
+    Ok(format!("Moved {} to {}", jail.display(&source), jail.display(&destination)))
+}
+
+fn delete_file(jail: &Jail, arguments: &Value) -> Result<String, String> {
+    let path = jail.resolve_entry(required_str(arguments, "path")?)?;
+    let metadata = fs::symlink_metadata(&path).map_err(|e| format!("Cannot access {}: {}", jail.display(&path), e))?;
+    let removed = if metadata.is_dir() {
+        if optional_bool(arguments, "recursive") {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_dir(&path)
+        }
+    } else {
+        fs::remove_file(&path)
+    };
+    removed.map_err(|e| format!("Cannot delete {}: {}", jail.display(&path), e))?;
+    Ok(format!("Deleted {}", jail.display(&path)))
+}
+
+fn glob(jail: &Jail, arguments: &Value) -> Result<String, String> {
+    let pattern = required_str(arguments, "pattern")?;
+    let base = jail.resolve(optional_str(arguments, "path").unwrap_or("."))?;
+    let (matches, truncated) = search::glob(jail, &base, pattern, MAX_LISTED_ENTRIES);
+    if matches.is_empty() {
+        return Ok("No files matched".to_string());
+    }
+    let mut lines: Vec<String> = matches.iter().map(|path| jail.display(path)).collect();
+    if truncated {
+        lines.push(format!("(stopped after {} files)", MAX_LISTED_ENTRIES));
+    }
+    Ok(lines.join("\n"))
+}
+
+#[async_trait]
+impl ToolServer for FilesystemServer {
+    fn name(&self) -> &'static str {
+        "filesystem"
+    }
+
+    fn instructions(&self) -> Option<String> {
+        let mut instructions = "Paths are relative to the first root directory (see list_roots); \
+                                access outside the roots is refused."
+            .to_string();
+        if self.options.read_only {
+            instructions.push_str(" The server is read-only.");
+        }
+        Some(instructions)
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        let tool = |name: &str, description: &str, parameters: Value| Tool {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            parameters: Some(parameters),
+            annotations: Some(ToolAnnotations {
+                read_only_hint: Some(!WRITE_TOOLS.contains(&name)),
+                ..Default::default()
+            }),
+        };
+        let path_arg = |description: &str| json!({ "type": "string", "description": description });
+
+        let mut tools = vec![
+            tool(
+                "list_roots",
+                "List the directories this server may access.",
+                json!({ "type": "object", "properties": {} }),
+            ),
+            tool(
+                "list_directory",
+                "List the entries of a directory, marked as [dir], [file] or [link].",
+                json!({
+                    "type": "object",
+                    "properties": { "path": path_arg("Directory to list, the first root by default") },
+                }),
+            ),
+            tool(
+                "read_file",
+                "Read a UTF-8 text file, optionally only a range of lines.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": path_arg("File to read"),
+                        "offset": { "type": "integer", "description": "First line to read, starting at 1" },
+                        "limit": { "type": "integer", "description": "Maximum number of lines to read" },
+                    },
+                    "required": ["path"],
+                }),
+            ),
+            tool(
+                "write_file",
+                "Create or overwrite a file, creating missing parent directories.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": path_arg("File to write"),
+                        "content": { "type": "string", "description": "New content of the file" },
+                        "append": { "type": "boolean", "description": "Append to the file instead of replacing it" },
+                    },
+                    "required": ["path", "content"],
+                }),
+            ),
+            tool(
+                "edit_file",
+                "Replace exact text in a file and return a unified diff of the change. Each old_text \
+                 must occur exactly once unless replace_all is set.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": path_arg("File to edit"),
+                        "edits": {
+                            "type": "array",
+                            "description": "Replacements, applied in order",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "old_text": { "type": "string", "description": "Text to replace" },
+                                    "new_text": { "type": "string", "description": "Replacement text" },
+                                    "replace_all": { "type": "boolean", "description": "Replace every occurrence" },
+                                },
+                                "required": ["old_text", "new_text"],
+                            },
+                        },
+                        "dry_run": { "type": "boolean", "description": "Only return the diff" },
+                    },
+                    "required": ["path", "edits"],
+                }),
+            ),
+            tool(
+                "move_file",
+                "Move or rename a file or directory.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "source": path_arg("Path to move"),
+                        "destination": path_arg("New path"),
+                        "overwrite": { "type": "boolean", "description": "Replace an existing destination" },
+                    },
+                    "required": ["source", "destination"],
+                }),
+            ),
+            tool(
+                "delete_file",
+                "Delete a file, or a directory (non-empty ones only when recursive is set).",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": path_arg("Path to delete"),
+                        "recursive": { "type": "boolean", "description": "Delete a directory with its contents" },
+                    },
+                    "required": ["path"],
+                }),
+            ),
+            tool(
+                "glob",
+                "Find files by path pattern. '*' and '?' match within a path segment and '**' \
+                 matches any number of directories, e.g. 'src/**/*.rs'.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": { "type": "string", "description": "Pattern relative to path" },
+                        "path": path_arg("Directory to search, the first root by default"),
+                    },
+                    "required": ["pattern"],
+                }),
+            ),
+            tool(
+                "grep",
+                "Search file contents with a regular expression. Prints 'path:line: text' for each match.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": { "type": "string", "description": "Regular expression" },
+                        "path": path_arg("File or directory to search, the first root by default"),
+                        "include": { "type": "string", "description": "Only search files whose name matches this glob, e.g. '*.rs'" },
+                        "case_insensitive": { "type": "boolean" },
+                        "max_results": { "type": "integer", "description": "Maximum number of matching lines" },
+                    },
+                    "required": ["pattern"],
+                }),
+            ),
+        ];
+        if self.options.read_only {
+            tools.retain(|tool| !WRITE_TOOLS.contains(&tool.name.as_str()));
+        }
+        tools
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value, context: ToolContext) -> ToolCallResult {
+        let jail = match self.jail(&context).await {
+            Ok(jail) => jail,
+            Err(e) => return error_result(e),
+        };
+        // The blocking task needs a server it owns
+        let server = Self {
+            options: self.options.clone(),
+        };
+        let name = name.to_string();
+        let outcome = tokio::task::spawn_blocking(move || server.run(&name, &arguments, &jail)).await;
+        match outcome {
+            Ok(Ok(text)) => text_result(text),
+            Ok(Err(e)) => error_result(e),
+            Err(e) => error_result(format!("Tool failed: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn server(root: &Path, read_only: bool) -> (FilesystemServer, Jail) {
+        let options = FilesystemOptions {
+            roots: vec![root.to_path_buf()],
+            read_only,
+            max_file_size: 64,
+        };
+        (FilesystemServer::new(options).unwrap(), Jail::new(&[root.to_path_buf()]).unwrap())
+    }
+
+    #[test]
+    fn test_edit_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "one\ntwo\ntwo\n").unwrap();
+        let (server, jail) = server(dir.path(), false);
+
+        let ambiguous = json!({ "path": "a.txt", "old_text": "two", "new_text": "2" });
+        assert!(server.run("edit_file", &ambiguous, &jail).unwrap_err().contains("occurs 2 times"));
+
+        let edits = json!({
+            "path": "a.txt",
+            "edits": [
+                { "old_text": "one", "new_text": "1" },
+                { "old_text": "two", "new_text": "2", "replace_all": true },
+            ],
+        });
+        let diff = server.run("edit_file", &edits, &jail).unwrap();
+        assert!(diff.contains("-one\n") && diff.contains("+1\n"));
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "1\n2\n2\n");
+
+        let oversized = json!({ "path": "b.txt", "content": "x".repeat(65) });
+        assert!(server.run("write_file", &oversized, &jail).unwrap_err().contains("limit"));
+
+        // Line ranges stop at the size limit, even within a line
+        fs::write(dir.path().join("long.txt"), format!("one\r\ntwo\n{}", "x".repeat(1000))).unwrap();
+        let range = |offset: u64, limit: u64| json!({ "path": "long.txt", "offset": offset, "limit": limit });
+        assert_eq!(server.run("read_file", &range(1, 2), &jail).unwrap(), "one\ntwo\n");
+        assert_eq!(server.run("read_file", &range(2, 5), &jail).unwrap(), "two\n");
+        assert_eq!(server.run("read_file", &range(3, 1), &jail).unwrap(), "");
+    }
+
+    #[test]
+    fn test_read_only_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hello\n").unwrap();
+        let (server, jail) = server(dir.path(), true);
+
+        let names: Vec<String> = server.tools().into_iter().map(|tool| tool.name).collect();
+        assert!(names.contains(&"read_file".to_string()));
+        assert!(!names.iter().any(|name| WRITE_TOOLS.contains(&name.as_str())));
+
+        assert_eq!(server.run("read_file", &json!({ "path": "a.txt" }), &jail).unwrap(), "hello\n");
+        let delete = json!({ "path": "a.txt" });
+        assert!(server.run("delete_file", &delete, &jail).unwrap_err().contains("read-only"));
+        assert!(dir.path().join("a.txt").exists());
+    }
+}
@@ -0,0 +1,219 @@
+// Directory walks for the `glob` and `grep` tools.
+//
+// Walks stay inside the jail: symlinked directories are not descended into, and
+// symlinked files are only reported when their target is inside a root. `.git`
+// directories are skipped.
+
+use super::jail::Jail;
+use crate::policy::glob_matches;
+use regex::Regex;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Files under `base` whose path relative to it matches `pattern`. `*` and `?`
+/// match within one path segment and `**` matches any number of segments.
+/// Returns the matches and whether the list was cut at `limit`.
+pub(crate) fn glob(jail: &Jail, base: &Path, pattern: &str, limit: usize) -> (Vec<PathBuf>, bool) {
+    let segments: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty() && *s != ".").collect();
+    let max_depth = if segments.contains(&"**") {
+        usize::MAX
+    } else {
+        segments.len()
+    };
+
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    walk(jail, base, max_depth, &mut |path| {
+        let Ok(relative) = path.strip_prefix(base) else {
+            return true;
+        };
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+        if path_matches(&segments, &parts) {
+            if matches.len() == limit {
+                truncated = true;
+                return false;
+            }
+            matches.push(path.to_path_buf());
+        }
+        true
+    });
+    matches.sort();
+    (matches, truncated)
+}
+
+/// Search options of the `grep` tool
+pub(crate) struct GrepOptions<'a> {
+    pub regex: &'a Regex,
+    /// Glob over file names
+    pub include: Option<&'a str>,
+    pub max_file_size: u64,
+    pub max_results: usize,
+}
+
+/// A matching line
+pub(crate) struct GrepMatch {
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub line: String,
+}
+
+/// Lines matching the regex in `base` (a file, or every file below a directory).
+/// Files over the size limit and binary files are skipped. Files are searched as
+/// the walk finds them, so it stops as soon as the result limit is reached.
+/// Returns the matches and whether the list was cut at the result limit.
+pub(crate) fn grep(jail: &Jail, base: &Path, options: &GrepOptions) -> (Vec<GrepMatch>, bool) {
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    if base.is_file() {
+        truncated = !grep_file(base, options, &mut matches);
+    } else {
+        walk(jail, base, usize::MAX, &mut |path| {
+            let included = options.include.is_none_or(|include| {
+                path.file_name()
+                    .is_some_and(|name| glob_matches(include, &name.to_string_lossy()))
+            });
+            if included && !grep_file(path, options, &mut matches) {
+                truncated = true;
+                return false;
+            }
+            true
+        });
+    }
+    (matches, truncated)
+}
+
+// Add the matching lines of one file; false once the result limit is exceeded
+fn grep_file(file: &Path, options: &GrepOptions, matches: &mut Vec<GrepMatch>) -> bool {
+    let too_large = fs::metadata(file).map_or(true, |m| m.len() > options.max_file_size);
+    if too_large || is_binary(file) {
+        return true;
+    }
+    let Ok(handle) = fs::File::open(file) else {
+        return true;
+    };
+    for (index, line) in BufReader::new(handle).lines().enumerate() {
+        let Ok(line) = line else {
+            break;
+        };
+        if options.regex.is_match(&line) {
+            if matches.len() == options.max_results {
+                return false;
+            }
+            matches.push(GrepMatch {
+                path: file.to_path_buf(),
+                line_number: index + 1,
+                line,
+            });
+        }
+    }
+    true
+}
+
+/// Whether a file looks binary: a NUL byte in its first 8 KiB
+pub(crate) fn is_binary(path: &Path) -> bool {
+    let mut head = [0u8; 8192];
+    match fs::File::open(path).and_then(|mut file| file.read(&mut head)) {
+        Ok(read) => head[..read].contains(&0),
+        Err(_) => true,
+    }
+}
+
+// Call `visit` with every file below `dir`, up to `max_depth` levels deep, until
+// it returns false
+fn walk(jail: &Jail, dir: &Path, max_depth: usize, visit: &mut dyn FnMut(&Path) -> bool) -> bool {
+    if max_depth == 0 {
+        return true;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return true;
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" && !walk(jail, &path, max_depth - 1, visit) {
+                return false;
+            }
+        } else if file_type.is_symlink() {
+            let target_inside = fs::canonicalize(&path).is_ok_and(|target| jail.contains(&target));
+            if target_inside && path.is_file() && !visit(&path) {
+                return false;
+            }
+        } else if !visit(&path) {
+            return false;
+        }
+    }
+    true
+}
+
+fn path_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| path_matches(rest, &path[skip..])),
+        Some((segment, rest)) => {
+            !path.is_empty() && glob_matches(segment, path[0]) && path_matches(rest, &path[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_and_grep() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn helper() {}\n// TODO: more\n").unwrap();
+        fs::write(root.join("src/nested/deep.rs"), "// todo later\n").unwrap();
+        fs::write(root.join("src/notes.txt"), "TODO: docs\n").unwrap();
+        fs::write(root.join(".git/config.rs"), "// TODO\n").unwrap();
+        fs::write(root.join("src/blob.rs"), b"TODO\0binary").unwrap();
+        let jail = Jail::new(std::slice::from_ref(&root)).unwrap();
+
+        let (all, truncated) = glob(&jail, &root, "**/*.rs", 100);
+        let names: Vec<_> = all.iter().map(|p| jail.display(p)).collect();
+        assert_eq!(names, ["main.rs", "src/blob.rs", "src/lib.rs", "src/nested/deep.rs"]);
+        assert!(!truncated);
+        let (top, _) = glob(&jail, &root, "*.rs", 100);
+        assert_eq!(top, [root.join("main.rs")]);
+        let (_, truncated) = glob(&jail, &root, "**/*.rs", 2);
+        assert!(truncated);
+
+        let regex = Regex::new("(?i)todo").unwrap();
+        let options = GrepOptions {
+            regex: &regex,
+            include: Some("*.rs"),
+            max_file_size: 1024,
+            max_results: 10,
+        };
+        let (found, _) = grep(&jail, &root, &options);
+        let found: Vec<_> = found
+            .iter()
+            .map(|m| format!("{}:{}", jail.display(&m.path), m.line_number))
+            .collect();
+        assert_eq!(found, ["src/lib.rs:2", "src/nested/deep.rs:1"]);
+
+        // The walk stops at the result limit
+        let options = GrepOptions {
+            max_results: 1,
+            ..options
+        };
+        let (found, truncated) = grep(&jail, &root, &options);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, root.join("src/lib.rs"));
+        assert!(truncated);
+    }
+}
@@ -0,0 +1,525 @@
+// Runtime for the MCP servers built into this crate.
+//
+// A server implements `ToolServer` and `serve_stdio` speaks JSON-RPC for it over
+// stdin and stdout. Messages are framed either with Content-Length headers, as
+// mcp-hostd sends them, or as one JSON object per line, as standard MCP clients
+// send them; replies use whichever framing the client used. Both the standard
+// `tools/list` and `tools/call` methods and the host's `mcp/tool/execute` are
+// answered. Tool calls run concurrently and can be cancelled with
+// `notifications/cancelled`.
+//
+// When the client supports roots, they are fetched with `roots/list` once the
+// session is initialized and again whenever the client reports a change, and
+// tools read them through their `ToolContext`.
+
+pub mod command;
+pub mod filesystem;
+
+use async_trait::async_trait;
+use gemini_core::rpc_types::{JsonRpcError, Response, Tool, ToolCallResult, ToolContent};
+use log::{debug, error, warn};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, watch};
+use tokio::task::AbortHandle;
+
+/// Protocol revision answered to standard clients that ask for one we do not know
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+// Largest message body accepted from the client, in bytes
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+// How long a tool waits for the client's first `roots/list` answer
+const ROOTS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An MCP server offering tools
+#[async_trait]
+pub trait ToolServer: Send + Sync + 'static {
+    /// Name reported in the `initialize` result
+    fn name(&self) -> &'static str;
+
+    /// Usage hints for the client's model, sent in the `initialize` result
+    fn instructions(&self) -> Option<String> {
+        None
+    }
+
+    fn tools(&self) -> Vec<Tool>;
+
+    /// Run a tool. Failures are returned as error results, so the model sees them.
+    async fn call_tool(&self, name: &str, arguments: Value, context: ToolContext) -> ToolCallResult;
+}
+
+/// What a running tool call can reach of its session
+#[derive(Clone)]
+pub struct ToolContext {
+    outgoing: mpsc::UnboundedSender<Value>,
+    progress_token: Option<Value>,
+    roots: watch::Receiver<Option<Vec<PathBuf>>>,
+}
+
+impl ToolContext {
+    pub(crate) fn new(
+        outgoing: mpsc::UnboundedSender<Value>,
+        progress_token: Option<Value>,
+        roots: watch::Receiver<Option<Vec<PathBuf>>>,
+    ) -> Self {
+        Self {
+            outgoing,
+            progress_token,
+            roots,
+        }
+    }
+
+    /// Report progress, if the client asked for it with a progress token
+    pub fn progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+        let Some(token) = &self.progress_token else {
+            return;
+        };
+        let mut params = json!({ "progressToken": token, "progress": progress });
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        if let Some(message) = message {
+            params["message"] = json!(message);
+        }
+        let _ = self.outgoing.send(notification("notifications/progress", params));
+    }
+
+    /// Directories the client exposes through `roots/list`; empty when it has none
+    /// or does not answer in time
+    pub async fn client_roots(&self) -> Vec<PathBuf> {
+        let mut roots = self.roots.clone();
+        let listed = tokio::time::timeout(ROOTS_TIMEOUT, roots.wait_for(Option::is_some))
+            .await
+            .ok()
+            .and_then(|listed| listed.ok().and_then(|roots| roots.clone()));
+        listed.unwrap_or_else(|| {
+            warn!("The client did not list its roots in time");
+            Vec::new()
+        })
+    }
+}
+
+/// Tool result holding a single text block
+pub fn text_result(text: impl Into<String>) -> ToolCallResult {
+    ToolCallResult {
+        content: vec![ToolContent::Text { text: text.into() }],
+        structured_content: None,
+        is_error: false,
+    }
+}
+
+/// Tool result reporting a failure to the model
+pub fn error_result(message: impl Into<String>) -> ToolCallResult {
+    ToolCallResult {
+        is_error: true,
+        ..text_result(message)
+    }
+}
+
+/// A required string argument
+pub fn required_str<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, String> {
+    arguments
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Missing required string argument '{}'", name))
+}
+
+pub fn optional_str<'a>(arguments: &'a Value, name: &str) -> Option<&'a str> {
+    arguments.get(name).and_then(Value::as_str)
+}
+
+pub fn optional_bool(arguments: &Value, name: &str) -> bool {
+    arguments.get(name).and_then(Value::as_bool).unwrap_or(false)
+}
+
+pub fn optional_u64(arguments: &Value, name: &str) -> Option<u64> {
+    arguments.get(name).and_then(Value::as_u64)
+}
+
+/// Serve `server` over stdin and stdout until the client closes stdin or sends `exit`
+pub async fn serve_stdio<S: ToolServer>(server: S) -> io::Result<()> {
+    let server = Arc::new(server);
+    let header_framing = Arc::new(AtomicBool::new(false));
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Value>();
+
+    // A single writer keeps messages from interleaving
+    let writer_framing = Arc::clone(&header_framing);
+    let writer = tokio::spawn(async move {
+        let mut stdout = io::stdout();
+        while let Some(message) = outgoing_rx.recv().await {
+            let body = message.to_string();
+            let framed = if writer_framing.load(Ordering::Relaxed) {
+                format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+            } else {
+                format!("{}\n", body)
+            };
+            if let Err(e) = stdout.write_all(framed.as_bytes()).await {
+                error!("Failed to write to stdout: {}", e);
+                break;
+            }
+            let _ = stdout.flush().await;
+        }
+    });
+
+    let mut session = Session {
+        server,
+        outgoing,
+        roots: watch::channel(None).0,
+        calls: Arc::new(Mutex::new(HashMap::new())),
+        roots_supported: false,
+        roots_request: None,
+        next_request_id: 0,
+    };
+    let mut stdin = BufReader::new(io::stdin());
+    while let Some((body, framed_with_headers)) = read_message(&mut stdin).await? {
+        header_framing.store(framed_with_headers, Ordering::Relaxed);
+        let message = match serde_json::from_slice::<Value>(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                session.send_error(Value::Null, -32700, format!("Parse error: {}", e));
+                continue;
+            }
+        };
+        if !session.handle(message) {
+            break;
+        }
+    }
+
+    debug!("Client closed the session");
+    drop(session);
+    let _ = writer.await;
+    Ok(())
+}
+
+// Read one message. Returns the body and whether it was framed with headers, or
+// None at the end of input.
+async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+) -> io::Result<Option<(Vec<u8>, bool)>> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let Some(length) = content_length(trimmed) else {
+            return Ok(Some((trimmed.as_bytes().to_vec(), false)));
+        };
+        let length = length.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Invalid header '{}'", trimmed))
+        })?;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Message of {} bytes is over the {} byte limit", length, MAX_MESSAGE_SIZE),
+            ));
+        }
+        // Skip any further headers up to the blank line
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body).await?;
+        return Ok(Some((body, true)));
+    }
+}
+
+// The value of a `Content-Length` header line; Some(None) if it does not parse
+fn content_length(line: &str) -> Option<Option<usize>> {
+    let (name, value) = line.split_once(':')?;
+    name.trim()
+        .eq_ignore_ascii_case("content-length")
+        .then(|| value.trim().parse().ok())
+}
+
+struct Session<S: ToolServer> {
+    server: Arc<S>,
+    outgoing: mpsc::UnboundedSender<Value>,
+    roots: watch::Sender<Option<Vec<PathBuf>>>,
+    // Running tool calls by request ID, for cancellation
+    calls: Arc<Mutex<HashMap<String, AbortHandle>>>,
+    roots_supported: bool,
+    roots_request: Option<Value>,
+    next_request_id: u64,
+}
+
+impl<S: ToolServer> Session<S> {
+    // Act on one message; false once the client asked to exit
+    fn handle(&mut self, message: Value) -> bool {
+        let method = message.get("method").and_then(Value::as_str).map(str::to_string);
+        let id = message.get("id").cloned();
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        match (method.as_deref(), id) {
+            (None, Some(id)) => self.handle_response(id, &message),
+            (None, None) => debug!("Ignoring message without method or ID"),
+            (Some("exit"), None) => return false,
+            (Some(method), None) => self.handle_notification(method, &params),
+            (Some(method), Some(id)) => self.handle_request(method, id, params),
+        }
+        true
+    }
+
+    fn handle_request(&mut self, method: &str, id: Value, params: Value) {
+        debug!("Handling '{}' request", method);
+        match method {
+            "initialize" => {
+                let standard = params.get("protocolVersion").is_some();
+                self.roots_supported = params
+                    .pointer("/capabilities/roots")
+                    .is_some_and(|roots| !roots.is_null());
+                self.send_result(id, self.initialize_result(&params));
+                if !self.roots_supported {
+                    self.roots.send_replace(Some(Vec::new()));
+                } else if !standard {
+                    // mcp-hostd sends no `initialized` notification
+                    self.request_roots();
+                }
+            }
+            "ping" | "shutdown" => self.send_result(id, json!({})),
+            "tools/list" => {
+                let tools: Vec<Value> = self
+                    .server
+                    .tools()
+                    .into_iter()
+                    .map(|tool| {
+                        let mut entry = json!({
+                            "name": tool.name,
+                            "description": tool.description,
+                            "inputSchema": tool.parameters.unwrap_or_else(|| json!({ "type": "object" })),
+                        });
+                        if let Some(annotations) = tool.annotations {
+                            entry["annotations"] = json!(annotations);
+                        }
+                        entry
+                    })
+                    .collect();
+                self.send_result(id, json!({ "tools": tools }));
+            }
+            "tools/call" | "mcp/tool/execute" => {
+                let Some(name) = params.get("name").and_then(Value::as_str) else {
+                    self.send_error(id, -32602, "Missing tool name".to_string());
+                    return;
+                };
+                let arguments = params
+                    .get("arguments")
+                    .or_else(|| params.get("args"))
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
+                let progress_token = params.pointer("/_meta/progressToken").cloned();
+                self.spawn_call(id, name.to_string(), arguments, progress_token);
+            }
+            method => self.send_error(id, -32601, format!("Method not found: {}", method)),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) {
+        match method {
+            "notifications/initialized" | "notifications/roots/list_changed"
+                if self.roots_supported =>
+            {
+                self.request_roots()
+            }
+            "notifications/cancelled" => {
+                let key = params.get("requestId").map(Value::to_string).unwrap_or_default();
+                if let Some(call) = self.calls.lock().ok().and_then(|mut calls| calls.remove(&key)) {
+                    debug!("Cancelling request {}", key);
+                    call.abort();
+                }
+            }
+            _ => debug!("Ignoring '{}' notification", method),
+        }
+    }
+
+    fn handle_response(&mut self, id: Value, message: &Value) {
+        if self.roots_request.as_ref() != Some(&id) {
+            debug!("Ignoring response to unknown request {}", id);
+            return;
+        }
+        self.roots_request = None;
+        let roots: Vec<PathBuf> = match message.pointer("/result/roots").and_then(Value::as_array) {
+            Some(roots) => roots
+                .iter()
+                .filter_map(|root| root.get("uri").and_then(Value::as_str))
+                .filter_map(file_uri_path)
+                .collect(),
+            None => {
+                warn!("The client failed to list its roots: {}", message);
+                Vec::new()
+            }
+        };
+        debug!("Client roots: {:?}", roots);
+        self.roots.send_replace(Some(roots));
+    }
+
+    fn initialize_result(&self, params: &Value) -> Value {
+        let server_info = json!({ "name": self.server.name(), "version": env!("CARGO_PKG_VERSION") });
+        let mut result = match params.get("protocolVersion").and_then(Value::as_str) {
+            Some(requested) => json!({
+                "protocolVersion": SUPPORTED_PROTOCOL_VERSIONS
+                    .iter()
+                    .find(|version| **version == requested)
+                    .copied()
+                    .unwrap_or(PROTOCOL_VERSION),
+                "capabilities": { "tools": {} },
+                "serverInfo": server_info,
+            }),
+            // mcp-hostd reads the tools from the capabilities
+            None => json!({
+                "capabilities": { "tools": self.server.tools() },
+                "serverInfo": server_info,
+            }),
+        };
+        if let Some(instructions) = self.server.instructions() {
+            result["instructions"] = Value::String(instructions);
+        }
+        result
+    }
+
+    fn spawn_call(&self, id: Value, name: String, arguments: Value, progress_token: Option<Value>) {
+        let server = Arc::clone(&self.server);
+        let outgoing = self.outgoing.clone();
+        let context = ToolContext::new(outgoing.clone(), progress_token, self.roots.subscribe());
+        let calls = Arc::clone(&self.calls);
+        let key = id.to_string();
+
+        // Hold the lock until the handle is registered, so a fast call cannot
+        // finish before it is recorded
+        let mut running = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        let task_key = key.clone();
+        let task = tokio::spawn(async move {
+            let result = server.call_tool(&name, arguments, context).await;
+            if let Ok(mut calls) = calls.lock() {
+                calls.remove(&task_key);
+            }
+            let result = serde_json::to_value(result).unwrap_or(Value::Null);
+            let _ = outgoing.send(response(id, Ok(result)));
+        });
+        running.insert(key, task.abort_handle());
+    }
+
+    fn request_roots(&mut self) {
+        self.next_request_id += 1;
+        let id = json!(format!("roots-{}", self.next_request_id));
+        self.roots_request = Some(id.clone());
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "roots/list",
+        }));
+    }
+
+    fn send_result(&self, id: Value, result: Value) {
+        let _ = self.outgoing.send(response(id, Ok(result)));
+    }
+
+    fn send_error(&self, id: Value, code: i64, message: String) {
+        warn!("Request failed: {}", message);
+        let error = JsonRpcError {
+            code,
+            message,
+            data: None,
+        };
+        let _ = self.outgoing.send(response(id, Err(error)));
+    }
+}
+
+fn response(id: Value, outcome: Result<Value, JsonRpcError>) -> Value {
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    serde_json::to_value(Response {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result,
+        error,
+    })
+    .unwrap_or(Value::Null)
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+// Local path of a `file://` URI, percent-decoded
+fn file_uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_message_framings() {
+        let body = r#"{"jsonrpc":"2.0","method":"ping","id":1}"#;
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{}\n{}\n",
+            body.len(),
+            body,
+            body
+        );
+        let mut reader = BufReader::new(input.as_bytes());
+
+        let (first, headers) = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(first, body.as_bytes());
+        assert!(headers);
+        let (second, headers) = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(second, body.as_bytes());
+        assert!(!headers);
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+
+        // Oversized bodies are refused before anything is allocated for them
+        let mut reader = BufReader::new("Content-Length: 99999999999\r\n\r\n{}".as_bytes());
+        let error = read_message(&mut reader).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_file_uri_path() {
+        assert_eq!(
+            file_uri_path("file:///home/me/My%20Project"),
+            Some(PathBuf::from("/home/me/My Project"))
+        );
+        assert_eq!(file_uri_path("https://example.com"), None);
+    }
+}
@@ -0,0 +1,206 @@
+// Mapping between qualified MCP tool names and Gemini function names.
+//
+// MCP tools are addressed as `server/tool`, and either half may contain
+// characters Gemini does not accept in a function name. The function name is
+// the qualified name with its first `/` replaced by `.` and any other invalid
+// character by `_`. Names that had to be shortened to the length limit, or that
+// would collide with another tool's name, end in a hash of the qualified name
+// instead. Names depend only on the set of tools, so a registry built from the
+// same tool list always produces and resolves the same names.
+
+use gemini_core::rpc_types::Tool;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+
+/// Longest function name Gemini accepts
+pub const MAX_FUNCTION_NAME_LEN: usize = 64;
+
+// Hex digits of the hash suffix, grown on the (unlikely) event of a collision
+const HASH_LEN: usize = 8;
+const MAX_HASH_LEN: usize = 32;
+
+/// Bidirectional map between qualified tool names and Gemini function names
+#[derive(Debug, Clone, Default)]
+pub struct ToolNameRegistry {
+    function_names: HashMap<String, String>,
+    qualified_names: HashMap<String, String>,
+}
+
+impl ToolNameRegistry {
+    /// Build the registry for the `server/tool` names of `tools`. Tools without a
+    /// server prefix are left out.
+    pub fn new(tools: &[Tool]) -> Self {
+        let qualified: BTreeSet<&str> = tools
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .filter(|name| name.contains('/'))
+            .collect();
+
+        let mut by_base: HashMap<String, Vec<&str>> = HashMap::new();
+        for name in &qualified {
+            by_base.entry(sanitize(name)).or_default().push(name);
+        }
+
+        // Names that fit and are unique are used as they are; the rest are
+        // assigned hashed names once every plain name is known
+        let mut registry = Self::default();
+        let mut hashed = Vec::new();
+        for name in &qualified {
+            let base = sanitize(name);
+            if base.len() <= MAX_FUNCTION_NAME_LEN && by_base[&base].len() == 1 {
+                registry.insert(name, base);
+            } else {
+                hashed.push((*name, base));
+            }
+        }
+        for (name, base) in hashed {
+            let function_name = (HASH_LEN..=MAX_HASH_LEN)
+                .map(|hash_len| with_hash(&base, name, hash_len))
+                .find(|candidate| !registry.qualified_names.contains_key(candidate))
+                .expect("hash suffixes of distinct names differ");
+            registry.insert(name, function_name);
+        }
+        registry
+    }
+
+    fn insert(&mut self, qualified_name: &str, function_name: String) {
+        self.qualified_names
+            .insert(function_name.clone(), qualified_name.to_string());
+        self.function_names
+            .insert(qualified_name.to_string(), function_name);
+    }
+
+    /// Gemini function name of a `server/tool` name
+    pub fn function_name(&self, qualified_name: &str) -> Option<&str> {
+        self.function_names.get(qualified_name).map(String::as_str)
+    }
+
+    /// `server/tool` name behind a Gemini function name
+    pub fn qualified_name(&self, function_name: &str) -> Option<&str> {
+        self.qualified_names.get(function_name).map(String::as_str)
+    }
+
+    /// Server and tool names behind a Gemini function name
+    pub fn resolve(&self, function_name: &str) -> Option<(&str, &str)> {
+        self.qualified_name(function_name)?.split_once('/')
+    }
+
+    pub fn len(&self) -> usize {
+        self.function_names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.function_names.is_empty()
+    }
+}
+
+/// Whether `name` is accepted as a function name by the Gemini API
+pub fn is_valid_function_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    name.len() <= MAX_FUNCTION_NAME_LEN
+        && (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(is_valid_char)
+}
+
+fn is_valid_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+// The readable form of a qualified name, not yet limited in length
+fn sanitize(qualified_name: &str) -> String {
+    let (server, tool) = qualified_name.split_once('/').unwrap_or(("", qualified_name));
+    let clean = |part: &str| -> String {
+        part.chars()
+            .map(|c| if is_valid_char(c) { c } else { '_' })
+            .collect()
+    };
+    let mut name = format!("{}.{}", clean(server), clean(tool));
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn with_hash(base: &str, qualified_name: &str, hash_len: usize) -> String {
+    let digest = format!("{:x}", Sha256::digest(qualified_name.as_bytes()));
+    let keep = MAX_FUNCTION_NAME_LEN - hash_len - 1;
+    // `base` is ASCII, so any byte index is a character boundary
+    let prefix = &base[..base.len().min(keep)];
+    format!("{}_{}", prefix, &digest[..hash_len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools(names: &[&str]) -> Vec<Tool> {
+        names
+            .iter()
+            .map(|name| Tool {
+                name: name.to_string(),
+                description: None,
+                parameters: None,
+                annotations: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_names_are_valid_unique_and_resolve_back() {
+        let long_tool = "x".repeat(80);
+        let long_name = format!("filesystem-mcp/{}", long_tool);
+        let names = [
+            "filesystem-mcp/read_file",
+            "a.b/c",
+            "a/b.c",
+            "git server/log:oneline",
+            "9lives/meow",
+            "command-mcp/run/detached",
+            long_name.as_str(),
+        ];
+        let registry = ToolNameRegistry::new(&tools(&names));
+        assert_eq!(registry.len(), names.len());
+
+        let mut seen = BTreeSet::new();
+        for name in names {
+            let function_name = registry.function_name(name).unwrap();
+            assert!(is_valid_function_name(function_name), "{}", function_name);
+            assert!(seen.insert(function_name.to_string()));
+            assert_eq!(registry.qualified_name(function_name), Some(name));
+        }
+
+        assert_eq!(
+            registry.function_name("filesystem-mcp/read_file"),
+            Some("filesystem-mcp.read_file")
+        );
+        assert_eq!(
+            registry.resolve("filesystem-mcp.read_file"),
+            Some(("filesystem-mcp", "read_file"))
+        );
+        assert_eq!(registry.function_name("9lives/meow"), Some("_9lives.meow"));
+        assert_eq!(
+            registry.resolve(registry.function_name("command-mcp/run/detached").unwrap()),
+            Some(("command-mcp", "run/detached"))
+        );
+        // Both sanitize to `a.b.c`, so neither gets the plain name
+        assert_ne!(registry.function_name("a.b/c"), Some("a.b.c"));
+        assert!(registry.function_name("a/b.c").unwrap().starts_with("a.b.c_"));
+        assert_eq!(registry.function_name(&long_name).unwrap().len(), MAX_FUNCTION_NAME_LEN);
+        assert_eq!(registry.resolve("unknown.tool"), None);
+    }
+
+    #[test]
+    fn test_names_do_not_depend_on_tool_order() {
+        let names = ["b/x", "a.b/c", "a/b.c", "a/b"];
+        let forward = ToolNameRegistry::new(&tools(&names));
+        let mut reversed = names;
+        reversed.reverse();
+        let backward = ToolNameRegistry::new(&tools(&reversed));
+        for name in names {
+            assert_eq!(forward.function_name(name), backward.function_name(name));
+        }
+    }
+}
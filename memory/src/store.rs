@@ -155,6 +155,21 @@ impl MemoryStore {
         related_keys: Option<Vec<String>>,
         // confidence_score is typically derived from search, so not added here
     ) -> Result<()> {
+        self.insert_memory(key, value, tags, session_id, source, related_keys)
+            .await
+            .map(|_| ())
+    }
+
+    /// Add a new memory, returning the ID it was stored under.
+    async fn insert_memory(
+        &self,
+        key: &str,
+        value: &str,
+        tags: Vec<String>,
+        session_id: Option<String>,
+        source: Option<String>,
+        related_keys: Option<Vec<String>>,
+    ) -> Result<Uuid> {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map_err(|e| anyhow::anyhow!("SystemTime error: {}", e))?
@@ -244,11 +259,12 @@ impl MemoryStore {
             .context("Failed to add memory to LanceDB table")?;
 
         debug!("Added memory: {} = {}", key, value);
-        Ok(())
+        Ok(id)
     }
 
     /// Update an existing memory identified by its key.
-    /// This is currently implemented as delete + add.
+    /// The new version is added first and the old ones are deleted only once it
+    /// is stored, so a failed update leaves the memory as it was.
     /// Returns true if a matching key was found and updated, false otherwise.
     /// Propagates optional metadata to the underlying add_memory call.
     pub async fn update_memory(
@@ -261,13 +277,13 @@ impl MemoryStore {
         source: Option<String>,
         related_keys: Option<Vec<String>>,
     ) -> Result<bool> {
-        // First, try to delete existing memories with the key
-        let delete_count = self.delete_by_key(key).await?;
-
-        // Then, add the new version with metadata
-        self.add_memory(key, value, tags, session_id, source, related_keys)
+        let id = self
+            .insert_memory(key, value, tags, session_id, source, related_keys)
             .await?;
 
+        let filter = format!("key = '{}' AND id != '{}'", key.replace("'", "''"), id);
+        let delete_count = self.delete_matching(&filter, key).await?;
+
         if delete_count > 0 {
             debug!("Updated memory with key: {}", key);
             Ok(true)
@@ -338,9 +354,14 @@ impl MemoryStore {
 
     /// Delete memories by key, returning the number of items removed.
     pub async fn delete_by_key(&self, key: &str) -> Result<usize> {
-        // First, count how many match
         let filter = format!("key = '{}'", key.replace("'", "''"));
-        let query = self.table.query().only_if(filter.clone());
+        self.delete_matching(&filter, key).await
+    }
+
+    /// Delete the memories matching `filter`, which selects memories with `key`.
+    async fn delete_matching(&self, filter: &str, key: &str) -> Result<usize> {
+        // First, count how many match
+        let query = self.table.query().only_if(filter);
         let matching_memories = self.execute_query(query).await?;
 
         // Collect all batches to count them
@@ -351,7 +372,7 @@ impl MemoryStore {
             debug!("Attempting to delete {} memories with key '{}'", count, key);
             // Now delete using the same filter
             self.table
-                .delete(&filter) // Pass the filter string
+                .delete(filter) // Pass the filter string
                 .await
                 .context(format!("Failed to delete memories with key '{}'", key))?;
             debug!("Successfully deleted memories with key '{}'", key);
@@ -444,10 +465,27 @@ impl MemoryStore {
 
         // Execute the query
         let result = self.execute_query(vector_query).await?;
-        let mut memories_with_scores = Vec::new();
+        let memories_with_scores = self
+            .batch_stream_to_scored_memories(result)
+            .await?
+            .into_iter()
+            .filter_map(|memory| {
+                let score = memory.confidence_score.unwrap_or(0.0);
+                (score >= min_relevance_score).then_some((memory, score))
+            })
+            .collect();
 
-        // Process the search results
-        let batches = result.try_collect::<Vec<_>>().await?;
+        Ok(memories_with_scores)
+    }
+
+    /// Convert the results of a vector search to memories whose
+    /// `confidence_score` holds their similarity to the query, most similar first.
+    async fn batch_stream_to_scored_memories(
+        &self,
+        stream: BoxStream<'static, Result<RecordBatch, Error>>,
+    ) -> Result<Vec<Memory>> {
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        let mut scored_memories = Vec::new();
 
         for batch in batches {
             if let Some(distance_array) = batch.column_by_name("_distance") {
@@ -459,26 +497,27 @@ impl MemoryStore {
                 // Use function from arrow_conversion module
                 let memories = arrow_conversion::batch_to_memories(&batch)?;
 
-                for (i, memory) in memories.into_iter().enumerate() {
-                    let distance = distances.value(i);
+                for (i, mut memory) in memories.into_iter().enumerate() {
                     // Calculate similarity score (convert distance to similarity)
-                    let similarity_score = 1.0 / (1.0 + distance);
-
-                    if similarity_score >= min_relevance_score {
-                        memories_with_scores.push((memory, similarity_score));
-                    }
+                    memory.confidence_score = Some(1.0 / (1.0 + distances.value(i)));
+                    scored_memories.push(memory);
                 }
             }
         }
 
         // Sort by similarity score (descending)
-        memories_with_scores
-            .sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored_memories.sort_by(|a, b| {
+            b.confidence_score
+                .partial_cmp(&a.confidence_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
-        Ok(memories_with_scores)
+        Ok(scored_memories)
     }
 
-    /// Searches memories combining vector similarity and filtering.
+    /// Searches memories combining vector similarity and filtering. When the
+    /// query text is embedded, results carry their similarity in
+    /// `confidence_score` and come most similar first.
     pub async fn search_memories(
         &self,
         query_text: Option<&str>,
@@ -549,7 +588,7 @@ impl MemoryStore {
                 match query.clone().nearest_to(query_vector) {
                     Ok(vector_query) => {
                         let result = self.execute_query(vector_query).await?;
-                        return self.batch_stream_to_memories(result).await;
+                        return self.batch_stream_to_scored_memories(result).await;
                     }
                     Err(e) => {
                         warn!(
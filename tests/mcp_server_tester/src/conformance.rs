@@ -0,0 +1,157 @@
+// Protocol conformance checks run against each server of a scenario.
+//
+// The checks go through `McpHost` like any other client would: the initialize
+// handshake must have completed, unknown methods must fail with "method not
+// found", a large request must be answered without breaking the framing, and a
+// cancelled call must not be answered with a result in the following seconds
+// while the server keeps serving other requests.
+
+use crate::report::{CaseResult, Outcome};
+use crate::runner::request;
+use crate::scenario::{CancellationCall, Conformance, DEFAULT_LARGE_PAYLOAD_BYTES};
+use gemini_mcp::rpc::ExecuteToolParams;
+use gemini_mcp::{McpHost, ServerState};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+
+// How long a check waits for a response
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+// How long a call runs before it is cancelled, and how long a response to it is
+// then waited for
+const CANCEL_AFTER: Duration = Duration::from_millis(500);
+const CANCEL_GRACE: Duration = Duration::from_secs(2);
+
+/// Run the checks against `servers`, plus the cancellation check if configured
+pub async fn run(host: &McpHost, conformance: &Conformance, servers: &[String]) -> Vec<CaseResult> {
+    let payload_bytes = conformance
+        .large_payload_bytes
+        .unwrap_or(DEFAULT_LARGE_PAYLOAD_BYTES);
+    let mut cases = Vec::new();
+    for server in servers {
+        cases.push(timed(format!("{}: initialize handshake", server), initialize(host, server)).await);
+        cases.push(timed(format!("{}: unknown method", server), unknown_method(host, server)).await);
+        cases.push(
+            timed(
+                format!("{}: large payload ({} bytes)", server, payload_bytes),
+                large_payload(host, server, payload_bytes),
+            )
+            .await,
+        );
+    }
+    match &conformance.cancellation {
+        Some(call) => {
+            cases.push(timed(format!("{}: cancellation", call.server), cancellation(host, call)).await)
+        }
+        None => cases.push(CaseResult {
+            name: "cancellation".to_string(),
+            kind: "conformance",
+            outcome: Outcome::Skipped("no `cancellation` call configured".to_string()),
+            duration: Duration::ZERO,
+        }),
+    }
+    cases
+}
+
+async fn timed(name: String, check: impl std::future::Future<Output = Outcome>) -> CaseResult {
+    let started = Instant::now();
+    let outcome = check.await;
+    CaseResult {
+        name,
+        kind: "conformance",
+        outcome,
+        duration: started.elapsed(),
+    }
+}
+
+// mcp-hostd sends `initialize` when it launches a server; the server must have
+// answered it with its capabilities
+async fn initialize(host: &McpHost, server: &str) -> Outcome {
+    match host.server_status(server).await {
+        Ok(status) if status.state == ServerState::Running => Outcome::Passed,
+        Ok(status) => Outcome::Failed(format!(
+            "server is {:?} after the handshake: {}",
+            status.state,
+            status.last_error.unwrap_or_else(|| "no error reported".to_string())
+        )),
+        Err(e) => Outcome::Failed(e),
+    }
+}
+
+async fn unknown_method(host: &McpHost, server: &str) -> Outcome {
+    match request(host, server, "mcp-tester/no-such-method", json!({}), CHECK_TIMEOUT).await {
+        Ok(response) => match response.error {
+            Some(error) if error.code == METHOD_NOT_FOUND => Outcome::Passed,
+            Some(error) => Outcome::Failed(format!(
+                "failed with code {} ({}), expected {} (method not found)",
+                error.code, error.message, METHOD_NOT_FOUND
+            )),
+            None => Outcome::Failed("answered an unknown method with a result".to_string()),
+        },
+        Err(e) => Outcome::Failed(e),
+    }
+}
+
+// Any answer will do, as long as the server read the whole request
+async fn large_payload(host: &McpHost, server: &str, bytes: usize) -> Outcome {
+    let params = json!({ "padding": "x".repeat(bytes) });
+    match request(host, server, "ping", params, CHECK_TIMEOUT).await {
+        Ok(response) => match response.error {
+            Some(error) if error.code == PARSE_ERROR || error.code == INVALID_REQUEST => {
+                Outcome::Failed(format!("could not read the request: {}", error.message))
+            }
+            _ => still_responsive(host, server).await,
+        },
+        Err(e) => Outcome::Failed(e),
+    }
+}
+
+async fn cancellation(host: &McpHost, call: &CancellationCall) -> Outcome {
+    let params = ExecuteToolParams {
+        tool_name: call.tool.clone(),
+        arguments: call.arguments.clone(),
+    };
+    let params = serde_json::to_value(params).unwrap_or_default();
+    let id = host.allocate_request_id();
+    let pending = {
+        let host = host.clone();
+        let server = call.server.clone();
+        tokio::spawn(async move {
+            host.send_request(&server, id, "mcp/tool/execute", params, CANCEL_AFTER + CANCEL_GRACE)
+                .await
+        })
+    };
+
+    tokio::time::sleep(CANCEL_AFTER).await;
+    if pending.is_finished() {
+        return Outcome::Failed(format!(
+            "the call finished within {:?}, before it could be cancelled; configure a slower call",
+            CANCEL_AFTER
+        ));
+    }
+    let cancel = json!({ "requestId": id, "reason": "cancelled by mcp-tester" });
+    if let Err(e) = host
+        .send_notification(&call.server, "notifications/cancelled", Some(cancel))
+        .await
+    {
+        return Outcome::Failed(format!("could not send the cancellation: {}", e));
+    }
+
+    // No answer at all is what the specification asks for; an error is tolerated
+    match pending.await {
+        Ok(Ok(response)) if response.error.is_none() => {
+            Outcome::Failed("the cancelled call was still answered with a result".to_string())
+        }
+        _ => still_responsive(host, &call.server).await,
+    }
+}
+
+async fn still_responsive(host: &McpHost, server: &str) -> Outcome {
+    match request(host, server, "ping", json!({}), CHECK_TIMEOUT).await {
+        Ok(_) => Outcome::Passed,
+        Err(e) => Outcome::Failed(format!("the server stopped responding: {}", e)),
+    }
+}
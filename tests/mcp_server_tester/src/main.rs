@@ -1,65 +1,121 @@
+// mcp-tester: runs YAML test scenarios against MCP servers through `McpHost`
+// and reports the results on the console and as JUnit or JSON reports.
+// See `scenario` for the file format and `pattern` for result patterns.
+
+mod conformance;
+mod pattern;
+mod report;
+mod runner;
+mod scenario;
+
 use anyhow::{Context, Result};
-use gemini_core::config::McpServerConfig;
-use gemini_mcp::McpHost;
-use log::{debug, error, info};
+use clap::Parser;
+use report::{Outcome, SuiteResult};
+use scenario::Scenario;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Initialize logger with more detailed configuration
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info) // Default level for other crates
-        .filter_module("gemini_mcp", log::LevelFilter::Debug) // Enable DEBUG for McpHost internals
-        .filter_module("mcp_server_tester", log::LevelFilter::Debug) // Enable DEBUG for this crate
-        .format_timestamp_millis() // Add timestamps
-        .init();
+#[derive(Parser, Debug)]
+#[command(name = "mcp-tester", about = "Run test scenarios and conformance checks against MCP servers")]
+struct Args {
+    /// Scenario files, or directories of `.yaml`/`.yml` scenarios
+    #[arg(required = true)]
+    scenarios: Vec<PathBuf>,
 
-    info!("Starting MCP Server Initialization Test (from config file)...");
+    /// Write a JUnit XML report to this file
+    #[arg(long)]
+    junit: Option<PathBuf>,
 
-    let config_path = Path::new(env!("CARGO_MANIFEST_DIR"))
-        .parent()
-        .ok_or_else(|| anyhow::anyhow!("Could not get parent directory"))?
-        .join("mcp_servers.json");
+    /// Write a JSON report to this file
+    #[arg(long)]
+    json: Option<PathBuf>,
 
-    debug!("Reading MCP configuration from: {}", config_path.display());
-    let config_content = fs::read_to_string(&config_path)
-        .with_context(|| format!("Failed to read config: {}", config_path.display()))?;
+    /// Run only the steps, not the conformance checks
+    #[arg(long)]
+    skip_conformance: bool,
+}
 
-    let mcp_configs: Vec<McpServerConfig> = serde_json::from_str(&config_content)
-        .with_context(|| format!("Failed to parse mcp_servers.json at {}", config_path.display()))?;
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+        .format_timestamp_millis()
+        .init();
+    let args = Args::parse();
 
-    debug!("Successfully parsed {} server configurations.", mcp_configs.len());
+    let mut suites = Vec::new();
+    for path in scenario_files(&args.scenarios)? {
+        let suite = match Scenario::load(&path) {
+            Ok(scenario) => runner::run(&scenario, args.skip_conformance).await,
+            Err(e) => invalid_scenario(&path, e),
+        };
+        print_suite(&suite);
+        suites.push(suite);
+    }
 
-    info!(
-        "Attempting to initialize McpHost (launching {} servers defined in config)...",
-        mcp_configs.len()
+    if let Some(path) = &args.junit {
+        report::write_junit(path, &suites)?;
+    }
+    if let Some(path) = &args.json {
+        report::write_json(path, &suites)?;
+    }
+
+    let totals = report::totals(&suites);
+    println!(
+        "\n{} passed, {} failed, {} skipped",
+        totals.passed, totals.failed, totals.skipped
     );
+    if totals.failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    match McpHost::new(mcp_configs).await {
-        Ok(host) => {
-            info!("✅ SUCCESS: McpHost initialized successfully.");
-            let capabilities = host.get_all_capabilities().await;
-            info!("Combined capabilities fetched:");
-            debug!("Tools:");
-            for tool in capabilities.tools {
-                debug!("  - {} ({:?})", tool.name, tool.description.unwrap_or_default());
-            }
-            debug!("Resources:");
-             for resource in capabilities.resources {
-                debug!("  - {}", resource.name);
-            }
-            info!("Shutting down McpHost...");
-            host.shutdown().await;
-            info!("McpHost shutdown complete.");
-            Ok(())
+// Expand directories into the scenarios they contain, in name order
+fn scenario_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = fs::read_dir(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| {
+                    file.extension()
+                        .is_some_and(|extension| extension == "yaml" || extension == "yml")
+                })
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
         }
-        Err(e) => {
-            error!("❌ FAILED: McpHost initialization encountered an error.");
-            error!("==================== ERROR DETAILS ====================");
-            error!("{:#}", e);
-            error!("=====================================================");
-            Err(anyhow::Error::msg(e))
+    }
+    Ok(files)
+}
+
+// A scenario that cannot be loaded is reported as a failed suite, so the other
+// scenarios still run
+fn invalid_scenario(path: &Path, error: anyhow::Error) -> SuiteResult {
+    SuiteResult {
+        name: path.display().to_string(),
+        file: path.to_path_buf(),
+        cases: vec![report::CaseResult {
+            name: "load".to_string(),
+            kind: "setup",
+            outcome: Outcome::Failed(format!("{:#}", error)),
+            duration: Duration::ZERO,
+        }],
+        duration: Duration::ZERO,
+    }
+}
+
+fn print_suite(suite: &SuiteResult) {
+    println!("\n{} ({})", suite.name, suite.file.display());
+    for case in &suite.cases {
+        match &case.outcome {
+            Outcome::Passed => println!("  ✅ {} ({} ms)", case.name, case.duration.as_millis()),
+            Outcome::Failed(message) => println!("  ❌ {}: {}", case.name, message),
+            Outcome::Skipped(reason) => println!("  ⏭  {}: {}", case.name, reason),
         }
     }
-} 
\ No newline at end of file
+}
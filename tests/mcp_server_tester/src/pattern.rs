@@ -0,0 +1,162 @@
+// JSON patterns used by scenario expectations.
+//
+// A pattern is plain JSON compared against a value:
+// - objects match when every key of the pattern is present and matches; other
+//   keys of the value are ignored
+// - arrays match element by element and must have the same length
+// - scalars must be equal
+//
+// An object with a single `$`-prefixed key is an operator instead:
+// - `{ $contains: <pattern> }` matches a string containing the given string, or
+//   an array with an element matching the pattern
+// - `{ $regex: <regex> }` matches a string the regex finds a match in
+// - `{ $type: <name> }` matches any value of a JSON type (null, boolean, number,
+//   string, array or object)
+// - `{ $any: true }` matches anything, including a missing key
+
+use regex::Regex;
+use serde_json::Value;
+
+/// Check `value` against `pattern`, describing the first mismatch
+pub fn matches(pattern: &Value, value: &Value) -> Result<(), String> {
+    match_at("$", pattern, Some(value))
+}
+
+fn match_at(path: &str, pattern: &Value, value: Option<&Value>) -> Result<(), String> {
+    if let Some((operator, operand)) = as_operator(pattern) {
+        return match_operator(path, operator, operand, value);
+    }
+    let Some(value) = value else {
+        return Err(format!("{}: missing, expected {}", path, pattern));
+    };
+    match (pattern, value) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, pattern) in expected {
+                match_at(&format!("{}.{}", path, key), pattern, actual.get(key))?;
+            }
+            Ok(())
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            if expected.len() != actual.len() {
+                return Err(format!(
+                    "{}: expected {} elements, got {}",
+                    path,
+                    expected.len(),
+                    actual.len()
+                ));
+            }
+            for (index, (pattern, value)) in expected.iter().zip(actual).enumerate() {
+                match_at(&format!("{}[{}]", path, index), pattern, Some(value))?;
+            }
+            Ok(())
+        }
+        (Value::Number(expected), Value::Number(actual)) if expected.as_f64() == actual.as_f64() => {
+            Ok(())
+        }
+        _ if pattern == value => Ok(()),
+        _ => Err(format!("{}: expected {}, got {}", path, pattern, brief(value))),
+    }
+}
+
+fn as_operator(pattern: &Value) -> Option<(&str, &Value)> {
+    let object = pattern.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+    let (key, operand) = object.iter().next()?;
+    key.starts_with('$').then_some((key.as_str(), operand))
+}
+
+fn match_operator(
+    path: &str,
+    operator: &str,
+    operand: &Value,
+    value: Option<&Value>,
+) -> Result<(), String> {
+    if operator == "$any" {
+        return Ok(());
+    }
+    let Some(value) = value else {
+        return Err(format!("{}: missing, expected {} {}", path, operator, operand));
+    };
+    let matched = match (operator, value) {
+        ("$contains", Value::String(text)) => match operand {
+            Value::String(needle) => text.contains(needle.as_str()),
+            _ => return Err(format!("{}: $contains on a string needs a string", path)),
+        },
+        ("$contains", Value::Array(items)) => items
+            .iter()
+            .any(|item| match_at(path, operand, Some(item)).is_ok()),
+        ("$regex", Value::String(text)) => {
+            let source = operand.as_str().unwrap_or_default();
+            let regex = Regex::new(source)
+                .map_err(|e| format!("{}: invalid regex {:?}: {}", path, source, e))?;
+            regex.is_match(text)
+        }
+        ("$type", value) => operand.as_str() == Some(type_name(value)),
+        ("$contains" | "$regex", _) => false,
+        _ => return Err(format!("{}: unknown operator {}", path, operator)),
+    };
+    if matched {
+        Ok(())
+    } else {
+        Err(format!("{}: expected {} {}, got {}", path, operator, operand, brief(value)))
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Values in mismatch messages are cut short so large results stay readable
+fn brief(value: &Value) -> String {
+    const MAX_CHARS: usize = 200;
+    let text = value.to_string();
+    match text.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_patterns() {
+        let value = json!({
+            "content": [{ "type": "text", "text": "3 files in /tmp" }],
+            "isError": false,
+            "count": 3,
+        });
+        assert!(matches(&json!({ "isError": false }), &value).is_ok());
+        assert!(matches(&json!({ "count": 3.0 }), &value).is_ok());
+        assert!(matches(
+            &json!({ "content": [{ "text": { "$regex": "^\\d+ files" } }] }),
+            &value
+        )
+        .is_ok());
+        assert!(matches(
+            &json!({ "content": { "$contains": { "text": { "$contains": "/tmp" } } } }),
+            &value
+        )
+        .is_ok());
+        assert!(matches(&json!({ "count": { "$type": "number" }, "extra": { "$any": true } }), &value).is_ok());
+
+        let error = matches(&json!({ "content": [{ "type": "image" }] }), &value).unwrap_err();
+        assert_eq!(error, r#"$.content[0].type: expected "image", got "text""#);
+        let error = matches(&json!({ "content": [] }), &value).unwrap_err();
+        assert_eq!(error, "$.content: expected 0 elements, got 1");
+        let error = matches(&json!({ "missing": 1 }), &value).unwrap_err();
+        assert_eq!(error, "$.missing: missing, expected 1");
+        assert!(matches(&json!({ "count": { "$nope": 1 } }), &value).is_err());
+    }
+}
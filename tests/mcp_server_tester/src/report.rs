@@ -0,0 +1,202 @@
+// Results of a test run and the JUnit and JSON reports written from them.
+//
+// Each scenario becomes a test suite whose cases are its conformance checks
+// followed by its steps.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "status", content = "message", rename_all = "lowercase")]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaseResult {
+    pub name: String,
+    /// `conformance` or `step`
+    pub kind: &'static str,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    #[serde(rename = "durationMs", serialize_with = "as_millis")]
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuiteResult {
+    pub name: String,
+    pub file: PathBuf,
+    pub cases: Vec<CaseResult>,
+    #[serde(rename = "durationMs", serialize_with = "as_millis")]
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Totals {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl SuiteResult {
+    pub fn totals(&self) -> Totals {
+        let mut totals = Totals::default();
+        for case in &self.cases {
+            match case.outcome {
+                Outcome::Passed => totals.passed += 1,
+                Outcome::Failed(_) => totals.failed += 1,
+                Outcome::Skipped(_) => totals.skipped += 1,
+            }
+        }
+        totals
+    }
+}
+
+pub fn totals(suites: &[SuiteResult]) -> Totals {
+    suites.iter().map(SuiteResult::totals).fold(Totals::default(), |a, b| Totals {
+        passed: a.passed + b.passed,
+        failed: a.failed + b.failed,
+        skipped: a.skipped + b.skipped,
+    })
+}
+
+fn as_millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+/// Write the results as JSON: the suites and the overall totals
+pub fn write_json(path: &Path, suites: &[SuiteResult]) -> Result<()> {
+    let report = serde_json::json!({
+        "totals": totals(suites),
+        "suites": suites,
+    });
+    let text = serde_json::to_string_pretty(&report)?;
+    fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Write the results as a JUnit XML report
+pub fn write_junit(path: &Path, suites: &[SuiteResult]) -> Result<()> {
+    fs::write(path, junit_xml(suites)).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn junit_xml(suites: &[SuiteResult]) -> String {
+    let all = totals(suites);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"mcp-tester\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+        all.passed + all.failed + all.skipped,
+        all.failed,
+        all.skipped
+    ));
+    for suite in suites {
+        let counts = suite.totals();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\" file=\"{}\">\n",
+            escape(&suite.name),
+            suite.cases.len(),
+            counts.failed,
+            counts.skipped,
+            suite.duration.as_secs_f64(),
+            escape(&suite.file.display().to_string())
+        ));
+        for case in &suite.cases {
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"{}.{}\" time=\"{:.3}\"",
+                escape(&case.name),
+                escape(&suite.name),
+                case.kind,
+                case.duration.as_secs_f64()
+            );
+            match &case.outcome {
+                Outcome::Passed => xml.push_str(&format!("{}/>\n", open)),
+                Outcome::Failed(message) => xml.push_str(&format!(
+                    "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                    open,
+                    escape(first_line(message)),
+                    escape(message)
+                )),
+                Outcome::Skipped(message) => xml.push_str(&format!(
+                    "{}>\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                    open,
+                    escape(message)
+                )),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+// Escape text for XML attributes and content; control characters other than
+// tab and newlines are not allowed in XML 1.0 and are dropped
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_and_json_reports() {
+        let case = |name: &str, outcome| CaseResult {
+            name: name.to_string(),
+            kind: "step",
+            outcome,
+            duration: Duration::from_millis(1500),
+        };
+        let suites = vec![SuiteResult {
+            name: "smoke".to_string(),
+            file: PathBuf::from("smoke.yaml"),
+            cases: vec![
+                case("reads <file>", Outcome::Passed),
+                case("fails", Outcome::Failed("$.text: expected \"a\"\ngot \u{1b}b".to_string())),
+                case("cancel", Outcome::Skipped("no call".to_string())),
+            ],
+            duration: Duration::from_secs(2),
+        }];
+
+        let xml = junit_xml(&suites);
+        assert!(xml.contains("<testsuites name=\"mcp-tester\" tests=\"3\" failures=\"1\" skipped=\"1\">"));
+        assert!(xml.contains("<testcase name=\"reads &lt;file&gt;\" classname=\"smoke.step\" time=\"1.500\"/>"));
+        assert!(xml.contains("<failure message=\"$.text: expected &quot;a&quot;\">"));
+        assert!(xml.contains("got b</failure>"));
+        assert!(xml.contains("<skipped message=\"no call\"/>"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        write_json(&path, &suites).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(report["totals"], serde_json::json!({ "passed": 1, "failed": 1, "skipped": 1 }));
+        let failed = &report["suites"][0]["cases"][1];
+        assert_eq!(failed["status"], "failed");
+        assert_eq!(failed["durationMs"], 1500);
+        assert_eq!(report["suites"][0]["cases"][0].get("message"), None);
+    }
+}
@@ -0,0 +1,174 @@
+// Runs a scenario: launches its servers in an `McpHost`, runs the conformance
+// checks, then each step in order.
+
+use crate::conformance;
+use crate::pattern;
+use crate::report::{CaseResult, Outcome, SuiteResult};
+use crate::scenario::{Expect, Scenario, Step, DEFAULT_STEP_TIMEOUT_SECS};
+use gemini_core::rpc_types::Response;
+use gemini_mcp::rpc::ExecuteToolParams;
+use gemini_mcp::McpHost;
+use log::info;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// Run `scenario`, with its conformance checks unless `skip_conformance` is set
+pub async fn run(scenario: &Scenario, skip_conformance: bool) -> SuiteResult {
+    let started = Instant::now();
+    let cases = match scenario.server_configs() {
+        Ok(configs) => {
+            let names: Vec<String> = configs.iter().map(|config| config.name.clone()).collect();
+            info!("Scenario '{}': launching {} servers", scenario.name, names.len());
+            match McpHost::new(configs).await {
+                Ok(host) => {
+                    let cases = run_with_host(&host, scenario, &names, skip_conformance).await;
+                    host.shutdown().await;
+                    cases
+                }
+                Err(e) => vec![setup_failure(e)],
+            }
+        }
+        Err(e) => vec![setup_failure(format!("{:#}", e))],
+    };
+    SuiteResult {
+        name: scenario.name.clone(),
+        file: scenario.file.clone(),
+        cases,
+        duration: started.elapsed(),
+    }
+}
+
+async fn run_with_host(
+    host: &McpHost,
+    scenario: &Scenario,
+    servers: &[String],
+    skip_conformance: bool,
+) -> Vec<CaseResult> {
+    let mut cases = Vec::new();
+    if let (Some(checks), false) = (&scenario.conformance, skip_conformance) {
+        let checked = if checks.servers.is_empty() {
+            servers
+        } else {
+            &checks.servers
+        };
+        cases.extend(conformance::run(host, checks, checked).await);
+    }
+    for step in &scenario.steps {
+        let started = Instant::now();
+        let outcome = run_step(host, step).await;
+        cases.push(CaseResult {
+            name: step.name.clone(),
+            kind: "step",
+            outcome,
+            duration: started.elapsed(),
+        });
+    }
+    cases
+}
+
+fn setup_failure(message: String) -> CaseResult {
+    CaseResult {
+        name: "setup".to_string(),
+        kind: "setup",
+        outcome: Outcome::Failed(message),
+        duration: Duration::ZERO,
+    }
+}
+
+/// Send a request to a server and wait up to `timeout` for its response
+pub async fn request(
+    host: &McpHost,
+    server: &str,
+    method: &str,
+    params: Value,
+    timeout: Duration,
+) -> Result<Response, String> {
+    let id = host.allocate_request_id();
+    host.send_request(server, id, method, params, timeout).await
+}
+
+async fn run_step(host: &McpHost, step: &Step) -> Outcome {
+    let (method, params) = match &step.tool {
+        // Tool calls are sent the way mcp-hostd sends them
+        Some(tool) => {
+            let params = ExecuteToolParams {
+                tool_name: tool.clone(),
+                arguments: object_or_empty(&step.arguments),
+            };
+            ("mcp/tool/execute", serde_json::to_value(params).unwrap_or_default())
+        }
+        None => (
+            step.method.as_deref().unwrap_or_default(),
+            object_or_empty(&step.params),
+        ),
+    };
+    let timeout = Duration::from_secs(step.timeout_secs.unwrap_or(DEFAULT_STEP_TIMEOUT_SECS));
+    match request(host, &step.server, method, params, timeout).await {
+        Ok(response) => check_response(&step.expect, &response, step.tool.is_some()),
+        Err(e) => Outcome::Failed(e),
+    }
+}
+
+fn object_or_empty(value: &Value) -> Value {
+    if value.is_null() {
+        json!({})
+    } else {
+        value.clone()
+    }
+}
+
+fn check_response(expect: &Expect, response: &Response, tool_call: bool) -> Outcome {
+    let result = match (&response.error, &expect.error) {
+        (Some(error), Some(expected)) => {
+            if let Some(code) = expected.code.filter(|code| *code != error.code) {
+                return Outcome::Failed(format!(
+                    "failed with code {} ({}), expected code {}",
+                    error.code, error.message, code
+                ));
+            }
+            return match &expected.message {
+                Some(pattern) => match pattern::matches(pattern, &json!(error.message)) {
+                    Ok(()) => Outcome::Passed,
+                    Err(mismatch) => Outcome::Failed(format!("error message {}", mismatch)),
+                },
+                None => Outcome::Passed,
+            };
+        }
+        (Some(error), None) => {
+            return Outcome::Failed(format!("failed with code {}: {}", error.code, error.message))
+        }
+        (None, Some(_)) => {
+            return Outcome::Failed(format!(
+                "expected an error, got a result: {}",
+                response.result.clone().unwrap_or_default()
+            ))
+        }
+        (None, None) => response.result.clone().unwrap_or_default(),
+    };
+
+    // Like `McpHost::execute_tool`, accept tool results with or without a `result` wrapper
+    let result = match result.get("result") {
+        Some(inner) if tool_call => inner.clone(),
+        _ => result,
+    };
+    let is_error = result.get("isError").and_then(Value::as_bool).unwrap_or(false);
+    match expect.is_error {
+        Some(expected) if expected != is_error => {
+            return Outcome::Failed(format!(
+                "expected isError {}, got {}: {}",
+                expected, is_error, result
+            ))
+        }
+        None if is_error && expect.result.is_none() => {
+            return Outcome::Failed(format!("the tool reported an error: {}", result))
+        }
+        _ => {}
+    }
+    match &expect.result {
+        Some(pattern) => match pattern::matches(pattern, &result) {
+            Ok(()) => Outcome::Passed,
+            Err(mismatch) => Outcome::Failed(mismatch),
+        },
+        None => Outcome::Passed,
+    }
+}
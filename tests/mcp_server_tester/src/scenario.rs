@@ -0,0 +1,248 @@
+// Declarative test scenarios, read from YAML.
+//
+// A scenario names the servers to launch, either inline or from an
+// `mcp_servers.json` file, the conformance checks to run against them and a
+// list of steps. Each step calls a tool (or sends a raw request) and states
+// what the response must look like:
+//
+//   name: smoke test
+//   servers:
+//     - name: fs
+//       command: [target/debug/filesystem-mcp, --root, /tmp]
+//     - name: cmd
+//       command: [target/debug/command-mcp, --allow, sleep]
+//   conformance:
+//     cancellation:
+//       server: cmd
+//       tool: execute_command
+//       arguments: { command: sleep, args: ["30"] }
+//   steps:
+//     - name: list the roots
+//       server: fs
+//       tool: list_roots
+//       expect:
+//         result: { content: [{ type: text, text: { $contains: /tmp } }] }
+//     - name: unknown method
+//       server: fs
+//       method: no/such/method
+//       expect:
+//         error: { code: -32601 }
+
+use anyhow::{bail, Context, Result};
+use gemini_core::config::{load_mcp_servers, McpServerConfig, McpTransport};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Timeout of a step that does not set one
+pub const DEFAULT_STEP_TIMEOUT_SECS: u64 = 30;
+
+/// Size of the request sent by the large payload check when none is set
+pub const DEFAULT_LARGE_PAYLOAD_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Servers to launch
+    #[serde(default)]
+    pub servers: Vec<ServerSpec>,
+    /// `mcp_servers.json` to read more servers from, relative to the scenario file
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    /// Protocol checks run against every server before the steps; none when unset
+    #[serde(default)]
+    pub conformance: Option<Conformance>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    /// Path of the file the scenario was read from
+    #[serde(skip)]
+    pub file: PathBuf,
+}
+
+/// A stdio server; the subset of `McpServerConfig` that matters for testing
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerSpec {
+    pub name: String,
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Conformance {
+    /// Servers to check; all of the scenario's servers when empty
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// Size of the request sent by the large payload check
+    #[serde(default)]
+    pub large_payload_bytes: Option<usize>,
+    /// A call that runs long enough to be cancelled; the check is skipped without one
+    #[serde(default)]
+    pub cancellation: Option<CancellationCall>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CancellationCall {
+    pub server: String,
+    pub tool: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub name: String,
+    pub server: String,
+    /// Tool to call with `arguments`
+    #[serde(default)]
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub arguments: Value,
+    /// Method to send as is with `params`, instead of a tool call
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+/// What a step's response must look like. Without any expectation the step
+/// passes on any successful response that is not a tool error.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    /// Pattern the result must match (see `pattern`)
+    #[serde(default)]
+    pub result: Option<Value>,
+    /// The request must fail with a JSON-RPC error
+    #[serde(default)]
+    pub error: Option<ExpectError>,
+    /// Whether the tool result must (or must not) be flagged `isError`
+    #[serde(default)]
+    pub is_error: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectError {
+    #[serde(default)]
+    pub code: Option<i64>,
+    /// Pattern the error message must match
+    #[serde(default)]
+    pub message: Option<Value>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+        let mut scenario: Scenario = serde_yaml::from_str(&text)
+            .with_context(|| format!("Failed to parse scenario {}", path.display()))?;
+        scenario.file = path.to_path_buf();
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.servers.is_empty() && self.config.is_none() {
+            bail!("Scenario '{}' has no servers", self.name);
+        }
+        for step in &self.steps {
+            if step.tool.is_some() == step.method.is_some() {
+                bail!("Step '{}' needs exactly one of `tool` and `method`", step.name);
+            }
+            if step.expect.result.is_some() && step.expect.error.is_some() {
+                bail!("Step '{}' cannot expect both a result and an error", step.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Configurations of the servers to launch
+    pub fn server_configs(&self) -> Result<Vec<McpServerConfig>> {
+        let mut configs = Vec::new();
+        if let Some(config) = &self.config {
+            let base = self.file.parent().unwrap_or(Path::new("."));
+            let path = base.join(config);
+            // The loader treats a missing file as an empty configuration
+            if !path.is_file() {
+                bail!("{} not found", path.display());
+            }
+            let loaded = load_mcp_servers(Some(&path)).map_err(|e| anyhow::anyhow!("{}", e))?;
+            configs.extend(loaded.into_iter().filter(|config| config.enabled));
+        }
+        configs.extend(self.servers.iter().map(ServerSpec::to_config));
+        Ok(configs)
+    }
+}
+
+impl ServerSpec {
+    fn to_config(&self) -> McpServerConfig {
+        McpServerConfig {
+            name: self.name.clone(),
+            enabled: true,
+            transport: McpTransport::Stdio,
+            command: self.command.clone(),
+            args: self.args.clone(),
+            env: self.env.clone(),
+            ..McpServerConfig::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_scenario() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("servers.json"),
+            r#"[{"name": "py", "enabled": true, "transport": "stdio", "command": ["python3"]},
+                {"name": "off", "enabled": false, "transport": "stdio", "command": ["false"]}]"#,
+        )
+        .unwrap();
+        let path = dir.path().join("scenario.yaml");
+        fs::write(
+            &path,
+            "name: smoke\n\
+             config: servers.json\n\
+             servers:\n  - name: fs\n    command: [filesystem-mcp, --read-only]\n\
+             conformance: {}\n\
+             steps:\n  - name: roots\n    server: fs\n    tool: list_roots\n    expect:\n      is_error: false\n",
+        )
+        .unwrap();
+
+        let scenario = Scenario::load(&path).unwrap();
+        assert_eq!(scenario.steps[0].expect.is_error, Some(false));
+        assert!(scenario.conformance.as_ref().unwrap().cancellation.is_none());
+        let names: Vec<_> = scenario
+            .server_configs()
+            .unwrap()
+            .into_iter()
+            .map(|config| config.name)
+            .collect();
+        assert_eq!(names, ["py", "fs"]);
+
+        fs::write(
+            &path,
+            "name: bad\nconfig: servers.json\nsteps:\n  - name: both\n    server: fs\n    tool: a\n    method: b\n",
+        )
+        .unwrap();
+        let error = Scenario::load(&path).unwrap_err();
+        assert!(error.to_string().contains("exactly one"), "{}", error);
+    }
+}
@@ -130,6 +130,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 env
             },
             auto_execute: vec![],
+            ..Default::default()
         };
 
         let command_server = McpServerConfig {
@@ -144,6 +145,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 env
             },
             auto_execute: vec![],
+            ..Default::default()
         };
 
         let memory_store_server = McpServerConfig {
@@ -164,6 +166,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 "retrieve_memory_by_tag".to_string(),
                 "delete_memory_by_key".to_string(),
             ],
+            ..Default::default()
         };
 
         let servers = vec![filesystem_server, command_server, memory_store_server];